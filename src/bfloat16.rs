@@ -0,0 +1,394 @@
+use std::{
+    collections::BTreeMap,
+    ops::{BitOr, BitOrAssign},
+};
+
+use crate::{
+    codec::{self, Reader},
+    Error, QuantileSketch,
+};
+
+/// Takes the most significant 16 bits of the floating point number.
+#[inline]
+fn to_bfloat16(value: f32) -> u16 {
+    (value.to_bits() >> 16) as u16
+}
+
+/// Puts the bits into the most significant 16 bits of the floating point
+/// number, and fills the other bits with zeros.
+#[inline]
+pub(crate) fn to_f32(value: u16) -> f32 {
+    f32::from_bits(u32::from(value) << 16)
+}
+
+/// Histogram of values rounded to [bfloat16], as used by ClickHouse
+/// [`quantileBFloat16`][ClickHouseRefBFloat16].
+///
+/// Each value is truncated to its 16 most significant bits, and the histogram
+/// counts how many times each truncated value was seen. This is usually faster
+/// and smaller than a t-digest, at the cost of a relative error of up to 2^-8
+/// on the quantiles.
+///
+/// The histogram can be read from and written to the state format of the
+/// `quantileBFloat16` aggregate function.
+///
+/// [bfloat16]: https://en.wikipedia.org/wiki/Bfloat16_floating-point_format
+/// [ClickHouseRefBFloat16]: https://clickhouse.com/docs/en/sql-reference/aggregate-functions/reference/quantilebfloat16
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::BFloat16Histogram;
+///
+/// let mut histogram = BFloat16Histogram::new();
+///
+/// // Add some elements.
+/// histogram.insert(1.0);
+/// histogram.insert(2.0);
+/// histogram.insert(3.0);
+///
+/// // Get the median of the distribution.
+/// let quantile = histogram.quantile(0.5);
+/// assert_eq!(quantile, 2.0);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BFloat16Histogram {
+    weights: BTreeMap<u16, u64>,
+    count: u64,
+}
+
+impl BFloat16Histogram {
+    /// Creates an empty `BFloat16Histogram`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    /// let histogram = BFloat16Histogram::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of elements in the histogram.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let mut histogram = BFloat16Histogram::new();
+    /// assert_eq!(histogram.len(), 0);
+    /// histogram.insert(1.0);
+    /// assert_eq!(histogram.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if the histogram contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let mut histogram = BFloat16Histogram::new();
+    /// assert!(histogram.is_empty());
+    /// histogram.insert(1.0);
+    /// assert!(!histogram.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Clears the histogram, removing all values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let mut histogram = BFloat16Histogram::new();
+    /// histogram.insert(1.0);
+    /// histogram.clear();
+    /// assert!(histogram.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.weights.clear();
+        self.count = 0;
+    }
+
+    /// Adds a value to the histogram.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let mut histogram = BFloat16Histogram::new();
+    ///
+    /// histogram.insert(1.0);
+    /// histogram.insert(2.0);
+    /// assert_eq!(histogram.len(), 2);
+    /// ```
+    #[inline]
+    pub fn insert(&mut self, value: f32) {
+        self.insert_many(value, 1);
+    }
+
+    /// Adds multiple values to the histogram.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let mut histogram = BFloat16Histogram::new();
+    ///
+    /// histogram.insert_many(1.0, 1);
+    /// histogram.insert_many(2.0, 2);
+    /// assert_eq!(histogram.len(), 3);
+    /// ```
+    pub fn insert_many(&mut self, value: f32, count: u64) {
+        if count == 0 || value.is_nan() {
+            return;
+        }
+        *self.weights.entry(to_bfloat16(value)).or_default() += count;
+        self.count += count;
+    }
+
+    /// Returns the estimated quantile of the histogram.
+    ///
+    /// The result is one of the inserted values, truncated to bfloat16
+    /// precision.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let histogram = BFloat16Histogram::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(histogram.quantile(0.0), 1.0);
+    /// assert_eq!(histogram.quantile(0.5), 3.0);
+    /// assert_eq!(histogram.quantile(1.0), 5.0);
+    /// ```
+    pub fn quantile(&self, level: f64) -> f32 {
+        let mut values: Vec<(f32, u64)> = self
+            .weights
+            .iter()
+            .map(|(&value, &weight)| (to_f32(value), weight))
+            .collect();
+        if values.is_empty() {
+            return f32::NAN;
+        }
+        values.sort_unstable_by(|l, r| l.0.total_cmp(&r.0));
+
+        let threshold = (self.count as f64 * level).ceil();
+        let mut accumulated = 0u64;
+        for (value, weight) in values.iter() {
+            accumulated += weight;
+            if accumulated as f64 >= threshold {
+                return *value;
+            }
+        }
+        values.last().unwrap().0
+    }
+
+    /// Serializes the histogram in the state format of ClickHouse
+    /// `quantileBFloat16` aggregate function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let histogram = BFloat16Histogram::from([1.0, 2.0, 2.0]);
+    /// let bytes = histogram.to_bytes();
+    /// assert_eq!(BFloat16Histogram::from_bytes(&bytes), Ok(histogram));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(10 + self.weights.len() * 10);
+        codec::write_varuint(&mut buf, self.weights.len() as u64);
+        for (&value, &weight) in self.weights.iter() {
+            buf.extend_from_slice(&value.to_le_bytes());
+            buf.extend_from_slice(&weight.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Deserializes a histogram from the state format of ClickHouse
+    /// `quantileBFloat16` aggregate function.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not a valid state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let bytes = [
+    ///     0x01, // One cell.
+    ///     0x80, 0x3f, // 1.0 as bfloat16.
+    ///     0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Weight of 3.
+    /// ];
+    /// let histogram = BFloat16Histogram::from_bytes(&bytes).unwrap();
+    /// assert_eq!(histogram.len(), 3);
+    /// assert_eq!(histogram.quantile(0.5), 1.0);
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let size = reader.read_varuint()?;
+        let mut histogram = Self::new();
+        for _ in 0..size {
+            let value = reader.read_u16()?;
+            let weight = reader.read_u64()?;
+            // A cell weighs no more than the total, which is checked first.
+            histogram.count = histogram
+                .count
+                .checked_add(weight)
+                .ok_or(Error::InvalidState("total weight overflows"))?;
+            *histogram.weights.entry(value).or_default() += weight;
+        }
+        reader.finish()?;
+        Ok(histogram)
+    }
+}
+
+impl BitOr<&BFloat16Histogram> for &BFloat16Histogram {
+    type Output = BFloat16Histogram;
+
+    /// Returns the union of `self` and `rhs` as a new `BFloat16Histogram`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let a = BFloat16Histogram::from([1.0, 2.0, 3.0]);
+    /// let b = BFloat16Histogram::from([3.0, 4.0, 5.0]);
+    ///
+    /// let c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 6);
+    /// assert_eq!(c.quantile(0.5), 3.0);
+    /// ```
+    fn bitor(self, rhs: &BFloat16Histogram) -> BFloat16Histogram {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&BFloat16Histogram> for BFloat16Histogram {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let mut a = BFloat16Histogram::from([1.0, 2.0, 3.0]);
+    /// let b = BFloat16Histogram::from([3.0, 4.0, 5.0]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 6);
+    /// assert_eq!(a.quantile(0.5), 3.0);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &BFloat16Histogram) {
+        for (&value, &weight) in rhs.weights.iter() {
+            *self.weights.entry(value).or_default() += weight;
+        }
+        self.count += rhs.count;
+    }
+}
+
+impl Extend<f32> for BFloat16Histogram {
+    fn extend<I: IntoIterator<Item = f32>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<const N: usize> From<[f32; N]> for BFloat16Histogram {
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::BFloat16Histogram;
+    ///
+    /// let histogram1 = BFloat16Histogram::from([1.0, 2.0, 3.0, 4.0]);
+    /// let histogram2: BFloat16Histogram = [1.0, 2.0, 3.0, 4.0].into();
+    /// assert_eq!(histogram1, histogram2);
+    /// ```
+    fn from(array: [f32; N]) -> Self {
+        let mut histogram = BFloat16Histogram::new();
+        histogram.extend(array);
+        histogram
+    }
+}
+
+impl FromIterator<f32> for BFloat16Histogram {
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> Self {
+        let mut histogram = BFloat16Histogram::new();
+        histogram.extend(iter);
+        histogram
+    }
+}
+
+impl QuantileSketch for BFloat16Histogram {
+    #[inline]
+    fn insert(&mut self, value: f32) {
+        self.insert(value);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        *self |= other;
+    }
+
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    #[inline]
+    fn quantile(&mut self, level: f64) -> f32 {
+        BFloat16Histogram::quantile(self, level)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for BFloat16Histogram {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(self.weights.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BFloat16Histogram {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let cells: Vec<(u16, u64)> = serde::Deserialize::deserialize(deserializer)?;
+        let mut histogram = Self::new();
+        for (value, weight) in cells {
+            histogram.count = histogram
+                .count
+                .checked_add(weight)
+                .ok_or_else(|| serde::de::Error::custom("total weight overflows"))?;
+            *histogram.weights.entry(value).or_default() += weight;
+        }
+        Ok(histogram)
+    }
+}
@@ -0,0 +1,326 @@
+//! Bulk construction of t-digests from sorted data.
+//!
+//! When the values are already sorted, such as the row groups of a Parquet
+//! file sorted on the measured column, a t-digest can be built much faster
+//! than by inserting the values one by one: [`from_sorted_chunks`] merges the
+//! chunks in a single streaming pass, and forms the centroids as the values go
+//! by, without buffering nor sorting them. Likewise,
+//! [`TDigest::extend_sorted`] merges sorted values into an existing t-digest.
+//!
+//! # Examples
+//!
+//! ```
+//! use tdigest_ch::{bulk, TDigest};
+//!
+//! let even: Vec<f32> = (0..1000).map(|v| (2 * v) as f32).collect();
+//! let odd: Vec<f32> = (0..1000).map(|v| (2 * v + 1) as f32).collect();
+//!
+//! let mut digest = bulk::from_sorted_chunks(TDigest::new(), [even, odd]);
+//! assert_eq!(digest.len(), 2000);
+//! assert_eq!(digest.quantile(0.0), 0.0);
+//! assert_eq!(digest.quantile(1.0), 1999.0);
+//! ```
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{can_be_merged, Centroid, Float, TDigest};
+
+/// The smallest value of a chunk that has not been merged yet.
+struct Head<F> {
+    value: F,
+    chunk: usize,
+}
+
+impl<F: Float> PartialEq for Head<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<F: Float> Eq for Head<F> {}
+
+impl<F: Float> PartialOrd for Head<F> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<F: Float> Ord for Head<F> {
+    // The ordering is reversed, so that the binary heap pops the smallest value.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .value
+            .total_cmp(&self.value)
+            .then(other.chunk.cmp(&self.chunk))
+    }
+}
+
+/// Returns the next value of `iter` that is not NaN, counting the NaN values
+/// into `nans`.
+fn next_value<F: Float>(iter: &mut impl Iterator<Item = F>, nans: &mut u64) -> Option<F> {
+    iter.find(|value| {
+        if value.is_nan() {
+            *nans += 1;
+        }
+        !value.is_nan()
+    })
+}
+
+/// Builds a t-digest from chunks of values, each sorted in increasing order.
+///
+/// The chunks are merged on the fly, and the values must be consumed in order:
+/// the t-digest holds one iterator per chunk at a time, but no value beyond
+/// the current one of each chunk. The number of values of each chunk must be
+/// known in advance, so as to size the centroids. The t-digest is compressed
+/// once, at the end, and has the configuration of `template`. NaN values are
+/// ignored, and counted as with [`TDigest::nan_count`].
+///
+/// # Panics
+///
+/// Panics if `template` is not empty, or if a chunk is not sorted.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{bulk, TDigest};
+///
+/// let mut builder = TDigest::builder();
+/// builder.max_centroids(64);
+///
+/// let chunks = (0..10).map(|chunk| (0..1000).map(move |v| (v * 10 + chunk) as f32));
+/// let mut digest = bulk::from_sorted_chunks(builder.build(), chunks);
+/// assert_eq!(digest.len(), 10_000);
+/// assert!((digest.quantile(0.5) - 5000.0).abs() < 100.0);
+/// ```
+pub fn from_sorted_chunks<F, I, J>(template: TDigest<F>, chunks: I) -> TDigest<F>
+where
+    F: Float,
+    I: IntoIterator<Item = J>,
+    J: IntoIterator<Item = F>,
+    J::IntoIter: ExactSizeIterator,
+{
+    assert!(template.is_empty(), "template must be empty");
+    let mut digest = template;
+    let mut chunks: Vec<J::IntoIter> = chunks.into_iter().map(IntoIterator::into_iter).collect();
+    let total: u64 = chunks.iter().map(|chunk| chunk.len() as u64).sum();
+    let nans = digest.nans;
+
+    let mut heap = BinaryHeap::with_capacity(chunks.len());
+    for (chunk, iter) in chunks.iter_mut().enumerate() {
+        if let Some(value) = next_value(iter, &mut digest.nans) {
+            heap.push(Head { value, chunk });
+        }
+    }
+
+    let mut merge = SortedMerge::new(
+        std::mem::take(&mut digest.centroids),
+        total,
+        digest.config.epsilon,
+    );
+    let mut count = 0;
+    while let Some(Head { value, chunk }) = heap.pop() {
+        if let Some(next) = next_value(&mut chunks[chunk], &mut digest.nans) {
+            assert!(next >= value, "chunks must be sorted");
+            heap.push(Head { value: next, chunk });
+        }
+        merge.set_total(total - (digest.nans - nans));
+        count += 1;
+        digest.sum += value.to_f64();
+        digest.min = digest.min.min(value);
+        digest.max = digest.max.max(value);
+        digest.extremes.insert(value, 1, digest.config.extremes);
+        digest
+            .raw
+            .insert(value, 1, count - 1, digest.config.raw_samples);
+        merge.push(value, 1);
+    }
+
+    digest.centroids = merge.finish();
+    digest.count = count;
+    digest.compress_brute();
+    digest
+}
+
+/// Forms centroids from weighted values in increasing order, with the same
+/// merge rule as compression.
+struct SortedMerge<F> {
+    centroids: Vec<Centroid<F>>,
+    total: f64,
+    epsilon_4: f64,
+    /// The weight of the centroids formed so far.
+    sum: u64,
+    /// The mean and weight of the centroid being formed.
+    current: Option<(f64, u64)>,
+}
+
+impl<F: Float> SortedMerge<F> {
+    /// Starts forming centroids into the empty `centroids`, for a total weight
+    /// of `total`.
+    fn new(centroids: Vec<Centroid<F>>, total: u64, epsilon: f32) -> Self {
+        SortedMerge {
+            centroids,
+            total: total as f64,
+            epsilon_4: epsilon as f64 * 4.,
+            sum: 0,
+            current: None,
+        }
+    }
+
+    /// Sets the total weight, once NaN values are found not to count.
+    fn set_total(&mut self, total: u64) {
+        self.total = total as f64;
+    }
+
+    fn push(&mut self, mean: F, count: u64) {
+        self.current = match self.current {
+            None => Some((mean.to_f64(), count)),
+            Some((mut l_mean, mut l_count)) => {
+                let ql = (self.sum as f64 + l_count as f64 * 0.5) / self.total;
+                let qr = (self.sum as f64 + l_count as f64 + count as f64 * 0.5) / self.total;
+                let k = self.total * self.epsilon_4 * f64::min(ql * (1. - ql), qr * (1. - qr));
+                if (l_count + count) as f64 <= k && can_be_merged(l_mean, mean) {
+                    l_count += count;
+                    if mean.to_f64() != l_mean {
+                        l_mean += count as f64 * (mean.to_f64() - l_mean) / l_count as f64;
+                    }
+                    Some((l_mean, l_count))
+                } else {
+                    self.centroids.push(Centroid {
+                        mean: F::from_f64(l_mean),
+                        count: l_count,
+                    });
+                    self.sum += l_count;
+                    Some((mean.to_f64(), count))
+                }
+            }
+        };
+    }
+
+    fn finish(mut self) -> Vec<Centroid<F>> {
+        if let Some((mean, count)) = self.current {
+            self.centroids.push(Centroid {
+                mean: F::from_f64(mean),
+                count,
+            });
+        }
+        self.centroids
+    }
+}
+
+impl<F: Float> TDigest<F> {
+    /// Adds values sorted in increasing order, such as from a sorted scan.
+    ///
+    /// The values are merged with the centroids as they go by, in a single
+    /// pass and without sorting them, as with [`from_sorted_chunks`]. The
+    /// t-digest is compressed first, and the number of values must be known in
+    /// advance, so as to size the centroids. NaN values are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values are not sorted, leaving the t-digest as it was.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..1000).map(|v| (2 * v) as f32));
+    /// digest.extend_sorted((0..1000).map(|v| (2 * v + 1) as f32));
+    /// assert_eq!(digest.len(), 2000);
+    /// assert_eq!(digest.quantile(1.0), 1999.0);
+    /// ```
+    pub fn extend_sorted<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = F>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let mut values = values.into_iter();
+        self.compress();
+        let total = self.count + values.len() as u64;
+        let mut centroids = self.centroids.iter().peekable();
+        let mut merge = SortedMerge::new(
+            Vec::with_capacity(centroids.len()),
+            total,
+            self.config.epsilon,
+        );
+
+        // The t-digest is only updated once all the values are known to be
+        // sorted, so that it is left as it was if they are not.
+        let (mut count, mut sum, mut min, mut max) = (self.count, self.sum, self.min, self.max);
+        let mut extremes = self.extremes.clone();
+        let mut raw = self.raw.clone();
+        let mut nans = 0;
+        let mut prev = F::NEG_INFINITY;
+        while let Some(value) = next_value(&mut values, &mut nans) {
+            assert!(value >= prev, "values must be sorted");
+            prev = value;
+            merge.set_total(total - nans);
+            while let Some(c) = centroids.next_if(|c| c.mean <= value) {
+                merge.push(c.mean, c.count);
+            }
+            sum += value.to_f64();
+            min = min.min(value);
+            max = max.max(value);
+            extremes.insert(value, 1, self.config.extremes);
+            raw.insert(value, 1, count, self.config.raw_samples);
+            count += 1;
+            merge.push(value, 1);
+        }
+        merge.set_total(total - nans);
+        for c in centroids {
+            merge.push(c.mean, c.count);
+        }
+
+        self.centroids = merge.finish();
+        (self.count, self.sum, self.min, self.max) = (count, sum, min, max);
+        (self.extremes, self.raw) = (extremes, raw);
+        self.nans += nans;
+        self.compress_brute();
+    }
+
+    /// Adds a slice of values sorted in increasing order. See
+    /// [`TDigest::extend_sorted`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values are not sorted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.insert_sorted_slice(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.median(), 3.0);
+    /// ```
+    pub fn insert_sorted_slice(&mut self, values: &[F]) {
+        self.extend_sorted(values.iter().copied());
+    }
+
+    /// Adds a slice of values in any order, sorting them once and merging
+    /// them with the centroids in a single pass.
+    ///
+    /// This is faster than [`Extend`] for large batches, which buffers the
+    /// values and compresses the t-digest each time the buffer is full. The
+    /// batch is copied to be sorted. NaN values are ignored, and counted as
+    /// with [`TDigest::nan_count`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.extend_from_slice(&[5.0, 1.0, f32::NAN, 4.0, 2.0, 3.0]);
+    /// assert_eq!(digest.len(), 5);
+    /// assert_eq!(digest.nan_count(), 1);
+    /// assert_eq!(digest.median(), 3.0);
+    /// ```
+    pub fn extend_from_slice(&mut self, values: &[F]) {
+        let mut sorted: Vec<F> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+        self.nans += (values.len() - sorted.len()) as u64;
+        sorted.sort_unstable_by(F::total_cmp);
+        self.extend_sorted(sorted);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{interpolation::tail_rank, Float, Interpolation, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Compresses the t-digest, and returns its cumulative distribution
+    /// function as a standalone piecewise-linear function.
+    ///
+    /// The function evaluates to the same values as [`TDigest::cdf`], from a
+    /// sorted table of breakpoints, so that it can be evaluated many times
+    /// without the t-digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// let cdf = digest.to_cdf();
+    /// assert_eq!(cdf.eval(250.0), digest.cdf(250.0));
+    /// assert_eq!(cdf.eval(-1.0), 0.0);
+    /// assert!((cdf.inverse(0.5) - 500.0).abs() <= 1.0);
+    /// ```
+    pub fn to_cdf(&mut self) -> CdfFunction<F> {
+        self.compress();
+        self.to_cdf_uncompressed()
+    }
+
+    /// Compresses the t-digest, and returns the points of its cumulative
+    /// distribution function, as pairs of a value and its cumulative fraction
+    /// in increasing order.
+    ///
+    /// The points are the breakpoints of [`TDigest::to_cdf`]: drawing lines
+    /// between them plots the function exactly, including its steps, which
+    /// appear as two points of the same value. The iterator owns the points,
+    /// so that it can be passed to plotting libraries as is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0]);
+    /// let points: Vec<(f32, f64)> = digest.ecdf().collect();
+    /// assert_eq!(points, [(1.0, 0.0), (1.0, 0.5), (2.0, 0.5), (2.0, 1.0)]);
+    /// ```
+    pub fn ecdf(&mut self) -> impl Iterator<Item = (F, f64)> {
+        self.to_cdf().into_points()
+    }
+
+    pub(crate) fn to_cdf_uncompressed(&self) -> CdfFunction<F> {
+        let mut breakpoints = Vec::new();
+        let count = self.count as f64;
+        if let Some(values) = self.raw.exact(self.count) {
+            // Each distinct value is a step of the exact function.
+            let mut prev = 0.;
+            for (i, &value) in values.iter().enumerate() {
+                if values.get(i + 1) != Some(&value) {
+                    breakpoints.push((value, prev));
+                    breakpoints.push((value, (i + 1) as f64));
+                    prev = (i + 1) as f64;
+                }
+            }
+            return CdfFunction { breakpoints, count };
+        }
+
+        // The same interpolation as `cdf_uncompressed`: each centroid is a
+        // step from its right rank to its left rank, and the function is
+        // linear between a left rank and the next right rank. The tails
+        // interpolated towards the extremes are steps of one rank at the
+        // extremes, linear up to the first and last centroids.
+        let singletons = self.config.interpolation.singletons();
+        let dunning = self.config.interpolation == Interpolation::Dunning;
+        let last = self.centroids.len().saturating_sub(1);
+        let mut sum = 0;
+        for (i, c) in self.centroids.iter().enumerate() {
+            let mut left = sum as f64 + c.count as f64 * 0.5;
+            let mut right = left;
+            if singletons && c.count == 1 {
+                left += 0.5;
+                right -= 0.5;
+            }
+            if i == 0 {
+                right = 0.;
+                if dunning && self.min < c.mean {
+                    breakpoints.push((self.min, 0.));
+                    breakpoints.push((self.min, 1.));
+                    right = tail_rank(c.count);
+                }
+            }
+            if i == last {
+                left = count;
+                if dunning && c.mean < self.max {
+                    left = count - tail_rank(c.count);
+                }
+            }
+            breakpoints.push((c.mean, right));
+            breakpoints.push((c.mean, left));
+            sum += c.count;
+        }
+        if let Some(c) = self.centroids.last() {
+            if dunning && c.mean < self.max {
+                breakpoints.push((self.max, count - 1.));
+                breakpoints.push((self.max, count));
+            }
+        }
+        CdfFunction { breakpoints, count }
+    }
+}
+
+/// A piecewise-linear cumulative distribution function.
+///
+/// This `struct` is created by the [`to_cdf`] method on [`TDigest`]. See its
+/// documentation for more.
+///
+/// [`to_cdf`]: TDigest::to_cdf
+#[derive(Clone, Debug, PartialEq)]
+pub struct CdfFunction<F = f32> {
+    /// The values and ranks of the breakpoints, in increasing order of both.
+    /// Each value appears twice, on both sides of its step.
+    breakpoints: Vec<(F, f64)>,
+    count: f64,
+}
+
+impl<F: Float> CdfFunction<F> {
+    /// Returns the fraction of the elements that are lower than or equal to
+    /// `value`, or NaN if there are no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let cdf = TDigest::from([1.0, 2.0, 3.0, 4.0]).to_cdf();
+    /// assert_eq!(cdf.eval(2.0), 0.5);
+    /// assert_eq!(cdf.eval(4.0), 1.0);
+    /// ```
+    pub fn eval(&self, value: F) -> f64 {
+        let Some(&(first, _)) = self.breakpoints.first() else {
+            return f64::NAN;
+        };
+        if value < first {
+            return 0.;
+        }
+        if value.is_nan() {
+            return 1.;
+        }
+        let index = self.breakpoints.partition_point(|&(x, _)| x <= value) - 1;
+        let (x1, y1) = self.breakpoints[index];
+        let rank = match self.breakpoints.get(index + 1) {
+            Some(&(x2, y2)) => {
+                let t = (value - x1).to_f64() / (x2 - x1).to_f64();
+                y1 + t * (y2 - y1)
+            }
+            None => y1,
+        };
+        rank / self.count
+    }
+
+    /// Returns the smallest value whose cumulative fraction is at least
+    /// `level`, or NaN if there are no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let cdf = TDigest::from([1.0, 2.0, 3.0, 4.0]).to_cdf();
+    /// assert_eq!(cdf.inverse(0.5), 2.0);
+    /// assert_eq!(cdf.inverse(0.0), 1.0);
+    /// ```
+    pub fn inverse(&self, level: f64) -> F {
+        let Some(&(first, _)) = self.breakpoints.first() else {
+            return F::NAN;
+        };
+        let rank = level * self.count;
+        let index = self.breakpoints.partition_point(|&(_, y)| y < rank);
+        if index == 0 {
+            return first;
+        }
+        let Some(&(x2, y2)) = self.breakpoints.get(index) else {
+            return self.breakpoints[index - 1].0;
+        };
+        let (x1, y1) = self.breakpoints[index - 1];
+        let t = (rank - y1) / (y2 - y1);
+        F::from_f64(x1.to_f64() + t * (x2 - x1).to_f64())
+    }
+
+    /// Returns the 1-Wasserstein distance between `self` and `other`, the area
+    /// between both functions, or NaN if either has no elements.
+    ///
+    /// The distance is in the unit of the values: it is the average distance
+    /// that the elements of one distribution must move to match the other.
+    /// Both functions are linear between their breakpoints, so that the area
+    /// is computed exactly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let a = TDigest::from([1.0, 2.0, 3.0]).to_cdf();
+    /// let b = TDigest::from([11.0, 12.0, 13.0]).to_cdf();
+    /// assert_eq!(a.wasserstein_distance(&b), 10.0);
+    /// ```
+    pub fn wasserstein_distance(&self, other: &CdfFunction<F>) -> f64 {
+        if self.breakpoints.is_empty() || other.breakpoints.is_empty() {
+            return f64::NAN;
+        }
+        let mut values: Vec<F> = self
+            .breakpoints
+            .iter()
+            .chain(&other.breakpoints)
+            .map(|b| b.0)
+            .collect();
+        values.sort_unstable_by(F::total_cmp);
+        values.dedup();
+
+        let mut distance = 0.;
+        for pair in values.windows(2) {
+            let (x0, x1) = (pair[0], pair[1]);
+            // Both functions are linear on the interval, whose difference is
+            // extrapolated to its end from its start and middle.
+            let d0 = self.eval(x0) - other.eval(x0);
+            let middle = F::from_f64((x0.to_f64() + x1.to_f64()) / 2.);
+            let d1 = 2. * (self.eval(middle) - other.eval(middle)) - d0;
+            let width = x1.to_f64() - x0.to_f64();
+            distance += if d0 * d1 >= 0. {
+                width * (d0.abs() + d1.abs()) / 2.
+            } else {
+                width * (d0 * d0 + d1 * d1) / (2. * (d0.abs() + d1.abs()))
+            };
+        }
+        distance
+    }
+
+    /// Returns the breakpoints, as pairs of a value and its cumulative
+    /// fraction, in increasing order.
+    ///
+    /// Each step of the function appears as two breakpoints of the same
+    /// value, for the fractions below and at the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let cdf = TDigest::from([1.0, 2.0]).to_cdf();
+    /// let breakpoints: Vec<_> = cdf.iter().collect();
+    /// assert_eq!(breakpoints, [(1.0, 0.0), (1.0, 0.5), (2.0, 0.5), (2.0, 1.0)]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (F, f64)> + '_ {
+        self.breakpoints
+            .iter()
+            .map(|&(value, rank)| (value, rank / self.count))
+    }
+
+    /// Returns the breakpoints as in [`CdfFunction::iter`], consuming the
+    /// function.
+    pub(crate) fn into_points(self) -> impl Iterator<Item = (F, f64)> {
+        let count = self.count;
+        self.breakpoints
+            .into_iter()
+            .map(move |(value, rank)| (value, rank / count))
+    }
+}
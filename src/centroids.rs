@@ -0,0 +1,61 @@
+use crate::{Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Creates a t-digest from the means and weights of centroids, such as
+    /// those produced by another t-digest implementation, with the
+    /// configuration of `template`.
+    ///
+    /// Each centroid stands for `count` elements equal to its mean, and the
+    /// t-digest is compressed once all of them are inserted. Centroids with a
+    /// count of 0 or a NaN mean are ignored, as with [`TDigest::insert_many`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_centroids(TDigest::new(), [(1.0, 2), (5.0, 1)]);
+    /// assert_eq!(digest.len(), 3);
+    /// assert_eq!(digest.quantile(1.0), 5.0);
+    /// assert_eq!(digest.into_centroids(), [(1.0, 2), (5.0, 1)]);
+    /// ```
+    pub fn from_centroids<I>(template: TDigest<F>, centroids: I) -> TDigest<F>
+    where
+        I: IntoIterator<Item = (F, u64)>,
+    {
+        assert!(template.is_empty(), "template must be empty");
+        let mut digest = template;
+        for (mean, count) in centroids {
+            digest.insert_many(mean, count);
+        }
+        digest.compress();
+        digest
+    }
+
+    /// Compresses the t-digest, and returns the means and weights of its
+    /// centroids, in increasing order of means.
+    ///
+    /// The centroids rebuild the same t-digest with
+    /// [`TDigest::from_centroids`], except for the exact extremes, sum and
+    /// raw values, which they do not hold.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let digest = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    /// let centroids = digest.into_centroids();
+    /// assert_eq!(centroids.iter().map(|c| c.1).sum::<u64>(), 10_000);
+    /// assert!(centroids.windows(2).all(|w| w[0].0 < w[1].0));
+    /// ```
+    #[must_use]
+    pub fn into_centroids(mut self) -> Vec<(F, u64)> {
+        self.compress();
+        self.centroids.iter().map(|c| (c.mean, c.count)).collect()
+    }
+}
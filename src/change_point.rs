@@ -0,0 +1,307 @@
+use crate::{Float, TDigest};
+
+/// Number of reference quantile bins used to compute the population stability
+/// index.
+const PSI_BINS: usize = 10;
+
+/// Smallest share of a bin, so that empty bins do not make the population
+/// stability index infinite.
+const PSI_MIN_SHARE: f64 = 1e-4;
+
+/// A divergence statistic between two distributions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// The Kolmogorov-Smirnov statistic, the largest difference between the
+    /// cumulative distribution functions. It ranges from 0 to 1.
+    Ks,
+    /// The population stability index, computed over the deciles of the
+    /// reference distribution. Values above 0.25 are commonly considered a
+    /// significant shift.
+    Psi,
+}
+
+/// Detects changes in the distribution of a stream of values.
+///
+/// The detector maintains a reference t-digest and a t-digest of the recent
+/// window of values. Each time the window is full, the divergence between the
+/// window and the reference is computed: if it exceeds the threshold, a
+/// [`ChangeEvent`] is emitted and the window becomes the new reference,
+/// otherwise the window is merged into the reference. The first window becomes
+/// the reference.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{ChangePointDetector, Divergence};
+///
+/// let mut detector = ChangePointDetector::new(Divergence::Ks, 0.5, 100);
+///
+/// let stable = (0..200).map(|v| (v % 100) as f32);
+/// assert!(stable.filter_map(|v| detector.insert(v)).next().is_none());
+///
+/// let shifted = (0..100).map(|v| (v + 1000) as f32);
+/// let event = shifted.filter_map(|v| detector.insert(v)).next().unwrap();
+/// assert_eq!(event.statistic(), 1.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangePointDetector {
+    divergence: Divergence,
+    threshold: f64,
+    window_size: usize,
+    reference: TDigest,
+    window: TDigest,
+}
+
+impl ChangePointDetector {
+    /// Creates a detector that compares windows of `window_size` values to the
+    /// reference, and reports a change when their `divergence` exceeds
+    /// `threshold`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window_size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{ChangePointDetector, Divergence};
+    /// let detector = ChangePointDetector::new(Divergence::Psi, 0.25, 1000);
+    /// ```
+    #[must_use]
+    pub fn new(divergence: Divergence, threshold: f64, window_size: usize) -> Self {
+        Self::with_template(TDigest::new(), divergence, threshold, window_size)
+    }
+
+    /// Creates a detector whose t-digests use the configuration of `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not empty, or if `window_size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{ChangePointDetector, Divergence, TDigest};
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.max_centroids(256);
+    ///
+    /// let detector = ChangePointDetector::with_template(builder.build(), Divergence::Ks, 0.2, 500);
+    /// ```
+    #[must_use]
+    pub fn with_template(
+        template: TDigest,
+        divergence: Divergence,
+        threshold: f64,
+        window_size: usize,
+    ) -> Self {
+        assert!(template.is_empty(), "template must be empty");
+        assert!(window_size > 0, "window size must be positive");
+        Self {
+            divergence,
+            threshold,
+            window_size,
+            reference: template.clone(),
+            window: template,
+        }
+    }
+
+    /// Returns the divergence statistic of the detector.
+    #[inline]
+    pub fn divergence(&self) -> Divergence {
+        self.divergence
+    }
+
+    /// Returns the threshold above which a change is reported.
+    #[inline]
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// Returns the number of values in each window.
+    #[inline]
+    pub fn window_size(&self) -> usize {
+        self.window_size
+    }
+
+    /// Returns the t-digest of the reference distribution.
+    #[inline]
+    pub fn reference(&self) -> &TDigest {
+        &self.reference
+    }
+
+    /// Returns the t-digest of the values of the current, incomplete window.
+    #[inline]
+    pub fn window(&self) -> &TDigest {
+        &self.window
+    }
+
+    /// Clears the detector, removing the reference and the current window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{ChangePointDetector, Divergence};
+    ///
+    /// let mut detector = ChangePointDetector::new(Divergence::Ks, 0.5, 2);
+    /// detector.extend([1.0, 2.0, 3.0]);
+    /// detector.clear();
+    /// assert!(detector.reference().is_empty());
+    /// assert!(detector.window().is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.reference.clear();
+        self.window.clear();
+    }
+
+    /// Inserts a value, and returns a change event if it completes a window
+    /// that diverges from the reference.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{ChangePointDetector, Divergence};
+    ///
+    /// let mut detector = ChangePointDetector::new(Divergence::Ks, 0.5, 2);
+    /// assert_eq!(detector.insert(1.0), None);
+    /// assert_eq!(detector.insert(2.0), None);
+    /// assert_eq!(detector.insert(10.0), None);
+    ///
+    /// let event = detector.insert(20.0).unwrap();
+    /// assert_eq!(event.statistic(), 1.0);
+    /// assert_eq!(event.before(), 2.0);
+    /// assert_eq!(event.after(), 20.0);
+    /// ```
+    pub fn insert(&mut self, value: f32) -> Option<ChangeEvent> {
+        self.window.insert(value);
+        if self.window.len() < self.window_size as u64 {
+            return None;
+        }
+        if self.reference.is_empty() {
+            std::mem::swap(&mut self.reference, &mut self.window);
+            return None;
+        }
+
+        let event = self.compare();
+        if event.statistic > self.threshold {
+            std::mem::swap(&mut self.reference, &mut self.window);
+            self.window.clear();
+            Some(event)
+        } else {
+            self.reference |= &self.window;
+            self.window.clear();
+            None
+        }
+    }
+
+    /// Computes the divergence between the window and the reference.
+    fn compare(&mut self) -> ChangeEvent {
+        self.reference.compress();
+        self.window.compress();
+        let (statistic, level) = match self.divergence {
+            Divergence::Ks => ks(&self.reference, &self.window),
+            Divergence::Psi => psi(&self.reference, &self.window),
+        };
+        ChangeEvent {
+            statistic,
+            level,
+            before: self.reference.quantile_uncompressed(level),
+            after: self.window.quantile_uncompressed(level),
+        }
+    }
+}
+
+/// Returns the Kolmogorov-Smirnov statistic between two compressed t-digests,
+/// along with the reference level where the difference is the largest.
+pub(crate) fn ks<F: Float>(reference: &TDigest<F>, window: &TDigest<F>) -> (f64, f64) {
+    reference
+        .centroids
+        .iter()
+        .chain(window.centroids.iter())
+        .map(|c| {
+            let level = reference.cdf_uncompressed(c.mean);
+            ((level - window.cdf_uncompressed(c.mean)).abs(), level)
+        })
+        .fold((0., 0.5), |max, d| if d.0 > max.0 { d } else { max })
+}
+
+/// Returns the population stability index between two compressed t-digests,
+/// along with the middle level of the bin that contributes the most.
+fn psi(reference: &TDigest, window: &TDigest) -> (f64, f64) {
+    let mut statistic = 0.;
+    let mut max = (0., 0.5);
+    let (mut expected_below, mut actual_below) = (0., 0.);
+    for bin in 0..PSI_BINS {
+        let (expected_upto, actual_upto) = if bin + 1 == PSI_BINS {
+            (1., 1.)
+        } else {
+            let edge = reference.quantile_uncompressed((bin + 1) as f64 / PSI_BINS as f64);
+            (
+                reference.cdf_uncompressed(edge),
+                window.cdf_uncompressed(edge),
+            )
+        };
+        let expected = f64::max(expected_upto - expected_below, PSI_MIN_SHARE);
+        let actual = f64::max(actual_upto - actual_below, PSI_MIN_SHARE);
+        let contribution = (actual - expected) * (actual / expected).ln();
+        statistic += contribution;
+        if contribution > max.0 {
+            max = (contribution, (bin as f64 + 0.5) / PSI_BINS as f64);
+        }
+        (expected_below, actual_below) = (expected_upto, actual_upto);
+    }
+    (statistic, max.1)
+}
+
+/// A change in the distribution, reported by a [`ChangePointDetector`].
+///
+/// This `struct` is created by the [`insert`] method on
+/// [`ChangePointDetector`]. See its documentation for more.
+///
+/// [`insert`]: ChangePointDetector::insert
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChangeEvent {
+    statistic: f64,
+    level: f64,
+    before: f32,
+    after: f32,
+}
+
+impl ChangeEvent {
+    /// Returns the divergence between the window and the reference.
+    #[inline]
+    pub fn statistic(&self) -> f64 {
+        self.statistic
+    }
+
+    /// Returns the level of the quantile that moved the most.
+    #[inline]
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Returns the quantile at [`level`] of the reference distribution.
+    ///
+    /// [`level`]: ChangeEvent::level
+    #[inline]
+    pub fn before(&self) -> f32 {
+        self.before
+    }
+
+    /// Returns the quantile at [`level`] of the window that changed.
+    ///
+    /// [`level`]: ChangeEvent::level
+    #[inline]
+    pub fn after(&self) -> f32 {
+        self.after
+    }
+}
+
+impl Extend<f32> for ChangePointDetector {
+    /// Inserts the values, discarding change events.
+    fn extend<T: IntoIterator<Item = f32>>(&mut self, iter: T) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
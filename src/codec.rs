@@ -0,0 +1,126 @@
+//! Helpers to read and write serialized sketch states.
+//!
+//! ClickHouse writes integers and floats in little-endian order, and sizes as
+//! LEB128 variable-length integers. Other formats may use big-endian integers.
+
+use crate::{Error, Float};
+
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        if self.bytes.len() < N {
+            return Err(Error::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        Ok(head.try_into().unwrap())
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, Error> {
+        self.read_array().map(u8::from_le_bytes)
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, Error> {
+        self.read_array().map(u16::from_le_bytes)
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, Error> {
+        self.read_array().map(u32::from_le_bytes)
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, Error> {
+        self.read_array().map(u64::from_le_bytes)
+    }
+
+    pub(crate) fn read_f32(&mut self) -> Result<f32, Error> {
+        self.read_array().map(f32::from_le_bytes)
+    }
+
+    pub(crate) fn read_f64(&mut self) -> Result<f64, Error> {
+        self.read_array().map(f64::from_le_bytes)
+    }
+
+    /// Reads a value of the width of `F`, as written by [`write_float`].
+    pub(crate) fn read_float<F: Float>(&mut self) -> Result<F, Error> {
+        if std::mem::size_of::<F>() == 4 {
+            self.read_f32().map(|value| F::from_f64(value as f64))
+        } else {
+            self.read_f64().map(F::from_f64)
+        }
+    }
+
+    pub(crate) fn read_u32_be(&mut self) -> Result<u32, Error> {
+        self.read_array().map(u32::from_be_bytes)
+    }
+
+    pub(crate) fn read_u64_be(&mut self) -> Result<u64, Error> {
+        self.read_array().map(u64::from_be_bytes)
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: u64) -> Result<&'a [u8], Error> {
+        if (self.bytes.len() as u64) < len {
+            return Err(Error::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(len as usize);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    /// Reads a string, as its length as a variable-length integer followed by
+    /// its UTF-8 bytes.
+    pub(crate) fn read_string(&mut self) -> Result<String, Error> {
+        let len = self.read_varuint()?;
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::InvalidState("string is not UTF-8"))
+    }
+
+    pub(crate) fn read_varuint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::InvalidState("variable-length integer is too long"))
+    }
+
+    /// Checks that the whole state has been read.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::TrailingBytes)
+        }
+    }
+}
+
+pub(crate) fn write_varuint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+pub(crate) fn write_string(buf: &mut Vec<u8>, value: &str) {
+    write_varuint(buf, value.len() as u64);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Writes a value at the width of `F`, so that it reads back exactly.
+pub(crate) fn write_float<F: Float>(buf: &mut Vec<u8>, value: F) {
+    if std::mem::size_of::<F>() == 4 {
+        buf.extend_from_slice(&(value.to_f64() as f32).to_le_bytes());
+    } else {
+        buf.extend_from_slice(&value.to_f64().to_le_bytes());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{change_point::ks, Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Compares the t-digest with a `baseline`, such as the same metric
+    /// before a release, at each of `levels`.
+    ///
+    /// The report holds the quantiles of both t-digests, their differences,
+    /// the Kolmogorov-Smirnov distance between the distributions, and the
+    /// change in the number of elements. With the `serde` feature, it can be
+    /// serialized for dashboards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut baseline = TDigest::from([10.0, 20.0, 30.0]);
+    /// let mut current = TDigest::from([10.0, 25.0, 30.0, 40.0]);
+    ///
+    /// let report = current.compare(&mut baseline, &[0.5]);
+    /// assert_eq!(report.baseline(), [20.0]);
+    /// assert_eq!(report.deltas(), [5.0]);
+    /// assert_eq!(report.relative_changes(), [0.25]);
+    /// assert_eq!(report.count_change(), 1);
+    /// ```
+    pub fn compare(&mut self, baseline: &mut TDigest<F>, levels: &[f64]) -> ComparisonReport<F> {
+        self.compress();
+        baseline.compress();
+        let baseline_quantiles = baseline.quantiles_many_uncompressed(levels);
+        let current_quantiles = self.quantiles_many_uncompressed(levels);
+        let deltas: Vec<F> = current_quantiles
+            .iter()
+            .zip(baseline_quantiles.iter())
+            .map(|(&current, &baseline)| current - baseline)
+            .collect();
+        let relative_changes = deltas
+            .iter()
+            .zip(baseline_quantiles.iter())
+            .map(|(&delta, &baseline)| delta.to_f64() / baseline.to_f64().abs())
+            .collect();
+        let ks_distance = baseline.ks_distance_uncompressed(self);
+
+        ComparisonReport {
+            levels: levels.to_vec(),
+            baseline: baseline_quantiles,
+            current: current_quantiles,
+            deltas,
+            relative_changes,
+            ks_distance,
+            baseline_count: baseline.count,
+            current_count: self.count,
+        }
+    }
+
+    /// Returns the Kolmogorov-Smirnov distance between the distributions of
+    /// `self` and `other`, the largest difference between their cumulative
+    /// distribution functions, or NaN if either t-digest is empty.
+    ///
+    /// The distance ranges from 0 for equal distributions to 1 for disjoint
+    /// ones. The functions are compared at the means of the centroids of both
+    /// t-digests, where their differences are the largest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut baseline = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// let mut canary = TDigest::from_iter((0..1000).map(|v| (v + 100) as f32));
+    ///
+    /// assert!((canary.ks_distance(&mut baseline) - 0.1).abs() < 0.01);
+    /// assert_eq!(baseline.ks_distance(&mut baseline.clone()), 0.0);
+    /// ```
+    pub fn ks_distance(&mut self, other: &mut TDigest<F>) -> f64 {
+        self.compress();
+        other.compress();
+        self.ks_distance_uncompressed(other)
+    }
+
+    /// Returns the approximate 1-Wasserstein distance between the
+    /// distributions of `self` and `other`, or NaN if either t-digest is
+    /// empty.
+    ///
+    /// The distance is the area between the piecewise-linear cumulative
+    /// distribution functions of both t-digests, see
+    /// [`CdfFunction::wasserstein_distance`]. Unlike the Kolmogorov-Smirnov
+    /// distance, it grows with how far the distributions are shifted apart,
+    /// in the unit of the values.
+    ///
+    /// [`CdfFunction::wasserstein_distance`]: crate::CdfFunction::wasserstein_distance
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut baseline = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// let mut shifted = TDigest::from_iter((0..1000).map(|v| (v + 100) as f32));
+    ///
+    /// assert!((shifted.wasserstein_distance(&mut baseline) - 100.0).abs() < 1.0);
+    /// ```
+    pub fn wasserstein_distance(&mut self, other: &mut TDigest<F>) -> f64 {
+        self.compress();
+        other.compress();
+        self.wasserstein_distance_uncompressed(other)
+    }
+
+    pub(crate) fn wasserstein_distance_uncompressed(&self, other: &TDigest<F>) -> f64 {
+        self.to_cdf_uncompressed()
+            .wasserstein_distance(&other.to_cdf_uncompressed())
+    }
+
+    pub(crate) fn ks_distance_uncompressed(&self, other: &TDigest<F>) -> f64 {
+        if self.is_empty() || other.is_empty() {
+            f64::NAN
+        } else {
+            ks(self, other).0
+        }
+    }
+}
+
+/// A comparison of a t-digest with a baseline.
+///
+/// This `struct` is created by the [`compare`] method on [`TDigest`]. See its
+/// documentation for more.
+///
+/// [`compare`]: TDigest::compare
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComparisonReport<F = f32> {
+    levels: Vec<f64>,
+    baseline: Vec<F>,
+    current: Vec<F>,
+    deltas: Vec<F>,
+    relative_changes: Vec<f64>,
+    ks_distance: f64,
+    baseline_count: u64,
+    current_count: u64,
+}
+
+impl<F: Float> ComparisonReport<F> {
+    /// Returns the levels of the quantiles.
+    #[inline]
+    pub fn levels(&self) -> &[f64] {
+        &self.levels
+    }
+
+    /// Returns the quantiles of the baseline, one for each level.
+    #[inline]
+    pub fn baseline(&self) -> &[F] {
+        &self.baseline
+    }
+
+    /// Returns the quantiles of the compared t-digest, one for each level.
+    #[inline]
+    pub fn current(&self) -> &[F] {
+        &self.current
+    }
+
+    /// Returns the differences between the compared and baseline quantiles,
+    /// one for each level.
+    #[inline]
+    pub fn deltas(&self) -> &[F] {
+        &self.deltas
+    }
+
+    /// Returns the differences relative to the magnitude of the baseline
+    /// quantiles, one for each level.
+    ///
+    /// A relative change is infinite or NaN if the baseline quantile is 0.
+    #[inline]
+    pub fn relative_changes(&self) -> &[f64] {
+        &self.relative_changes
+    }
+
+    /// Returns the Kolmogorov-Smirnov distance between the distributions, the
+    /// largest difference between their cumulative distribution functions, or
+    /// NaN if either t-digest is empty.
+    #[inline]
+    pub fn ks_distance(&self) -> f64 {
+        self.ks_distance
+    }
+
+    /// Returns the number of elements of the baseline.
+    #[inline]
+    pub fn baseline_count(&self) -> u64 {
+        self.baseline_count
+    }
+
+    /// Returns the number of elements of the compared t-digest.
+    #[inline]
+    pub fn current_count(&self) -> u64 {
+        self.current_count
+    }
+
+    /// Returns the change in the number of elements, from the baseline to the
+    /// compared t-digest.
+    #[inline]
+    pub fn count_change(&self) -> i64 {
+        self.current_count as i64 - self.baseline_count as i64
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<F: Float + serde::Serialize> serde::Serialize for ComparisonReport<F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        // Unlike sketch states, reports are serialized with named fields, for
+        // consumption by dashboards.
+        let mut report = serializer.serialize_struct("ComparisonReport", 9)?;
+        report.serialize_field("levels", &self.levels)?;
+        report.serialize_field("baseline", &self.baseline)?;
+        report.serialize_field("current", &self.current)?;
+        report.serialize_field("deltas", &self.deltas)?;
+        report.serialize_field("relative_changes", &self.relative_changes)?;
+        report.serialize_field("ks_distance", &self.ks_distance)?;
+        report.serialize_field("baseline_count", &self.baseline_count)?;
+        report.serialize_field("current_count", &self.current_count)?;
+        report.serialize_field("count_change", &self.count_change())?;
+        report.end()
+    }
+}
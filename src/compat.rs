@@ -0,0 +1,342 @@
+//! ClickHouse parity checks: the aggregate state format of `quantileTDigest`,
+//! and golden vectors of quantile estimates.
+//!
+//! ClickHouse serializes a t-digest state as the number of centroids, as a
+//! LEB128 variable-length integer, followed by a 32-bit little-endian mean
+//! and weight for each centroid. [`encode`] and [`decode`] read and write this
+//! format, and [`quantiles`] evaluates a state as `quantilesTDigestMerge`
+//! does.
+//!
+//! The [`golden_vectors`] are the parity cases that this crate is tested
+//! against, mostly streams of infinite values whose estimates depend on the
+//! exact compression and interpolation rules of ClickHouse. Each vector can
+//! print the ClickHouse query that reproduces it with
+//! [`GoldenVector::sql`], so that integrators can check their own server
+//! versions and catch changes early.
+//!
+//! This module requires the `compat` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use tdigest_ch::compat;
+//!
+//! for vector in compat::golden_vectors() {
+//!     let mut digest = vector.digest();
+//!     let state = compat::encode(&mut digest);
+//!     let estimates = compat::quantiles(&state, vector.levels()).unwrap();
+//!     assert!(vector.matches(&estimates), "{}", vector.name());
+//! }
+//! ```
+
+use crate::{
+    codec::{self, Reader},
+    Error, TDigest,
+};
+
+/// Serializes `digest` in the state format of ClickHouse `quantileTDigest`,
+/// once compressed.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{compat, TDigest};
+///
+/// let mut digest = TDigest::new();
+/// digest.insert_many(1.0, 2);
+/// assert_eq!(
+///     compat::encode(&mut digest),
+///     [
+///         0x01, // One centroid.
+///         0x00, 0x00, 0x80, 0x3f, // Mean of 1.0.
+///         0x00, 0x00, 0x00, 0x40, // Weight of 2.0.
+///     ],
+/// );
+/// ```
+pub fn encode(digest: &mut TDigest) -> Vec<u8> {
+    digest.compress();
+    let mut buf = Vec::with_capacity(10 + digest.centroids.len() * 8);
+    codec::write_varuint(&mut buf, digest.centroids.len() as u64);
+    for c in digest.centroids.iter() {
+        buf.extend_from_slice(&c.mean.to_le_bytes());
+        buf.extend_from_slice(&(c.count as f32).to_le_bytes());
+    }
+    buf
+}
+
+/// Deserializes a t-digest from the state format of ClickHouse
+/// `quantileTDigest`, and compresses it as ClickHouse does.
+///
+/// # Errors
+///
+/// Returns an error if `bytes` is not a valid state, or if a centroid has a
+/// NaN mean or a weight that is not a non-negative integer.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{compat, Error};
+///
+/// let bytes = [0x01, 0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0x40];
+/// let mut digest = compat::decode(&bytes).unwrap();
+/// assert_eq!(digest.len(), 2);
+/// assert_eq!(digest.quantile(0.5), 1.0);
+///
+/// assert_eq!(compat::decode(&bytes[..5]), Err(Error::UnexpectedEof));
+/// ```
+pub fn decode(bytes: &[u8]) -> Result<TDigest, Error> {
+    let mut reader = Reader::new(bytes);
+    let size = reader.read_varuint()?;
+    let mut digest = TDigest::new();
+    for _ in 0..size {
+        let mean = reader.read_f32()?;
+        let count = reader.read_f32()?;
+        if mean.is_nan() {
+            return Err(Error::InvalidState("centroid mean is NaN"));
+        }
+        if !(count >= 0. && count.fract() == 0.) {
+            return Err(Error::InvalidState("centroid weight is not an integer"));
+        }
+        digest.insert_many(mean, count as u64);
+    }
+    reader.finish()?;
+    digest.compress();
+    Ok(digest)
+}
+
+/// Evaluates the quantiles at `levels` of a state in the format of ClickHouse
+/// `quantileTDigest`, as `quantilesTDigestMerge` does.
+///
+/// # Errors
+///
+/// Returns an error if `state` is not valid, see [`decode`].
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{compat, TDigest};
+///
+/// let state = compat::encode(&mut TDigest::from([1.0, 2.0, 3.0]));
+/// assert_eq!(compat::quantiles(&state, &[0.0, 0.5]).unwrap(), [1.0, 2.0]);
+/// ```
+pub fn quantiles(state: &[u8], levels: &[f64]) -> Result<Vec<f32>, Error> {
+    Ok(decode(state)?.quantiles_many_uncompressed(levels))
+}
+
+/// A stream of values, along with the quantile estimates of ClickHouse.
+///
+/// The values are inserted in order: each run of `(value, count)` inserts
+/// `count` copies of `value`, and the whole sequence of runs is repeated a
+/// number of times.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoldenVector {
+    name: &'static str,
+    runs: &'static [(f32, usize)],
+    repeat: usize,
+    levels: &'static [f64],
+    expected: &'static [f32],
+}
+
+impl GoldenVector {
+    /// Returns the name of the vector.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the runs of values, as `(value, count)` pairs.
+    #[inline]
+    pub fn runs(&self) -> &'static [(f32, usize)] {
+        self.runs
+    }
+
+    /// Returns the number of times that the runs are repeated.
+    #[inline]
+    pub fn repeat(&self) -> usize {
+        self.repeat
+    }
+
+    /// Returns the levels of the quantiles.
+    #[inline]
+    pub fn levels(&self) -> &'static [f64] {
+        self.levels
+    }
+
+    /// Returns the quantiles estimated by ClickHouse, one for each level.
+    #[inline]
+    pub fn expected(&self) -> &'static [f32] {
+        self.expected
+    }
+
+    /// Returns the values of the stream, in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = f32> {
+        let runs = self.runs;
+        (0..self.repeat).flat_map(move |_| {
+            runs.iter()
+                .flat_map(|&(value, count)| std::iter::repeat_n(value, count))
+        })
+    }
+
+    /// Returns a t-digest of the values of the stream, with the default
+    /// configuration of ClickHouse.
+    pub fn digest(&self) -> TDigest {
+        TDigest::from_iter(self.values())
+    }
+
+    /// Returns `true` if `estimates` are the expected quantiles, with NaN
+    /// matching NaN.
+    pub fn matches(&self, estimates: &[f32]) -> bool {
+        estimates.len() == self.expected.len()
+            && estimates
+                .iter()
+                .zip(self.expected)
+                .all(|(&estimate, &expected)| {
+                    estimate == expected || (estimate.is_nan() && expected.is_nan())
+                })
+    }
+
+    /// Returns a ClickHouse query that evaluates the quantiles of the stream,
+    /// as a single row with a single array column.
+    ///
+    /// The query runs on a single thread, so that the values are inserted in
+    /// order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::compat;
+    ///
+    /// let vector = compat::golden_vectors()[0];
+    /// assert_eq!(
+    ///     vector.sql(),
+    ///     "SELECT quantilesTDigest(0.5)(x) FROM (SELECT multiIf(number % 3 < 1, toFloat32(1), \
+    ///      number % 3 < 2, toFloat32(2), toFloat32(3)) AS x FROM numbers(3)) \
+    ///      SETTINGS max_threads = 1",
+    /// );
+    /// ```
+    pub fn sql(&self) -> String {
+        let period: usize = self.runs.iter().map(|run| run.1).sum();
+        let mut branches = Vec::with_capacity(self.runs.len());
+        let mut end = 0;
+        for (i, &(value, count)) in self.runs.iter().enumerate() {
+            end += count;
+            let value = if value.is_infinite() {
+                format!("toFloat32({}inf)", if value < 0. { "-" } else { "" })
+            } else {
+                format!("toFloat32({value})")
+            };
+            if i + 1 == self.runs.len() {
+                branches.push(value);
+            } else {
+                branches.push(format!("number % {period} < {end}, {value}"));
+            }
+        }
+        let levels: Vec<String> = self.levels.iter().map(f64::to_string).collect();
+        format!(
+            "SELECT quantilesTDigest({})(x) FROM (SELECT multiIf({}) AS x FROM numbers({})) \
+             SETTINGS max_threads = 1",
+            levels.join(", "),
+            branches.join(", "),
+            period * self.repeat,
+        )
+    }
+}
+
+const INF: f32 = f32::INFINITY;
+const LEVELS: &[f64] = &[0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99];
+
+/// Returns the golden vectors.
+pub fn golden_vectors() -> &'static [GoldenVector] {
+    const fn vector(
+        name: &'static str,
+        runs: &'static [(f32, usize)],
+        repeat: usize,
+        levels: &'static [f64],
+        expected: &'static [f32],
+    ) -> GoldenVector {
+        GoldenVector {
+            name,
+            runs,
+            repeat,
+            levels,
+            expected,
+        }
+    }
+
+    const VECTORS: &[GoldenVector] = &[
+        vector("small", &[(1., 1), (2., 1), (3., 1)], 1, &[0.5], &[2.]),
+        vector(
+            "negative",
+            &[(-1., 1), (-2., 1), (-3., 1)],
+            1,
+            &[0.5],
+            &[-2.],
+        ),
+        vector(
+            "infinity_1",
+            &[(INF, 1), (-INF, 499_999), (INF, 500_000)],
+            1,
+            LEVELS,
+            &[-INF, -INF, -INF, f32::NAN, INF, INF, INF],
+        ),
+        vector(
+            "infinity_1b",
+            &[(INF, 500_000), (-INF, 500_000)],
+            1,
+            LEVELS,
+            &[-INF, -INF, -INF, f32::NAN, INF, INF, INF],
+        ),
+        vector(
+            "infinity_1c",
+            &[(INF, 500_000), (0., 500_000)],
+            1,
+            LEVELS,
+            &[0., 0., 0., INF, INF, INF, INF],
+        ),
+        vector(
+            "infinity_1d",
+            &[(INF, 1), (-INF, 499_999), (0., 500_000)],
+            1,
+            LEVELS,
+            &[-INF, -INF, -INF, -INF, 0., 0., 0.],
+        ),
+        vector(
+            "infinity_1e",
+            &[(0., 1), (INF, 499_999), (-INF, 500_000)],
+            1,
+            LEVELS,
+            &[-INF, -INF, -INF, 0., INF, INF, INF],
+        ),
+        vector(
+            "infinity_1f",
+            &[(0., 1), (-INF, 499_999), (INF, 500_000)],
+            1,
+            LEVELS,
+            &[-INF, -INF, -INF, 0., INF, INF, INF],
+        ),
+        vector(
+            "infinity_2",
+            &[(INF, 1), (-INF, 1)],
+            150,
+            &[0.05, 0.5, 0.95],
+            &[-INF, -INF, INF],
+        ),
+        vector("infinity_3", &[(INF, 300)], 1, &[0.5], &[INF]),
+        vector("infinity_3b", &[(-INF, 300)], 1, &[0.5], &[-INF]),
+        vector(
+            "infinity_4",
+            &[(INF, 1), (0., 1), (-INF, 1)],
+            1,
+            &[0.5],
+            &[0.],
+        ),
+        vector(
+            "infinity_4b",
+            &[(INF, 2), (0., 1), (-INF, 2), (0., 1)],
+            1,
+            &[0.5],
+            &[0.],
+        ),
+    ];
+    VECTORS
+}
@@ -0,0 +1,453 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    CdfFunction, CentroidWeight, DigestStats, Float, QuantileExplanation, Quantiles, RangeStats,
+    TDigest, Unit,
+};
+
+impl<F: Float> TDigest<F> {
+    /// Compresses the t-digest, and returns it as an immutable
+    /// `CompressedTDigest`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// let frozen = digest.freeze();
+    /// assert_eq!(frozen.quantile(0.5), 3.0);
+    /// ```
+    #[must_use]
+    pub fn freeze(mut self) -> CompressedTDigest<F> {
+        self.compress();
+        CompressedTDigest { digest: self }
+    }
+}
+
+/// An immutable, compressed t-digest.
+///
+/// A `TDigest` compresses its elements lazily, so that its queries take
+/// `&mut self`. A `CompressedTDigest` is compressed once, when created with
+/// [`TDigest::freeze`], and answers the same queries with `&self`: it can be
+/// shared across threads without locking. It converts back to a `TDigest`
+/// with [`CompressedTDigest::thaw`], to insert more elements.
+///
+/// # Examples
+///
+/// ```
+/// use std::{sync::Arc, thread};
+///
+/// use tdigest_ch::TDigest;
+///
+/// let digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+/// let frozen = Arc::new(digest.freeze());
+///
+/// let handles: Vec<_> = [0.5, 0.99]
+///     .into_iter()
+///     .map(|level| {
+///         let frozen = Arc::clone(&frozen);
+///         thread::spawn(move || frozen.quantile(level))
+///     })
+///     .collect();
+/// for handle in handles {
+///     assert!(handle.join().unwrap() > 400.0);
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompressedTDigest<F = f32> {
+    digest: TDigest<F>,
+}
+
+impl<F: Float> PartialEq for CompressedTDigest<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.digest == other.digest
+    }
+}
+
+impl<F: Float> CompressedTDigest<F> {
+    /// Converts the compressed t-digest back into a `TDigest`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let frozen = TDigest::from([1.0, 2.0]).freeze();
+    /// let mut digest = frozen.thaw();
+    /// digest.insert(3.0);
+    /// assert_eq!(digest.len(), 3);
+    /// ```
+    #[must_use]
+    pub fn thaw(self) -> TDigest<F> {
+        self.digest
+    }
+
+    /// Returns the number of elements. See [`TDigest::len`].
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.digest.len()
+    }
+
+    /// Returns `true` if there are no elements. See [`TDigest::is_empty`].
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.digest.is_empty()
+    }
+
+    /// Returns the number of NaN values that were inserted. See
+    /// [`TDigest::nan_count`].
+    #[inline]
+    pub fn nan_count(&self) -> u64 {
+        self.digest.nan_count()
+    }
+
+    /// Returns the mean of the elements. See [`TDigest::mean`].
+    #[inline]
+    pub fn mean(&self) -> f64 {
+        self.digest.mean()
+    }
+
+    /// Returns the exact smallest value. See [`TDigest::min`].
+    #[inline]
+    pub fn min(&self) -> F {
+        self.digest.min()
+    }
+
+    /// Returns the exact largest value. See [`TDigest::max`].
+    #[inline]
+    pub fn max(&self) -> F {
+        self.digest.max()
+    }
+
+    /// Returns the smallest retained values. See [`TDigest::smallest`].
+    #[inline]
+    pub fn smallest(&self) -> &[F] {
+        self.digest.smallest()
+    }
+
+    /// Returns the largest retained values. See [`TDigest::largest`].
+    #[inline]
+    pub fn largest(&self) -> &[F] {
+        self.digest.largest()
+    }
+
+    /// Returns the labels. See [`TDigest::labels`].
+    #[inline]
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        self.digest.labels()
+    }
+
+    /// Returns the unit of the values. See [`TDigest::unit`].
+    #[inline]
+    pub fn unit(&self) -> Option<&Unit> {
+        self.digest.unit()
+    }
+
+    /// Returns the estimated quantile at `level`. See [`TDigest::quantile`].
+    pub fn quantile(&self, level: f64) -> F {
+        self.digest.quantile_uncompressed(level)
+    }
+
+    /// Returns the estimated quantile at `level`, or `None` if there are no
+    /// elements or if `level` is not between 0 and 1. See
+    /// [`TDigest::quantile_checked`].
+    pub fn quantile_checked(&self, level: f64) -> Option<F> {
+        self.digest.quantile_checked_uncompressed(level)
+    }
+
+    /// Returns the estimated percentile, for a `percentile` between 0 and
+    /// 100. See [`TDigest::percentile`].
+    pub fn percentile(&self, percentile: f64) -> F {
+        self.quantile(percentile / 100.)
+    }
+
+    /// Returns the estimated median. See [`TDigest::median`].
+    pub fn median(&self) -> F {
+        self.quantile(0.5)
+    }
+
+    /// Returns the estimated quantile at level `1 - tail_level`, computed
+    /// from the upper end of the distribution. See [`TDigest::top_quantile`].
+    pub fn top_quantile(&self, tail_level: f64) -> F {
+        self.digest.top_quantile_uncompressed(tail_level)
+    }
+
+    /// Returns the estimated quantiles at each of `levels`. See
+    /// [`TDigest::quantiles_many`].
+    pub fn quantiles_many(&self, levels: &[f64]) -> Vec<F> {
+        self.digest.quantiles_many_uncompressed(levels)
+    }
+
+    /// Returns the estimated quantile at `level`, along with the details of
+    /// how it was estimated. See [`TDigest::quantile_explain`].
+    pub fn quantile_explain(&self, level: f64) -> QuantileExplanation<F> {
+        self.digest.explain_uncompressed(level)
+    }
+
+    /// Returns a quantile estimator borrowing the t-digest. See
+    /// [`TDigest::quantiles`].
+    pub fn quantiles(&self) -> Quantiles<'_, F> {
+        Quantiles {
+            digest: &self.digest,
+        }
+    }
+
+    /// Returns the estimated fraction of the elements that are lower than or
+    /// equal to `value`. See [`TDigest::cdf`].
+    pub fn cdf(&self, value: F) -> f64 {
+        self.digest.cdf_uncompressed(value)
+    }
+
+    /// Returns the points of the cumulative distribution function. See
+    /// [`TDigest::ecdf`].
+    pub fn ecdf(&self) -> impl Iterator<Item = (F, f64)> {
+        self.to_cdf().into_points()
+    }
+
+    /// Returns the estimated fractions of the elements that are lower than or
+    /// equal to each of `values`. See [`TDigest::cdf_many`].
+    pub fn cdf_many(&self, values: &[F]) -> Vec<f64> {
+        self.digest.cdf_many_uncompressed(values)
+    }
+
+    /// Returns the estimated percentile rank of each of `values`. See
+    /// [`TDigest::score`].
+    pub fn score(&self, values: &[F]) -> Vec<f64> {
+        self.digest.score_uncompressed(values)
+    }
+
+    /// Returns the estimated fraction of the elements that are greater than
+    /// `value`. See [`TDigest::sf`].
+    pub fn sf(&self, value: F) -> f64 {
+        self.digest.sf_uncompressed(value)
+    }
+
+    /// Returns the cumulative distribution function as a standalone
+    /// piecewise-linear function. See [`TDigest::to_cdf`].
+    pub fn to_cdf(&self) -> CdfFunction<F> {
+        self.digest.to_cdf_uncompressed()
+    }
+
+    /// Returns the Kolmogorov-Smirnov distance between the distributions of
+    /// `self` and `other`. See [`TDigest::ks_distance`].
+    pub fn ks_distance(&self, other: &CompressedTDigest<F>) -> f64 {
+        self.digest.ks_distance_uncompressed(&other.digest)
+    }
+
+    /// Returns the approximate 1-Wasserstein distance between the
+    /// distributions of `self` and `other`. See
+    /// [`TDigest::wasserstein_distance`].
+    pub fn wasserstein_distance(&self, other: &CompressedTDigest<F>) -> f64 {
+        self.digest.wasserstein_distance_uncompressed(&other.digest)
+    }
+
+    /// Returns the estimated probability density at `value`. See
+    /// [`TDigest::density`].
+    pub fn density(&self, value: F) -> f64 {
+        self.digest.density_uncompressed(value)
+    }
+
+    /// Returns the estimated quantile density at `level`. See
+    /// [`TDigest::quantile_density`].
+    pub fn quantile_density(&self, level: f64) -> f64 {
+        self.digest.quantile_density_uncompressed(level)
+    }
+
+    /// Returns the estimated mode. See [`TDigest::mode`].
+    pub fn mode(&self) -> F {
+        self.digest.mode_uncompressed()
+    }
+
+    /// Draws a random value from the distribution of the t-digest. See
+    /// [`TDigest::sample`].
+    ///
+    /// This method requires the `rand` feature.
+    #[cfg(feature = "rand")]
+    pub fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> F {
+        self.digest.sample_uncompressed(rng)
+    }
+
+    /// Returns an endless iterator of random values drawn from the
+    /// distribution of the t-digest. See [`TDigest::sample_iter`].
+    ///
+    /// This method requires the `rand` feature.
+    #[cfg(feature = "rand")]
+    pub fn sample_iter<'a, R: rand::Rng + ?Sized>(
+        &'a self,
+        rng: &'a mut R,
+    ) -> impl Iterator<Item = F> + 'a {
+        let digest = &self.digest;
+        std::iter::from_fn(move || (!digest.is_empty()).then(|| digest.sample_uncompressed(rng)))
+    }
+
+    /// Returns the estimated number of elements that are lower than or equal
+    /// to `value`. See [`TDigest::rank`].
+    pub fn rank(&self, value: F) -> f64 {
+        self.cdf(value) * self.digest.count as f64
+    }
+
+    /// Returns the estimated `k`-th smallest element, counting from 0. See
+    /// [`TDigest::value_at_rank`].
+    pub fn value_at_rank(&self, k: u64) -> F {
+        self.digest.value_at_rank_uncompressed(k)
+    }
+
+    /// Returns the estimated Apdex score. See [`TDigest::apdex`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tolerating` is lower than `satisfied`.
+    pub fn apdex(&self, satisfied: F, tolerating: F) -> f64 {
+        self.digest.apdex_uncompressed(satisfied, tolerating)
+    }
+
+    /// Returns statistics of the elements between two quantile levels. See
+    /// [`TDigest::range_stats`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the levels are not between 0 and 1, or if `lo_level` is
+    /// greater than `hi_level`.
+    pub fn range_stats(&self, lo_level: f64, hi_level: f64) -> RangeStats<F> {
+        self.digest.range_stats_uncompressed(lo_level, hi_level)
+    }
+
+    /// Returns the estimated mean of the elements between two quantile
+    /// levels. See [`TDigest::trimmed_mean`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the levels are not between 0 and 1, or if `lower` is greater
+    /// than `upper`.
+    pub fn trimmed_mean(&self, lower: f64, upper: f64) -> F {
+        F::from_f64(self.range_stats(lower, upper).mean())
+    }
+
+    /// Returns the estimated quantiles at two levels. See
+    /// [`TDigest::quantile_range`].
+    pub fn quantile_range(&self, lo_level: f64, hi_level: f64) -> (F, F) {
+        self.digest.quantile_range_uncompressed(lo_level, hi_level)
+    }
+
+    /// Returns the estimated winsorized mean between two quantile levels. See
+    /// [`TDigest::winsorized_mean`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the levels are not between 0 and 1, or if `lower` is greater
+    /// than `upper`.
+    pub fn winsorized_mean(&self, lower: f64, upper: f64) -> F {
+        self.digest.winsorized_mean_uncompressed(lower, upper)
+    }
+
+    /// Returns the estimated difference between the quantiles at two levels.
+    /// See [`TDigest::range`].
+    pub fn range(&self, lo_level: f64, hi_level: f64) -> F {
+        self.digest.range_uncompressed(lo_level, hi_level)
+    }
+
+    /// Returns the estimated interquartile range. See [`TDigest::iqr`].
+    pub fn iqr(&self) -> F {
+        self.range(0.25, 0.75)
+    }
+
+    /// Returns the estimated ratio of the quantile at `hi` to the quantile at
+    /// `lo`. See [`TDigest::tail_ratio`].
+    pub fn tail_ratio(&self, hi: f64, lo: f64) -> F {
+        self.digest.tail_ratio_uncompressed(hi, lo)
+    }
+
+    /// Returns the estimated number of elements between `lo` and `hi`,
+    /// inclusive. See [`TDigest::count_between`].
+    pub fn count_between(&self, lo: F, hi: F) -> f64 {
+        self.digest.count_between_uncompressed(lo, hi)
+    }
+
+    /// Returns the estimated quantile at `level`, along with the estimated
+    /// number of elements greater than it. See [`TDigest::tail_mass_above`].
+    pub fn tail_mass_above(&self, level: f64) -> (F, f64) {
+        self.digest.tail_mass_above_uncompressed(level)
+    }
+
+    /// Returns the estimated quantile at `level`, along with the estimated
+    /// number of elements lower than or equal to it. See
+    /// [`TDigest::tail_mass_below`].
+    pub fn tail_mass_below(&self, level: f64) -> (F, f64) {
+        let (threshold, above) = self.tail_mass_above(level);
+        (threshold, self.len() as f64 - above)
+    }
+
+    /// Returns the estimated number of elements in each bin delimited by
+    /// `edges`. See [`TDigest::histogram`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `edges` are not strictly increasing.
+    pub fn histogram(&self, edges: &[F]) -> Vec<u64> {
+        self.digest.histogram_uncompressed(edges)
+    }
+
+    /// Returns the cut points that split the elements into `n` bins of equal
+    /// weight. See [`TDigest::equal_weight_bins`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn equal_weight_bins(&self, n: usize) -> Vec<F> {
+        self.digest.equal_weight_bins_uncompressed(n)
+    }
+
+    /// Returns a worst-case estimate of the error of the quantile at `level`.
+    /// See [`TDigest::error_bound`].
+    pub fn error_bound(&self, level: f64) -> F {
+        self.digest.error_bound_uncompressed(level)
+    }
+
+    /// Returns health statistics of the t-digest. See [`TDigest::stats`].
+    pub fn stats(&self) -> DigestStats<F> {
+        self.digest.stats()
+    }
+
+    /// Returns a table of quantiles at evenly spaced levels. See
+    /// [`TDigest::build_lookup_table`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `resolution` is lower than 2.
+    pub fn build_lookup_table(&self, resolution: usize) -> Vec<F> {
+        self.digest.build_lookup_table_uncompressed(resolution)
+    }
+
+    /// Returns a compressed t-digest of at most `max_centroids` centroids
+    /// that approximates the same distribution. See [`TDigest::resampled`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_centroids` is zero.
+    pub fn resampled(&self, max_centroids: usize) -> CompressedTDigest<F> {
+        CompressedTDigest {
+            digest: self.digest.resampled_uncompressed(max_centroids),
+        }
+    }
+
+    /// Returns the weight of each centroid, along with its bound. See
+    /// [`TDigest::centroid_weight_profile`].
+    pub fn centroid_weight_profile(&self) -> Vec<CentroidWeight<F>> {
+        self.digest.centroid_weight_profile()
+    }
+}
+
+impl<F: Float> From<CompressedTDigest<F>> for TDigest<F> {
+    #[inline]
+    fn from(digest: CompressedTDigest<F>) -> Self {
+        digest.thaw()
+    }
+}
+
+impl<F: Float> From<TDigest<F>> for CompressedTDigest<F> {
+    #[inline]
+    fn from(digest: TDigest<F>) -> Self {
+        digest.freeze()
+    }
+}
@@ -0,0 +1,670 @@
+use std::{
+    collections::BTreeMap,
+    ops::{BitOr, BitOrAssign, Bound, RangeBounds},
+};
+
+use crate::TDigest;
+
+/// T-digests of a distribution, conditioned on a categorical value.
+///
+/// Values are inserted along with a condition, such as a status code or the
+/// bin of a numeric value (see [`Bins`]), and each condition gets its own
+/// t-digest. This answers conditional quantile queries, and compares them
+/// across conditions.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::ConditionalDigests;
+///
+/// let mut latencies = ConditionalDigests::new();
+///
+/// // Record the latency of some requests, by status code.
+/// latencies.insert(200, 12.0);
+/// latencies.insert(200, 15.0);
+/// latencies.insert(200, 18.0);
+/// latencies.insert(500, 250.0);
+///
+/// assert_eq!(latencies.quantile(&200, 0.5), 15.0);
+/// assert_eq!(latencies.quantile(&500, 0.5), 250.0);
+/// assert!(latencies.quantile(&404, 0.5).is_nan());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConditionalDigests<K> {
+    template: TDigest,
+    digests: BTreeMap<K, TDigest>,
+}
+
+impl<K: Ord + Clone> ConditionalDigests<K> {
+    /// Creates an empty `ConditionalDigests`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    /// let digests: ConditionalDigests<u16> = ConditionalDigests::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_template(TDigest::new())
+    }
+
+    /// Creates an empty `ConditionalDigests` whose t-digests use the
+    /// configuration of `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{ConditionalDigests, TDigest};
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.max_centroids(256);
+    ///
+    /// let digests: ConditionalDigests<u16> = ConditionalDigests::with_template(builder.build());
+    /// ```
+    #[must_use]
+    pub fn with_template(template: TDigest) -> Self {
+        assert!(template.is_empty(), "template must be empty");
+        Self {
+            template,
+            digests: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of values, across all conditions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// assert_eq!(digests.len(), 0);
+    /// digests.insert("a", 1.0);
+    /// digests.insert("b", 2.0);
+    /// assert_eq!(digests.len(), 2);
+    /// ```
+    pub fn len(&self) -> u64 {
+        self.digests.values().map(TDigest::len).sum()
+    }
+
+    /// Returns `true` if there are no values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// assert!(digests.is_empty());
+    /// digests.insert("a", 1.0);
+    /// assert!(!digests.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.digests.values().all(TDigest::is_empty)
+    }
+
+    /// Clears the container, removing all conditions and values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.insert("a", 1.0);
+    /// digests.clear();
+    /// assert!(digests.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.digests.clear();
+    }
+
+    /// Returns the conditions that have values, in increasing order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.insert("b", 1.0);
+    /// digests.insert("a", 2.0);
+    /// assert_eq!(digests.conditions().collect::<Vec<_>>(), [&"a", &"b"]);
+    /// ```
+    pub fn conditions(&self) -> impl Iterator<Item = &K> {
+        self.digests.keys()
+    }
+
+    /// Returns the t-digest of a condition, if it has values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.insert("a", 1.0);
+    /// assert_eq!(digests.get(&"a").map(|d| d.len()), Some(1));
+    /// assert!(digests.get(&"b").is_none());
+    /// ```
+    pub fn get(&self, condition: &K) -> Option<&TDigest> {
+        self.digests.get(condition)
+    }
+
+    /// Adds a value under a condition.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.insert(200, 1.0);
+    /// assert_eq!(digests.len(), 1);
+    /// ```
+    #[inline]
+    pub fn insert(&mut self, condition: K, value: f32) {
+        self.insert_many(condition, value, 1);
+    }
+
+    /// Adds multiple values under a condition.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.insert_many(200, 1.0, 3);
+    /// assert_eq!(digests.len(), 3);
+    /// ```
+    pub fn insert_many(&mut self, condition: K, value: f32, count: u64) {
+        if count == 0 || value.is_nan() {
+            return;
+        }
+        self.digest_mut(condition).insert_many(value, count);
+    }
+
+    /// Returns the estimated quantile of the values under a condition, or NaN
+    /// if it has none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.extend([("a", 1.0), ("a", 2.0), ("a", 3.0)]);
+    /// assert_eq!(digests.quantile(&"a", 0.5), 2.0);
+    /// ```
+    pub fn quantile(&mut self, condition: &K, level: f64) -> f32 {
+        match self.digests.get_mut(condition) {
+            Some(digest) => digest.quantile(level),
+            None => f32::NAN,
+        }
+    }
+
+    /// Returns the estimated quantile of each condition, in increasing order of
+    /// conditions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.extend([("a", 1.0), ("b", 5.0)]);
+    /// assert_eq!(digests.quantiles(0.5), [(&"a", 1.0), (&"b", 5.0)]);
+    /// ```
+    pub fn quantiles(&mut self, level: f64) -> Vec<(&K, f32)> {
+        self.digests
+            .iter_mut()
+            .map(|(condition, digest)| (condition, digest.quantile(level)))
+            .collect()
+    }
+
+    /// Returns how much the quantile at `level` moves from condition `from` to
+    /// condition `to`, or NaN if either has no values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.extend([("small", 10.0), ("large", 25.0)]);
+    /// assert_eq!(digests.shift(&"small", &"large", 0.5), 15.0);
+    /// ```
+    pub fn shift(&mut self, from: &K, to: &K, level: f64) -> f32 {
+        self.quantile(to, level) - self.quantile(from, level)
+    }
+
+    /// Returns a t-digest of the values under all conditions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut digests = ConditionalDigests::new();
+    /// digests.extend([("a", 1.0), ("b", 2.0), ("c", 3.0)]);
+    /// assert_eq!(digests.overall().quantile(0.5), 2.0);
+    /// ```
+    pub fn overall(&self) -> TDigest {
+        let mut overall = self.template.clone();
+        for digest in self.digests.values() {
+            overall |= digest;
+        }
+        overall
+    }
+
+    fn digest_mut(&mut self, condition: K) -> &mut TDigest {
+        self.digests
+            .entry(condition)
+            .or_insert_with(|| self.template.clone())
+    }
+}
+
+impl<K: Ord + Clone> BitOr<&ConditionalDigests<K>> for &ConditionalDigests<K> {
+    type Output = ConditionalDigests<K>;
+
+    /// Returns the union of `self` and `rhs` as a new `ConditionalDigests`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let a = ConditionalDigests::from([("a", 1.0), ("b", 2.0)]);
+    /// let b = ConditionalDigests::from([("b", 3.0)]);
+    ///
+    /// let c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 3);
+    /// assert_eq!(c.get(&"b").unwrap().len(), 2);
+    /// ```
+    fn bitor(self, rhs: &ConditionalDigests<K>) -> ConditionalDigests<K> {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl<K: Ord + Clone> BitOrAssign<&ConditionalDigests<K>> for ConditionalDigests<K> {
+    /// Merges `self` and `rhs` into `self`, condition by condition.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let mut a = ConditionalDigests::from([("a", 1.0), ("b", 2.0)]);
+    /// let b = ConditionalDigests::from([("b", 3.0)]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 3);
+    /// assert_eq!(a.get(&"b").unwrap().len(), 2);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &ConditionalDigests<K>) {
+        for (condition, digest) in rhs.digests.iter() {
+            *self.digest_mut(condition.clone()) |= digest;
+        }
+    }
+}
+
+impl<K: Ord + Clone> Default for ConditionalDigests<K> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone> Extend<(K, f32)> for ConditionalDigests<K> {
+    fn extend<I: IntoIterator<Item = (K, f32)>>(&mut self, iter: I) {
+        for (condition, value) in iter {
+            self.insert(condition, value);
+        }
+    }
+}
+
+impl<K: Ord + Clone, const N: usize> From<[(K, f32); N]> for ConditionalDigests<K> {
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::ConditionalDigests;
+    ///
+    /// let digests1 = ConditionalDigests::from([(1, 1.0), (2, 2.0)]);
+    /// let digests2: ConditionalDigests<_> = [(1, 1.0), (2, 2.0)].into();
+    /// assert_eq!(digests1, digests2);
+    /// ```
+    fn from(array: [(K, f32); N]) -> Self {
+        let mut digests = ConditionalDigests::new();
+        digests.extend(array);
+        digests
+    }
+}
+
+impl<K: Ord + Clone> FromIterator<(K, f32)> for ConditionalDigests<K> {
+    fn from_iter<I: IntoIterator<Item = (K, f32)>>(iter: I) -> Self {
+        let mut digests = ConditionalDigests::new();
+        digests.extend(iter);
+        digests
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K: serde::Serialize> serde::Serialize for ConditionalDigests<K> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (&self.template, &self.digests).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K: Ord + serde::Deserialize<'de>> serde::Deserialize<'de> for ConditionalDigests<K> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (template, digests) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self { template, digests })
+    }
+}
+
+/// Bins of a numeric condition, delimited by increasing edges.
+///
+/// Values below the first edge fall in bin 0, values from the first edge up
+/// to the second in bin 1, and so on, so that `n` edges make `n + 1` bins.
+/// NaN values fall in no bin.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{Bins, ConditionalDigests};
+///
+/// // Bin requests by payload size.
+/// let bins = Bins::new(vec![1024.0, 65536.0]);
+/// let mut latencies = ConditionalDigests::new();
+///
+/// for (size, latency) in [(512.0, 10.0), (4096.0, 20.0), (1e6, 80.0)] {
+///     if let Some(bin) = bins.index(size) {
+///         latencies.insert(bin, latency);
+///     }
+/// }
+///
+/// assert_eq!(latencies.quantile(&1, 0.5), 20.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bins {
+    edges: Vec<f32>,
+}
+
+impl Bins {
+    /// Creates bins delimited by `edges`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `edges` are not strictly increasing, or if one is NaN.
+    #[must_use]
+    pub fn new(edges: Vec<f32>) -> Self {
+        assert!(valid_edges(&edges), "edges must be strictly increasing");
+        Self { edges }
+    }
+
+    /// Returns the edges of the bins.
+    #[inline]
+    pub fn edges(&self) -> &[f32] {
+        &self.edges
+    }
+
+    /// Returns the index of the bin that contains `value`, or `None` if it is
+    /// NaN.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::Bins;
+    ///
+    /// let bins = Bins::new(vec![1.0, 2.0]);
+    /// assert_eq!(bins.index(0.5), Some(0));
+    /// assert_eq!(bins.index(1.0), Some(1));
+    /// assert_eq!(bins.index(3.0), Some(2));
+    /// assert_eq!(bins.index(f32::NAN), None);
+    /// ```
+    pub fn index(&self, value: f32) -> Option<usize> {
+        (!value.is_nan()).then(|| self.edges.partition_point(|&edge| edge <= value))
+    }
+}
+
+/// Returns `true` if `edges` are strictly increasing, and none is NaN.
+fn valid_edges(edges: &[f32]) -> bool {
+    edges.iter().all(|edge| !edge.is_nan()) && edges.windows(2).all(|w| w[0] < w[1])
+}
+
+/// T-digests of a distribution, conditioned on the bin of a numeric
+/// covariate, such as the latency of requests by payload size.
+///
+/// This is a [`ConditionalDigests`] keyed by [`Bins`], that answers quantile
+/// queries over a range of the covariate by merging the t-digests of the bins
+/// it overlaps. The range is thus rounded out to the edges of the bins: the
+/// finer the bins, the closer the answer to the conditional quantile of the
+/// exact range, at the cost of a t-digest per bin.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{BinnedDigests, Bins};
+///
+/// let mut latencies = BinnedDigests::new(Bins::new(vec![1024.0, 65536.0]));
+///
+/// // Record the latency of some requests, by payload size.
+/// latencies.insert(512.0, 10.0);
+/// latencies.insert(4096.0, 20.0);
+/// latencies.insert(8192.0, 30.0);
+/// latencies.insert(1e6, 80.0);
+///
+/// assert_eq!(latencies.quantile_given(1024.0..65536.0, 1.0), 30.0);
+/// assert_eq!(latencies.quantile_given(2048.0.., 1.0), 80.0);
+/// assert_eq!(latencies.quantile_given(.., 0.0), 10.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinnedDigests {
+    bins: Bins,
+    digests: ConditionalDigests<usize>,
+}
+
+impl BinnedDigests {
+    /// Creates an empty `BinnedDigests` over `bins`.
+    #[must_use]
+    pub fn new(bins: Bins) -> Self {
+        Self::with_template(bins, TDigest::new())
+    }
+
+    /// Creates an empty `BinnedDigests` over `bins`, whose t-digests use the
+    /// configuration of `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not empty.
+    #[must_use]
+    pub fn with_template(bins: Bins, template: TDigest) -> Self {
+        Self {
+            bins,
+            digests: ConditionalDigests::with_template(template),
+        }
+    }
+
+    /// Returns the bins of the covariate.
+    #[inline]
+    pub fn bins(&self) -> &Bins {
+        &self.bins
+    }
+
+    /// Returns the t-digests of the bins that have values, by bin index.
+    #[inline]
+    pub fn digests(&self) -> &ConditionalDigests<usize> {
+        &self.digests
+    }
+
+    /// Returns the number of values, across all bins.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.digests.len()
+    }
+
+    /// Returns `true` if there are no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+
+    /// Adds a value, in the bin of its covariate.
+    ///
+    /// Values whose covariate is NaN fall in no bin, and are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{BinnedDigests, Bins};
+    ///
+    /// let mut digests = BinnedDigests::new(Bins::new(vec![1.0]));
+    /// digests.insert(0.5, 10.0);
+    /// digests.insert(2.0, 20.0);
+    /// digests.insert(f32::NAN, 30.0);
+    /// assert_eq!(digests.digests().get(&1).map(|d| d.len()), Some(1));
+    /// assert_eq!(digests.len(), 2);
+    /// ```
+    #[inline]
+    pub fn insert(&mut self, covariate: f32, value: f32) {
+        self.insert_many(covariate, value, 1);
+    }
+
+    /// Adds multiple values, in the bin of their covariate.
+    ///
+    /// Values whose covariate is NaN fall in no bin, and are ignored.
+    pub fn insert_many(&mut self, covariate: f32, value: f32, count: u64) {
+        if let Some(bin) = self.bins.index(covariate) {
+            self.digests.insert_many(bin, value, count);
+        }
+    }
+
+    /// Returns a t-digest of the values whose bins overlap `covariates`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{BinnedDigests, Bins};
+    ///
+    /// let mut digests = BinnedDigests::new(Bins::new(vec![10.0, 20.0]));
+    /// digests.insert(5.0, 1.0);
+    /// digests.insert(15.0, 2.0);
+    /// digests.insert(25.0, 3.0);
+    /// assert_eq!(digests.digest_given(12.0..=25.0).len(), 2);
+    /// assert_eq!(digests.digest_given(..10.0).len(), 1);
+    /// ```
+    pub fn digest_given<R: RangeBounds<f32>>(&self, covariates: R) -> TDigest {
+        let mut digest = self.digests.template.clone();
+        // No covariate is in a range bounded by NaN.
+        let first = match covariates.start_bound() {
+            Bound::Included(&start) | Bound::Excluded(&start) => self.bins.index(start),
+            Bound::Unbounded => Some(0),
+        };
+        let last = match covariates.end_bound() {
+            Bound::Included(&end) => self.bins.index(end),
+            // The bin that starts at `end` holds no value below it.
+            Bound::Excluded(&end) => self
+                .bins
+                .index(end)
+                .map(|_| self.bins.edges.partition_point(|&edge| edge < end)),
+            Bound::Unbounded => Some(self.bins.edges.len()),
+        };
+        let (Some(first), Some(last)) = (first, last) else {
+            return digest;
+        };
+        if first <= last {
+            for bin in self.digests.digests.range(first..=last).map(|(_, d)| d) {
+                digest |= bin;
+            }
+        }
+        digest
+    }
+
+    /// Returns the estimated quantile of the values whose bins overlap
+    /// `covariates`, or NaN if there are none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{BinnedDigests, Bins};
+    ///
+    /// let mut latencies = BinnedDigests::new(Bins::new(vec![100.0, 1000.0]));
+    /// for size in 0..2000 {
+    ///     latencies.insert(size as f32, size as f32 / 10.0);
+    /// }
+    /// let small = latencies.quantile_given(..100.0, 0.5);
+    /// let large = latencies.quantile_given(1000.0.., 0.5);
+    /// assert!(small < 10.0 && large > 100.0);
+    /// assert!(latencies.quantile_given(5000.0..1.0, 0.5).is_nan());
+    /// ```
+    pub fn quantile_given<R: RangeBounds<f32>>(&self, covariates: R, level: f64) -> f32 {
+        self.digest_given(covariates).quantile(level)
+    }
+}
+
+impl BitOrAssign<&BinnedDigests> for BinnedDigests {
+    /// Merges `self` and `rhs` into `self`, bin by bin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bins of `self` and `rhs` are not the same.
+    fn bitor_assign(&mut self, rhs: &BinnedDigests) {
+        assert_eq!(self.bins, rhs.bins, "bins must be the same");
+        self.digests |= &rhs.digests;
+    }
+}
+
+impl Extend<(f32, f32)> for BinnedDigests {
+    /// Adds pairs of a covariate and a value.
+    fn extend<I: IntoIterator<Item = (f32, f32)>>(&mut self, iter: I) {
+        for (covariate, value) in iter {
+            self.insert(covariate, value);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for BinnedDigests {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (&self.bins.edges, &self.digests).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BinnedDigests {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (edges, digests): (Vec<f32>, _) = serde::Deserialize::deserialize(deserializer)?;
+        if !valid_edges(&edges) {
+            return Err(serde::de::Error::custom(
+                "edges must be strictly increasing",
+            ));
+        }
+        Ok(Self {
+            bins: Bins { edges },
+            digests,
+        })
+    }
+}
@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::WeightedTDigest;
+
+/// The number of half-lives after the landmark beyond which the weights are
+/// scaled back, so that they stay far from overflowing.
+const MAX_HALF_LIVES: f64 = 64.;
+
+/// The number of half-lives after the landmark beyond which the values are
+/// dropped instead, as their weights would round to zero.
+const FORGOTTEN_HALF_LIVES: f64 = 512.;
+
+/// T-digest whose values lose half of their weight every half-life, so that
+/// quantiles track the recent values of a stream.
+///
+/// Values are recorded with the time at which they were observed, and their
+/// weights are computed from it: callers do not decay the t-digest at fixed
+/// intervals, as with [`WeightedTDigest::decay`]. The weights grow forward in
+/// time from a landmark, the time of the first value, instead of decaying
+/// all the centroids at each value: quantiles only depend on the relative
+/// weights, and the weights are scaled back every 64 half-lives. Values may
+/// be recorded out of order, and are dropped after 512 half-lives without a
+/// new value.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use tdigest_ch::DecayedTDigest;
+///
+/// let mut digest = DecayedTDigest::new(Duration::from_secs(60));
+/// let start = Instant::now();
+/// digest.record(1.0, start);
+/// digest.record(1.0, start);
+/// digest.record(1.0, start);
+/// digest.record(10.0, start + Duration::from_secs(120));
+///
+/// // The first values weigh a quarter each, two half-lives later.
+/// let now = start + Duration::from_secs(120);
+/// assert_eq!(digest.total_weight_at(now), 1.75);
+/// assert_eq!(digest.quantile(0.9), 10.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecayedTDigest {
+    half_life: Duration,
+    /// The values, weighted relative to the landmark.
+    digest: WeightedTDigest,
+    landmark: Option<Instant>,
+}
+
+impl DecayedTDigest {
+    /// Creates an empty `DecayedTDigest` whose values lose half of their
+    /// weight every `half_life`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `half_life` is zero.
+    #[must_use]
+    pub fn new(half_life: Duration) -> Self {
+        Self::with_epsilon(half_life, 0.01)
+    }
+
+    /// Creates an empty `DecayedTDigest` with the given compression
+    /// parameter, as with [`WeightedTDigest::with_epsilon`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `half_life` is zero, or if `epsilon` is not between 0 and 1.
+    #[must_use]
+    pub fn with_epsilon(half_life: Duration, epsilon: f64) -> Self {
+        assert!(!half_life.is_zero(), "half-life must not be zero");
+        Self {
+            half_life,
+            digest: WeightedTDigest::with_epsilon(epsilon),
+            landmark: None,
+        }
+    }
+
+    /// Returns the half-life of the weights.
+    #[inline]
+    pub fn half_life(&self) -> Duration {
+        self.half_life
+    }
+
+    /// Returns the number of recorded values, regardless of their weights.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.digest.len()
+    }
+
+    /// Returns `true` if the t-digest contains no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.digest.is_empty()
+    }
+
+    /// Clears the t-digest, removing all values.
+    pub fn clear(&mut self) {
+        self.digest.clear();
+        self.landmark = None;
+    }
+
+    /// Adds a value observed at `at`, with a weight of 1 at that time.
+    ///
+    /// NaN values are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use tdigest_ch::DecayedTDigest;
+    ///
+    /// let mut digest = DecayedTDigest::new(Duration::from_secs(1));
+    /// let start = Instant::now();
+    /// digest.record(5.0, start + Duration::from_secs(1));
+    /// // Out of order, and worth half as much.
+    /// digest.record(1.0, start);
+    /// assert_eq!(digest.total_weight_at(start + Duration::from_secs(1)), 1.5);
+    /// ```
+    pub fn record(&mut self, value: f32, at: Instant) {
+        if value.is_nan() {
+            return;
+        }
+        self.landmark.get_or_insert(at);
+        let mut half_lives = self.half_lives(at);
+        if half_lives > MAX_HALF_LIVES {
+            if half_lives > FORGOTTEN_HALF_LIVES {
+                self.digest.clear();
+            } else {
+                self.digest.decay((-half_lives).exp2());
+            }
+            self.landmark = Some(at);
+            half_lives = 0.;
+        }
+        let weight = half_lives.exp2();
+        if weight > 0. {
+            self.digest.insert_weighted(value, weight);
+        }
+    }
+
+    /// Adds a value observed at a time of the system clock, such as the
+    /// timestamp of a log line. See [`DecayedTDigest::record`].
+    ///
+    /// The time is converted into an [`Instant`] from the current times of
+    /// both clocks. Values older than any `Instant`, usually from before the
+    /// system booted, are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    /// use tdigest_ch::DecayedTDigest;
+    ///
+    /// let mut digest = DecayedTDigest::new(Duration::from_secs(3600));
+    /// let now = SystemTime::now();
+    /// digest.record_system_time(1.0, now - Duration::from_secs(3600));
+    /// digest.record_system_time(2.0, now);
+    /// assert_eq!(digest.len(), 2);
+    /// assert_eq!(digest.quantile(1.0), 2.0);
+    /// ```
+    pub fn record_system_time(&mut self, value: f32, at: SystemTime) {
+        if let Some(at) = instant_of(at) {
+            self.record(value, at);
+        }
+    }
+
+    /// Returns the total weight of the values as of `now`.
+    ///
+    /// This is the decayed number of values: a stream of one value per
+    /// second, with a half-life of a minute, weighs about 87 at any time.
+    pub fn total_weight_at(&self, now: Instant) -> f64 {
+        self.digest.total_weight() * (-self.half_lives(now)).exp2()
+    }
+
+    /// Returns the estimated quantile of the decayed values, or NaN if there
+    /// are none.
+    ///
+    /// The relative weights of the values do not change over time, so that
+    /// the quantiles do not depend on the time of the query.
+    pub fn quantile(&mut self, level: f64) -> f32 {
+        self.digest.quantile(level)
+    }
+
+    /// Returns the fraction of the decayed weight of the values that are
+    /// lower than or equal to `value`, or NaN if there are none.
+    pub fn cdf(&mut self, value: f32) -> f64 {
+        self.digest.cdf(value)
+    }
+
+    /// Returns the number of half-lives from the landmark to `at`, negative
+    /// before the landmark.
+    fn half_lives(&self, at: Instant) -> f64 {
+        let Some(landmark) = self.landmark else {
+            return 0.;
+        };
+        let half_life = self.half_life.as_secs_f64();
+        match at.checked_duration_since(landmark) {
+            Some(elapsed) => elapsed.as_secs_f64() / half_life,
+            None => -(landmark - at).as_secs_f64() / half_life,
+        }
+    }
+}
+
+/// Converts a time of the system clock into an [`Instant`], from the current
+/// times of both clocks, or returns `None` if it cannot be represented.
+pub(crate) fn instant_of(at: SystemTime) -> Option<Instant> {
+    let now = Instant::now();
+    match SystemTime::now().duration_since(at) {
+        Ok(age) => now.checked_sub(age),
+        Err(err) => now.checked_add(err.duration()),
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{interpolation::tail_rank, Float, Interpolation, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns the estimated probability density at `value`, or NaN if the
+    /// t-digest is empty.
+    ///
+    /// This is the slope of the cumulative distribution function estimated by
+    /// [`TDigest::cdf`], which is linear between the means of consecutive
+    /// centroids. The density is 0 outside of the means of the centroids, and
+    /// the steps of the function at singleton centroids are ignored. A low
+    /// density flags values that are rarely observed, which makes for a cheap
+    /// anomaly score.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..10_000).map(|v| (v % 100) as f32));
+    /// assert!((digest.density(50.0) - 0.01).abs() < 0.001);
+    /// assert_eq!(digest.density(200.0), 0.0);
+    /// ```
+    pub fn density(&mut self, value: F) -> f64 {
+        self.compress();
+        self.density_uncompressed(value)
+    }
+
+    /// Returns the estimated quantile density at `level`, the slope of the
+    /// quantile function, or NaN if the t-digest is empty.
+    ///
+    /// This is the increase of the value per increase of the level, which is
+    /// large where the distribution is spread out and small where it is
+    /// concentrated: the inverse of the [`TDigest::density`] at the quantile.
+    /// The quantile function is taken as linear between the midpoints of
+    /// consecutive centroids, ignoring the steps at singleton centroids, and
+    /// the density is 0 outside of the midpoints of the first and last
+    /// centroids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// // Uniform values between 0 and 1000.
+    /// let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32 / 10.0));
+    /// assert!((digest.quantile_density(0.5) - 1000.0).abs() < 10.0);
+    /// ```
+    pub fn quantile_density(&mut self, level: f64) -> f64 {
+        self.compress();
+        self.quantile_density_uncompressed(level)
+    }
+
+    pub(crate) fn quantile_density_uncompressed(&self, level: f64) -> f64 {
+        // Same walk as `quantile_uncompressed`, returning the slope between
+        // the midpoints of the centroids.
+        if self.centroids.is_empty() || level.is_nan() {
+            return f64::NAN;
+        }
+        let count = self.count as f64;
+        let x = level * count;
+        let mut prev_x = 0f64;
+        let mut sum = 0u64;
+        let mut prev = self.centroids[0];
+
+        for (i, c) in self.centroids.iter().enumerate() {
+            let current_x = sum as f64 + c.count as f64 * 0.5;
+
+            if current_x >= x {
+                if i == 0 || x < 0. {
+                    return 0.;
+                }
+                let width = c.mean.to_f64() - prev.mean.to_f64();
+                return width / (current_x - prev_x) * count;
+            }
+
+            sum += c.count;
+            prev = *c;
+            prev_x = current_x;
+        }
+
+        0.
+    }
+
+    /// Returns the estimated mode of the t-digest, the value of highest
+    /// density, or NaN if it is empty.
+    ///
+    /// The mode is the mean of the centroid with the highest weight relative
+    /// to the spacing of its neighbors: the weight of a centroid spreads
+    /// halfway to the means of the previous and next centroids. For skewed
+    /// distributions, such as latencies, this is the typical value rather
+    /// than the median.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// // Mostly fast requests, with a long tail of slow ones.
+    /// let mut digest = TDigest::from_iter((0..10_000).map(|v| 5.0 + (v % 100) as f32 * 0.01));
+    /// digest.extend((0..15_000).map(|v| 10.0 + v as f32 * 0.1));
+    /// assert!(digest.mode() < 6.0);
+    /// assert!(digest.median() > 6.0);
+    /// ```
+    pub fn mode(&mut self) -> F {
+        self.compress();
+        self.mode_uncompressed()
+    }
+
+    pub(crate) fn mode_uncompressed(&self) -> F {
+        let centroids = &self.centroids;
+        match centroids.len() {
+            0 => return F::NAN,
+            1 => return centroids[0].mean,
+            _ => {}
+        }
+        let mut mode = F::NAN;
+        let mut max_density = f64::NEG_INFINITY;
+        for (i, c) in centroids.iter().enumerate() {
+            // The end centroids spread as far outwards as inwards.
+            let lower = i.checked_sub(1).map(|j| centroids[j].mean.to_f64());
+            let upper = centroids.get(i + 1).map(|n| n.mean.to_f64());
+            let width = match (lower, upper) {
+                (Some(lower), Some(upper)) => (upper - lower) / 2.,
+                (Some(lower), None) => c.mean.to_f64() - lower,
+                (None, upper) => upper.unwrap() - c.mean.to_f64(),
+            };
+            let density = c.count as f64 / width;
+            if density > max_density {
+                (mode, max_density) = (c.mean, density);
+            }
+        }
+        mode
+    }
+
+    pub(crate) fn density_uncompressed(&self, value: F) -> f64 {
+        // Same walk as `cdf_uncompressed`, returning the slope rather than the
+        // interpolated rank.
+        let (first, last) = match (self.centroids.first(), self.centroids.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return f64::NAN,
+        };
+        if self.config.interpolation == Interpolation::Dunning {
+            // The tails interpolated towards the extremes.
+            let count = self.count as f64;
+            if self.min <= value && value < first.mean {
+                let width = first.mean.to_f64() - self.min.to_f64();
+                return (tail_rank(first.count) - 1.) / width / count;
+            }
+            if last.mean <= value && value < self.max {
+                let width = self.max.to_f64() - last.mean.to_f64();
+                return (tail_rank(last.count) - 1.) / width / count;
+            }
+        }
+        if value < first.mean || value >= last.mean {
+            return 0.;
+        }
+
+        let singletons = self.config.interpolation.singletons();
+        let mut prev_x = first.count as f64 * 0.5;
+        let mut sum = first.count;
+        let mut prev = *first;
+
+        for c in self.centroids[1..].iter() {
+            let current_x = sum as f64 + c.count as f64 * 0.5;
+
+            if value < c.mean {
+                // Special handling of singletons.
+                let mut left = prev_x;
+                if singletons && prev.count == 1 {
+                    left += 0.5;
+                }
+                let mut right = current_x;
+                if singletons && c.count == 1 {
+                    right -= 0.5;
+                }
+
+                let width = c.mean.to_f64() - prev.mean.to_f64();
+                return (right - left) / width / self.count as f64;
+            }
+
+            sum += c.count;
+            prev = *c;
+            prev_x = current_x;
+        }
+
+        0.
+    }
+}
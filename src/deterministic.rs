@@ -0,0 +1,503 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::{codec::Reader, Error, QuantileSketch};
+
+const DEFAULT_MAX_SAMPLE_SIZE: usize = 8192;
+const MAX_SKIP_DEGREE: u8 = 32;
+const MAX_RESERVOIR_SIZE: u64 = 1 << 30;
+
+/// ClickHouse `intHash64` function, the finalizer of MurmurHash3.
+#[inline]
+pub(crate) fn int_hash64(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51afd7ed558ccd);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xc4ceb9fe1a85ec53);
+    x ^= x >> 33;
+    x
+}
+
+/// Reservoir sample of a stream, as used by ClickHouse
+/// [`quantileDeterministic`][ClickHouseRefDeterministic].
+///
+/// Instead of sampling at random, each value comes with a determinator whose
+/// hash decides whether the value is kept. The sample is thus fully determined
+/// by the stream, regardless of the insertion order and of how partial samples
+/// are merged. When the reservoir is full, only the values whose hash is a
+/// multiple of a growing power of two are retained.
+///
+/// The reservoir can be read from and written to the state format of the
+/// `quantileDeterministic` aggregate function applied to `Float32` values.
+///
+/// [ClickHouseRefDeterministic]: https://clickhouse.com/docs/en/sql-reference/aggregate-functions/reference/quantiledeterministic
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::DeterministicReservoir;
+///
+/// let mut reservoir = DeterministicReservoir::new();
+///
+/// // Add some elements, using their row number as determinator.
+/// reservoir.insert(1.0, 0);
+/// reservoir.insert(2.0, 1);
+/// reservoir.insert(3.0, 2);
+///
+/// // Get the median of the distribution.
+/// let quantile = reservoir.quantile(0.5);
+/// assert_eq!(quantile, 2.0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeterministicReservoir {
+    max_sample_size: usize,
+    total_values: u64,
+    samples: Vec<(f32, u32)>,
+    skip_degree: u8,
+    sorted: bool,
+}
+
+impl DeterministicReservoir {
+    /// Creates an empty `DeterministicReservoir` that retains up to 8192
+    /// values, like ClickHouse does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    /// let reservoir = DeterministicReservoir::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_sample_size(DEFAULT_MAX_SAMPLE_SIZE)
+    }
+
+    /// Creates an empty `DeterministicReservoir` that retains up to
+    /// `max_sample_size` values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_sample_size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    /// let reservoir = DeterministicReservoir::with_max_sample_size(1024);
+    /// ```
+    #[must_use]
+    pub fn with_max_sample_size(max_sample_size: usize) -> Self {
+        assert!(max_sample_size > 0, "max sample size must be positive");
+        Self {
+            max_sample_size,
+            total_values: 0,
+            samples: Vec::new(),
+            skip_degree: 0,
+            sorted: true,
+        }
+    }
+
+    /// Returns the number of elements inserted in the reservoir, including
+    /// those that were not retained in the sample.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut reservoir = DeterministicReservoir::with_max_sample_size(2);
+    /// reservoir.extend([(1.0, 0), (2.0, 1), (3.0, 2)]);
+    /// assert_eq!(reservoir.len(), 3);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.total_values
+    }
+
+    /// Returns `true` if the reservoir contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut reservoir = DeterministicReservoir::new();
+    /// assert!(reservoir.is_empty());
+    /// reservoir.insert(1.0, 0);
+    /// assert!(!reservoir.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.total_values == 0
+    }
+
+    /// Returns the number of elements retained in the sample.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut reservoir = DeterministicReservoir::with_max_sample_size(2);
+    /// reservoir.extend([(1.0, 0), (2.0, 1), (3.0, 2)]);
+    /// assert!(reservoir.sample_len() <= 2);
+    /// ```
+    #[inline]
+    pub fn sample_len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Clears the reservoir, removing all values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut reservoir = DeterministicReservoir::new();
+    /// reservoir.insert(1.0, 0);
+    /// reservoir.clear();
+    /// assert!(reservoir.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.total_values = 0;
+        self.samples.clear();
+        self.skip_degree = 0;
+        self.sorted = true;
+    }
+
+    /// Adds a value to the reservoir.
+    ///
+    /// Whether the value is retained only depends on the hash of
+    /// `determinator`. Values sharing a determinator are retained or dropped
+    /// together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut reservoir = DeterministicReservoir::new();
+    ///
+    /// reservoir.insert(1.0, 42);
+    /// reservoir.insert(2.0, 43);
+    /// assert_eq!(reservoir.len(), 2);
+    /// ```
+    pub fn insert(&mut self, value: f32, determinator: u64) {
+        if value.is_nan() {
+            return;
+        }
+        let hash = int_hash64(determinator) as u32;
+        self.insert_sample(value, hash);
+        self.total_values += 1;
+    }
+
+    /// Returns the estimated quantile of the reservoir, linearly interpolated
+    /// between the two nearest sampled values.
+    ///
+    /// The level is clamped to `[0, 1]`, as in ClickHouse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut reservoir = DeterministicReservoir::new();
+    /// reservoir.extend([(1.0, 0), (2.0, 1), (3.0, 2), (4.0, 3)]);
+    /// assert_eq!(reservoir.quantile(0.0), 1.0);
+    /// assert_eq!(reservoir.quantile(0.5), 2.5);
+    /// assert_eq!(reservoir.quantile(1.0), 4.0);
+    /// ```
+    pub fn quantile(&mut self, level: f64) -> f32 {
+        if self.samples.is_empty() {
+            return f32::NAN;
+        }
+        self.sort();
+
+        let index = (self.samples.len() - 1) as f64 * level.clamp(0., 1.);
+        let left_index = index as usize;
+        let right_index = left_index + 1;
+        if right_index >= self.samples.len() {
+            return self.samples[left_index.min(self.samples.len() - 1)].0;
+        }
+        let left_coef = right_index as f64 - index;
+        let right_coef = index - left_index as f64;
+        (self.samples[left_index].0 as f64 * left_coef
+            + self.samples[right_index].0 as f64 * right_coef) as f32
+    }
+
+    /// Serializes the reservoir in the state format of ClickHouse
+    /// `quantileDeterministic` aggregate function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut reservoir = DeterministicReservoir::new();
+    /// reservoir.extend([(1.0, 0), (2.0, 1), (3.0, 2)]);
+    ///
+    /// let bytes = reservoir.to_bytes();
+    /// let mut other = DeterministicReservoir::from_bytes(&bytes).unwrap();
+    /// assert_eq!(other.quantile(0.5), reservoir.quantile(0.5));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(16 + self.samples.len() * 8);
+        buf.extend_from_slice(&(self.samples.len() as u64).to_le_bytes());
+        buf.extend_from_slice(&self.total_values.to_le_bytes());
+        for &(value, hash) in self.samples.iter() {
+            buf.extend_from_slice(&value.to_le_bytes());
+            buf.extend_from_slice(&hash.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Deserializes a reservoir from the state format of ClickHouse
+    /// `quantileDeterministic` aggregate function.
+    ///
+    /// The reservoir retains up to 8192 values, like ClickHouse does.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not a valid state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// assert!(DeterministicReservoir::from_bytes(&[0x00]).is_err());
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let mut size = reader.read_u64()?;
+        let total_values = reader.read_u64()?;
+        // Compatibility with old versions of ClickHouse.
+        size = size.min(total_values);
+        if size > MAX_RESERVOIR_SIZE {
+            return Err(Error::InvalidState("reservoir is too large"));
+        }
+
+        let mut reservoir = Self::new();
+        reservoir.total_values = total_values;
+        reservoir.samples.reserve(size as usize);
+        for _ in 0..size {
+            let value = reader.read_f32()?;
+            let hash = reader.read_u32()?;
+            reservoir.samples.push((value, hash));
+        }
+        reader.finish()?;
+        reservoir.sorted = false;
+        Ok(reservoir)
+    }
+
+    #[inline]
+    fn is_good(&self, hash: u32) -> bool {
+        let mask = if self.skip_degree >= MAX_SKIP_DEGREE {
+            u32::MAX
+        } else {
+            (1u32 << self.skip_degree) - 1
+        };
+        hash & mask == 0
+    }
+
+    fn insert_sample(&mut self, value: f32, hash: u32) {
+        if !self.is_good(hash) {
+            return;
+        }
+        // Make room for one more element.
+        while self.samples.len() >= self.max_sample_size {
+            if self.skip_degree >= MAX_SKIP_DEGREE {
+                // Only hashes of zero are left, which no skip degree thins
+                // out: the reservoir is full of them.
+                return;
+            }
+            self.set_skip_degree(self.skip_degree + 1);
+            if !self.is_good(hash) {
+                return;
+            }
+        }
+        self.samples.push((value, hash));
+        self.sorted = false;
+    }
+
+    fn set_skip_degree(&mut self, skip_degree: u8) {
+        if skip_degree == self.skip_degree {
+            return;
+        }
+        assert!(
+            skip_degree <= MAX_SKIP_DEGREE,
+            "skip degree exceeds maximum value"
+        );
+        self.skip_degree = skip_degree;
+        self.thin_out();
+    }
+
+    fn thin_out(&mut self) {
+        let mask = if self.skip_degree >= MAX_SKIP_DEGREE {
+            u32::MAX
+        } else {
+            (1u32 << self.skip_degree) - 1
+        };
+        self.samples.retain(|&(_, hash)| hash & mask == 0);
+    }
+
+    fn sort(&mut self) {
+        if !self.sorted {
+            self.samples.sort_unstable_by(|l, r| l.0.total_cmp(&r.0));
+            self.sorted = true;
+        }
+    }
+}
+
+impl BitOr<&DeterministicReservoir> for &DeterministicReservoir {
+    type Output = DeterministicReservoir;
+
+    /// Returns the union of `self` and `rhs` as a new `DeterministicReservoir`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reservoirs have different maximum sample sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let a = DeterministicReservoir::from_iter([(1.0, 0), (2.0, 1), (3.0, 2)]);
+    /// let b = DeterministicReservoir::from_iter([(4.0, 3), (5.0, 4)]);
+    ///
+    /// let mut c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 5);
+    /// assert_eq!(c.quantile(0.5), 3.0);
+    /// ```
+    fn bitor(self, rhs: &DeterministicReservoir) -> DeterministicReservoir {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&DeterministicReservoir> for DeterministicReservoir {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reservoirs have different maximum sample sizes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DeterministicReservoir;
+    ///
+    /// let mut a = DeterministicReservoir::from_iter([(1.0, 0), (2.0, 1), (3.0, 2)]);
+    /// let b = DeterministicReservoir::from_iter([(4.0, 3), (5.0, 4)]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 5);
+    /// assert_eq!(a.quantile(0.5), 3.0);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &DeterministicReservoir) {
+        assert_eq!(
+            self.max_sample_size, rhs.max_sample_size,
+            "cannot merge reservoirs with different max sample sizes"
+        );
+        if self.skip_degree < rhs.skip_degree {
+            self.set_skip_degree(rhs.skip_degree);
+        }
+        for &(value, hash) in rhs.samples.iter() {
+            self.insert_sample(value, hash);
+        }
+        self.total_values += rhs.total_values;
+    }
+}
+
+impl Default for DeterministicReservoir {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<(f32, u64)> for DeterministicReservoir {
+    fn extend<I: IntoIterator<Item = (f32, u64)>>(&mut self, iter: I) {
+        for (value, determinator) in iter {
+            self.insert(value, determinator);
+        }
+    }
+}
+
+impl FromIterator<(f32, u64)> for DeterministicReservoir {
+    fn from_iter<I: IntoIterator<Item = (f32, u64)>>(iter: I) -> Self {
+        let mut reservoir = DeterministicReservoir::new();
+        reservoir.extend(iter);
+        reservoir
+    }
+}
+
+/// The determinator of the values inserted through [`QuantileSketch::insert`]
+/// is the number of values already in the reservoir, that is their row number
+/// when all the values go through the same reservoir.
+///
+/// When partial reservoirs are later merged, their row numbers overlap, and
+/// the values sharing a row number are retained or dropped together. The
+/// sample then depends on how the stream was split, so call
+/// [`DeterministicReservoir::insert`] with an explicit determinator instead.
+impl QuantileSketch for DeterministicReservoir {
+    /// Adds a value to the reservoir, using the number of values inserted so
+    /// far as determinator.
+    #[inline]
+    fn insert(&mut self, value: f32) {
+        self.insert(value, self.total_values);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        *self |= other;
+    }
+
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    #[inline]
+    fn quantile(&mut self, level: f64) -> f32 {
+        self.quantile(level)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeterministicReservoir {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (
+            self.max_sample_size,
+            self.total_values,
+            &self.samples,
+            self.skip_degree,
+        )
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeterministicReservoir {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (max_sample_size, total_values, samples, skip_degree) =
+            serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            max_sample_size,
+            total_values,
+            samples,
+            skip_degree,
+            sorted: false,
+        })
+    }
+}
@@ -0,0 +1,230 @@
+//! Differential testing of t-digests against exact quantiles.
+//!
+//! A [`Differential`] feeds the same values to a t-digest and to an exact
+//! oracle, which keeps all the values, and reports how far the estimated
+//! quantiles are from the exact ones. Since the oracle holds every value, it is
+//! meant for tests, or for shadow validation on a sampled fraction of the
+//! production traffic.
+//!
+//! This module requires the `differential` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use tdigest_ch::differential::Differential;
+//!
+//! let mut differential = Differential::new();
+//! differential.extend((0..10_000).map(|v| v as f32));
+//!
+//! let report = differential.report(&[0.5, 0.99]);
+//! assert!(report.max_rank_error() < 0.01);
+//! ```
+
+use crate::TDigest;
+
+/// A t-digest, along with all the values inserted into it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Differential {
+    digest: TDigest,
+    values: Vec<f32>,
+}
+
+impl Differential {
+    /// Creates an empty `Differential`, with a t-digest of the default
+    /// configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::differential::Differential;
+    /// let differential = Differential::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_template(TDigest::new())
+    }
+
+    /// Creates an empty `Differential`, whose t-digest uses the configuration
+    /// of `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{differential::Differential, TDigest};
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.max_centroids(64);
+    ///
+    /// let differential = Differential::with_template(builder.build());
+    /// ```
+    #[must_use]
+    pub fn with_template(template: TDigest) -> Self {
+        assert!(template.is_empty(), "template must be empty");
+        Self {
+            digest: template,
+            values: Vec::new(),
+        }
+    }
+
+    /// Returns the t-digest under test.
+    #[inline]
+    pub fn digest(&self) -> &TDigest {
+        &self.digest
+    }
+
+    /// Returns the number of values.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if there are no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Clears the t-digest and the oracle.
+    pub fn clear(&mut self) {
+        self.digest.clear();
+        self.values.clear();
+    }
+
+    /// Inserts a value into the t-digest and the oracle.
+    ///
+    /// NaN values are ignored, as they are by the t-digest.
+    pub fn insert(&mut self, value: f32) {
+        if !value.is_nan() {
+            self.digest.insert(value);
+            self.values.push(value);
+        }
+    }
+
+    /// Compares the estimated and exact quantiles at each of `levels`.
+    ///
+    /// The exact quantile at `level` is the smallest value such that at least
+    /// a fraction `level` of the values are lower or equal to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::differential::Differential;
+    ///
+    /// let mut differential = Differential::new();
+    /// differential.extend([1.0, 2.0, 3.0, 4.0, 5.0]);
+    ///
+    /// let report = differential.report(&[0.5]);
+    /// assert_eq!(report.estimates(), [3.0]);
+    /// assert_eq!(report.exact(), [3.0]);
+    /// assert_eq!(report.max_value_error(), 0.0);
+    /// ```
+    pub fn report(&mut self, levels: &[f64]) -> DifferentialReport {
+        self.values.sort_unstable_by(f32::total_cmp);
+        let n = self.values.len();
+        let mut report = DifferentialReport {
+            levels: levels.to_vec(),
+            estimates: Vec::with_capacity(levels.len()),
+            exact: Vec::with_capacity(levels.len()),
+            rank_errors: Vec::with_capacity(levels.len()),
+        };
+        for &level in levels {
+            let estimate = self.digest.quantile(level);
+            let exact = if n == 0 {
+                f32::NAN
+            } else {
+                let rank = (level * n as f64).ceil() as usize;
+                self.values[rank.clamp(1, n) - 1]
+            };
+            // The estimate is exact for any level between the fractions of
+            // values lower than, and lower or equal to it.
+            let below = self.values.partition_point(|&v| v < estimate) as f64 / n as f64;
+            let upto = self.values.partition_point(|&v| v <= estimate) as f64 / n as f64;
+            let rank_error = if n == 0 {
+                f64::NAN
+            } else {
+                (below - level).max(level - upto).max(0.)
+            };
+            report.estimates.push(estimate);
+            report.exact.push(exact);
+            report.rank_errors.push(rank_error);
+        }
+        report
+    }
+}
+
+/// Estimated and exact quantiles of a [`Differential`].
+///
+/// This `struct` is created by the [`report`] method on [`Differential`]. See
+/// its documentation for more.
+///
+/// [`report`]: Differential::report
+#[derive(Clone, Debug, PartialEq)]
+pub struct DifferentialReport {
+    levels: Vec<f64>,
+    estimates: Vec<f32>,
+    exact: Vec<f32>,
+    rank_errors: Vec<f64>,
+}
+
+impl DifferentialReport {
+    /// Returns the levels of the quantiles.
+    #[inline]
+    pub fn levels(&self) -> &[f64] {
+        &self.levels
+    }
+
+    /// Returns the estimated quantiles, one for each level.
+    #[inline]
+    pub fn estimates(&self) -> &[f32] {
+        &self.estimates
+    }
+
+    /// Returns the exact quantiles, one for each level.
+    #[inline]
+    pub fn exact(&self) -> &[f32] {
+        &self.exact
+    }
+
+    /// Returns the rank errors, one for each level.
+    ///
+    /// The rank error of an estimate is the distance between its level and the
+    /// fraction of values below the estimate.
+    #[inline]
+    pub fn rank_errors(&self) -> &[f64] {
+        &self.rank_errors
+    }
+
+    /// Returns the largest absolute difference between an estimated and an
+    /// exact quantile, or 0 if there are no levels.
+    pub fn max_value_error(&self) -> f32 {
+        self.estimates
+            .iter()
+            .zip(self.exact.iter())
+            .map(|(estimate, exact)| (estimate - exact).abs())
+            .fold(0., f32::max)
+    }
+
+    /// Returns the largest rank error, or 0 if there are no levels.
+    pub fn max_rank_error(&self) -> f64 {
+        self.rank_errors.iter().copied().fold(0., f64::max)
+    }
+}
+
+impl Default for Differential {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<f32> for Differential {
+    fn extend<T: IntoIterator<Item = f32>>(&mut self, iter: T) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
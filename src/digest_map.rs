@@ -0,0 +1,1114 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Display, Write as _},
+    fs::{self, File},
+    hash::Hash,
+    io::{self, Write as _},
+    ops::{BitOr, BitOrAssign, Deref, DerefMut},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{MemoryPressure, TDigest};
+
+/// The name of the manifest of a checkpoint.
+const MANIFEST: &str = "MANIFEST";
+
+/// The first line of the manifest, with the version of its format.
+const MANIFEST_HEADER: &str = "tdigest-ch digest map 1";
+
+/// T-digests of values grouped by key.
+///
+/// Each key gets its own t-digest, created from a template so that all the
+/// t-digests share the same configuration. [`DigestMap::aggregate_by_key`]
+/// builds the t-digests from a stream of `(key, value)` pairs, such as the
+/// events of an ETL job.
+///
+/// Keys can be evicted to bound the map, when labels of high cardinality
+/// would otherwise grow it without limit: see [`DigestMap::set_max_keys`],
+/// [`DigestMap::set_idle_ttl`] and [`DigestMap::set_memory_budget`]. The
+/// evicted t-digests are handed to [`DigestMap::set_eviction_callback`],
+/// for instance to archive them.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::DigestMap;
+///
+/// let events = [("/users", 12.0), ("/users", 18.0), ("/orders", 250.0)];
+/// let mut latencies = DigestMap::from_pairs(events);
+///
+/// assert_eq!(latencies.len(), 2);
+/// assert_eq!(latencies.quantile(&"/users", 1.0), 18.0);
+/// assert!(latencies.quantile(&"/health", 0.5).is_nan());
+/// ```
+#[derive(Debug)]
+pub struct DigestMap<K> {
+    template: TDigest,
+    digests: HashMap<K, Slot>,
+    /// The registered memory pressure signal, along with the number of
+    /// signals already handled.
+    pressure: Option<(MemoryPressure, u64)>,
+    /// The last checkpoint written by [`DigestMap::persist`], or read by
+    /// [`DigestMap::restore`].
+    checkpoint: Option<Checkpoint>,
+    eviction: Eviction<K>,
+}
+
+/// The t-digest of a key.
+#[derive(Clone, Debug)]
+struct Slot {
+    digest: TDigest,
+    /// The file that holds the t-digest in the last checkpoint, or `None` if
+    /// it changed since.
+    file: Option<u64>,
+    /// The logical time of the last use of the key.
+    used: u64,
+    /// The time of the last use of the key, if the map has an idle TTL.
+    used_at: Option<Instant>,
+    /// The memory footprint of the t-digest, as of its last use.
+    bytes: usize,
+}
+
+impl Slot {
+    fn new(digest: TDigest) -> Self {
+        Self {
+            bytes: digest.memory_bytes(),
+            digest,
+            file: None,
+            used: 0,
+            used_at: None,
+        }
+    }
+
+    /// Measures the memory footprint of the t-digest again, and updates the
+    /// total footprint of the map accordingly.
+    fn measure(&mut self, total: &mut usize) {
+        let bytes = self.digest.memory_bytes();
+        *total = *total + bytes - self.bytes;
+        self.bytes = bytes;
+    }
+}
+
+/// The limits beyond which keys are evicted, and the state needed to
+/// enforce them.
+#[derive(Clone, Debug)]
+struct Eviction<K> {
+    max_keys: Option<usize>,
+    idle_ttl: Option<Duration>,
+    memory_budget: Option<usize>,
+    callback: Option<EvictionCallback<K>>,
+    /// The logical time of the last use of a key.
+    clock: u64,
+    /// The total memory footprint of the t-digests.
+    memory_bytes: usize,
+    /// The time of the last eviction of idle keys.
+    swept_at: Option<Instant>,
+}
+
+impl<K> Default for Eviction<K> {
+    fn default() -> Self {
+        Self {
+            max_keys: None,
+            idle_ttl: None,
+            memory_budget: None,
+            callback: None,
+            clock: 0,
+            memory_bytes: 0,
+            swept_at: None,
+        }
+    }
+}
+
+/// The eviction callback, shared with the clones of the map.
+struct EvictionCallback<K>(Arc<Mutex<dyn FnMut(K, TDigest) + Send>>);
+
+impl<K> Clone for EvictionCallback<K> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<K> std::fmt::Debug for EvictionCallback<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EvictionCallback")
+    }
+}
+
+#[derive(Debug)]
+struct Checkpoint {
+    dir: PathBuf,
+    template_file: u64,
+    /// The smallest file number that no file of the checkpoint uses.
+    next_file: u64,
+}
+
+impl<K: Hash + Eq> DigestMap<K> {
+    /// Creates an empty `DigestMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    /// let digests: DigestMap<String> = DigestMap::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_template(TDigest::new())
+    }
+
+    /// Creates an empty `DigestMap` whose t-digests use the configuration of
+    /// `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{DigestMap, TDigest};
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.max_centroids(256);
+    ///
+    /// let digests: DigestMap<u32> = DigestMap::with_template(builder.build());
+    /// ```
+    #[must_use]
+    pub fn with_template(template: TDigest) -> Self {
+        assert!(template.is_empty(), "template must be empty");
+        Self {
+            template,
+            digests: HashMap::new(),
+            pressure: None,
+            checkpoint: None,
+            eviction: Eviction::default(),
+        }
+    }
+
+    /// Creates a `DigestMap` from `(key, value)` pairs.
+    ///
+    /// This is the same as calling [`DigestMap::aggregate_by_key`] on an empty
+    /// `DigestMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let digests = DigestMap::from_pairs((0..100).map(|v| (v % 3, v as f32)));
+    /// assert_eq!(digests.len(), 3);
+    /// ```
+    #[must_use]
+    pub fn from_pairs<I: IntoIterator<Item = (K, f32)>>(pairs: I) -> Self {
+        let mut digests = Self::new();
+        digests.aggregate_by_key(pairs);
+        digests
+    }
+
+    /// Returns the number of keys.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.digests.len()
+    }
+
+    /// Returns `true` if there are no keys.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+
+    /// Clears the map, removing all keys and values.
+    pub fn clear(&mut self) {
+        self.digests.clear();
+        self.eviction.memory_bytes = 0;
+    }
+
+    /// Reserves capacity for at least `additional` more keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests: DigestMap<u64> = DigestMap::new();
+    /// digests.reserve(100_000);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        self.digests.reserve(additional);
+    }
+
+    /// Compresses all the t-digests, and releases the memory that they and the
+    /// map do not use. See [`TDigest::shrink_to_fit`].
+    pub fn shrink_to_fit(&mut self) {
+        for slot in self.digests.values_mut() {
+            slot.digest.shrink_to_fit();
+        }
+        self.digests.shrink_to_fit();
+        self.measure();
+    }
+
+    /// Registers a memory pressure signal: once it is signaled, the map is
+    /// shrunk with [`DigestMap::shrink_to_fit`] on its next insertion.
+    ///
+    /// This replaces any previously registered signal. Signals sent before
+    /// the registration are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{DigestMap, MemoryPressure};
+    ///
+    /// let pressure = MemoryPressure::new();
+    /// let mut digests = DigestMap::new();
+    /// digests.set_memory_pressure(&pressure);
+    ///
+    /// digests.insert("a", 1.0);
+    /// pressure.signal();
+    /// digests.insert("b", 2.0);
+    /// ```
+    pub fn set_memory_pressure(&mut self, pressure: &MemoryPressure) {
+        self.pressure = Some((pressure.clone(), pressure.signals()));
+    }
+
+    /// Bounds the number of keys: once a new key exceeds `max_keys`, the
+    /// least recently used keys are evicted.
+    ///
+    /// Keys are used when values are added to them, when they are merged
+    /// into, and by [`DigestMap::get_mut`] and [`DigestMap::quantile`]. So
+    /// that each new key does not scan the whole map, a sixteenth of the
+    /// keys are evicted at once: the map is left with `max_keys - max_keys /
+    /// 16` keys. Keys beyond the new limit are evicted right away.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_keys` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests = DigestMap::new();
+    /// digests.set_max_keys(2);
+    /// digests.insert("a", 1.0);
+    /// digests.insert("b", 2.0);
+    /// digests.insert("a", 3.0);
+    /// digests.insert("c", 4.0);
+    /// assert_eq!(digests.len(), 2);
+    /// assert!(digests.get(&"b").is_none());
+    /// ```
+    pub fn set_max_keys(&mut self, max_keys: usize) {
+        assert!(max_keys > 0, "max_keys must not be zero");
+        self.eviction.max_keys = Some(max_keys);
+        self.enforce_limits();
+    }
+
+    /// Evicts the keys that have not been used for longer than `ttl`. See
+    /// [`DigestMap::set_max_keys`] for what uses a key.
+    ///
+    /// Idle keys are evicted as other keys are used, at most every eighth of
+    /// `ttl`, so that a key may be evicted up to `ttl / 8` after it expires.
+    /// Call [`DigestMap::evict_idle`] to evict them when the map is not used.
+    /// Keys already in the map are considered used now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ttl` is zero.
+    pub fn set_idle_ttl(&mut self, ttl: Duration) {
+        assert!(!ttl.is_zero(), "TTL must not be zero");
+        self.eviction.idle_ttl = Some(ttl);
+        let now = Instant::now();
+        for slot in self.digests.values_mut() {
+            slot.used_at.get_or_insert(now);
+        }
+        self.eviction.swept_at = Some(now);
+    }
+
+    /// Bounds the memory footprint of the t-digests, in bytes, as estimated
+    /// by [`DigestStats::memory_bytes`]: once the t-digests exceed `bytes`,
+    /// the least recently used keys are evicted.
+    ///
+    /// As with [`DigestMap::set_max_keys`], keys are evicted until the
+    /// t-digests are within `bytes - bytes / 16`. The footprint of the keys
+    /// and of the map itself is not counted, and the most recently used key
+    /// is never evicted.
+    ///
+    /// [`DigestStats::memory_bytes`]: crate::DigestStats::memory_bytes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests = DigestMap::new();
+    /// digests.set_memory_budget(64 * 1024);
+    /// for key in 0..10_000 {
+    ///     digests.insert(key, 1.0);
+    /// }
+    /// assert!(digests.len() < 10_000);
+    /// assert!(digests.get(&9_999).is_some());
+    /// ```
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.eviction.memory_budget = Some(bytes);
+        self.enforce_limits();
+    }
+
+    /// Registers a callback that receives the evicted keys with their
+    /// t-digests, for instance to archive them.
+    ///
+    /// This replaces any previously registered callback. Without a callback,
+    /// evicted t-digests are dropped. Clones of the map share the callback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    ///
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let (archive, archived) = mpsc::channel();
+    /// let mut digests = DigestMap::new();
+    /// digests.set_eviction_callback(move |key, digest| archive.send((key, digest)).unwrap());
+    /// digests.set_max_keys(1);
+    /// digests.insert("a", 1.0);
+    /// digests.insert("b", 2.0);
+    ///
+    /// let (key, digest) = archived.try_recv().unwrap();
+    /// assert_eq!(key, "a");
+    /// assert_eq!(digest.len(), 1);
+    /// ```
+    pub fn set_eviction_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(K, TDigest) + Send + 'static,
+    {
+        self.eviction.callback = Some(EvictionCallback(Arc::new(Mutex::new(callback))));
+    }
+
+    /// Evicts the keys that have not been used for longer than the idle TTL
+    /// as of `now`. See [`DigestMap::set_idle_ttl`].
+    ///
+    /// This does nothing if the map has no idle TTL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests = DigestMap::new();
+    /// digests.set_idle_ttl(Duration::from_secs(60));
+    /// digests.insert("a", 1.0);
+    ///
+    /// digests.evict_idle(Instant::now() + Duration::from_secs(30));
+    /// assert_eq!(digests.len(), 1);
+    /// digests.evict_idle(Instant::now() + Duration::from_secs(90));
+    /// assert!(digests.is_empty());
+    /// ```
+    pub fn evict_idle(&mut self, now: Instant) {
+        let Some(ttl) = self.eviction.idle_ttl else {
+            return;
+        };
+        self.eviction.swept_at = Some(now);
+        self.evict_where(|slot| {
+            slot.used_at
+                .is_some_and(|used_at| now.saturating_duration_since(used_at) > ttl)
+        });
+    }
+
+    /// Returns the keys, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.digests.keys()
+    }
+
+    /// Returns the keys with their t-digests, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &TDigest)> {
+        self.digests.iter().map(|(key, slot)| (key, &slot.digest))
+    }
+
+    /// Returns the keys with their t-digests, in increasing order of keys.
+    ///
+    /// Unlike [`DigestMap::iter`], the order does not depend on the history
+    /// of the map, so replicas that hold the same t-digests iterate over them
+    /// in the same order. This is also the order in which the map is
+    /// serialized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let digests = DigestMap::from_pairs([("b", 1.0), ("c", 2.0), ("a", 3.0)]);
+    /// let keys: Vec<_> = digests.iter_sorted().map(|(key, _)| *key).collect();
+    /// assert_eq!(keys, ["a", "b", "c"]);
+    /// ```
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&K, &TDigest)>
+    where
+        K: Ord,
+    {
+        self.sorted().into_iter()
+    }
+
+    /// Returns the t-digest of a key, if it has values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let digests = DigestMap::from_pairs([("a", 1.0)]);
+    /// assert_eq!(digests.get(&"a").map(|d| d.len()), Some(1));
+    /// assert!(digests.get(&"b").is_none());
+    /// ```
+    pub fn get(&self, key: &K) -> Option<&TDigest> {
+        self.digests.get(key).map(|slot| &slot.digest)
+    }
+
+    /// Returns a mutable reference to the t-digest of a key, if it has values.
+    ///
+    /// The t-digest is written again by the next [`DigestMap::persist`],
+    /// whether or not it is changed. Its memory footprint is measured again
+    /// once the reference is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests = DigestMap::from_pairs([("a", 1.0)]);
+    /// digests.get_mut(&"a").unwrap().insert_many(2.0, 3);
+    /// assert_eq!(digests.get(&"a").unwrap().len(), 4);
+    /// ```
+    pub fn get_mut(&mut self, key: &K) -> Option<DigestMut<'_>> {
+        let used = self.eviction.tick();
+        let slot = self.digests.get_mut(key)?;
+        slot.file = None;
+        (slot.used, slot.used_at) = used;
+        Some(DigestMut {
+            slot,
+            memory_bytes: &mut self.eviction.memory_bytes,
+        })
+    }
+
+    /// Removes a key, and returns its t-digest.
+    ///
+    /// The t-digest is not handed to the eviction callback.
+    pub fn remove(&mut self, key: &K) -> Option<TDigest> {
+        let slot = self.digests.remove(key)?;
+        self.eviction.memory_bytes -= slot.bytes;
+        Some(slot.digest)
+    }
+
+    /// Adds a value under a key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests = DigestMap::new();
+    /// digests.insert("a", 1.0);
+    /// digests.insert("a", 2.0);
+    /// assert_eq!(digests.get(&"a").unwrap().len(), 2);
+    /// ```
+    pub fn insert(&mut self, key: K, value: f32) {
+        if !value.is_nan() {
+            self.update(key, |digest| digest.insert(value));
+        }
+    }
+
+    /// Adds `(key, value)` pairs.
+    ///
+    /// Consecutive pairs with the same key are added in batches, looking the
+    /// key up and compressing its t-digest once per batch, so that streams
+    /// grouped or sorted by key are faster to aggregate. The t-digests of the
+    /// other keys are left as they are. When the number of keys is known in advance, call
+    /// [`DigestMap::reserve`] first to avoid growing the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests = DigestMap::new();
+    /// digests.aggregate_by_key([("a", 1.0), ("a", 2.0), ("b", 3.0)]);
+    /// digests.aggregate_by_key([("b", 4.0)]);
+    /// assert_eq!(digests.quantile(&"b", 1.0), 4.0);
+    /// ```
+    pub fn aggregate_by_key<I: IntoIterator<Item = (K, f32)>>(&mut self, pairs: I) {
+        let mut batch = Vec::new();
+        let mut batch_key = None;
+        for (key, value) in pairs {
+            if value.is_nan() {
+                continue;
+            }
+            if batch_key.as_ref() != Some(&key) {
+                if let Some(batch_key) = batch_key.replace(key) {
+                    self.update(batch_key, |digest| {
+                        digest.extend(batch.drain(..));
+                        digest.compress();
+                    });
+                }
+            }
+            batch.push(value);
+        }
+        if let Some(batch_key) = batch_key {
+            self.update(batch_key, |digest| {
+                digest.extend(batch);
+                digest.compress();
+            });
+        }
+    }
+
+    /// Returns the estimated quantile of the values under a key, or NaN if it
+    /// has none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut digests = DigestMap::from_pairs([("a", 1.0), ("a", 2.0), ("a", 3.0)]);
+    /// assert_eq!(digests.quantile(&"a", 0.5), 2.0);
+    /// ```
+    pub fn quantile(&mut self, key: &K, level: f64) -> f32 {
+        let used = self.eviction.tick();
+        match self.digests.get_mut(key) {
+            Some(slot) => {
+                (slot.used, slot.used_at) = used;
+                slot.digest.quantile(level)
+            }
+            None => f32::NAN,
+        }
+    }
+
+    /// Writes a checkpoint of the map to the directory `dir`, creating it if
+    /// needed.
+    ///
+    /// The checkpoint is a file per key, holding its t-digest, and a manifest
+    /// that lists the keys along with their files. Only the t-digests that
+    /// changed since the last checkpoint to the same directory are written
+    /// again, and each file is written under a temporary name and then
+    /// renamed. The manifest is written last, the same way, so that a crash
+    /// at any point leaves either the previous or the new checkpoint. The
+    /// files that the new manifest no longer lists are then removed.
+    ///
+    /// Keys are written with their [`Display`] implementation, and read back
+    /// by [`DigestMap::restore`] with their [`FromStr`] implementation. A
+    /// directory holds the checkpoint of a single map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be written, in which case the
+    /// previous checkpoint is left, along with its manifest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("digest-map-{}", std::process::id()));
+    /// let mut latencies = DigestMap::from_pairs([("/users".to_string(), 12.0)]);
+    /// latencies.persist(&dir).unwrap();
+    ///
+    /// // Only the t-digest of `/orders` is written.
+    /// latencies.insert("/orders".to_string(), 250.0);
+    /// latencies.persist(&dir).unwrap();
+    ///
+    /// let restored: DigestMap<String> = DigestMap::restore(&dir).unwrap();
+    /// assert_eq!(restored, latencies);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn persist<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<()>
+    where
+        K: Display,
+    {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let checkpoint = match &mut self.checkpoint {
+            Some(checkpoint) if checkpoint.dir == dir => checkpoint,
+            checkpoint => {
+                // The files of another checkpoint cannot be reused, and those
+                // in the directory must not be overwritten.
+                for slot in self.digests.values_mut() {
+                    slot.file = None;
+                }
+                let template_file = next_free_file(dir)?;
+                write_atomic(
+                    dir,
+                    &file_name(template_file),
+                    &self.template.clone().encode_snapshot(),
+                )?;
+                checkpoint.insert(Checkpoint {
+                    dir: dir.to_path_buf(),
+                    template_file,
+                    next_file: template_file + 1,
+                })
+            }
+        };
+
+        let mut manifest = format!("{MANIFEST_HEADER}\ntemplate {}\n", checkpoint.template_file);
+        let mut files = HashSet::with_capacity(self.digests.len() + 1);
+        files.insert(checkpoint.template_file);
+        for (key, slot) in self.digests.iter_mut() {
+            let file = match slot.file {
+                Some(file) => file,
+                None => {
+                    let file = checkpoint.next_file;
+                    write_atomic(dir, &file_name(file), &slot.digest.encode_snapshot())?;
+                    checkpoint.next_file += 1;
+                    slot.file = Some(file);
+                    file
+                }
+            };
+            files.insert(file);
+            writeln!(manifest, "{file} {}", escape(&key.to_string())).unwrap();
+        }
+        sync_dir(dir)?;
+        write_atomic(dir, MANIFEST, manifest.as_bytes())?;
+        sync_dir(dir)?;
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            let stale = match name.strip_suffix(".tdigest") {
+                Some(file) => file.parse().is_ok_and(|file| !files.contains(&file)),
+                None => name.ends_with(".tmp"),
+            };
+            if stale {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the checkpoint written by [`DigestMap::persist`] to the
+    /// directory `dir`.
+    ///
+    /// The restored map remembers the checkpoint, so that persisting it to
+    /// the same directory only writes the t-digests that changed since.
+    /// Files left by an interrupted checkpoint are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the manifest or a file that it lists cannot be
+    /// read, or with [`io::ErrorKind::InvalidData`] if one of them is not
+    /// valid, or if a key cannot be parsed.
+    pub fn restore<P: AsRef<Path>>(dir: P) -> io::Result<Self>
+    where
+        K: FromStr,
+    {
+        let dir = dir.as_ref();
+        let manifest = fs::read_to_string(dir.join(MANIFEST))?;
+        let mut lines = manifest.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err(invalid_data("not a digest map manifest"));
+        }
+        let template_file = lines
+            .next()
+            .and_then(|line| line.strip_prefix("template "))
+            .and_then(|file| file.parse().ok())
+            .ok_or_else(|| invalid_data("missing template"))?;
+        let template = read_snapshot(dir, template_file)?;
+        if !template.is_empty() {
+            return Err(invalid_data("template is not empty"));
+        }
+
+        let mut digests = HashMap::new();
+        let mut next_file = template_file + 1;
+        for line in lines {
+            let (file, key) = line
+                .split_once(' ')
+                .ok_or_else(|| invalid_data("invalid manifest entry"))?;
+            let file: u64 = file
+                .parse()
+                .map_err(|_| invalid_data("invalid manifest entry"))?;
+            let key = unescape(key)
+                .and_then(|key| key.parse().ok())
+                .ok_or_else(|| invalid_data("invalid key"))?;
+            let mut slot = Slot::new(read_snapshot(dir, file)?);
+            slot.file = Some(file);
+            if digests.insert(key, slot).is_some() {
+                return Err(invalid_data("duplicate key"));
+            }
+            next_file = next_file.max(file + 1);
+        }
+        let mut digests = Self::from_slots(template, digests);
+        digests.checkpoint = Some(Checkpoint {
+            dir: dir.to_path_buf(),
+            template_file,
+            next_file,
+        });
+        Ok(digests)
+    }
+
+    fn sorted(&self) -> BTreeMap<&K, &TDigest>
+    where
+        K: Ord,
+    {
+        self.iter().collect()
+    }
+
+    /// Creates a map from the t-digests of its keys, as if they had been
+    /// used in turn.
+    fn from_slots(template: TDigest, mut digests: HashMap<K, Slot>) -> Self {
+        let mut eviction = Eviction::default();
+        for slot in digests.values_mut() {
+            eviction.clock += 1;
+            slot.used = eviction.clock;
+            eviction.memory_bytes += slot.bytes;
+        }
+        Self {
+            template,
+            digests,
+            pressure: None,
+            checkpoint: None,
+            eviction,
+        }
+    }
+
+    /// Applies `f` to the t-digest of a key, created if needed, and then
+    /// evicts keys if the map is beyond its limits.
+    fn update(&mut self, key: K, f: impl FnOnce(&mut TDigest)) {
+        if let Some((pressure, handled)) = &mut self.pressure {
+            let signals = pressure.signals();
+            if signals != *handled {
+                *handled = signals;
+                self.shrink_to_fit();
+            }
+        }
+        let used = self.eviction.tick();
+        let slot = self.digests.entry(key).or_insert_with(|| {
+            let slot = Slot::new(self.template.clone());
+            self.eviction.memory_bytes += slot.bytes;
+            slot
+        });
+        slot.file = None;
+        (slot.used, slot.used_at) = used;
+        f(&mut slot.digest);
+        slot.measure(&mut self.eviction.memory_bytes);
+
+        if let (Some(now), Some(ttl)) = (used.1, self.eviction.idle_ttl) {
+            if self
+                .eviction
+                .swept_at
+                .is_none_or(|swept_at| now.saturating_duration_since(swept_at) >= ttl / 8)
+            {
+                self.evict_idle(now);
+            }
+        }
+        self.enforce_limits();
+    }
+
+    /// Measures the memory footprint of all the t-digests again.
+    fn measure(&mut self) {
+        self.eviction.memory_bytes = 0;
+        for slot in self.digests.values_mut() {
+            slot.bytes = slot.digest.memory_bytes();
+            self.eviction.memory_bytes += slot.bytes;
+        }
+    }
+
+    /// Evicts the least recently used keys while the map exceeds its maximum
+    /// number of keys or its memory budget.
+    fn enforce_limits(&mut self) {
+        if let Some(max_keys) = self.eviction.max_keys {
+            if self.digests.len() > max_keys {
+                let excess = self.digests.len() - (max_keys - max_keys / 16);
+                let mut used: Vec<u64> = self.digests.values().map(|slot| slot.used).collect();
+                let (_, &mut oldest_kept, _) = used.select_nth_unstable(excess);
+                self.evict_where(|slot| slot.used < oldest_kept);
+            }
+        }
+        if let Some(budget) = self.eviction.memory_budget {
+            if self.eviction.memory_bytes > budget {
+                let target = budget - budget / 16;
+                let mut slots: Vec<(u64, usize)> = self
+                    .digests
+                    .values()
+                    .map(|slot| (slot.used, slot.bytes))
+                    .collect();
+                slots.sort_unstable();
+                let mut bytes = self.eviction.memory_bytes;
+                let mut evicted = 0;
+                while bytes > target && evicted + 1 < slots.len() {
+                    bytes -= slots[evicted].1;
+                    evicted += 1;
+                }
+                let oldest_kept = slots[evicted].0;
+                self.evict_where(|slot| slot.used < oldest_kept);
+            }
+        }
+    }
+
+    /// Removes the keys whose slots match `evict`, and hands them to the
+    /// eviction callback.
+    fn evict_where(&mut self, evict: impl Fn(&Slot) -> bool) {
+        let evicted: Vec<(K, Slot)> = self.digests.extract_if(|_, slot| evict(slot)).collect();
+        for (key, slot) in evicted {
+            self.eviction.memory_bytes -= slot.bytes;
+            if let Some(callback) = &self.eviction.callback {
+                let mut callback = callback.0.lock().unwrap_or_else(PoisonError::into_inner);
+                callback(key, slot.digest);
+            }
+        }
+    }
+}
+
+/// A mutable reference to the t-digest of a key of a [`DigestMap`].
+///
+/// This `struct` is created by the [`get_mut`] method on [`DigestMap`]. See
+/// its documentation for more.
+///
+/// [`get_mut`]: DigestMap::get_mut
+#[derive(Debug)]
+pub struct DigestMut<'a> {
+    slot: &'a mut Slot,
+    /// The total memory footprint of the map, updated on drop.
+    memory_bytes: &'a mut usize,
+}
+
+impl Deref for DigestMut<'_> {
+    type Target = TDigest;
+
+    #[inline]
+    fn deref(&self) -> &TDigest {
+        &self.slot.digest
+    }
+}
+
+impl DerefMut for DigestMut<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut TDigest {
+        &mut self.slot.digest
+    }
+}
+
+impl Drop for DigestMut<'_> {
+    fn drop(&mut self) {
+        self.slot.measure(self.memory_bytes);
+    }
+}
+
+impl<K> Eviction<K> {
+    /// Returns the logical time of a new use of a key, along with the current
+    /// time if the map has an idle TTL.
+    fn tick(&mut self) -> (u64, Option<Instant>) {
+        self.clock += 1;
+        (self.clock, self.idle_ttl.map(|_| Instant::now()))
+    }
+}
+
+impl<K: Hash + Eq + Clone> BitOr<&DigestMap<K>> for &DigestMap<K> {
+    type Output = DigestMap<K>;
+
+    /// Returns the union of `self` and `rhs` as a new `DigestMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let a = DigestMap::from_pairs([("a", 1.0), ("b", 2.0)]);
+    /// let b = DigestMap::from_pairs([("b", 3.0)]);
+    ///
+    /// let c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 2);
+    /// assert_eq!(c.get(&"b").unwrap().len(), 2);
+    /// ```
+    fn bitor(self, rhs: &DigestMap<K>) -> DigestMap<K> {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl<K: Hash + Eq + Clone> BitOrAssign<&DigestMap<K>> for DigestMap<K> {
+    /// Merges `self` and `rhs` into `self`, key by key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the t-digests of a key have units that cannot be converted
+    /// into each other, as with `|=` on [`TDigest`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::DigestMap;
+    ///
+    /// let mut a = DigestMap::from_pairs([("a", 1.0), ("b", 2.0)]);
+    /// let b = DigestMap::from_pairs([("b", 3.0), ("c", 4.0)]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 3);
+    /// assert_eq!(a.get(&"b").unwrap().len(), 2);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &DigestMap<K>) {
+        for (key, digest) in rhs.iter() {
+            self.update(key.clone(), |lhs| *lhs |= digest);
+        }
+    }
+}
+
+impl<K: Clone> Clone for DigestMap<K> {
+    /// Returns a copy of the map, which does not share the checkpoint of
+    /// `self`: persisting it writes all its t-digests.
+    fn clone(&self) -> Self {
+        Self {
+            template: self.template.clone(),
+            digests: self.digests.clone(),
+            pressure: self.pressure.clone(),
+            checkpoint: None,
+            eviction: self.eviction.clone(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> PartialEq for DigestMap<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.template == other.template
+            && self.digests.len() == other.digests.len()
+            && self
+                .iter()
+                .all(|(key, digest)| other.get(key) == Some(digest))
+    }
+}
+
+impl<K: Hash + Eq> Default for DigestMap<K> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Hash + Eq> Extend<(K, f32)> for DigestMap<K> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, f32)>>(&mut self, iter: I) {
+        self.aggregate_by_key(iter);
+    }
+}
+
+impl<K: Hash + Eq> FromIterator<(K, f32)> for DigestMap<K> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, f32)>>(iter: I) -> Self {
+        Self::from_pairs(iter)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<K: Hash + Eq + Ord + serde::Serialize> serde::Serialize for DigestMap<K> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // Keys are sorted, so that equal maps are serialized identically.
+        (&self.template, self.sorted()).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, K: Hash + Eq + serde::Deserialize<'de>> serde::Deserialize<'de> for DigestMap<K> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (template, digests): (TDigest, HashMap<K, TDigest>) =
+            serde::Deserialize::deserialize(deserializer)?;
+        let digests = digests
+            .into_iter()
+            .map(|(key, digest)| (key, Slot::new(digest)))
+            .collect();
+        Ok(Self::from_slots(template, digests))
+    }
+}
+
+fn file_name(file: u64) -> String {
+    format!("{file}.tdigest")
+}
+
+/// Returns a file number above those of all the t-digest files in `dir`.
+fn next_free_file(dir: &Path) -> io::Result<u64> {
+    let mut next = 0;
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let file = name.to_str().and_then(|name| name.strip_suffix(".tdigest"));
+        if let Some(file) = file.and_then(|file| file.parse::<u64>().ok()) {
+            next = next.max(file + 1);
+        }
+    }
+    Ok(next)
+}
+
+/// Writes `bytes` to the file `name` in `dir`, through a temporary file, so
+/// that the file is either left as it was or entirely written. The rename is
+/// only durable once `dir` is synced.
+fn write_atomic(dir: &Path, name: &str, bytes: &[u8]) -> io::Result<()> {
+    let path = dir.join(name);
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, &path)
+}
+
+/// Flushes the entries of `dir`, so that renames survive a crash of the
+/// system.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn read_snapshot(dir: &Path, file: u64) -> io::Result<TDigest> {
+    let bytes = fs::read(dir.join(file_name(file)))?;
+    TDigest::decode_snapshot(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Escapes backslashes and line breaks, so that a key fits on a line of the
+/// manifest.
+fn escape(key: &str) -> String {
+    let mut escaped = String::with_capacity(key.len());
+    for c in key.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(escaped: &str) -> Option<String> {
+    let mut key = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            key.push(match chars.next()? {
+                '\\' => '\\',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            });
+        } else {
+            key.push(c);
+        }
+    }
+    Some(key)
+}
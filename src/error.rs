@@ -0,0 +1,41 @@
+use std::fmt;
+
+/// The error type for fallible operations on sketches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The serialized state ended before it was fully read.
+    UnexpectedEof,
+    /// The serialized state has bytes left after it was fully read.
+    TrailingBytes,
+    /// The serialized state is malformed.
+    InvalidState(&'static str),
+    /// The sketch to subtract has more elements than the sketch it is
+    /// subtracted from.
+    CountUnderflow,
+    /// The sketches to merge have conflicting values for the given label.
+    LabelMismatch(String),
+    /// The units of the sketches cannot be converted into each other.
+    IncompatibleUnits,
+    /// The value to insert is NaN.
+    NanValue,
+    /// The number of copies of the value to insert is zero.
+    ZeroCount,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof => f.write_str("unexpected end of serialized state"),
+            Self::TrailingBytes => f.write_str("trailing bytes after serialized state"),
+            Self::InvalidState(reason) => write!(f, "invalid serialized state: {reason}"),
+            Self::CountUnderflow => f.write_str("subtracted sketch has more elements"),
+            Self::LabelMismatch(key) => write!(f, "mismatched label: {key}"),
+            Self::IncompatibleUnits => f.write_str("incompatible units"),
+            Self::NanValue => f.write_str("value is NaN"),
+            Self::ZeroCount => f.write_str("count is zero"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
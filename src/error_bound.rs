@@ -0,0 +1,63 @@
+use crate::{Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns a worst-case estimate of the error of the quantile at `level`,
+    /// in the unit of the values, or NaN if the t-digest is empty.
+    ///
+    /// The rank of the quantile is uncertain by half the weight of the
+    /// centroid that covers it, and by at least `2 q (1 - q) epsilon N`, half
+    /// the largest weight that compression allows at level `q`. The bound is
+    /// the largest distance from the quantile to the quantiles at the levels
+    /// that far away on either side. It is 0 while the t-digest retains all
+    /// its elements, see [`TDigestBuilder::retain_raw_samples`].
+    ///
+    /// [`TDigestBuilder::retain_raw_samples`]: crate::TDigestBuilder::retain_raw_samples
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..100_000).map(|v| v as f32));
+    /// for level in [0.5, 0.99, 0.999] {
+    ///     let exact = (level * 100_000.0) as f32;
+    ///     assert!((digest.quantile(level) - exact).abs() <= digest.error_bound(level));
+    /// }
+    /// assert!(digest.error_bound(0.999) < digest.error_bound(0.5));
+    /// ```
+    pub fn error_bound(&mut self, level: f64) -> F {
+        self.compress();
+        self.error_bound_uncompressed(level)
+    }
+
+    pub(crate) fn error_bound_uncompressed(&self, level: f64) -> F {
+        if self.is_empty() {
+            return F::NAN;
+        }
+        if self.is_exact() {
+            return F::from_f64(0.);
+        }
+        let level = level.clamp(0., 1.);
+        let count = self.count as f64;
+        let rank = level * count;
+
+        let mut start = 0.;
+        let mut weight = 0.;
+        for c in self.centroids.iter() {
+            weight = c.count as f64;
+            if start + weight >= rank {
+                break;
+            }
+            start += weight;
+        }
+        let allowed = 2. * level * (1. - level) * self.config.epsilon as f64 * count;
+        let spread = f64::max(weight / 2., allowed) / count;
+
+        let quantiles = self.quantiles_many_uncompressed(&[
+            f64::max(level - spread, 0.),
+            level,
+            f64::min(level + spread, 1.),
+        ]);
+        F::max(quantiles[1] - quantiles[0], quantiles[2] - quantiles[1])
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{interpolate, raw_samples, Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns the estimated quantile of the t-digest, along with the details
+    /// of how it was estimated.
+    ///
+    /// The explanation holds the centroids around the rank of `level`, the
+    /// ranks between which the quantile is interpolated, and the rule that
+    /// produced the estimate. This helps understand an unexpected estimate,
+    /// such as a p99 that matches a centroid far from the tail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{QuantileRule, TDigest};
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// let explanation = digest.quantile_explain(0.5);
+    /// assert_eq!(explanation.value(), digest.quantile(0.5));
+    /// assert_eq!(explanation.lower(), Some((2.0, 1)));
+    /// assert_eq!(explanation.upper(), Some((3.0, 1)));
+    /// assert!(explanation.lower_singleton() && explanation.upper_singleton());
+    /// assert_eq!(explanation.rule(), QuantileRule::Upper);
+    /// ```
+    pub fn quantile_explain(&mut self, level: f64) -> QuantileExplanation<F> {
+        self.compress();
+        self.explain_uncompressed(level)
+    }
+
+    pub(crate) fn explain_uncompressed(&self, level: f64) -> QuantileExplanation<F> {
+        let x = level * self.count as f64;
+        let mut explanation = QuantileExplanation {
+            level,
+            rank: x,
+            value: F::NAN,
+            lower: None,
+            upper: None,
+            lower_rank: f64::NAN,
+            upper_rank: f64::NAN,
+            lower_singleton: false,
+            upper_singleton: false,
+            rule: QuantileRule::Empty,
+        };
+
+        // Calculates the quantile q [0, 1] based on the digest.
+        // For an empty digest returns NaN.
+        let Some(&first) = self.centroids.first() else {
+            return explanation;
+        };
+
+        // The boundary quantiles are the exact extremes.
+        if level <= 0. {
+            explanation.value = self.min;
+            explanation.rule = QuantileRule::Min;
+            return explanation;
+        }
+        if level >= 1. {
+            explanation.value = self.max;
+            explanation.rule = QuantileRule::Max;
+            return explanation;
+        }
+
+        if let Some(values) = self.raw.exact(self.count) {
+            explanation.value = raw_samples::quantile(values, level);
+            explanation.rule = QuantileRule::RawSamples;
+            return explanation;
+        }
+
+        if let Some(value) = self.retained_quantile(level) {
+            explanation.value = value;
+            explanation.rule = QuantileRule::Extremes;
+            return explanation;
+        }
+
+        if let Some(tail) = self.dunning_tail(x) {
+            explanation.value = tail;
+            explanation.rule = QuantileRule::Tail;
+            return explanation;
+        }
+
+        if self.centroids.len() == 1 {
+            explanation.value = first.mean;
+            explanation.lower = Some((first.mean, first.count));
+            explanation.rule = QuantileRule::SingleCentroid;
+            return explanation;
+        }
+
+        let singletons = self.config.interpolation.singletons();
+        let mut prev_x = 0f64;
+        let mut sum = 0u64;
+        let mut prev = first;
+
+        for c in self.centroids.iter() {
+            let current_x = sum as f64 + c.count as f64 * 0.5;
+
+            if current_x >= x {
+                // Special handling of singletons.
+                let mut left = prev_x;
+                if singletons && prev.count == 1 {
+                    left += 0.5;
+                }
+                let mut right = current_x;
+                if singletons && c.count == 1 {
+                    right -= 0.5;
+                }
+
+                (explanation.value, explanation.rule) = if x <= left {
+                    (prev.mean, QuantileRule::Lower)
+                } else if x >= right {
+                    (c.mean, QuantileRule::Upper)
+                } else {
+                    (
+                        interpolate(
+                            F::from_f64(x),
+                            F::from_f64(left),
+                            prev.mean,
+                            F::from_f64(right),
+                            c.mean,
+                        ),
+                        QuantileRule::Interpolated,
+                    )
+                };
+                explanation.lower = Some((prev.mean, prev.count));
+                explanation.upper = Some((c.mean, c.count));
+                explanation.lower_rank = left;
+                explanation.upper_rank = right;
+                explanation.lower_singleton = prev.count == 1;
+                explanation.upper_singleton = c.count == 1;
+                return explanation;
+            }
+
+            sum += c.count;
+            prev = *c;
+            prev_x = current_x;
+        }
+
+        let last = self.centroids.last().unwrap();
+        explanation.value = last.mean;
+        explanation.lower = Some((last.mean, last.count));
+        explanation.rule = QuantileRule::AfterLast;
+        explanation
+    }
+}
+
+/// The rule that produced a quantile estimate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuantileRule {
+    /// The t-digest is empty, and the estimate is NaN.
+    Empty,
+    /// The level is 0 or lower, and the estimate is the exact smallest value.
+    Min,
+    /// The level is 1 or greater, and the estimate is the exact largest value.
+    Max,
+    /// The t-digest retains all its elements as raw values, and the estimate
+    /// is the exact quantile.
+    RawSamples,
+    /// The rank is among the smallest or largest values that the t-digest
+    /// retains, and the estimate is the exact quantile.
+    Extremes,
+    /// The rank is in the outer half of the first or last centroid, and the
+    /// estimate is interpolated towards the exact extreme, as with
+    /// [`Interpolation::Dunning`].
+    ///
+    /// [`Interpolation::Dunning`]: crate::Interpolation::Dunning
+    Tail,
+    /// The t-digest has a single centroid, whose mean is the estimate.
+    SingleCentroid,
+    /// The rank is at or below the lower rank, and the estimate is the mean
+    /// of the lower centroid.
+    Lower,
+    /// The rank is at or above the upper rank, and the estimate is the mean
+    /// of the upper centroid.
+    Upper,
+    /// The estimate is interpolated between the means of the lower and upper
+    /// centroids.
+    Interpolated,
+    /// The rank is past the middle of the last centroid, whose mean is the
+    /// estimate.
+    AfterLast,
+}
+
+/// A quantile estimate, along with the details of how it was estimated.
+///
+/// This `struct` is created by the [`quantile_explain`] method on
+/// [`TDigest`]. See its documentation for more.
+///
+/// [`quantile_explain`]: TDigest::quantile_explain
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuantileExplanation<F = f32> {
+    level: f64,
+    rank: f64,
+    value: F,
+    lower: Option<(F, u64)>,
+    upper: Option<(F, u64)>,
+    lower_rank: f64,
+    upper_rank: f64,
+    lower_singleton: bool,
+    upper_singleton: bool,
+    rule: QuantileRule,
+}
+
+impl<F: Float> QuantileExplanation<F> {
+    /// Returns the level of the quantile.
+    #[inline]
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Returns the rank of the quantile, that is the level times the number
+    /// of elements.
+    #[inline]
+    pub fn rank(&self) -> f64 {
+        self.rank
+    }
+
+    /// Returns the estimated quantile.
+    #[inline]
+    pub fn value(&self) -> F {
+        self.value
+    }
+
+    /// Returns the mean and weight of the centroid below the rank, or of the
+    /// only centroid used for the estimate.
+    #[inline]
+    pub fn lower(&self) -> Option<(F, u64)> {
+        self.lower
+    }
+
+    /// Returns the mean and weight of the centroid above the rank, if the
+    /// estimate involves two centroids.
+    #[inline]
+    pub fn upper(&self) -> Option<(F, u64)> {
+        self.upper
+    }
+
+    /// Returns the rank at which the estimate is the mean of the lower
+    /// centroid, or NaN if the estimate involves a single centroid.
+    #[inline]
+    pub fn lower_rank(&self) -> f64 {
+        self.lower_rank
+    }
+
+    /// Returns the rank at which the estimate is the mean of the upper
+    /// centroid, or NaN if the estimate involves a single centroid.
+    #[inline]
+    pub fn upper_rank(&self) -> f64 {
+        self.upper_rank
+    }
+
+    /// Returns `true` if the lower centroid is a singleton, which moves the
+    /// lower rank half an element up.
+    #[inline]
+    pub fn lower_singleton(&self) -> bool {
+        self.lower_singleton
+    }
+
+    /// Returns `true` if the upper centroid is a singleton, which moves the
+    /// upper rank half an element down.
+    #[inline]
+    pub fn upper_singleton(&self) -> bool {
+        self.upper_singleton
+    }
+
+    /// Returns the rule that produced the estimate.
+    #[inline]
+    pub fn rule(&self) -> QuantileRule {
+        self.rule
+    }
+}
@@ -0,0 +1,176 @@
+use std::cmp::Ordering;
+
+use crate::{
+    codec::{self, Reader},
+    Error, Float,
+};
+
+/// Exact smallest and largest values seen by a t-digest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Extremes<F = f32> {
+    /// The smallest values, in increasing order.
+    smallest: Vec<F>,
+    /// The largest values, in decreasing order.
+    largest: Vec<F>,
+    /// `true` if the values may not be the extremes of the t-digest, such as
+    /// after a subtraction.
+    stale: bool,
+}
+
+/// Inserts `count` copies of `value` into `values`, sorted by `cmp`, keeping
+/// only the first `k` values.
+fn retain_first<F: Float>(
+    values: &mut Vec<F>,
+    value: F,
+    count: u64,
+    k: usize,
+    cmp: fn(&F, &F) -> Ordering,
+) {
+    let index = values.partition_point(|v| cmp(v, &value) != Ordering::Greater);
+    let copies = count.min((k - index.min(k)) as u64) as usize;
+    if copies == 0 {
+        return;
+    }
+    values.splice(index..index, std::iter::repeat_n(value, copies));
+    values.truncate(k);
+}
+
+impl<F: Float> Extremes<F> {
+    #[inline]
+    pub(crate) fn smallest(&self) -> &[F] {
+        &self.smallest
+    }
+
+    #[inline]
+    pub(crate) fn largest(&self) -> &[F] {
+        &self.largest
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.smallest.clear();
+        self.largest.clear();
+        self.stale = false;
+    }
+
+    /// Clears the values, since the extremes of the t-digest can no longer be
+    /// known.
+    pub(crate) fn invalidate(&mut self) {
+        self.clear();
+        self.stale = true;
+    }
+
+    /// Returns `true` if the values are the `k` smallest and largest of the
+    /// `count` elements of the t-digest.
+    fn holds(&self, count: u64, k: usize) -> bool {
+        let len = count.min(k as u64);
+        !self.stale && self.smallest.len() as u64 >= len && self.largest.len() as u64 >= len
+    }
+
+    /// Returns the exact value at `index` in increasing order of the `count`
+    /// elements of the t-digest, if it is retained.
+    pub(crate) fn value(&self, index: u64, count: u64, k: usize) -> Option<F> {
+        if k == 0 || index >= count || !self.holds(count, k) {
+            return None;
+        }
+        if index < self.smallest.len() as u64 {
+            return Some(self.smallest[index as usize]);
+        }
+        let from_top = count - 1 - index;
+        (from_top < self.largest.len() as u64).then(|| self.largest[from_top as usize])
+    }
+
+    /// Multiplies the values by a positive factor.
+    pub(crate) fn scale(&mut self, factor: f64) {
+        for value in self.smallest.iter_mut().chain(self.largest.iter_mut()) {
+            *value = F::from_f64(value.to_f64() * factor);
+        }
+    }
+
+    pub(crate) fn insert(&mut self, value: F, count: u64, k: usize) {
+        if k == 0 {
+            return;
+        }
+        retain_first(&mut self.smallest, value, count, k, F::total_cmp);
+        retain_first(&mut self.largest, value, count, k, |l, r| r.total_cmp(l));
+    }
+
+    pub(crate) fn merge(&mut self, other: &Extremes<F>, other_count: u64, k: usize) {
+        if k == 0 {
+            return;
+        }
+        if !other.holds(other_count, k) {
+            self.stale = true;
+        }
+        for &value in other.smallest.iter() {
+            retain_first(&mut self.smallest, value, 1, k, F::total_cmp);
+        }
+        for &value in other.largest.iter() {
+            retain_first(&mut self.largest, value, 1, k, |l, r| r.total_cmp(l));
+        }
+    }
+}
+
+impl<F: Float> Extremes<F> {
+    /// Writes the values, as a flag for stale values followed by the lengths
+    /// and the values of both lists.
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.stale as u8);
+        for values in [&self.smallest, &self.largest] {
+            codec::write_varuint(buf, values.len() as u64);
+            for value in values {
+                codec::write_float(buf, *value);
+            }
+        }
+    }
+
+    /// Reads values written by [`Extremes::encode`], keeping at most `k` of
+    /// each.
+    pub(crate) fn decode(reader: &mut Reader<'_>, k: usize) -> Result<Self, Error> {
+        let stale = match reader.read_u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(Error::InvalidState("invalid flag")),
+        };
+        let mut lists = [Vec::new(), Vec::new()];
+        for values in lists.iter_mut() {
+            let len = reader.read_varuint()?;
+            if len > k as u64 {
+                return Err(Error::InvalidState("too many extremes"));
+            }
+            for _ in 0..len {
+                values.push(reader.read_float()?);
+            }
+        }
+        let [smallest, largest] = lists;
+        Ok(Self {
+            smallest,
+            largest,
+            stale,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<F: serde::Serialize> serde::Serialize for Extremes<F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (&self.smallest, &self.largest, self.stale).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, F: serde::Deserialize<'de>> serde::Deserialize<'de> for Extremes<F> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (smallest, largest, stale) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            smallest,
+            largest,
+            stale,
+        })
+    }
+}
@@ -0,0 +1,136 @@
+//! Goodness-of-fit checks of t-digests against analytic distributions.
+//!
+//! [`ks_statistic`] compares the cumulative distribution function of a
+//! t-digest with that of a reference [`Distribution`], so that a detector can
+//! check whether a metric still roughly follows a known distribution without
+//! keeping its raw values.
+//!
+//! # Examples
+//!
+//! ```
+//! use tdigest_ch::{fit, TDigest};
+//!
+//! let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32 / 10_000.0));
+//!
+//! let uniform = fit::Distribution::Uniform { low: 0.0, high: 1.0 };
+//! assert!(fit::ks_statistic(&mut digest, &uniform) < 0.01);
+//!
+//! let normal = fit::Distribution::Normal { mean: 0.5, std_dev: 0.1 };
+//! assert!(fit::ks_statistic(&mut digest, &normal) > 0.1);
+//! ```
+
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use crate::TDigest;
+
+/// An analytic reference distribution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    /// The uniform distribution between `low` and `high`.
+    Uniform {
+        /// The lower bound.
+        low: f64,
+        /// The upper bound.
+        high: f64,
+    },
+    /// The normal distribution.
+    Normal {
+        /// The mean.
+        mean: f64,
+        /// The standard deviation.
+        std_dev: f64,
+    },
+    /// The exponential distribution, such as of the time between independent
+    /// events.
+    Exponential {
+        /// The rate, inverse of the mean.
+        rate: f64,
+    },
+}
+
+impl Distribution {
+    /// Returns the probability that a value of the distribution is lower than
+    /// or equal to `value`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::fit::Distribution;
+    ///
+    /// let normal = Distribution::Normal { mean: 0.0, std_dev: 1.0 };
+    /// assert!((normal.cdf(1.96) - 0.975).abs() < 1e-4);
+    ///
+    /// let exponential = Distribution::Exponential { rate: 2.0 };
+    /// assert_eq!(exponential.cdf(-1.0), 0.0);
+    /// ```
+    pub fn cdf(&self, value: f64) -> f64 {
+        match *self {
+            Self::Uniform { low, high } => ((value - low) / (high - low)).clamp(0., 1.),
+            Self::Normal { mean, std_dev } => {
+                0.5 * (1. + erf((value - mean) / std_dev * FRAC_1_SQRT_2))
+            }
+            Self::Exponential { rate } => {
+                if value <= 0. {
+                    0.
+                } else {
+                    -(-rate * value).exp_m1()
+                }
+            }
+        }
+    }
+}
+
+/// Returns the Kolmogorov-Smirnov statistic between `digest` and
+/// `reference`, the largest difference between their cumulative distribution
+/// functions, or NaN if `digest` is empty.
+///
+/// The functions are compared on both sides of the mean of each centroid,
+/// where the function of the t-digest has its breakpoints. The statistic
+/// ranges from 0 to 1, and includes the error of the t-digest itself, so that
+/// values below a few times the compression parameter are not significant.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{fit, TDigest};
+///
+/// // Exponential values by inverse transform sampling.
+/// let mut digest =
+///     TDigest::from_iter((0..10_000).map(|v| -f32::ln(1.0 - (v as f32 + 0.5) / 10_000.0)));
+///
+/// let exponential = fit::Distribution::Exponential { rate: 1.0 };
+/// assert!(fit::ks_statistic(&mut digest, &exponential) < 0.01);
+/// ```
+pub fn ks_statistic(digest: &mut TDigest, reference: &Distribution) -> f64 {
+    digest.compress();
+    if digest.is_empty() {
+        return f64::NAN;
+    }
+    digest
+        .centroids
+        .iter()
+        .map(|c| {
+            let expected = reference.cdf(c.mean as f64);
+            let below = digest.cdf_uncompressed(c.mean.next_down());
+            let upto = digest.cdf_uncompressed(c.mean);
+            f64::max((below - expected).abs(), (upto - expected).abs())
+        })
+        .fold(0., f64::max)
+}
+
+/// Returns the error function at `x`, with an absolute error below 1.5e-7.
+fn erf(x: f64) -> f64 {
+    // Abramowitz and Stegun, formula 7.1.26.
+    const A: [f64; 5] = [
+        0.254829592,
+        -0.284496736,
+        1.421413741,
+        -1.453152027,
+        1.061405429,
+    ];
+    const P: f64 = 0.3275911;
+    let t = 1. / (1. + P * x.abs());
+    let poly = A.iter().rev().fold(0., |acc, a| (acc + a) * t);
+    let y = 1. - poly * (-x * x).exp();
+    y.copysign(x)
+}
@@ -0,0 +1,137 @@
+use std::{
+    cmp::Ordering,
+    fmt::Debug,
+    ops::{Add, Div, Mul, Sub},
+};
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for f32 {}
+    impl Sealed for f64 {}
+}
+
+/// A floating-point type of the values of a [`TDigest`].
+///
+/// This trait is implemented for `f32`, the default, and `f64`, for values
+/// such as timestamps or money amounts that `f32` cannot represent exactly.
+/// It is sealed: centroids are merged in `f64`, so that wider types would
+/// lose precision.
+///
+/// [`TDigest`]: crate::TDigest
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{Float, TDigest};
+///
+/// fn median<F: Float>(digest: &mut TDigest<F>) -> F {
+///     digest.quantile(0.5)
+/// }
+///
+/// let mut digest = TDigest::<f64>::default();
+/// digest.extend([1_700_000_000.25, 1_700_000_000.5, 1_700_000_000.75]);
+/// assert_eq!(median(&mut digest), 1_700_000_000.5);
+/// ```
+pub trait Float:
+    private::Sealed
+    + Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Send
+    + Sync
+    + 'static
+{
+    /// Positive infinity.
+    const INFINITY: Self;
+    /// Negative infinity.
+    const NEG_INFINITY: Self;
+    /// Not a number.
+    const NAN: Self;
+    /// The value 1.
+    const ONE: Self;
+
+    /// Converts a `f64` into the nearest value.
+    fn from_f64(value: f64) -> Self;
+
+    /// Converts the value into a `f64`.
+    fn to_f64(self) -> f64;
+
+    /// Returns `true` if the value is NaN.
+    fn is_nan(self) -> bool;
+
+    /// Returns `true` if the value is an infinity.
+    fn is_infinite(self) -> bool;
+
+    /// Returns the minimum of two values, ignoring NaN.
+    fn min(self, other: Self) -> Self;
+
+    /// Returns the maximum of two values, ignoring NaN.
+    fn max(self, other: Self) -> Self;
+
+    /// Returns the total ordering of two values, as with [`f32::total_cmp`].
+    fn total_cmp(&self, other: &Self) -> Ordering;
+
+    /// Returns the greatest value lower than this one, as with
+    /// [`f32::next_down`].
+    fn next_down(self) -> Self;
+}
+
+macro_rules! impl_float {
+    ($float:ident) => {
+        impl Float for $float {
+            const INFINITY: Self = $float::INFINITY;
+            const NEG_INFINITY: Self = $float::NEG_INFINITY;
+            const NAN: Self = $float::NAN;
+            const ONE: Self = 1.;
+
+            #[inline]
+            fn from_f64(value: f64) -> Self {
+                value as $float
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            #[inline]
+            fn is_nan(self) -> bool {
+                $float::is_nan(self)
+            }
+
+            #[inline]
+            fn is_infinite(self) -> bool {
+                $float::is_infinite(self)
+            }
+
+            #[inline]
+            fn min(self, other: Self) -> Self {
+                $float::min(self, other)
+            }
+
+            #[inline]
+            fn max(self, other: Self) -> Self {
+                $float::max(self, other)
+            }
+
+            #[inline]
+            fn total_cmp(&self, other: &Self) -> Ordering {
+                $float::total_cmp(self, other)
+            }
+
+            #[inline]
+            fn next_down(self) -> Self {
+                $float::next_down(self)
+            }
+        }
+    };
+}
+
+impl_float!(f32);
+impl_float!(f64);
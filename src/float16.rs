@@ -0,0 +1,66 @@
+use crate::{bfloat16, TDigest};
+
+/// Converts the bits of an IEEE 754 half-precision number into `f32`.
+pub(crate) fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1f32 } else { 1f32 };
+    let exponent = u32::from(bits >> 10) & 0x1f;
+    let mantissa = u32::from(bits) & 0x3ff;
+    match exponent {
+        // Zeros and subnormal numbers.
+        0 => sign * mantissa as f32 * 2f32.powi(-24),
+        // Infinities and NaNs.
+        0x1f if mantissa == 0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => f32::from_bits(
+            u32::from(bits & 0x8000) << 16 | (exponent + 127 - 15) << 23 | mantissa << 13,
+        ),
+    }
+}
+
+impl TDigest {
+    /// Adds a half-precision value to the t-digest, given by its bits.
+    ///
+    /// The value is converted to `f32` exactly. With the [`half`] crate, the
+    /// bits of an `f16` are given by `f16::to_bits`.
+    ///
+    /// [`half`]: https://docs.rs/half
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.insert_f16_bits(0x3c00); // 1.0
+    /// digest.insert_f16_bits(0x4000); // 2.0
+    /// digest.insert_f16_bits(0x4200); // 3.0
+    /// assert_eq!(digest.quantile(0.5), 2.0);
+    /// ```
+    #[inline]
+    pub fn insert_f16_bits(&mut self, bits: u16) {
+        self.insert(f16_to_f32(bits));
+    }
+
+    /// Adds a bfloat16 value to the t-digest, given by its bits.
+    ///
+    /// The value is converted to `f32` exactly. With the [`half`] crate, the
+    /// bits of a `bf16` are given by `bf16::to_bits`.
+    ///
+    /// [`half`]: https://docs.rs/half
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.insert_bf16_bits(0x3f80); // 1.0
+    /// digest.insert_bf16_bits(0x4000); // 2.0
+    /// digest.insert_bf16_bits(0x4040); // 3.0
+    /// assert_eq!(digest.quantile(0.5), 2.0);
+    /// ```
+    #[inline]
+    pub fn insert_bf16_bits(&mut self, bits: u16) {
+        self.insert(bfloat16::to_f32(bits));
+    }
+}
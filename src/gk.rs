@@ -0,0 +1,629 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::{codec::Reader, Error, QuantileSketch, TDigest};
+
+const DEFAULT_EPSILON: f64 = 0.01;
+const DEFAULT_COMPRESS_THRESHOLD: usize = 10000;
+
+/// A value of a Greenwald-Khanna summary, with the bounds on its rank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Tuple {
+    pub(crate) value: f32,
+    /// Difference between the minimum rank of this value and the minimum rank
+    /// of the previous one.
+    pub(crate) g: u64,
+    /// Difference between the maximum and the minimum rank of this value.
+    pub(crate) delta: u64,
+}
+
+/// Greenwald-Khanna ε-approximate quantile summary, from
+/// [Greenwald and Khanna][GK01].
+///
+/// The summary keeps a subset of the values along with bounds on their ranks,
+/// and guarantees that the rank of any estimated quantile is within `epsilon`
+/// times the number of elements of the exact one. This worst-case bound is
+/// deterministic, which makes the summary suitable when the error must be
+/// proven, at the cost of more memory than a t-digest.
+///
+/// This implementation follows the one of Apache Spark, which buffers incoming
+/// values and inserts them in batches.
+///
+/// [GK01]: https://doi.org/10.1145/376284.375670
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::GkSummary;
+///
+/// let mut summary = GkSummary::new();
+///
+/// // Add some elements.
+/// summary.insert(1.0);
+/// summary.insert(2.0);
+/// summary.insert(3.0);
+///
+/// // Get the median of the distribution.
+/// let quantile = summary.quantile(0.5);
+/// assert_eq!(quantile, 2.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct GkSummary {
+    epsilon: f64,
+    compress_threshold: usize,
+    count: u64,
+    sampled: Vec<Tuple>,
+    head: Vec<f32>,
+}
+
+impl GkSummary {
+    /// Creates an empty `GkSummary` with a relative error of 0.01.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    /// let summary = GkSummary::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_epsilon(DEFAULT_EPSILON)
+    }
+
+    /// Creates an empty `GkSummary` with the given relative error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `epsilon` is not between 0 and 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    /// let summary = GkSummary::with_epsilon(0.001);
+    /// ```
+    #[must_use]
+    pub fn with_epsilon(epsilon: f64) -> Self {
+        assert!(
+            epsilon > 0. && epsilon < 1.,
+            "epsilon must be between 0 and 1"
+        );
+        Self {
+            epsilon,
+            compress_threshold: DEFAULT_COMPRESS_THRESHOLD,
+            count: 0,
+            sampled: Vec::new(),
+            head: Vec::new(),
+        }
+    }
+
+    pub(crate) fn from_parts(
+        epsilon: f64,
+        compress_threshold: usize,
+        count: u64,
+        sampled: Vec<Tuple>,
+    ) -> Self {
+        Self {
+            epsilon,
+            compress_threshold,
+            count,
+            sampled,
+            head: Vec::new(),
+        }
+    }
+
+    /// Returns the relative error of the summary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let summary = GkSummary::with_epsilon(0.001);
+    /// assert_eq!(summary.epsilon(), 0.001);
+    /// ```
+    #[inline]
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    /// Returns the number of elements in the summary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let mut summary = GkSummary::new();
+    /// assert_eq!(summary.len(), 0);
+    /// summary.insert(1.0);
+    /// assert_eq!(summary.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.count + self.head.len() as u64
+    }
+
+    /// Returns `true` if the summary contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let mut summary = GkSummary::new();
+    /// assert!(summary.is_empty());
+    /// summary.insert(1.0);
+    /// assert!(!summary.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Clears the summary, removing all values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let mut summary = GkSummary::new();
+    /// summary.insert(1.0);
+    /// summary.clear();
+    /// assert!(summary.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.sampled.clear();
+        self.head.clear();
+    }
+
+    /// Adds a value to the summary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let mut summary = GkSummary::new();
+    ///
+    /// summary.insert(1.0);
+    /// summary.insert(2.0);
+    /// assert_eq!(summary.len(), 2);
+    /// ```
+    pub fn insert(&mut self, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+        self.head.push(value);
+        if self.head.len() >= self.compress_threshold {
+            self.compress();
+        }
+    }
+
+    /// Returns the estimated quantile of the summary.
+    ///
+    /// The rank of the result is guaranteed to be within `epsilon` times the
+    /// number of elements of the rank of the exact quantile. Levels are
+    /// clamped to `[0, 1]`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let mut summary = GkSummary::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(summary.quantile(0.0), 1.0);
+    /// assert_eq!(summary.quantile(0.5), 3.0);
+    /// assert_eq!(summary.quantile(1.0), 5.0);
+    /// ```
+    pub fn quantile(&mut self, level: f64) -> f32 {
+        self.compress();
+        self.quantile_compressed(level)
+    }
+
+    pub(crate) fn quantile_compressed(&self, level: f64) -> f32 {
+        let (Some(first), Some(last)) = (self.sampled.first(), self.sampled.last()) else {
+            return f32::NAN;
+        };
+        let level = level.clamp(0., 1.);
+        if level <= self.epsilon {
+            return first.value;
+        }
+        if level >= 1. - self.epsilon {
+            return last.value;
+        }
+
+        // Target rank.
+        let rank = (level * self.count as f64).ceil() as u64;
+        let target_error = self.sampled.iter().map(|t| t.g + t.delta).max().unwrap() / 2;
+        let mut min_rank = 0u64;
+        for tuple in self.sampled[..self.sampled.len() - 1].iter() {
+            min_rank += tuple.g;
+            let max_rank = min_rank + tuple.delta;
+            if max_rank.saturating_sub(target_error) <= rank && rank <= min_rank + target_error {
+                return tuple.value;
+            }
+        }
+        last.value
+    }
+
+    pub(crate) fn tuples(&mut self) -> &[Tuple] {
+        self.compress();
+        &self.sampled
+    }
+
+    /// Serializes the summary in the format of the Apache Spark
+    /// `percentile_approx` aggregation buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let mut summary = GkSummary::from([1.0, 2.0, 3.0]);
+    /// let bytes = summary.to_spark_bytes();
+    /// assert_eq!(GkSummary::from_spark_bytes(&bytes), Ok(summary));
+    /// ```
+    pub fn to_spark_bytes(&mut self) -> Vec<u8> {
+        self.compress();
+        let mut buf = Vec::with_capacity(24 + self.sampled.len() * 24);
+        buf.extend_from_slice(&(self.compress_threshold as i32).to_be_bytes());
+        buf.extend_from_slice(&self.epsilon.to_be_bytes());
+        buf.extend_from_slice(&(self.count as i64).to_be_bytes());
+        buf.extend_from_slice(&(self.sampled.len() as i32).to_be_bytes());
+        for tuple in self.sampled.iter() {
+            buf.extend_from_slice(&(tuple.value as f64).to_be_bytes());
+            buf.extend_from_slice(&(tuple.g as i64).to_be_bytes());
+            buf.extend_from_slice(&(tuple.delta as i64).to_be_bytes());
+        }
+        buf
+    }
+
+    /// Deserializes a summary from the format of the Apache Spark
+    /// `percentile_approx` aggregation buffer, which holds the
+    /// `QuantileSummaries` of the aggregation.
+    ///
+    /// The sampled values are narrowed to `f32`. The summary can then be
+    /// converted into a [`TDigest`], to be merged with other t-digests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not a valid buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{GkSummary, TDigest};
+    ///
+    /// let mut bytes = Vec::new();
+    /// bytes.extend_from_slice(&10000i32.to_be_bytes()); // Compress threshold.
+    /// bytes.extend_from_slice(&0.01f64.to_be_bytes()); // Relative error.
+    /// bytes.extend_from_slice(&3i64.to_be_bytes()); // Count.
+    /// bytes.extend_from_slice(&2i32.to_be_bytes()); // Number of samples.
+    /// for (value, g, delta) in [(1.0f64, 1i64, 0i64), (5.0, 2, 0)] {
+    ///     bytes.extend_from_slice(&value.to_be_bytes());
+    ///     bytes.extend_from_slice(&g.to_be_bytes());
+    ///     bytes.extend_from_slice(&delta.to_be_bytes());
+    /// }
+    ///
+    /// let summary = GkSummary::from_spark_bytes(&bytes).unwrap();
+    /// assert_eq!(summary.len(), 3);
+    ///
+    /// let mut digest = TDigest::from(summary);
+    /// assert_eq!(digest.quantile(1.0), 5.0);
+    /// ```
+    pub fn from_spark_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let compress_threshold = reader.read_u32_be()? as i32;
+        let epsilon = f64::from_bits(reader.read_u64_be()?);
+        let count = reader.read_u64_be()? as i64;
+        let len = reader.read_u32_be()? as i32;
+        if compress_threshold <= 0 || !(epsilon > 0. && epsilon < 1.) || count < 0 || len < 0 {
+            return Err(Error::InvalidState("invalid summary header"));
+        }
+        let mut sampled = Vec::new();
+        let mut total = 0u64;
+        for _ in 0..len {
+            let value = f64::from_bits(reader.read_u64_be()?) as f32;
+            let g = reader.read_u64_be()? as i64;
+            let delta = reader.read_u64_be()? as i64;
+            if value.is_nan() || g < 0 || delta < 0 {
+                return Err(Error::InvalidState("invalid sample"));
+            }
+            if sampled.last().is_some_and(|t: &Tuple| t.value > value) {
+                return Err(Error::InvalidState("samples are not sorted"));
+            }
+            total += g as u64;
+            sampled.push(Tuple {
+                value,
+                g: g as u64,
+                delta: delta as u64,
+            });
+        }
+        reader.finish()?;
+        if total != count as u64 {
+            return Err(Error::InvalidState("count does not match the samples"));
+        }
+        Ok(Self::from_parts(
+            epsilon,
+            compress_threshold as usize,
+            count as u64,
+            sampled,
+        ))
+    }
+
+    /// Inserts the buffered values, and compresses the resulting tuples.
+    fn compress(&mut self) {
+        self.insert_head();
+        self.sampled = compress_tuples(&self.sampled, 2. * self.epsilon * self.count as f64);
+    }
+
+    fn insert_head(&mut self) {
+        if self.head.is_empty() {
+            return;
+        }
+        let mut head = std::mem::take(&mut self.head);
+        head.sort_unstable_by(f32::total_cmp);
+
+        let mut sampled = Vec::with_capacity(self.sampled.len() + head.len());
+        let mut sample_index = 0;
+        for (head_index, &value) in head.iter().enumerate() {
+            // Add all the samples before the next value.
+            while sample_index < self.sampled.len() && self.sampled[sample_index].value <= value {
+                sampled.push(self.sampled[sample_index]);
+                sample_index += 1;
+            }
+            self.count += 1;
+            // The first and the last values have an exact rank.
+            let delta = if sampled.is_empty()
+                || (sample_index == self.sampled.len() && head_index == head.len() - 1)
+            {
+                0
+            } else {
+                (2. * self.epsilon * self.count as f64).floor() as u64
+            };
+            sampled.push(Tuple { value, g: 1, delta });
+        }
+        sampled.extend_from_slice(&self.sampled[sample_index..]);
+        self.sampled = sampled;
+        head.clear();
+        self.head = head;
+    }
+}
+
+fn compress_tuples(tuples: &[Tuple], merge_threshold: f64) -> Vec<Tuple> {
+    let Some((&last, rest)) = tuples.split_last() else {
+        return Vec::new();
+    };
+    let mut result = Vec::with_capacity(tuples.len());
+    // Start from the last tuple, which is always kept. The head may absorb the
+    // tuples before it.
+    let mut head = last;
+    // Do not compress the first tuple.
+    for &tuple in rest.iter().skip(1).rev() {
+        if ((tuple.g + head.g + head.delta) as f64) < merge_threshold {
+            head.g += tuple.g;
+        } else {
+            result.push(head);
+            head = tuple;
+        }
+    }
+    result.push(head);
+    if let Some(&first) = rest.first() {
+        if first.value <= head.value {
+            result.push(first);
+        }
+    }
+    result.reverse();
+    result
+}
+
+impl BitOr<&GkSummary> for &GkSummary {
+    type Output = GkSummary;
+
+    /// Returns the union of `self` and `rhs` as a new `GkSummary`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let a = GkSummary::from([1.0, 2.0, 3.0]);
+    /// let b = GkSummary::from([3.0, 4.0, 5.0]);
+    ///
+    /// let mut c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 6);
+    /// assert_eq!(c.quantile(0.5), 3.0);
+    /// ```
+    fn bitor(self, rhs: &GkSummary) -> GkSummary {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&GkSummary> for GkSummary {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// The relative error of the result is the largest of the two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let mut a = GkSummary::from([1.0, 2.0, 3.0]);
+    /// let b = GkSummary::from([3.0, 4.0, 5.0]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 6);
+    /// assert_eq!(a.quantile(0.5), 3.0);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &GkSummary) {
+        let mut rhs = rhs.clone();
+        rhs.compress();
+        self.compress();
+        if rhs.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            let compress_threshold = self.compress_threshold;
+            *self = rhs;
+            self.compress_threshold = compress_threshold;
+            return;
+        }
+
+        // Values coming from one side suffer from the lack of precision of the
+        // other side, so their rank uncertainty grows accordingly.
+        let self_extra_delta = (2. * rhs.epsilon * rhs.count as f64).floor() as u64;
+        let rhs_extra_delta = (2. * self.epsilon * self.count as f64).floor() as u64;
+        let mut merged = Vec::with_capacity(self.sampled.len() + rhs.sampled.len());
+        let (mut i, mut j) = (0, 0);
+        while i < self.sampled.len() && j < rhs.sampled.len() {
+            let (mut tuple, extra_delta) = if self.sampled[i].value < rhs.sampled[j].value {
+                i += 1;
+                (
+                    self.sampled[i - 1],
+                    if j > 0 { self_extra_delta } else { 0 },
+                )
+            } else {
+                j += 1;
+                (rhs.sampled[j - 1], if i > 0 { rhs_extra_delta } else { 0 })
+            };
+            tuple.delta += extra_delta;
+            merged.push(tuple);
+        }
+        merged.extend_from_slice(&self.sampled[i..]);
+        merged.extend_from_slice(&rhs.sampled[j..]);
+
+        self.epsilon = self.epsilon.max(rhs.epsilon);
+        self.count += rhs.count;
+        self.sampled = compress_tuples(&merged, 2. * self.epsilon * self.count as f64);
+    }
+}
+
+impl Default for GkSummary {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<f32> for GkSummary {
+    fn extend<I: IntoIterator<Item = f32>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<const N: usize> From<[f32; N]> for GkSummary {
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::GkSummary;
+    ///
+    /// let summary1 = GkSummary::from([1.0, 2.0, 3.0, 4.0]);
+    /// let summary2: GkSummary = [1.0, 2.0, 3.0, 4.0].into();
+    /// assert_eq!(summary1, summary2);
+    /// ```
+    fn from(array: [f32; N]) -> Self {
+        let mut summary = GkSummary::new();
+        summary.extend(array);
+        summary
+    }
+}
+
+impl FromIterator<f32> for GkSummary {
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> Self {
+        let mut summary = GkSummary::new();
+        summary.extend(iter);
+        summary
+    }
+}
+
+impl From<GkSummary> for TDigest {
+    /// Converts a summary into a t-digest, with a centroid for each tuple of the
+    /// summary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{GkSummary, TDigest};
+    ///
+    /// let summary = GkSummary::from_iter((1..=1000).map(|v| v as f32));
+    /// let mut digest = TDigest::from(summary);
+    /// assert_eq!(digest.len(), 1000);
+    /// assert!((digest.quantile(0.5) - 500.0).abs() <= 10.0);
+    /// ```
+    fn from(mut summary: GkSummary) -> Self {
+        let mut digest = TDigest::new();
+        for tuple in summary.tuples() {
+            digest.insert_many(tuple.value, tuple.g);
+        }
+        digest
+    }
+}
+
+impl From<TDigest> for GkSummary {
+    /// Converts a t-digest into a summary, with a tuple for each centroid of the
+    /// t-digest.
+    ///
+    /// Each centroid is treated as that many copies of its mean. The guarantee
+    /// of the summary thus only holds relative to the t-digest, not to the
+    /// values it was built from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{GkSummary, TDigest};
+    ///
+    /// let digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let mut summary = GkSummary::from(digest);
+    /// assert_eq!(summary.len(), 1000);
+    /// assert!((summary.quantile(0.5) - 500.0).abs() <= 10.0);
+    /// ```
+    fn from(mut digest: TDigest) -> Self {
+        digest.compress();
+        let sampled = digest
+            .centroids
+            .iter()
+            .map(|c| Tuple {
+                value: c.mean,
+                g: c.count,
+                delta: 0,
+            })
+            .collect();
+        GkSummary::from_parts(
+            DEFAULT_EPSILON,
+            DEFAULT_COMPRESS_THRESHOLD,
+            digest.count,
+            sampled,
+        )
+    }
+}
+
+impl QuantileSketch for GkSummary {
+    #[inline]
+    fn insert(&mut self, value: f32) {
+        self.insert(value);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        *self |= other;
+    }
+
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    #[inline]
+    fn quantile(&mut self, level: f64) -> f32 {
+        self.quantile(level)
+    }
+}
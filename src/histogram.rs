@@ -0,0 +1,88 @@
+use crate::{Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns the estimated number of elements in each bin delimited by
+    /// `edges`.
+    ///
+    /// As with [`Bins`], `n` edges make `n + 1` bins: values below the first
+    /// edge fall in bin 0, values from the first edge up to the second in bin
+    /// 1, and so on, so that the counts add up to the number of elements.
+    /// Centroids that straddle an edge are split between bins by
+    /// interpolation, as with [`TDigest::rank`], and the counts are rounded.
+    ///
+    /// [`Bins`]: crate::Bins
+    ///
+    /// # Panics
+    ///
+    /// Panics if `edges` are not strictly increasing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// let counts = digest.histogram(&[100.0, 500.0]);
+    /// assert_eq!(counts, [100, 400, 500]);
+    /// ```
+    pub fn histogram(&mut self, edges: &[F]) -> Vec<u64> {
+        self.compress();
+        self.histogram_uncompressed(edges)
+    }
+
+    /// Returns the `n - 1` estimated cut points that split the elements into
+    /// `n` bins of equal weight, in increasing order.
+    ///
+    /// These are the quantiles at levels `1 / n`, `2 / n`, and so on. They can
+    /// serve as the edges of an adaptive histogram axis, or of buckets for
+    /// stratified sampling. The cut points are NaN if the t-digest is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let edges = digest.equal_weight_bins(4);
+    /// assert_eq!(edges, [250.5, 500.5, 750.5]);
+    /// assert_eq!(digest.histogram(&edges), [250, 250, 250, 250]);
+    /// ```
+    pub fn equal_weight_bins(&mut self, n: usize) -> Vec<F> {
+        self.compress();
+        self.equal_weight_bins_uncompressed(n)
+    }
+
+    pub(crate) fn equal_weight_bins_uncompressed(&self, n: usize) -> Vec<F> {
+        assert!(n > 0, "number of bins must be positive");
+        let levels: Vec<f64> = (1..n).map(|i| i as f64 / n as f64).collect();
+        self.quantiles_many_uncompressed(&levels)
+    }
+
+    pub(crate) fn histogram_uncompressed(&self, edges: &[F]) -> Vec<u64> {
+        assert!(
+            edges.windows(2).all(|w| w[0] < w[1]),
+            "edges must be strictly increasing"
+        );
+        let mut counts = Vec::with_capacity(edges.len() + 1);
+        if self.is_empty() {
+            counts.resize(edges.len() + 1, 0);
+            return counts;
+        }
+
+        // Rounding the cumulative counts, rather than each bin, keeps the
+        // total exact.
+        let mut below = 0;
+        for &edge in edges {
+            let rank = self.cdf_uncompressed(edge.next_down()) * self.count as f64;
+            let rank = rank.round() as u64;
+            counts.push(rank - below);
+            below = rank;
+        }
+        counts.push(self.count - below);
+        counts
+    }
+}
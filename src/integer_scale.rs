@@ -0,0 +1,274 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::TDigest;
+
+/// A t-digest of integer ticks, such as the nanoseconds of a timer, where
+/// each value is a number of ticks times a fixed resolution.
+///
+/// Insertion takes `u64` ticks and quantiles are returned in ticks, so that
+/// the hot path does not convert timers to floating-point values. The ticks
+/// are stored as `f32` centroids: ticks up to 2<sup>24</sup> (about 16.7
+/// million) are exact, and larger ticks are rounded to the nearest `f32`,
+/// with a relative error of at most 2<sup>-24</sup>. Quantiles are rounded to
+/// the nearest tick, and saturate at `u64::MAX`.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::IntegerScale;
+///
+/// // Nanosecond ticks, with values in seconds.
+/// let mut timer = IntegerScale::new(1e-9);
+/// timer.extend([1_000, 2_000, 3_000, 4_000, 5_000]);
+///
+/// assert_eq!(timer.quantile(0.5), Some(3_000));
+/// assert_eq!(timer.quantile_value(0.5), 3e-6);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntegerScale {
+    resolution: f64,
+    digest: TDigest,
+}
+
+impl IntegerScale {
+    /// Creates an empty `IntegerScale` whose ticks stand for `resolution`
+    /// units of value each.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `resolution` is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::IntegerScale;
+    /// let timer = IntegerScale::new(1e-6);
+    /// ```
+    #[must_use]
+    pub fn new(resolution: f64) -> Self {
+        Self::with_template(TDigest::new(), resolution)
+    }
+
+    /// Creates an `IntegerScale` whose t-digest uses the configuration of
+    /// `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not empty, or if `resolution` is not positive
+    /// and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{IntegerScale, TDigest};
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.max_centroids(256);
+    ///
+    /// let timer = IntegerScale::with_template(builder.build(), 1e-9);
+    /// ```
+    #[must_use]
+    pub fn with_template(template: TDigest, resolution: f64) -> Self {
+        assert!(template.is_empty(), "template must be empty");
+        assert!(
+            resolution > 0. && resolution.is_finite(),
+            "resolution must be positive and finite"
+        );
+        Self {
+            resolution,
+            digest: template,
+        }
+    }
+
+    /// Returns the value of one tick.
+    #[inline]
+    pub fn resolution(&self) -> f64 {
+        self.resolution
+    }
+
+    /// Returns the t-digest of the ticks.
+    #[inline]
+    pub fn digest(&self) -> &TDigest {
+        &self.digest
+    }
+
+    /// Returns the number of elements.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.digest.len()
+    }
+
+    /// Returns `true` if there are no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.digest.is_empty()
+    }
+
+    /// Clears the `IntegerScale`, removing all elements.
+    pub fn clear(&mut self) {
+        self.digest.clear();
+    }
+
+    /// Adds a number of ticks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::IntegerScale;
+    ///
+    /// let mut timer = IntegerScale::new(1e-9);
+    /// timer.insert(1_500);
+    /// assert_eq!(timer.len(), 1);
+    /// ```
+    #[inline]
+    pub fn insert(&mut self, ticks: u64) {
+        self.insert_many(ticks, 1);
+    }
+
+    /// Adds `count` elements of the same number of ticks.
+    pub fn insert_many(&mut self, ticks: u64, count: u64) {
+        self.digest.insert_many(ticks as f32, count);
+    }
+
+    /// Returns the estimated quantile at `level`, in ticks, or `None` if
+    /// there are no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::IntegerScale;
+    ///
+    /// let mut timer = IntegerScale::new(1e-9);
+    /// assert_eq!(timer.quantile(0.5), None);
+    ///
+    /// timer.extend([10, 20, 30]);
+    /// assert_eq!(timer.quantile(1.0), Some(30));
+    /// ```
+    pub fn quantile(&mut self, level: f64) -> Option<u64> {
+        to_ticks(self.digest.quantile(level))
+    }
+
+    /// Returns the estimated quantiles at each of `levels`, in ticks, or
+    /// `None` for each level if there are no elements.
+    pub fn quantiles_many(&mut self, levels: &[f64]) -> Vec<Option<u64>> {
+        self.digest
+            .quantiles_many(levels)
+            .into_iter()
+            .map(to_ticks)
+            .collect()
+    }
+
+    /// Returns the estimated quantile at `level`, in units of value, or NaN
+    /// if there are no elements.
+    pub fn quantile_value(&mut self, level: f64) -> f64 {
+        self.quantile(level)
+            .map_or(f64::NAN, |ticks| ticks as f64 * self.resolution)
+    }
+
+    /// Returns the estimated fraction of the elements that are lower than or
+    /// equal to `ticks`, or NaN if there are no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::IntegerScale;
+    ///
+    /// let mut timer = IntegerScale::new(1e-9);
+    /// timer.extend([10, 20, 30, 40]);
+    /// assert_eq!(timer.cdf(20), 0.5);
+    /// ```
+    pub fn cdf(&mut self, ticks: u64) -> f64 {
+        self.digest.cdf(ticks as f32)
+    }
+}
+
+/// Rounds a quantile to the nearest tick.
+fn to_ticks(quantile: f32) -> Option<u64> {
+    (!quantile.is_nan()).then(|| quantile.round() as u64)
+}
+
+impl BitOr<&IntegerScale> for &IntegerScale {
+    type Output = IntegerScale;
+
+    /// Returns the union of `self` and `rhs` as a new `IntegerScale`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::IntegerScale;
+    ///
+    /// let mut a = IntegerScale::new(1e-9);
+    /// a.extend([1, 2]);
+    /// let mut b = IntegerScale::new(1e-9);
+    /// b.insert(3);
+    ///
+    /// let c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 3);
+    /// ```
+    fn bitor(self, rhs: &IntegerScale) -> IntegerScale {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&IntegerScale> for IntegerScale {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// The resolution of `self` applies: the ticks of `rhs` are converted if
+    /// its resolution differs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::IntegerScale;
+    ///
+    /// let mut nanos = IntegerScale::new(1e-9);
+    /// nanos.insert(1_000);
+    /// let mut micros = IntegerScale::new(1e-6);
+    /// micros.insert(2);
+    ///
+    /// nanos |= &micros;
+    ///
+    /// assert_eq!(nanos.quantile(1.0), Some(2_000));
+    /// ```
+    fn bitor_assign(&mut self, rhs: &IntegerScale) {
+        if rhs.resolution == self.resolution {
+            self.digest |= &rhs.digest;
+        } else {
+            let mut digest = rhs.digest.clone();
+            digest.scale_values(rhs.resolution / self.resolution);
+            self.digest |= &digest;
+        }
+    }
+}
+
+impl Extend<u64> for IntegerScale {
+    fn extend<I: IntoIterator<Item = u64>>(&mut self, iter: I) {
+        for ticks in iter {
+            self.insert(ticks);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for IntegerScale {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (self.resolution, &self.digest).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IntegerScale {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (resolution, digest) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self { resolution, digest })
+    }
+}
@@ -0,0 +1,161 @@
+use crate::{Float, TDigest};
+
+/// How quantiles are interpolated between centroids.
+///
+/// Implementations of t-digests agree on the centroids, but give slightly
+/// different quantiles around singletons and in the tails. The cumulative
+/// distribution function inverts the interpolation of the quantiles.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{Interpolation, TDigest};
+///
+/// let mut clickhouse = TDigest::from([1.0, 2.0]);
+/// assert_eq!(clickhouse.quantile(0.5), 1.0);
+///
+/// let mut builder = TDigest::builder();
+/// builder.interpolation(Interpolation::Midpoint);
+/// let mut midpoint = builder.build();
+/// midpoint.extend([1.0, 2.0]);
+/// assert_eq!(midpoint.quantile(0.5), 1.5);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// The interpolation of ClickHouse: quantiles are interpolated between the
+    /// midpoints of neighboring centroids, and a singleton centroid is a point
+    /// mass that covers half a rank on each side of its midpoint. Ranks
+    /// beyond the midpoints of the first and last centroids get their means.
+    #[default]
+    ClickHouse,
+    /// The interpolation of the reference implementation of Ted Dunning: as
+    /// with ClickHouse between centroids, but ranks beyond the midpoints of
+    /// the first and last centroids are interpolated towards the exact
+    /// smallest and largest values.
+    Dunning,
+    /// Quantiles are interpolated between the midpoints of neighboring
+    /// centroids, without special handling of singletons.
+    Midpoint,
+}
+
+impl Interpolation {
+    /// Returns `true` if singleton centroids cover half a rank on each side.
+    #[inline]
+    pub(crate) fn singletons(self) -> bool {
+        self != Self::Midpoint
+    }
+}
+
+impl<F: Float> TDigest<F> {
+    /// Returns the quantile at rank `x` if it is in a tail interpolated
+    /// towards the extremes, as with [`Interpolation::Dunning`]. The t-digest
+    /// must be compressed and not empty.
+    pub(crate) fn dunning_tail(&self, x: f64) -> Option<F> {
+        if self.config.interpolation != Interpolation::Dunning {
+            return None;
+        }
+        let (first, last) = (self.centroids[0], self.centroids[self.centroids.len() - 1]);
+        let count = self.count as f64;
+        if x < 1. {
+            return Some(self.min);
+        }
+        let half = first.count as f64 / 2.;
+        if first.count > 2 && x < half {
+            let t = (x - 1.) / (half - 1.);
+            let (min, mean) = (self.min.to_f64(), first.mean.to_f64());
+            return Some(F::from_f64(min + t * (mean - min)));
+        }
+        if x > count - 1. {
+            return Some(self.max);
+        }
+        let half = last.count as f64 / 2.;
+        if last.count > 2 && count - x <= half {
+            let t = (count - x - 1.) / (half - 1.);
+            let (max, mean) = (self.max.to_f64(), last.mean.to_f64());
+            return Some(F::from_f64(max - t * (max - mean)));
+        }
+        None
+    }
+
+    /// Returns the rank of `value`, counted from the bottom, if it is below
+    /// the first centroid, in the tail interpolated towards the smallest
+    /// value as with [`Interpolation::Dunning`]. The t-digest must be
+    /// compressed and not empty.
+    pub(crate) fn dunning_lower_rank(&self, value: F) -> Option<f64> {
+        let first = self.centroids[0];
+        if self.config.interpolation != Interpolation::Dunning
+            || value.is_nan()
+            || value >= first.mean
+        {
+            return None;
+        }
+        if value < self.min {
+            return Some(0.);
+        }
+        let t = (value - self.min).to_f64() / (first.mean - self.min).to_f64();
+        Some(1. + t * (tail_rank(first.count) - 1.))
+    }
+
+    /// Returns the rank of `value`, counted from the top, if it is not below
+    /// the last centroid, in the tail interpolated towards the largest value
+    /// as with [`Interpolation::Dunning`]. The t-digest must be compressed
+    /// and not empty.
+    pub(crate) fn dunning_upper_rank(&self, value: F) -> Option<f64> {
+        let last = self.centroids[self.centroids.len() - 1];
+        if self.config.interpolation != Interpolation::Dunning
+            || value.is_nan()
+            || value < last.mean
+        {
+            return None;
+        }
+        if value >= self.max {
+            return Some(0.);
+        }
+        let t = (self.max - value).to_f64() / (self.max - last.mean).to_f64();
+        Some(1. + t * (tail_rank(last.count) - 1.))
+    }
+}
+
+/// Returns the rank, counted from the nearest extreme, at which the tail
+/// interpolated towards the extreme reaches the mean of a centroid of
+/// `count` elements, as with [`Interpolation::Dunning`].
+///
+/// The rank of the extreme itself is 1, and there is no tail to interpolate
+/// if the centroid holds fewer than three elements.
+pub(crate) fn tail_rank(count: u64) -> f64 {
+    (count as f64 / 2.).max(1.)
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Interpolation {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::ClickHouse => "clickhouse",
+            Self::Dunning => "dunning",
+            Self::Midpoint => "midpoint",
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Interpolation {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let interpolation: String = serde::Deserialize::deserialize(deserializer)?;
+        match interpolation.as_str() {
+            "clickhouse" => Ok(Self::ClickHouse),
+            "dunning" => Ok(Self::Dunning),
+            "midpoint" => Ok(Self::Midpoint),
+            _ => Err(serde::de::Error::unknown_variant(
+                &interpolation,
+                &["clickhouse", "dunning", "midpoint"],
+            )),
+        }
+    }
+}
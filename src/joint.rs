@@ -0,0 +1,398 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::{deterministic::int_hash64, TDigest};
+
+const DEFAULT_SAMPLE_SIZE: usize = 1024;
+
+/// A pair of t-digests over two dimensions of the same events, along with a
+/// sample of the joint values.
+///
+/// Each dimension is summarized by its own [`TDigest`], which answers
+/// per-dimension quantiles. The joint sample keeps a bounded, uniformly chosen
+/// subset of the pairs, from which the relation between the two dimensions can
+/// be plotted. The sample keeps the pairs with the smallest hashes, so that
+/// merging joint digests keeps a uniform sample of all their pairs.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::JointDigest;
+///
+/// let mut joint = JointDigest::new();
+///
+/// // Record the latency and the payload size of some requests.
+/// joint.insert(12.0, 512.0);
+/// joint.insert(25.0, 2048.0);
+/// joint.insert(31.0, 4096.0);
+///
+/// let report = joint.report(&[0.5]);
+/// assert_eq!(report.x_quantiles(), [25.0]);
+/// assert_eq!(report.y_quantiles(), [2048.0]);
+/// assert_eq!(report.samples().len(), 3);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct JointDigest {
+    x: TDigest,
+    y: TDigest,
+    sample_size: usize,
+    inserted: u64,
+    /// Sampled pairs, along with their hash. Only the `sample_size` pairs with
+    /// the smallest hashes are part of the sample.
+    samples: Vec<(u64, f32, f32)>,
+}
+
+impl JointDigest {
+    /// Creates an empty `JointDigest` that samples up to 1024 pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    /// let joint = JointDigest::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_sample_size(DEFAULT_SAMPLE_SIZE)
+    }
+
+    /// Creates an empty `JointDigest` that samples up to `sample_size` pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let mut joint = JointDigest::with_sample_size(2);
+    /// joint.extend([(1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]);
+    /// assert_eq!(joint.report(&[]).samples().len(), 2);
+    /// ```
+    #[must_use]
+    pub fn with_sample_size(sample_size: usize) -> Self {
+        Self::from_digests(TDigest::new(), TDigest::new(), sample_size)
+    }
+
+    /// Creates a `JointDigest` from empty t-digests, so that their
+    /// configuration is used for each dimension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `y` is not empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{JointDigest, TDigest};
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.max_centroids(256);
+    /// let x = builder.build();
+    ///
+    /// let joint = JointDigest::from_digests(x, TDigest::new(), 512);
+    /// ```
+    #[must_use]
+    pub fn from_digests(x: TDigest, y: TDigest, sample_size: usize) -> Self {
+        assert!(x.is_empty() && y.is_empty(), "digests must be empty");
+        Self {
+            x,
+            y,
+            sample_size,
+            inserted: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Returns the t-digest of the first dimension.
+    #[inline]
+    pub fn x(&self) -> &TDigest {
+        &self.x
+    }
+
+    /// Returns the t-digest of the second dimension.
+    #[inline]
+    pub fn y(&self) -> &TDigest {
+        &self.y
+    }
+
+    /// Returns the number of pairs in the joint digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let mut joint = JointDigest::new();
+    /// assert_eq!(joint.len(), 0);
+    /// joint.insert(1.0, 2.0);
+    /// assert_eq!(joint.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.inserted
+    }
+
+    /// Returns `true` if the joint digest contains no pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let mut joint = JointDigest::new();
+    /// assert!(joint.is_empty());
+    /// joint.insert(1.0, 2.0);
+    /// assert!(!joint.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inserted == 0
+    }
+
+    /// Clears the joint digest, removing all pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let mut joint = JointDigest::new();
+    /// joint.insert(1.0, 2.0);
+    /// joint.clear();
+    /// assert!(joint.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.x.clear();
+        self.y.clear();
+        self.inserted = 0;
+        self.samples.clear();
+    }
+
+    /// Adds a pair of values to the joint digest.
+    ///
+    /// Pairs where either value is NaN are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let mut joint = JointDigest::new();
+    /// joint.insert(1.0, 2.0);
+    /// joint.insert(f32::NAN, 3.0);
+    /// assert_eq!(joint.len(), 1);
+    /// ```
+    pub fn insert(&mut self, x: f32, y: f32) {
+        if x.is_nan() || y.is_nan() {
+            return;
+        }
+        self.x.insert(x);
+        self.y.insert(y);
+        let bits = u64::from(x.to_bits()) << 32 | u64::from(y.to_bits());
+        let hash = int_hash64(self.inserted ^ int_hash64(bits));
+        self.inserted += 1;
+        self.samples.push((hash, x, y));
+        if self.samples.len() >= 2 * self.sample_size.max(1) {
+            self.shrink_samples();
+        }
+    }
+
+    /// Returns the quantiles of both dimensions at the given levels, along
+    /// with the sampled pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let mut joint = JointDigest::from_iter((1..=5).map(|v| (v as f32, 10.0 * v as f32)));
+    /// let report = joint.report(&[0.0, 1.0]);
+    /// assert_eq!(report.levels(), [0.0, 1.0]);
+    /// assert_eq!(report.x_quantiles(), [1.0, 5.0]);
+    /// assert_eq!(report.y_quantiles(), [10.0, 50.0]);
+    /// ```
+    pub fn report(&mut self, levels: &[f64]) -> JointReport {
+        self.shrink_samples();
+        let x_quantiles = levels.iter().map(|&level| self.x.quantile(level)).collect();
+        let y_quantiles = levels.iter().map(|&level| self.y.quantile(level)).collect();
+        let samples = self.samples.iter().map(|&(_, x, y)| (x, y)).collect();
+        JointReport {
+            levels: levels.to_vec(),
+            x_quantiles,
+            y_quantiles,
+            samples,
+        }
+    }
+
+    /// Keeps only the pairs with the smallest hashes.
+    fn shrink_samples(&mut self) {
+        if self.samples.len() > self.sample_size {
+            self.samples
+                .select_nth_unstable_by_key(self.sample_size, |&(hash, _, _)| hash);
+            self.samples.truncate(self.sample_size);
+        }
+    }
+}
+
+/// Per-dimension quantiles and sampled pairs of a joint digest.
+///
+/// This `struct` is created by the [`report`] method on [`JointDigest`]. See its
+/// documentation for more.
+///
+/// [`report`]: JointDigest::report
+#[derive(Clone, Debug, PartialEq)]
+pub struct JointReport {
+    levels: Vec<f64>,
+    x_quantiles: Vec<f32>,
+    y_quantiles: Vec<f32>,
+    samples: Vec<(f32, f32)>,
+}
+
+impl JointReport {
+    /// Returns the levels of the quantiles.
+    #[inline]
+    pub fn levels(&self) -> &[f64] {
+        &self.levels
+    }
+
+    /// Returns the quantiles of the first dimension, one for each level.
+    #[inline]
+    pub fn x_quantiles(&self) -> &[f32] {
+        &self.x_quantiles
+    }
+
+    /// Returns the quantiles of the second dimension, one for each level.
+    #[inline]
+    pub fn y_quantiles(&self) -> &[f32] {
+        &self.y_quantiles
+    }
+
+    /// Returns the sampled pairs, in no particular order.
+    #[inline]
+    pub fn samples(&self) -> &[(f32, f32)] {
+        &self.samples
+    }
+}
+
+impl BitOr<&JointDigest> for &JointDigest {
+    type Output = JointDigest;
+
+    /// Returns the union of `self` and `rhs` as a new `JointDigest`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let a = JointDigest::from([(1.0, 2.0), (2.0, 4.0)]);
+    /// let b = JointDigest::from([(3.0, 6.0)]);
+    ///
+    /// let c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 3);
+    /// ```
+    fn bitor(self, rhs: &JointDigest) -> JointDigest {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&JointDigest> for JointDigest {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// The sample size of `self` applies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let mut a = JointDigest::from([(1.0, 2.0), (2.0, 4.0)]);
+    /// let b = JointDigest::from([(3.0, 6.0)]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 3);
+    /// assert_eq!(a.report(&[]).samples().len(), 3);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &JointDigest) {
+        self.x |= &rhs.x;
+        self.y |= &rhs.y;
+        self.inserted += rhs.inserted;
+        self.samples.extend_from_slice(&rhs.samples);
+        self.shrink_samples();
+    }
+}
+
+impl Default for JointDigest {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<(f32, f32)> for JointDigest {
+    fn extend<I: IntoIterator<Item = (f32, f32)>>(&mut self, iter: I) {
+        for (x, y) in iter {
+            self.insert(x, y);
+        }
+    }
+}
+
+impl<const N: usize> From<[(f32, f32); N]> for JointDigest {
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::JointDigest;
+    ///
+    /// let joint1 = JointDigest::from([(1.0, 2.0), (3.0, 4.0)]);
+    /// let joint2: JointDigest = [(1.0, 2.0), (3.0, 4.0)].into();
+    /// assert_eq!(joint1, joint2);
+    /// ```
+    fn from(array: [(f32, f32); N]) -> Self {
+        let mut joint = JointDigest::new();
+        joint.extend(array);
+        joint
+    }
+}
+
+impl FromIterator<(f32, f32)> for JointDigest {
+    fn from_iter<I: IntoIterator<Item = (f32, f32)>>(iter: I) -> Self {
+        let mut joint = JointDigest::new();
+        joint.extend(iter);
+        joint
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for JointDigest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (
+            &self.x,
+            &self.y,
+            self.sample_size,
+            self.inserted,
+            &self.samples,
+        )
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for JointDigest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (x, y, sample_size, inserted, samples) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            x,
+            y,
+            sample_size,
+            inserted,
+            samples,
+        })
+    }
+}
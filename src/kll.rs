@@ -0,0 +1,527 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::{
+    codec::{self, Reader},
+    Error, QuantileSketch,
+};
+
+const DEFAULT_K: u32 = 200;
+const FORMAT_VERSION: u8 = 1;
+/// Ratio between the capacities of two consecutive compactors.
+const CAPACITY_RATIO: f64 = 2. / 3.;
+const RNG_SEED: u64 = 0x9e3779b97f4a7c15;
+
+/// KLL quantile sketch, from [Karnin, Lang and Liberty][KLL16].
+///
+/// The sketch stores values in a hierarchy of compactors. When a compactor is
+/// full, it sorts its values and promotes every other one to the next level,
+/// where each value stands for twice as many elements. Unlike a t-digest, the
+/// sketch bounds the error on the rank of the quantiles uniformly over all
+/// levels: with the default `k` of 200, the rank error is typically below
+/// 1.65%.
+///
+/// Compactions are randomized with a generator seeded at construction, so that
+/// the sketch is fully determined by its inputs.
+///
+/// [KLL16]: https://arxiv.org/abs/1603.05346
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::KllSketch;
+///
+/// let mut sketch = KllSketch::new();
+///
+/// // Add some elements.
+/// sketch.insert(1.0);
+/// sketch.insert(2.0);
+/// sketch.insert(3.0);
+///
+/// // Get the median of the distribution.
+/// let quantile = sketch.quantile(0.5);
+/// assert_eq!(quantile, 2.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct KllSketch {
+    k: u32,
+    count: u64,
+    compactors: Vec<Vec<f32>>,
+    rng: u64,
+}
+
+impl KllSketch {
+    /// Creates an empty `KllSketch` with `k` set to 200.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    /// let sketch = KllSketch::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_k(DEFAULT_K)
+    }
+
+    /// Creates an empty `KllSketch` with the given `k` parameter.
+    ///
+    /// Larger values of `k` increase the accuracy of the sketch, and its size.
+    /// The rank error decreases roughly as `1 / k`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is less than 2.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    /// let sketch = KllSketch::with_k(400);
+    /// ```
+    #[must_use]
+    pub fn with_k(k: u32) -> Self {
+        assert!(k >= 2, "k must be at least 2");
+        Self {
+            k,
+            count: 0,
+            compactors: vec![Vec::new()],
+            rng: RNG_SEED,
+        }
+    }
+
+    /// Returns the number of elements in the sketch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let mut sketch = KllSketch::new();
+    /// assert_eq!(sketch.len(), 0);
+    /// sketch.insert(1.0);
+    /// assert_eq!(sketch.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if the sketch contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let mut sketch = KllSketch::new();
+    /// assert!(sketch.is_empty());
+    /// sketch.insert(1.0);
+    /// assert!(!sketch.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Clears the sketch, removing all values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let mut sketch = KllSketch::new();
+    /// sketch.insert(1.0);
+    /// sketch.clear();
+    /// assert!(sketch.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        *self = Self::with_k(self.k);
+    }
+
+    /// Adds a value to the sketch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let mut sketch = KllSketch::new();
+    ///
+    /// sketch.insert(1.0);
+    /// sketch.insert(2.0);
+    /// assert_eq!(sketch.len(), 2);
+    /// ```
+    pub fn insert(&mut self, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+        self.compactors[0].push(value);
+        self.count += 1;
+        if self.size() >= self.max_size() {
+            self.compress();
+        }
+    }
+
+    /// Returns the estimated rank of `value`, that is the number of elements
+    /// in the sketch that are less than or equal to `value`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let sketch = KllSketch::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(sketch.rank(0.0), 0);
+    /// assert_eq!(sketch.rank(3.0), 3);
+    /// assert_eq!(sketch.rank(10.0), 5);
+    /// ```
+    pub fn rank(&self, value: f32) -> u64 {
+        self.compactors
+            .iter()
+            .enumerate()
+            .map(|(height, compactor)| {
+                let below = compactor.iter().filter(|&&v| v <= value).count() as u64;
+                below << height
+            })
+            .sum()
+    }
+
+    /// Returns the estimated quantile of the sketch.
+    ///
+    /// The result is the smallest retained value whose estimated rank reaches
+    /// `level` times the number of elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let sketch = KllSketch::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(sketch.quantile(0.0), 1.0);
+    /// assert_eq!(sketch.quantile(0.5), 3.0);
+    /// assert_eq!(sketch.quantile(1.0), 5.0);
+    /// ```
+    pub fn quantile(&self, level: f64) -> f32 {
+        let mut items: Vec<(f32, u64)> = self
+            .compactors
+            .iter()
+            .enumerate()
+            .flat_map(|(height, compactor)| compactor.iter().map(move |&v| (v, 1u64 << height)))
+            .collect();
+        if items.is_empty() {
+            return f32::NAN;
+        }
+        items.sort_unstable_by(|l, r| l.0.total_cmp(&r.0));
+
+        let threshold = level * self.count as f64;
+        let mut accumulated = 0u64;
+        for &(value, weight) in items.iter() {
+            accumulated += weight;
+            if accumulated as f64 >= threshold {
+                return value;
+            }
+        }
+        items.last().unwrap().0
+    }
+
+    /// Serializes the sketch into bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let sketch = KllSketch::from_iter((0..10_000).map(|i| i as f32));
+    /// let bytes = sketch.to_bytes();
+    /// assert_eq!(KllSketch::from_bytes(&bytes), Ok(sketch));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(32 + self.size() * 4);
+        buf.push(FORMAT_VERSION);
+        buf.extend_from_slice(&self.k.to_le_bytes());
+        buf.extend_from_slice(&self.count.to_le_bytes());
+        buf.extend_from_slice(&self.rng.to_le_bytes());
+        codec::write_varuint(&mut buf, self.compactors.len() as u64);
+        for compactor in self.compactors.iter() {
+            codec::write_varuint(&mut buf, compactor.len() as u64);
+            for value in compactor.iter() {
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Deserializes a sketch from bytes produced by [`to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not a valid serialized sketch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// assert!(KllSketch::from_bytes(&[]).is_err());
+    /// ```
+    ///
+    /// [`to_bytes`]: KllSketch::to_bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        if reader.read_u8()? != FORMAT_VERSION {
+            return Err(Error::InvalidState("unsupported format version"));
+        }
+        let k = reader.read_u32()?;
+        if k < 2 {
+            return Err(Error::InvalidState("k must be at least 2"));
+        }
+        let count = reader.read_u64()?;
+        let rng = reader.read_u64()?;
+        let height = reader.read_varuint()?;
+        if height == 0 || height > 64 {
+            return Err(Error::InvalidState("invalid number of compactors"));
+        }
+        let mut compactors = Vec::with_capacity(height as usize);
+        for _ in 0..height {
+            let size = reader.read_varuint()?;
+            if size > bytes.len() as u64 {
+                return Err(Error::UnexpectedEof);
+            }
+            let mut compactor = Vec::with_capacity(size as usize);
+            for _ in 0..size {
+                compactor.push(reader.read_f32()?);
+            }
+            compactors.push(compactor);
+        }
+        reader.finish()?;
+        let sketch = Self {
+            k,
+            count,
+            compactors,
+            rng,
+        };
+        sketch.validate()?;
+        Ok(sketch)
+    }
+
+    /// Checks the invariants of a decoded sketch, which the other methods
+    /// rely on.
+    fn validate(&self) -> Result<(), Error> {
+        if self.k < 2 {
+            return Err(Error::InvalidState("k must be at least 2"));
+        }
+        if self.compactors.is_empty() || self.compactors.len() > 64 {
+            return Err(Error::InvalidState("invalid number of compactors"));
+        }
+        let weight = self
+            .compactors
+            .iter()
+            .enumerate()
+            .fold(0u64, |weight, (h, compactor)| {
+                weight.saturating_add((compactor.len() as u64).saturating_mul(1 << h))
+            });
+        if weight != self.count {
+            return Err(Error::InvalidState("weights do not sum to the count"));
+        }
+        // Xorshift never leaves the zero state.
+        if self.rng == 0 {
+            return Err(Error::InvalidState("random state is zero"));
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.compactors.iter().map(Vec::len).sum()
+    }
+
+    fn capacity(&self, height: usize) -> usize {
+        let depth = self.compactors.len() - height - 1;
+        (CAPACITY_RATIO.powi(depth as i32) * self.k as f64).ceil() as usize + 1
+    }
+
+    fn max_size(&self) -> usize {
+        (0..self.compactors.len()).map(|h| self.capacity(h)).sum()
+    }
+
+    /// Returns a pseudo-random bit, using xorshift64.
+    fn random_bit(&mut self) -> bool {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng & 1 == 1
+    }
+
+    fn compress(&mut self) {
+        for height in 0..self.compactors.len() {
+            if self.compactors[height].len() >= self.capacity(height) {
+                if height + 1 >= self.compactors.len() {
+                    self.compactors.push(Vec::new());
+                }
+                self.compact(height);
+                if self.size() < self.max_size() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Sorts the compactor at `height`, and promotes every other value to the
+    /// next one. If the compactor holds an odd number of values, the smallest
+    /// one stays in place.
+    fn compact(&mut self, height: usize) {
+        let offset = usize::from(self.random_bit());
+        let mut compactor = std::mem::take(&mut self.compactors[height]);
+        compactor.sort_unstable_by(f32::total_cmp);
+        let kept = compactor.len() % 2;
+        let promoted = compactor[kept..].iter().skip(offset).step_by(2).copied();
+        self.compactors[height + 1].extend(promoted);
+        compactor.truncate(kept);
+        self.compactors[height] = compactor;
+    }
+}
+
+impl BitOr<&KllSketch> for &KllSketch {
+    type Output = KllSketch;
+
+    /// Returns the union of `self` and `rhs` as a new `KllSketch`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let a = KllSketch::from([1.0, 2.0, 3.0]);
+    /// let b = KllSketch::from([3.0, 4.0, 5.0]);
+    ///
+    /// let c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 6);
+    /// assert_eq!(c.quantile(0.5), 3.0);
+    /// ```
+    fn bitor(self, rhs: &KllSketch) -> KllSketch {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&KllSketch> for KllSketch {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let mut a = KllSketch::from([1.0, 2.0, 3.0]);
+    /// let b = KllSketch::from([3.0, 4.0, 5.0]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 6);
+    /// assert_eq!(a.quantile(0.5), 3.0);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &KllSketch) {
+        while self.compactors.len() < rhs.compactors.len() {
+            self.compactors.push(Vec::new());
+        }
+        for (compactor, rhs_compactor) in self.compactors.iter_mut().zip(&rhs.compactors) {
+            compactor.extend_from_slice(rhs_compactor);
+        }
+        self.count += rhs.count;
+        while self.size() >= self.max_size() {
+            self.compress();
+        }
+    }
+}
+
+impl Default for KllSketch {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<f32> for KllSketch {
+    fn extend<I: IntoIterator<Item = f32>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<const N: usize> From<[f32; N]> for KllSketch {
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::KllSketch;
+    ///
+    /// let sketch1 = KllSketch::from([1.0, 2.0, 3.0, 4.0]);
+    /// let sketch2: KllSketch = [1.0, 2.0, 3.0, 4.0].into();
+    /// assert_eq!(sketch1, sketch2);
+    /// ```
+    fn from(array: [f32; N]) -> Self {
+        let mut sketch = KllSketch::new();
+        sketch.extend(array);
+        sketch
+    }
+}
+
+impl FromIterator<f32> for KllSketch {
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> Self {
+        let mut sketch = KllSketch::new();
+        sketch.extend(iter);
+        sketch
+    }
+}
+
+impl QuantileSketch for KllSketch {
+    #[inline]
+    fn insert(&mut self, value: f32) {
+        self.insert(value);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        *self |= other;
+    }
+
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    #[inline]
+    fn quantile(&mut self, level: f64) -> f32 {
+        KllSketch::quantile(self, level)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for KllSketch {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (self.k, self.count, &self.compactors, self.rng).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for KllSketch {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (k, count, compactors, rng) = serde::Deserialize::deserialize(deserializer)?;
+        let sketch = Self {
+            k,
+            count,
+            compactors,
+            rng,
+        };
+        sketch.validate().map_err(serde::de::Error::custom)?;
+        Ok(sketch)
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+
+use crate::Error;
+
+/// Labels attached to a t-digest, such as the service, the endpoint or the
+/// unit of the values.
+pub(crate) type Labels = BTreeMap<String, String>;
+
+/// How the labels of t-digests are validated when merging them.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{Error, LabelPolicy, TDigest};
+///
+/// let mut builder = TDigest::builder();
+/// builder.label_policy(LabelPolicy::Union);
+///
+/// let mut a = builder.build();
+/// a.set_label("service", "api");
+/// let mut b = TDigest::new();
+/// b.set_label("endpoint", "/users");
+///
+/// a.try_merge(&b).unwrap();
+/// assert_eq!(a.labels().len(), 2);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LabelPolicy {
+    /// Both t-digests must have the same labels.
+    #[default]
+    MustMatch,
+    /// The labels of both t-digests are combined, and labels that are set on
+    /// both must have the same value.
+    Union,
+}
+
+/// Merges the labels of `rhs` into `lhs`, according to `policy`.
+pub(crate) fn merge(lhs: &mut Labels, rhs: &Labels, policy: LabelPolicy) -> Result<(), Error> {
+    match policy {
+        LabelPolicy::MustMatch => {
+            let mismatch = lhs
+                .iter()
+                .find(|&(key, value)| rhs.get(key) != Some(value))
+                .map(|(key, _)| key)
+                .or_else(|| rhs.keys().find(|&key| !lhs.contains_key(key)));
+            match mismatch {
+                Some(key) => Err(Error::LabelMismatch(key.clone())),
+                None => Ok(()),
+            }
+        }
+        LabelPolicy::Union => {
+            if let Some((key, _)) = rhs
+                .iter()
+                .find(|&(key, value)| lhs.get(key).is_some_and(|v| v != value))
+            {
+                return Err(Error::LabelMismatch(key.clone()));
+            }
+            union(lhs, rhs);
+            Ok(())
+        }
+    }
+}
+
+/// Adds the labels of `rhs` that are not set on `lhs`.
+pub(crate) fn union(lhs: &mut Labels, rhs: &Labels) {
+    for (key, value) in rhs.iter() {
+        lhs.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for LabelPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::MustMatch => "must_match",
+            Self::Union => "union",
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LabelPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let policy: String = serde::Deserialize::deserialize(deserializer)?;
+        match policy.as_str() {
+            "must_match" => Ok(Self::MustMatch),
+            "union" => Ok(Self::Union),
+            _ => Err(serde::de::Error::unknown_variant(
+                &policy,
+                &["must_match", "union"],
+            )),
+        }
+    }
+}
@@ -30,30 +30,119 @@
 //! assert_eq!(quantile, 2.0);
 //! ```
 
+mod bfloat16;
+pub mod bulk;
+mod cdf;
+mod centroids;
+mod change_point;
+mod codec;
+mod compare;
+#[cfg(feature = "compat")]
+pub mod compat;
+mod compressed;
+mod conditional;
+mod decayed;
+mod density;
+mod deterministic;
+#[cfg(feature = "differential")]
+pub mod differential;
+mod digest_map;
+mod error;
+mod error_bound;
+mod explain;
+mod extremes;
+pub mod fit;
+mod float;
+mod float16;
+mod gk;
+mod histogram;
+mod integer_scale;
+mod interpolation;
+mod joint;
+mod kll;
+mod labels;
+mod memory_pressure;
+mod narrowing;
+#[cfg(feature = "npy")]
+mod npy;
+pub mod planner;
+mod range_stats;
+mod raw_samples;
+mod redis;
+#[cfg(feature = "rand")]
+pub mod resample;
+mod resampled;
+mod series;
+mod sketch;
+mod sliding;
+mod snapshot;
+mod stats;
+mod timing;
+mod top_quantile;
+mod unit;
+mod weight_profile;
+mod weighted;
+
 use std::{
     cmp::Ordering,
+    collections::BTreeMap,
     ops::{BitOr, BitOrAssign},
+    time::Duration,
+};
+
+use crate::{extremes::Extremes, labels::Labels, raw_samples::RawSamples};
+
+pub use crate::{
+    bfloat16::BFloat16Histogram,
+    cdf::CdfFunction,
+    change_point::{ChangeEvent, ChangePointDetector, Divergence},
+    compare::ComparisonReport,
+    compressed::CompressedTDigest,
+    conditional::{BinnedDigests, Bins, ConditionalDigests},
+    decayed::DecayedTDigest,
+    deterministic::DeterministicReservoir,
+    digest_map::{DigestMap, DigestMut},
+    error::Error,
+    explain::{QuantileExplanation, QuantileRule},
+    float::Float,
+    gk::GkSummary,
+    integer_scale::IntegerScale,
+    interpolation::Interpolation,
+    joint::{JointDigest, JointReport},
+    kll::KllSketch,
+    labels::LabelPolicy,
+    memory_pressure::MemoryPressure,
+    narrowing::Narrowing,
+    range_stats::RangeStats,
+    series::QuantileSeries,
+    sketch::QuantileSketch,
+    sliding::{SlidingTDigest, TimeWindowTDigest},
+    stats::DigestStats,
+    timing::TimingHistogram,
+    unit::Unit,
+    weight_profile::CentroidWeight,
+    weighted::WeightedTDigest,
 };
 
 /// Stores the weight of points around their mean value.
 #[derive(Clone, Copy, Debug, PartialEq)]
-struct Centroid {
-    mean: f32,
-    count: usize,
+struct Centroid<F = f32> {
+    mean: F,
+    count: u64,
 }
 
 #[cfg(feature = "serde")]
-impl serde::Serialize for Centroid {
+impl<F: serde::Serialize> serde::Serialize for Centroid<F> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        (self.mean, self.count).serialize(serializer)
+        (&self.mean, self.count).serialize(serializer)
     }
 }
 
 #[cfg(feature = "serde")]
-impl<'de> serde::Deserialize<'de> for Centroid {
+impl<'de, F: serde::Deserialize<'de>> serde::Deserialize<'de> for Centroid<F> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
@@ -68,6 +157,11 @@ struct Config {
     epsilon: f32,
     max_centroids: usize,
     max_unmerged: usize,
+    coalesce: bool,
+    extremes: usize,
+    raw_samples: usize,
+    interpolation: Interpolation,
+    label_policy: LabelPolicy,
 }
 
 impl Default for Config {
@@ -76,6 +170,11 @@ impl Default for Config {
             epsilon: 0.01,
             max_centroids: 2048,
             max_unmerged: 2048,
+            coalesce: false,
+            extremes: 0,
+            raw_samples: 0,
+            interpolation: Interpolation::ClickHouse,
+            label_policy: LabelPolicy::MustMatch,
         }
     }
 }
@@ -86,7 +185,40 @@ impl serde::Serialize for Config {
     where
         S: serde::Serializer,
     {
-        (self.epsilon, self.max_centroids, self.max_unmerged).serialize(serializer)
+        use serde::ser::SerializeTuple;
+
+        // The fields added after the first release are only written up to the
+        // last one that is not at its default, so that configurations that do
+        // not use them keep the shape of the first release.
+        let default = Config::default();
+        let added = [
+            self.coalesce != default.coalesce,
+            self.extremes != default.extremes,
+            self.raw_samples != default.raw_samples,
+            self.interpolation != default.interpolation,
+            self.label_policy != default.label_policy,
+        ];
+        let len = 3 + added.iter().rposition(|&set| set).map_or(0, |i| i + 1);
+        let mut tuple = serializer.serialize_tuple(len)?;
+        tuple.serialize_element(&self.epsilon)?;
+        tuple.serialize_element(&self.max_centroids)?;
+        tuple.serialize_element(&self.max_unmerged)?;
+        if len > 3 {
+            tuple.serialize_element(&self.coalesce)?;
+        }
+        if len > 4 {
+            tuple.serialize_element(&self.extremes)?;
+        }
+        if len > 5 {
+            tuple.serialize_element(&self.raw_samples)?;
+        }
+        if len > 6 {
+            tuple.serialize_element(&self.interpolation)?;
+        }
+        if len > 7 {
+            tuple.serialize_element(&self.label_policy)?;
+        }
+        tuple.end()
     }
 }
 
@@ -96,15 +228,58 @@ impl<'de> serde::Deserialize<'de> for Config {
     where
         D: serde::Deserializer<'de>,
     {
-        let (epsilon, max_centroids, max_unmerged) = serde::Deserialize::deserialize(deserializer)?;
-        Ok(Self {
-            epsilon,
-            max_centroids,
-            max_unmerged,
-        })
+        struct ConfigVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ConfigVisitor {
+            type Value = Config;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a t-digest configuration")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Config, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let epsilon = next_required(&mut seq, 0, &self)?;
+                let max_centroids = next_required(&mut seq, 1, &self)?;
+                let max_unmerged = next_required(&mut seq, 2, &self)?;
+                // The other fields were added after the first release, and
+                // take their default values in older states.
+                let default = Config::default();
+                Ok(Config {
+                    epsilon,
+                    max_centroids,
+                    max_unmerged,
+                    coalesce: seq.next_element()?.unwrap_or(default.coalesce),
+                    extremes: seq.next_element()?.unwrap_or(default.extremes),
+                    raw_samples: seq.next_element()?.unwrap_or(default.raw_samples),
+                    interpolation: seq.next_element()?.unwrap_or(default.interpolation),
+                    label_policy: seq.next_element()?.unwrap_or(default.label_policy),
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(8, ConfigVisitor)
     }
 }
 
+/// Returns the next element of a serialized tuple, or an error if the tuple
+/// ends before it.
+#[cfg(feature = "serde")]
+fn next_required<'de, A, T>(
+    seq: &mut A,
+    index: usize,
+    expected: &dyn serde::de::Expected,
+) -> Result<T, A::Error>
+where
+    A: serde::de::SeqAccess<'de>,
+    T: serde::Deserialize<'de>,
+{
+    seq.next_element()?
+        .ok_or_else(|| serde::de::Error::invalid_length(index, expected))
+}
+
 /// A `TDigestBuilder` can be used to create a `TDigest` with custom
 /// configuration.
 ///
@@ -122,6 +297,7 @@ impl<'de> serde::Deserialize<'de> for Config {
 #[derive(Debug)]
 pub struct TDigestBuilder {
     config: Config,
+    unit: Option<Unit>,
 }
 
 impl TDigestBuilder {
@@ -131,17 +307,48 @@ impl TDigestBuilder {
     pub fn new() -> Self {
         Self {
             config: Config::default(),
+            unit: None,
         }
     }
 
     /// Returns a `TDigest` that uses this `TDigestBuilder` configuration.
     pub fn build(self) -> TDigest {
+        self.build_as()
+    }
+
+    /// Returns a `TDigest` of values of type `F` that uses this
+    /// `TDigestBuilder` configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigestBuilder;
+    ///
+    /// let mut builder = TDigestBuilder::new();
+    /// builder.retain_extremes(1);
+    ///
+    /// let mut digest = builder.build_as::<f64>();
+    /// digest.extend([16_777_217.0, 16_777_218.0]);
+    /// assert_eq!(digest.smallest(), [16_777_217.0]);
+    /// ```
+    pub fn build_as<F: Float>(self) -> TDigest<F> {
         let centroids = Vec::with_capacity(self.config.max_centroids);
         TDigest {
             config: self.config,
             centroids,
             count: 0,
+            sum: 0.,
+            min: F::INFINITY,
+            max: F::NEG_INFINITY,
             unmerged: 0,
+            extremes: Extremes::default(),
+            raw: RawSamples::default(),
+            labels: Labels::new(),
+            unit: self.unit,
+            nans: 0,
+            compressions: 0,
+            brute_compressions: 0,
+            sampled: 0.,
         }
     }
 
@@ -164,6 +371,136 @@ impl TDigestBuilder {
         self.config.max_unmerged = max_unmerged;
         self
     }
+
+    /// Sets whether runs of equal consecutive values are coalesced into a
+    /// single weighted centroid before being buffered. Defaults to false.
+    ///
+    /// This reduces sorting and compression work on streams with long runs of
+    /// identical values, such as quantized timers or integer counters. A
+    /// coalesced centroid never grows past `epsilon` times the number of
+    /// elements in the `TDigest`, so that compression stays balanced.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigestBuilder;
+    ///
+    /// let mut builder = TDigestBuilder::new();
+    /// builder.coalesce_duplicates(true);
+    ///
+    /// let mut digest = builder.build();
+    /// digest.extend(std::iter::repeat(1.0).take(10_000));
+    /// digest.extend(std::iter::repeat(2.0).take(10_000));
+    /// assert_eq!(digest.len(), 20_000);
+    /// assert_eq!(digest.quantile(0.25), 1.0);
+    /// assert_eq!(digest.quantile(0.75), 2.0);
+    /// ```
+    pub fn coalesce_duplicates(&mut self, coalesce: bool) -> &mut Self {
+        self.config.coalesce = coalesce;
+        self
+    }
+
+    /// Sets the number of smallest and largest values that the `TDigest`
+    /// retains exactly, alongside the approximate centroids. Defaults to 0.
+    ///
+    /// The retained values are kept through merges and serialization, and can
+    /// be read with [`TDigest::smallest`] and [`TDigest::largest`]. When
+    /// merging t-digests, the value of the left-hand side applies.
+    ///
+    /// Quantiles whose ranks are among the retained values are exact, so that
+    /// extreme levels such as p99.999 do not depend on the interpolation of
+    /// the centroids. This holds as long as the values are the extremes of
+    /// all the elements: not after a subtraction or a blend, nor after a merge
+    /// with a t-digest that retains fewer values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigestBuilder;
+    ///
+    /// let mut builder = TDigestBuilder::new();
+    /// builder.retain_extremes(2);
+    ///
+    /// let mut digest = builder.build();
+    /// digest.extend([5.0, 1.0, 4.0, 2.0, 3.0]);
+    /// assert_eq!(digest.smallest(), [1.0, 2.0]);
+    /// assert_eq!(digest.largest(), [5.0, 4.0]);
+    /// ```
+    pub fn retain_extremes(&mut self, k: usize) -> &mut Self {
+        self.config.extremes = k;
+        self
+    }
+
+    /// Sets the number of raw values that the `TDigest` retains alongside the
+    /// centroids, so that it answers exactly while it holds few elements.
+    /// Defaults to 0.
+    ///
+    /// As long as the `TDigest` has at most `cap` elements, its quantiles and
+    /// cumulative distribution are computed from the raw values, as with
+    /// [`TDigest::exact_for_test`]. Beyond `cap`, the raw values are dropped
+    /// and the `TDigest` only holds centroids until it is cleared. This suits
+    /// many small groups, such as per-endpoint latencies, where most groups
+    /// are too small for the approximation to be meaningful. The raw values
+    /// are kept through merges whose result holds at most `cap` elements, and
+    /// through serialization.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigestBuilder;
+    ///
+    /// let mut builder = TDigestBuilder::new();
+    /// builder.retain_raw_samples(4);
+    ///
+    /// let mut digest = builder.build();
+    /// digest.extend([1.0, 2.0, 10.0, 20.0]);
+    /// assert!(digest.is_exact());
+    /// assert_eq!(digest.quantile(0.5), 2.0);
+    ///
+    /// digest.insert(30.0);
+    /// assert!(!digest.is_exact());
+    /// ```
+    pub fn retain_raw_samples(&mut self, cap: usize) -> &mut Self {
+        self.config.raw_samples = cap;
+        self
+    }
+
+    /// Sets how quantiles are interpolated between centroids. Defaults to
+    /// [`Interpolation::ClickHouse`].
+    pub fn interpolation(&mut self, interpolation: Interpolation) -> &mut Self {
+        self.config.interpolation = interpolation;
+        self
+    }
+
+    /// Sets how labels are validated when merging into the `TDigest`.
+    /// Defaults to [`LabelPolicy::MustMatch`].
+    pub fn label_policy(&mut self, policy: LabelPolicy) -> &mut Self {
+        self.config.label_policy = policy;
+        self
+    }
+
+    /// Sets the unit of the values of the `TDigest`, such as the unit into
+    /// which [`TDigest::insert_duration`] converts durations. Defaults to no
+    /// unit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tdigest_ch::{TDigest, Unit};
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.unit(Unit::Micros);
+    ///
+    /// let mut digest = builder.build();
+    /// digest.insert_duration(Duration::from_millis(3));
+    /// assert_eq!(digest.unit(), Some(&Unit::Micros));
+    /// assert_eq!(digest.quantile(0.5), 3000.0);
+    /// ```
+    pub fn unit(&mut self, unit: Unit) -> &mut Self {
+        self.unit = Some(unit);
+        self
+    }
 }
 
 impl Default for TDigestBuilder {
@@ -173,17 +510,37 @@ impl Default for TDigestBuilder {
     }
 }
 
-fn interpolate(x: f32, x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+fn interpolate<F: Float>(x: F, x1: F, y1: F, x2: F, y2: F) -> F {
     let k = (x - x1) / (x2 - x1);
-    (1. - k) * y1 + k * y2
+    (F::ONE - k) * y1 + k * y2
 }
 
 #[inline]
-fn can_be_merged(l_mean: f64, r_mean: f32) -> bool {
-    l_mean == r_mean as f64 || (!l_mean.is_infinite() && !r_mean.is_infinite())
+fn can_be_merged<F: Float>(l_mean: f64, r_mean: F) -> bool {
+    l_mean == r_mean.to_f64() || (!l_mean.is_infinite() && !r_mean.is_infinite())
+}
+
+/// Scales the weights of the centroids by `factor`, rounding the cumulative
+/// weight so that rounding errors do not add up.
+fn scale_centroids<F: Float>(centroids: &[Centroid<F>], factor: f64) -> Vec<Centroid<F>> {
+    let mut scaled = Vec::with_capacity(centroids.len());
+    let mut cumulative = 0f64;
+    let mut rounded = 0u64;
+    for c in centroids {
+        cumulative += c.count as f64 * factor;
+        let count = cumulative.round() as u64 - rounded;
+        rounded += count;
+        if count > 0 {
+            scaled.push(Centroid {
+                mean: c.mean,
+                count,
+            });
+        }
+    }
+    scaled
 }
 
-fn cmp_f32(lhs: f32, rhs: f32) -> Ordering {
+fn cmp_float<F: Float>(lhs: F, rhs: F) -> Ordering {
     match lhs.partial_cmp(&rhs) {
         Some(ordering) => ordering,
         None => {
@@ -202,6 +559,13 @@ fn cmp_f32(lhs: f32, rhs: f32) -> Ordering {
 
 /// T-digest data structure for approximating the quantiles of a distribution.
 ///
+/// The values are `f32` by default. A `TDigest<f64>` holds values that `f32`
+/// would round, such as timestamps, money amounts or large counters, at the
+/// cost of twice the memory per centroid. It is created with
+/// [`TDigest::default`] or [`TDigestBuilder::build_as`], and has the same
+/// methods, except for the encodings whose format fixes `f32` values, such as
+/// the Redis and NumPy ones.
+///
 /// # Examples
 ///
 /// ```
@@ -217,13 +581,37 @@ fn cmp_f32(lhs: f32, rhs: f32) -> Ordering {
 /// // Get the median of the distribution.
 /// let quantile = digest.quantile(0.5);
 /// assert_eq!(quantile, 2.0);
+///
+/// let mut timestamps = TDigest::<f64>::default();
+/// timestamps.extend([1_700_000_000.125, 1_700_000_000.375]);
+/// assert_eq!(timestamps.quantile(1.0), 1_700_000_000.375);
 /// ```
-#[derive(Clone, Debug, PartialEq)]
-pub struct TDigest {
+#[derive(Clone, Debug)]
+pub struct TDigest<F = f32> {
     config: Config,
-    centroids: Vec<Centroid>,
-    count: usize,
+    centroids: Vec<Centroid<F>>,
+    count: u64,
+    /// The exact sum of the inserted values, which compression does not
+    /// round.
+    sum: f64,
+    /// The exact smallest and largest inserted values, or infinities of the
+    /// opposite sign if empty.
+    min: F,
+    max: F,
     unmerged: usize,
+    extremes: Extremes<F>,
+    raw: RawSamples<F>,
+    labels: Labels,
+    unit: Option<Unit>,
+    /// The number of NaN values that were inserted, and ignored.
+    nans: u64,
+    /// The number of compressions, and of brute-force compressions, which
+    /// are neither compared nor serialized.
+    compressions: u64,
+    brute_compressions: u64,
+    /// The fraction of weight left over by [`TDigest::insert_sampled`], which
+    /// is carried over to the next sampled value.
+    sampled: f64,
 }
 
 impl TDigest {
@@ -240,6 +628,34 @@ impl TDigest {
         Self::builder().build()
     }
 
+    /// Creates an empty `TDigest` that never compresses its elements, for use
+    /// in tests.
+    ///
+    /// Each element is kept as its own centroid, so that quantiles are exact:
+    /// the quantile at `level` is the smallest element such that at least a
+    /// fraction `level` of the elements are lower or equal to it. Memory grows
+    /// with the number of elements, which makes this unsuitable for production
+    /// use.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::exact_for_test();
+    /// digest.extend((1..=100_000).rev().map(|v| v as f32));
+    /// assert_eq!(digest.quantile(0.5), 50_000.0);
+    /// assert_eq!(digest.quantile(0.99), 99_000.0);
+    /// ```
+    #[must_use]
+    pub fn exact_for_test() -> Self {
+        let mut digest = Self::new();
+        digest.config.epsilon = 0.;
+        digest.config.max_centroids = usize::MAX;
+        digest.config.max_unmerged = usize::MAX;
+        digest
+    }
+
     /// Creates a `TDigestBuilder` to configure a `TDigest`.
     ///
     /// This is the same as `TDigestBuilder::new()`.
@@ -247,7 +663,9 @@ impl TDigest {
     pub fn builder() -> TDigestBuilder {
         TDigestBuilder::new()
     }
+}
 
+impl<F: Float> TDigest<F> {
     /// Moves all the elements of `other` into `self`, leaving `other` empty.
     ///
     /// # Examples
@@ -263,12 +681,155 @@ impl TDigest {
     /// assert_eq!(a.len(), 8);
     /// assert!(b.is_empty());
     /// ```
-    pub fn append(&mut self, other: &mut TDigest) {
+    pub fn append(&mut self, other: &mut TDigest<F>) {
         self.bitor_assign(other);
         other.clear();
     }
 
-    /// Returns the number of elements in the t-digest.
+    /// Merges `other` into `self`, after checking that their labels are
+    /// compatible under the label policy of `self`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LabelMismatch`] and leaves `self` unchanged if the
+    /// labels are not compatible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{Error, TDigest};
+    ///
+    /// let mut a = TDigest::from([1.0, 2.0]);
+    /// a.set_label("unit", "ms");
+    /// let mut b = TDigest::from([3.0]);
+    /// b.set_label("unit", "s");
+    ///
+    /// assert_eq!(a.try_merge(&b), Err(Error::LabelMismatch("unit".to_string())));
+    ///
+    /// b.set_label("unit", "ms");
+    /// a.try_merge(&b).unwrap();
+    /// assert_eq!(a.len(), 3);
+    /// ```
+    pub fn try_merge(&mut self, other: &TDigest<F>) -> Result<(), Error> {
+        let factor = self.unit_factor(other)?;
+        if self.labels != other.labels {
+            let mut labels = self.labels.clone();
+            labels::merge(&mut labels, &other.labels, self.config.label_policy)?;
+            self.labels = labels;
+        }
+        self.merge_scaled(other, factor);
+        Ok(())
+    }
+
+    /// Returns the factor that converts the values of `other` into the unit
+    /// of `self`, or `None` if they need no conversion.
+    fn unit_factor(&self, other: &TDigest<F>) -> Result<Option<f64>, Error> {
+        match (&self.unit, &other.unit) {
+            (Some(unit), Some(other_unit)) if unit != other_unit => other_unit
+                .factor_to(unit)
+                .map(Some)
+                .ok_or(Error::IncompatibleUnits),
+            _ => Ok(None),
+        }
+    }
+
+    fn merge_scaled(&mut self, other: &TDigest<F>, factor: Option<f64>) {
+        if self.unit.is_none() {
+            self.unit.clone_from(&other.unit);
+        }
+        if let Some(factor) = factor {
+            let mut other = other.clone();
+            other.scale_values(factor);
+            self.merge_values(&other);
+        } else {
+            self.merge_values(other);
+        }
+    }
+
+    fn merge_values(&mut self, other: &TDigest<F>) {
+        self.raw
+            .merge(self.count, &other.raw, other.count, self.config.raw_samples);
+        for c in &other.centroids {
+            self.insert_centroid(c);
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.extremes
+            .merge(&other.extremes, other.count, self.config.extremes);
+        self.nans += other.nans;
+    }
+
+    /// Returns the unit of the values of the t-digest, if it has been set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{TDigest, Unit};
+    ///
+    /// let mut digest = TDigest::new();
+    /// assert_eq!(digest.unit(), None);
+    /// digest.set_unit(Unit::Bytes);
+    /// assert_eq!(digest.unit(), Some(&Unit::Bytes));
+    /// ```
+    #[inline]
+    pub fn unit(&self) -> Option<&Unit> {
+        self.unit.as_ref()
+    }
+
+    /// Sets the unit of the values of the t-digest, without converting them,
+    /// and returns the previous unit.
+    ///
+    /// A t-digest without a unit adopts the unit of the t-digests merged into
+    /// it, and t-digests without a unit are merged without conversion.
+    pub fn set_unit(&mut self, unit: Unit) -> Option<Unit> {
+        self.unit.replace(unit)
+    }
+
+    /// Converts the values of the t-digest into `unit`.
+    ///
+    /// If the t-digest has no unit yet, `unit` is set without converting.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::IncompatibleUnits`] and leaves `self` unchanged if its
+    /// unit cannot be converted into `unit`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{Error, TDigest, Unit};
+    ///
+    /// let mut digest = TDigest::from([0.5, 1.5]);
+    /// digest.set_unit(Unit::Seconds);
+    ///
+    /// digest.convert_to(Unit::Millis).unwrap();
+    /// assert_eq!(digest.quantile(0.0), 500.0);
+    /// assert_eq!(digest.convert_to(Unit::Bytes), Err(Error::IncompatibleUnits));
+    /// ```
+    pub fn convert_to(&mut self, unit: Unit) -> Result<(), Error> {
+        if let Some(current) = &self.unit {
+            let factor = current.factor_to(&unit).ok_or(Error::IncompatibleUnits)?;
+            if factor != 1. {
+                self.scale_values(factor);
+            }
+        }
+        self.unit = Some(unit);
+        Ok(())
+    }
+
+    /// Multiplies the values of the t-digest by a positive factor.
+    fn scale_values(&mut self, factor: f64) {
+        for c in self.centroids.iter_mut() {
+            c.mean = F::from_f64(c.mean.to_f64() * factor);
+        }
+        self.sum *= factor;
+        self.min = F::from_f64(self.min.to_f64() * factor);
+        self.max = F::from_f64(self.max.to_f64() * factor);
+        self.extremes.scale(factor);
+        self.raw.scale(factor);
+    }
+
+    /// Returns the labels of the t-digest.
     ///
     /// # Examples
     ///
@@ -276,16 +837,18 @@ impl TDigest {
     /// use tdigest_ch::TDigest;
     ///
     /// let mut digest = TDigest::new();
-    /// assert_eq!(digest.len(), 0);
-    /// digest.insert(1.0);
-    /// assert_eq!(digest.len(), 1);
+    /// digest.set_label("service", "api");
+    /// assert_eq!(digest.labels().get("service").map(String::as_str), Some("api"));
     /// ```
     #[inline]
-    pub fn len(&self) -> usize {
-        self.count
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.labels
     }
 
-    /// Returns `true` if the t-digest contains no elements.
+    /// Sets a label of the t-digest, returning its previous value.
+    ///
+    /// Labels are kept by [`TDigest::clear`], since they describe the
+    /// t-digest rather than its values.
     ///
     /// # Examples
     ///
@@ -293,93 +856,1119 @@ impl TDigest {
     /// use tdigest_ch::TDigest;
     ///
     /// let mut digest = TDigest::new();
-    /// assert!(digest.is_empty());
-    /// digest.insert(1.0);
-    /// assert!(!digest.is_empty());
+    /// assert_eq!(digest.set_label("unit", "ms"), None);
+    /// assert_eq!(digest.set_label("unit", "s"), Some("ms".to_string()));
+    /// ```
+    pub fn set_label(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Option<String> {
+        self.labels.insert(key.into(), value.into())
+    }
+
+    /// Removes a label of the t-digest, returning its value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.set_label("unit", "ms");
+    /// assert_eq!(digest.remove_label("unit"), Some("ms".to_string()));
+    /// assert!(digest.labels().is_empty());
+    /// ```
+    pub fn remove_label(&mut self, key: &str) -> Option<String> {
+        self.labels.remove(key)
+    }
+
+    /// Removes the elements of `other` from `self`, where `other` is an earlier
+    /// snapshot of `self` or was otherwise merged into it.
+    ///
+    /// The weight of each centroid of `other` is taken from the centroids of
+    /// `self` with the nearest means. The subtraction is approximate: the
+    /// absolute rank error of the result is bounded by the sum of the errors of
+    /// both t-digests, so relative to the remaining elements it grows by a
+    /// factor of `self.len() / (self.len() - other.len())`.
+    ///
+    /// The exact extremes retained with [`TDigestBuilder::retain_extremes`]
+    /// and the raw values retained with [`TDigestBuilder::retain_raw_samples`]
+    /// cannot be known after a subtraction, so they are cleared.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CountUnderflow`] and leaves `self` unchanged if `other`
+    /// has more elements than `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let before = TDigest::from_iter((0..500).map(|v| v as f32));
+    /// let mut now = &before | &TDigest::from_iter((500..1000).map(|v| v as f32));
+    ///
+    /// now.subtract(&before).unwrap();
+    ///
+    /// assert_eq!(now.len(), 500);
+    /// assert!((now.quantile(0.5) - 750.0).abs() <= 10.0);
+    /// ```
+    pub fn subtract(&mut self, other: &TDigest<F>) -> Result<(), Error> {
+        if other.count > self.count {
+            return Err(Error::CountUnderflow);
+        }
+        let mut other = other.clone();
+        other.compress();
+        self.compress();
+        for c in other.centroids.iter() {
+            self.remove_centroid(c);
+        }
+        self.finish_removal(other.sum, other.min, other.max);
+        self.nans = self.nans.saturating_sub(other.nans);
+        Ok(())
+    }
+
+    /// Removes `count` copies of `value` from the t-digest, taking their
+    /// weight from the centroids with the nearest means.
+    ///
+    /// This is the approximate inverse of [`TDigest::insert_many`], such as to
+    /// undo a batch that was inserted twice. The values are removed from the
+    /// centroids, not the elements, so that the result is only as accurate as
+    /// with [`TDigest::subtract`]: it is close to a t-digest that never held
+    /// the values if they were inserted, and meaningless otherwise. The exact
+    /// extremes and raw values are cleared, as with a subtraction. NaN values
+    /// are ignored, as with [`TDigest::insert_many`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CountUnderflow`] and leaves `self` unchanged if `count`
+    /// is greater than the number of elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// // A batch of slow requests was recorded twice.
+    /// digest.insert_many(5000.0, 10);
+    /// digest.insert_many(5000.0, 10);
+    ///
+    /// digest.remove_many(5000.0, 10).unwrap();
+    /// assert_eq!(digest.len(), 1010);
+    /// assert_eq!(digest.quantile(1.0), 5000.0);
+    /// assert!(digest.remove_many(1.0, 2000).is_err());
+    /// ```
+    pub fn remove_many(&mut self, value: F, count: u64) -> Result<(), Error> {
+        if count > self.count {
+            return Err(Error::CountUnderflow);
+        }
+        if count == 0 || value.is_nan() {
+            return Ok(());
+        }
+        self.compress();
+        self.remove_centroid(&Centroid { mean: value, count });
+        self.finish_removal(value.to_f64() * count as f64, value, value);
+        Ok(())
+    }
+
+    /// Removes a value from the t-digest, as with [`TDigest::remove_many`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CountUnderflow`] and leaves `self` unchanged if the
+    /// t-digest is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0]);
+    /// digest.remove(3.0).unwrap();
+    /// assert_eq!(digest.len(), 2);
+    /// assert_eq!(digest.quantile(1.0), 2.0);
     /// ```
     #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    pub fn remove(&mut self, value: F) -> Result<(), Error> {
+        self.remove_many(value, 1)
+    }
+
+    /// Updates the t-digest after the removal of elements of the given sum,
+    /// smallest and largest values from its centroids.
+    fn finish_removal(&mut self, removed_sum: f64, removed_min: F, removed_max: F) {
+        self.centroids.retain(|c| c.count != 0);
+        self.count = self.centroids.iter().map(|c| c.count).sum();
+        self.sum = if self.count == 0 {
+            0.
+        } else {
+            self.sum - removed_sum
+        };
+        // The extremes are left if the removed elements cannot contain them,
+        // otherwise they are estimated by the remaining centroids.
+        if removed_min <= self.min {
+            self.min = self.centroids.first().map_or(F::INFINITY, |c| c.mean);
+        }
+        if removed_max >= self.max {
+            self.max = self.centroids.last().map_or(F::NEG_INFINITY, |c| c.mean);
+        }
+        if self.count == 0 {
+            self.extremes.clear();
+        } else {
+            self.extremes.invalidate();
+        }
+        self.raw.clear();
+    }
+
+    /// Takes the weight of `centroid` from the nearest centroids, leaving
+    /// emptied centroids in place. The t-digest must be compressed.
+    fn remove_centroid(&mut self, centroid: &Centroid<F>) {
+        let mut remaining = centroid.count;
+        let index = self
+            .centroids
+            .partition_point(|c| cmp_float(c.mean, centroid.mean) == Ordering::Less);
+        let (mut left, mut right) = (index, index);
+        while remaining > 0 {
+            // Pick the nearest non-empty centroid on either side.
+            while left > 0 && self.centroids[left - 1].count == 0 {
+                left -= 1;
+            }
+            while right < self.centroids.len() && self.centroids[right].count == 0 {
+                right += 1;
+            }
+            let nearest = match (left.checked_sub(1), right < self.centroids.len()) {
+                (Some(l), true) => {
+                    let l_distance = centroid.mean.to_f64() - self.centroids[l].mean.to_f64();
+                    let r_distance = self.centroids[right].mean.to_f64() - centroid.mean.to_f64();
+                    if l_distance <= r_distance {
+                        l
+                    } else {
+                        right
+                    }
+                }
+                (Some(l), false) => l,
+                (None, true) => right,
+                (None, false) => return,
+            };
+            let c = &mut self.centroids[nearest];
+            let removed = remaining.min(c.count);
+            c.count -= removed;
+            remaining -= removed;
+        }
+    }
+
+    /// Blends `other` into `self`, as one step of an exponentially weighted
+    /// moving average over distributions.
+    ///
+    /// The weights of `self` are scaled by `1 - alpha` and those of `other` by
+    /// `alpha`, then both are merged and rescaled so that `self` keeps its
+    /// number of elements. Blending each new period into a baseline thus gives
+    /// a smooth baseline, that adapts to regime changes at a rate set by
+    /// `alpha`. If `self` is empty, it takes the distribution of `other`; if
+    /// `other` is empty, `self` is left unchanged.
+    ///
+    /// Centroids whose scaled weight rounds to zero are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alpha` is not between 0 and 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut baseline = TDigest::from_iter((0..1000).map(|_| 10.0));
+    /// let spike = TDigest::from_iter((0..100).map(|_| 20.0));
+    ///
+    /// baseline.blend(&spike, 0.25);
+    ///
+    /// assert_eq!(baseline.len(), 1000);
+    /// assert_eq!(baseline.quantile(0.5), 10.0);
+    /// assert_eq!(baseline.quantile(0.9), 20.0);
+    /// ```
+    pub fn blend(&mut self, other: &TDigest<F>, alpha: f64) {
+        assert!((0. ..=1.).contains(&alpha), "alpha must be between 0 and 1");
+        if other.is_empty() {
+            return;
+        }
+        let (self_factor, other_factor) = if self.is_empty() {
+            (0., 1.)
+        } else {
+            (1. - alpha, alpha * self.count as f64 / other.count as f64)
+        };
+        let mut other = other.clone();
+        other.compress();
+        self.compress();
+
+        let mut centroids = scale_centroids(&self.centroids, self_factor);
+        let (min, max, sum) = self.scaled_bounds(&centroids);
+        let scaled = scale_centroids(&other.centroids, other_factor);
+        let (other_min, other_max, other_sum) = other.scaled_bounds(&scaled);
+        centroids.extend(scaled);
+        self.count = centroids.iter().map(|c| c.count).sum();
+        self.sum = sum + other_sum;
+        self.min = min.min(other_min);
+        self.max = max.max(other_max);
+        self.unmerged = centroids.len();
+        self.centroids = centroids;
+        // The weights of the elements change, so that the retained values no
+        // longer give exact quantiles.
+        self.extremes.invalidate();
+        self.raw.clear();
+        self.compress();
+    }
+
+    /// Returns the smallest and largest values and the sum of the elements
+    /// kept in `scaled`, the compressed centroids of `self` once scaled.
+    ///
+    /// The exact bounds and sum are kept when no centroid is scaled, and
+    /// each bound is kept while the centroid at that end keeps elements.
+    /// Otherwise they are those of the kept centroids, so that centroids
+    /// dropped by the scaling leave no trace.
+    fn scaled_bounds(&self, scaled: &[Centroid<F>]) -> (F, F, f64) {
+        let (Some(first), Some(last)) = (scaled.first(), scaled.last()) else {
+            return (F::INFINITY, F::NEG_INFINITY, 0.);
+        };
+        if scaled == self.centroids.as_slice() {
+            return (self.min, self.max, self.sum);
+        }
+        let min = if first.mean == self.centroids[0].mean {
+            self.min
+        } else {
+            first.mean
+        };
+        let max = if last.mean == self.centroids[self.centroids.len() - 1].mean {
+            self.max
+        } else {
+            last.mean
+        };
+        let sum = scaled
+            .iter()
+            .map(|c| c.mean.to_f64() * c.count as f64)
+            .sum();
+        (min, max, sum)
+    }
+
+    /// Returns the number of elements in the t-digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// assert_eq!(digest.len(), 0);
+    /// digest.insert(1.0);
+    /// assert_eq!(digest.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns `true` if the t-digest contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// assert!(digest.is_empty());
+    /// digest.insert(1.0);
+    /// assert!(!digest.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of NaN values that were inserted into the t-digest.
+    ///
+    /// NaN values are not elements of the t-digest, and do not count in its
+    /// length, but this counts them so as to reveal data quality issues. The
+    /// count is kept through merges and serialization.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, f32::NAN, 2.0]);
+    /// assert_eq!(digest.len(), 2);
+    /// assert_eq!(digest.nan_count(), 1);
+    ///
+    /// digest.insert_many(f32::NAN, 3);
+    /// assert_eq!(digest.nan_count(), 4);
+    /// ```
+    #[inline]
+    pub fn nan_count(&self) -> u64 {
+        self.nans
+    }
+
+    /// Returns the mean of the elements in the t-digest, or NaN if it is
+    /// empty.
+    ///
+    /// The mean is computed from a running sum of the inserted values, so it
+    /// is exact, unlike the means of the merged centroids. It remains exact
+    /// after merges and after [`TDigest::subtract`], but is estimated after
+    /// [`TDigest::blend`], which rounds the rescaled weights.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// digest.insert(100_000.0);
+    /// assert_eq!(digest.mean(), 600_500.0 / 1001.0);
+    /// ```
+    #[inline]
+    pub fn mean(&self) -> f64 {
+        if self.is_empty() {
+            f64::NAN
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// Clears the t-digest, removing all values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.insert(1.0);
+    /// digest.clear();
+    /// assert!(digest.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.centroids.clear();
+        self.count = 0;
+        self.sum = 0.;
+        self.min = F::INFINITY;
+        self.max = F::NEG_INFINITY;
+        self.unmerged = 0;
+        self.extremes.clear();
+        self.raw.clear();
+        self.nans = 0;
+        self.sampled = 0.;
+    }
+
+    /// Compresses the t-digest, and releases the memory reserved for
+    /// centroids that it does not hold.
+    ///
+    /// The t-digest reserves room for its maximum number of centroids when
+    /// created, and grows again as elements are inserted, so shrinking it is
+    /// only useful to bound memory when many t-digests are kept idle.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0]);
+    /// digest.shrink_to_fit();
+    /// assert_eq!(digest.quantile(0.5), 2.0);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.compress();
+        self.centroids.shrink_to_fit();
+        self.raw.shrink_to_fit();
+    }
+
+    /// Returns `true` if the t-digest retains all its elements as raw values,
+    /// so that its quantiles are exact.
+    ///
+    /// This is the case when it is not empty and has at most as many elements
+    /// as set with [`TDigestBuilder::retain_raw_samples`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.retain_raw_samples(100);
+    ///
+    /// let mut digest = builder.build();
+    /// assert!(!digest.is_exact());
+    /// digest.extend((0..100).map(|v| v as f32));
+    /// assert!(digest.is_exact());
+    /// ```
+    #[inline]
+    pub fn is_exact(&self) -> bool {
+        self.raw.exact(self.count).is_some()
+    }
+
+    /// Returns the exact smallest value of the t-digest, or NaN if it is
+    /// empty.
+    ///
+    /// This is also the quantile at level 0. After [`TDigest::subtract`]
+    /// removes the smallest value, it is estimated by the remaining
+    /// centroids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..100_000).map(|v| (v % 1000) as f32 + 0.5));
+    /// assert_eq!(digest.min(), 0.5);
+    /// assert_eq!(digest.quantile(0.0), 0.5);
+    /// ```
+    #[inline]
+    pub fn min(&self) -> F {
+        if self.is_empty() {
+            F::NAN
+        } else {
+            self.min
+        }
+    }
+
+    /// Returns the exact largest value of the t-digest, or NaN if it is
+    /// empty.
+    ///
+    /// This is also the quantile at level 1. After [`TDigest::subtract`]
+    /// removes the largest value, it is estimated by the remaining centroids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..100_000).map(|v| (v % 1000) as f32 + 0.5));
+    /// assert_eq!(digest.max(), 999.5);
+    /// assert_eq!(digest.quantile(1.0), 999.5);
+    /// ```
+    #[inline]
+    pub fn max(&self) -> F {
+        if self.is_empty() {
+            F::NAN
+        } else {
+            self.max
+        }
+    }
+
+    /// Returns the smallest values of the t-digest, in increasing order.
+    ///
+    /// At most as many values as set with [`TDigestBuilder::retain_extremes`]
+    /// are returned, and none by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.retain_extremes(3);
+    ///
+    /// let mut digest = builder.build();
+    /// digest.extend([3.0, 1.0]);
+    /// assert_eq!(digest.smallest(), [1.0, 3.0]);
+    /// ```
+    #[inline]
+    pub fn smallest(&self) -> &[F] {
+        self.extremes.smallest()
+    }
+
+    /// Returns the largest values of the t-digest, in decreasing order.
+    ///
+    /// At most as many values as set with [`TDigestBuilder::retain_extremes`]
+    /// are returned, and none by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut builder = TDigest::builder();
+    /// builder.retain_extremes(3);
+    ///
+    /// let mut digest = builder.build();
+    /// digest.extend([3.0, 1.0]);
+    /// assert_eq!(digest.largest(), [3.0, 1.0]);
+    /// ```
+    #[inline]
+    pub fn largest(&self) -> &[F] {
+        self.extremes.largest()
+    }
+
+    /// Returns the estimated quantile of the t-digest.
+    ///
+    /// This method expects `self` to be mutable, since the t-digest may be
+    /// compressed. If you require an immutable, shared reference to compute
+    /// quantiles, consider using `quantiles` instead.
+    ///
+    /// This method does not allocate, even when the t-digest is compressed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.quantile(0.0), 1.0);
+    /// assert_eq!(digest.quantile(0.5), 3.0);
+    /// assert_eq!(digest.quantile(1.0), 5.0);
+    /// ```
+    pub fn quantile(&mut self, level: f64) -> F {
+        self.compress();
+        self.quantile_uncompressed(level)
+    }
+
+    /// Returns the estimated quantile of the t-digest, or `None` if it is empty
+    /// or if `level` is not between 0 and 1.
+    ///
+    /// This is the same as [`TDigest::quantile`], which returns NaN for an
+    /// empty t-digest and clamps the level, but lets the caller handle these
+    /// cases explicitly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.quantile_checked(0.5), Some(3.0));
+    /// assert_eq!(digest.quantile_checked(1.5), None);
+    /// assert_eq!(digest.quantile_checked(f64::NAN), None);
+    /// assert_eq!(TDigest::new().quantile_checked(0.5), None);
+    /// ```
+    pub fn quantile_checked(&mut self, level: f64) -> Option<F> {
+        self.compress();
+        self.quantile_checked_uncompressed(level)
+    }
+
+    /// Returns the estimated percentile of the t-digest, for a `percentile`
+    /// between 0 and 100.
+    ///
+    /// This is the same as `quantile(percentile / 100.0)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// assert_eq!(digest.percentile(99.0), digest.quantile(0.99));
+    /// assert_eq!(digest.percentile(100.0), 999.0);
+    /// ```
+    pub fn percentile(&mut self, percentile: f64) -> F {
+        self.quantile(percentile / 100.)
+    }
+
+    /// Returns the estimated median of the t-digest.
+    ///
+    /// This is the same as `quantile(0.5)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.median(), 3.0);
+    /// ```
+    pub fn median(&mut self) -> F {
+        self.quantile(0.5)
+    }
+
+    /// Returns the estimated fraction of the elements of the t-digest that are
+    /// lower than or equal to `value`.
+    ///
+    /// This is the inverse of [`TDigest::quantile`]: the fraction interpolates
+    /// between centroids in the same way. It is 0 below the smallest centroid,
+    /// 1 from the largest one, and NaN for an empty t-digest.
+    ///
+    /// This method does not allocate, even when the t-digest is compressed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.cdf(0.0), 0.0);
+    /// assert_eq!(digest.cdf(3.0), 0.6);
+    /// assert_eq!(digest.cdf(5.0), 1.0);
+    /// ```
+    pub fn cdf(&mut self, value: F) -> f64 {
+        self.compress();
+        self.cdf_uncompressed(value)
+    }
+
+    /// Returns the estimated fraction of the elements of the t-digest that are
+    /// greater than `value`, such as the requests that miss a latency
+    /// objective.
+    ///
+    /// This is `1.0 - cdf(value)`, computed from the largest centroids, so
+    /// that small fractions keep their precision: the difference would round
+    /// to a multiple of 1.1e-16. It is 1 below the smallest centroid, 0 from
+    /// the largest one, and NaN for an empty t-digest.
+    ///
+    /// This method does not allocate, even when the t-digest is compressed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.sf(0.0), 1.0);
+    /// assert_eq!(digest.sf(3.0), 0.4);
+    /// assert_eq!(digest.sf(5.0), 0.0);
+    /// ```
+    pub fn sf(&mut self, value: F) -> f64 {
+        self.compress();
+        self.sf_uncompressed(value)
+    }
+
+    /// Returns the estimated fractions of the elements of the t-digest that
+    /// are lower than or equal to each of `values`, in the same order.
+    ///
+    /// This is the same as calling [`TDigest::cdf`] for each value, but the
+    /// centroids are traversed once for all the values, which is faster for
+    /// many of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.cdf_many(&[5.0, 0.0, 3.0]), [1.0, 0.0, 0.6]);
+    /// ```
+    pub fn cdf_many(&mut self, values: &[F]) -> Vec<f64> {
+        self.compress();
+        self.cdf_many_uncompressed(values)
+    }
+
+    /// Returns the estimated percentile rank of each of `values` in the
+    /// t-digest, between 0 and 100, in the same order.
+    ///
+    /// This scores observations against a baseline t-digest: it is the same as
+    /// `cdf_many(values)` scaled to percentiles, the inverse of
+    /// [`TDigest::percentile`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut baseline = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let scores = baseline.score(&[0.0, 500.0, 2000.0]);
+    /// assert_eq!(scores[0], 0.0);
+    /// assert!((scores[1] - 50.0).abs() < 0.5);
+    /// assert_eq!(scores[2], 100.0);
+    /// ```
+    pub fn score(&mut self, values: &[F]) -> Vec<f64> {
+        self.compress();
+        self.score_uncompressed(values)
+    }
+
+    fn score_uncompressed(&self, values: &[F]) -> Vec<f64> {
+        let mut scores = self.cdf_many_uncompressed(values);
+        for score in scores.iter_mut() {
+            *score *= 100.;
+        }
+        scores
+    }
+
+    /// Returns the estimated number of elements of the t-digest that are lower
+    /// than or equal to `value`.
+    ///
+    /// This is [`TDigest::cdf`] scaled by [`TDigest::len`]: it is 0 below the
+    /// smallest centroid, the number of elements from the largest one, and
+    /// NaN for an empty t-digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut latencies = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let slow = latencies.len() as f64 - latencies.rank(500.0);
+    /// assert!((slow - 500.0).abs() < 5.0);
+    /// ```
+    pub fn rank(&mut self, value: F) -> f64 {
+        self.cdf(value) * self.count as f64
+    }
+
+    /// Returns the estimated `k`-th smallest element of the t-digest, counting
+    /// from 0, or NaN if `k` is not lower than the number of elements.
+    ///
+    /// This is the quantile at the middle of the rank of the element, so that
+    /// it is exact for the elements of singleton centroids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut latencies = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// assert_eq!(latencies.value_at_rank(0), 1.0);
+    /// assert_eq!(latencies.value_at_rank(999), 1000.0);
+    /// assert!((latencies.value_at_rank(500) - 501.0).abs() < 5.0);
+    /// assert!(latencies.value_at_rank(1000).is_nan());
+    /// ```
+    pub fn value_at_rank(&mut self, k: u64) -> F {
+        self.compress();
+        self.value_at_rank_uncompressed(k)
+    }
+
+    fn value_at_rank_uncompressed(&self, k: u64) -> F {
+        if k >= self.count {
+            return F::NAN;
+        }
+        self.quantile_uncompressed((k as f64 + 0.5) / self.count as f64)
+    }
+
+    /// Returns the estimated [Apdex] score of the t-digest.
+    ///
+    /// Values up to `satisfied` count as satisfied, values up to `tolerating`
+    /// count as half satisfied, and larger values do not count. The score
+    /// ranges from 0 to 1, and is NaN for an empty t-digest.
+    ///
+    /// [Apdex]: https://en.wikipedia.org/wiki/Apdex
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tolerating` is lower than `satisfied`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=100).map(|v| v as f32));
+    /// let score = digest.apdex(50.0, 200.0);
+    /// assert!((score - 0.75).abs() < 0.01);
+    /// ```
+    pub fn apdex(&mut self, satisfied: F, tolerating: F) -> f64 {
+        self.compress();
+        self.apdex_uncompressed(satisfied, tolerating)
+    }
+
+    fn apdex_uncompressed(&self, satisfied: F, tolerating: F) -> f64 {
+        assert!(
+            tolerating >= satisfied,
+            "tolerating threshold must not be lower than the satisfied threshold"
+        );
+        let satisfied = self.cdf_uncompressed(satisfied);
+        let tolerating = self.cdf_uncompressed(tolerating);
+        (satisfied + tolerating) / 2.
+    }
+
+    /// Returns a table of `resolution` quantiles, at evenly spaced levels from
+    /// 0 to 1.
+    ///
+    /// Entry `i` of the table is the quantile at level `i / (resolution - 1)`,
+    /// so that a consumer can look up an approximate quantile in constant time,
+    /// without the centroids of the t-digest. The entries are NaN for an empty
+    /// t-digest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `resolution` is lower than 2.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.build_lookup_table(3), [1.0, 3.0, 5.0]);
+    /// ```
+    pub fn build_lookup_table(&mut self, resolution: usize) -> Vec<F> {
+        self.compress();
+        self.build_lookup_table_uncompressed(resolution)
+    }
+
+    fn build_lookup_table_uncompressed(&self, resolution: usize) -> Vec<F> {
+        assert!(resolution >= 2, "resolution must be at least 2");
+        let last = (resolution - 1) as f64;
+        (0..resolution)
+            .map(|i| self.quantile_uncompressed(i as f64 / last))
+            .collect()
+    }
+
+    /// Returns the estimated quantiles of the t-digest at each of `levels`, in
+    /// the order of `levels`.
+    ///
+    /// The levels are sorted, and answered in a single pass over the
+    /// centroids. The estimates are the same as with [`TDigest::quantile`],
+    /// except that they are guaranteed to be non-decreasing with the levels:
+    /// an estimate that would be lower than the estimate at a lower level,
+    /// from rounding of the interpolation, is raised to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// assert_eq!(digest.quantiles_many(&[1.0, 0.0, 0.5]), [5.0, 1.0, 3.0]);
+    /// ```
+    pub fn quantiles_many(&mut self, levels: &[f64]) -> Vec<F> {
+        self.compress();
+        self.quantiles_many_uncompressed(levels)
+    }
+
+    fn quantile_uncompressed(&self, level: f64) -> F {
+        self.explain_uncompressed(level).value()
+    }
+
+    fn quantile_checked_uncompressed(&self, level: f64) -> Option<F> {
+        if self.is_empty() || !(0. ..=1.).contains(&level) {
+            return None;
+        }
+        Some(self.quantile_uncompressed(level))
+    }
+
+    fn quantiles_many_uncompressed(&self, levels: &[f64]) -> Vec<F> {
+        // Same as `quantile_uncompressed`, for levels in increasing order.
+        let mut quantiles = vec![F::NAN; levels.len()];
+        if self.centroids.is_empty() {
+            return quantiles;
+        }
+        if let Some(values) = self.raw.exact(self.count) {
+            for (quantile, &level) in quantiles.iter_mut().zip(levels) {
+                *quantile = raw_samples::quantile(values, level);
+            }
+            return quantiles;
+        }
+
+        if self.centroids.len() == 1 {
+            quantiles.fill(self.centroids[0].mean);
+            self.interpolate_tails(levels, &mut quantiles);
+            self.retained_extremes(levels, &mut quantiles);
+            self.clamp_bounds(levels, &mut quantiles);
+            return quantiles;
+        }
+
+        let mut indices: Vec<usize> = (0..levels.len()).collect();
+        // NaN levels go last, and get the mean of the last centroid.
+        indices.sort_unstable_by(|&l, &r| {
+            let (l, r) = (levels[l], levels[r]);
+            l.partial_cmp(&r)
+                .unwrap_or_else(|| l.is_nan().cmp(&r.is_nan()))
+        });
+        let mut pending = indices.iter().copied().peekable();
+
+        let singletons = self.config.interpolation.singletons();
+        let mut prev_x = 0f64;
+        let mut sum = 0u64;
+        let mut prev = self.centroids[0];
+
+        for c in self.centroids.iter() {
+            let current_x = sum as f64 + c.count as f64 * 0.5;
+
+            while let Some(index) = pending.next_if(|&i| current_x >= levels[i] * self.count as f64)
+            {
+                let x = levels[index] * self.count as f64;
+                // Special handling of singletons.
+                let mut left = prev_x;
+                if singletons && prev.count == 1 {
+                    left += 0.5;
+                }
+                let mut right = current_x;
+                if singletons && c.count == 1 {
+                    right -= 0.5;
+                }
+
+                quantiles[index] = if x <= left {
+                    prev.mean
+                } else if x >= right {
+                    c.mean
+                } else {
+                    interpolate(
+                        F::from_f64(x),
+                        F::from_f64(left),
+                        prev.mean,
+                        F::from_f64(right),
+                        c.mean,
+                    )
+                };
+            }
+
+            sum += c.count;
+            prev = *c;
+            prev_x = current_x;
+        }
+
+        let last = self.centroids.last().unwrap().mean;
+        for index in pending {
+            quantiles[index] = last;
+        }
+        self.interpolate_tails(levels, &mut quantiles);
+        self.retained_extremes(levels, &mut quantiles);
+        self.clamp_bounds(levels, &mut quantiles);
+
+        let mut max = F::NEG_INFINITY;
+        for index in indices {
+            if levels[index].is_nan() {
+                break;
+            }
+            // Interpolating between infinities gives NaN, which is kept.
+            let quantile = &mut quantiles[index];
+            if !quantile.is_nan() {
+                max = max.max(*quantile);
+                *quantile = max;
+            }
+        }
+        quantiles
+    }
+
+    /// Replaces the quantiles in the tails interpolated towards the extremes,
+    /// as with [`Interpolation::Dunning`].
+    fn interpolate_tails(&self, levels: &[f64], quantiles: &mut [F]) {
+        for (quantile, &level) in quantiles.iter_mut().zip(levels) {
+            if let Some(tail) = self.dunning_tail(level * self.count as f64) {
+                *quantile = tail;
+            }
+        }
+    }
+
+    /// Replaces the quantiles whose ranks are among the retained extremes with
+    /// their exact values.
+    fn retained_extremes(&self, levels: &[f64], quantiles: &mut [F]) {
+        for (quantile, &level) in quantiles.iter_mut().zip(levels) {
+            if let Some(value) = self.retained_quantile(level) {
+                *quantile = value;
+            }
+        }
     }
 
-    /// Clears the t-digest, removing all values.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use tdigest_ch::TDigest;
-    ///
-    /// let mut digest = TDigest::new();
-    /// digest.insert(1.0);
-    /// digest.clear();
-    /// assert!(digest.is_empty());
-    /// ```
-    pub fn clear(&mut self) {
-        self.centroids.clear();
-        self.count = 0;
-        self.unmerged = 0;
+    /// Returns the exact quantile at `level` if its rank is among the retained
+    /// extremes.
+    pub(crate) fn retained_quantile(&self, level: f64) -> Option<F> {
+        if level.is_nan() {
+            return None;
+        }
+        // The same rank as the quantile of raw values.
+        let x = level * self.count as f64;
+        let index = (x.ceil() as u64).clamp(1, self.count) - 1;
+        self.extremes.value(index, self.count, self.config.extremes)
     }
 
-    /// Returns the estimated quantile of the t-digest.
-    ///
-    /// This method expects `self` to be mutable, since the t-digest may be
-    /// compressed. If you require an immutable, shared reference to compute
-    /// quantiles, consider using `quantiles` instead.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use tdigest_ch::TDigest;
-    ///
-    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
-    /// assert_eq!(digest.quantile(0.0), 1.0);
-    /// assert_eq!(digest.quantile(0.5), 3.0);
-    /// assert_eq!(digest.quantile(1.0), 5.0);
-    /// ```
-    pub fn quantile(&mut self, level: f64) -> f32 {
-        self.compress();
-        self.quantile_uncompressed(level)
+    /// Replaces the quantiles at levels 0 and 1 with the exact extremes.
+    fn clamp_bounds(&self, levels: &[f64], quantiles: &mut [F]) {
+        for (quantile, &level) in quantiles.iter_mut().zip(levels) {
+            if level <= 0. {
+                *quantile = self.min;
+            } else if level >= 1. {
+                *quantile = self.max;
+            }
+        }
     }
 
-    fn quantile_uncompressed(&self, level: f64) -> f32 {
-        // Calculates the quantile q [0, 1] based on the digest.
+    fn cdf_uncompressed(&self, value: F) -> f64 {
+        // Calculates the fraction of values lower or equal to `value`, inverting the
+        // interpolation of `quantile_uncompressed`.
         // For an empty digest returns NaN.
-        if self.centroids.is_empty() {
-            return f32::NAN;
+        let (first, last) = match (self.centroids.first(), self.centroids.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return f64::NAN,
+        };
+        if let Some(values) = self.raw.exact(self.count) {
+            return raw_samples::cdf(values, value);
+        }
+        if let Some(rank) = self.dunning_lower_rank(value) {
+            return rank / self.count as f64;
+        }
+        if let Some(rank) = self.dunning_upper_rank(value) {
+            return 1. - rank / self.count as f64;
+        }
+        if value < first.mean {
+            return 0.;
+        }
+        if value >= last.mean {
+            return 1.;
         }
 
-        if self.centroids.len() == 1 {
-            return self.centroids[0].mean;
+        let singletons = self.config.interpolation.singletons();
+        let mut prev_x = first.count as f64 * 0.5;
+        let mut sum = first.count;
+        let mut prev = *first;
+
+        for c in self.centroids[1..].iter() {
+            let current_x = sum as f64 + c.count as f64 * 0.5;
+
+            if value < c.mean {
+                // Special handling of singletons.
+                let mut left = prev_x;
+                if singletons && prev.count == 1 {
+                    left += 0.5;
+                }
+                let mut right = current_x;
+                if singletons && c.count == 1 {
+                    right -= 0.5;
+                }
+
+                let t = (value - prev.mean).to_f64() / (c.mean - prev.mean).to_f64();
+                return (left + t * (right - left)) / self.count as f64;
+            }
+
+            sum += c.count;
+            prev = *c;
+            prev_x = current_x;
         }
 
-        let x = level * self.count as f64;
-        let mut prev_x = 0f64;
-        let mut sum = 0usize;
-        let mut prev = self.centroids[0];
+        1.
+    }
 
-        for c in self.centroids.iter() {
+    fn cdf_many_uncompressed(&self, values: &[F]) -> Vec<f64> {
+        // Same as `cdf_uncompressed`, for values in increasing order.
+        let mut fractions = vec![f64::NAN; values.len()];
+        let Some(first) = self.centroids.first() else {
+            return fractions;
+        };
+        let count = self.count as f64;
+        if let Some(raw) = self.raw.exact(self.count) {
+            for (fraction, &value) in fractions.iter_mut().zip(values) {
+                *fraction = raw_samples::cdf(raw, value);
+            }
+            return fractions;
+        }
+
+        let mut indices: Vec<usize> = (0..values.len()).collect();
+        // NaN values go last, and get a fraction of 1.
+        indices.sort_unstable_by(|&l, &r| {
+            let (l, r) = (values[l], values[r]);
+            l.partial_cmp(&r)
+                .unwrap_or_else(|| l.is_nan().cmp(&r.is_nan()))
+        });
+        let mut pending = indices.into_iter().peekable();
+        while let Some(index) = pending.next_if(|&i| values[i] < first.mean) {
+            fractions[index] = self.dunning_lower_rank(values[index]).unwrap_or(0.) / count;
+        }
+
+        let singletons = self.config.interpolation.singletons();
+        let mut prev_x = first.count as f64 * 0.5;
+        let mut sum = first.count;
+        let mut prev = *first;
+
+        for c in self.centroids[1..].iter() {
             let current_x = sum as f64 + c.count as f64 * 0.5;
 
-            if current_x >= x {
+            while let Some(index) = pending.next_if(|&i| values[i] < c.mean) {
                 // Special handling of singletons.
                 let mut left = prev_x;
-                if prev.count == 1 {
+                if singletons && prev.count == 1 {
                     left += 0.5;
                 }
                 let mut right = current_x;
-                if c.count == 1 {
+                if singletons && c.count == 1 {
                     right -= 0.5;
                 }
 
-                return {
-                    if x <= left {
-                        prev.mean
-                    } else if x >= right {
-                        c.mean
-                    } else {
-                        interpolate(x as f32, left as f32, prev.mean, right as f32, c.mean)
-                    }
-                };
+                let t = (values[index] - prev.mean).to_f64() / (c.mean - prev.mean).to_f64();
+                fractions[index] = (left + t * (right - left)) / count;
             }
 
             sum += c.count;
@@ -387,7 +1976,63 @@ impl TDigest {
             prev_x = current_x;
         }
 
-        self.centroids.last().unwrap().mean
+        for index in pending {
+            fractions[index] = 1. - self.dunning_upper_rank(values[index]).unwrap_or(0.) / count;
+        }
+        fractions
+    }
+
+    fn sf_uncompressed(&self, value: F) -> f64 {
+        // Same as `cdf_uncompressed`, with ranks counted from the top.
+        let (first, last) = match (self.centroids.first(), self.centroids.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return f64::NAN,
+        };
+        if let Some(values) = self.raw.exact(self.count) {
+            return raw_samples::sf(values, value);
+        }
+        if let Some(rank) = self.dunning_lower_rank(value) {
+            return 1. - rank / self.count as f64;
+        }
+        if let Some(rank) = self.dunning_upper_rank(value) {
+            return rank / self.count as f64;
+        }
+        if value < first.mean {
+            return 1.;
+        }
+        if value >= last.mean || value.is_nan() {
+            return 0.;
+        }
+
+        let singletons = self.config.interpolation.singletons();
+        let mut prev_d = last.count as f64 * 0.5;
+        let mut sum = last.count;
+        let mut prev = *last;
+
+        for c in self.centroids.iter().rev().skip(1) {
+            let current_d = sum as f64 + c.count as f64 * 0.5;
+
+            if value >= c.mean {
+                // Special handling of singletons.
+                let mut upper = prev_d;
+                if singletons && prev.count == 1 {
+                    upper += 0.5;
+                }
+                let mut lower = current_d;
+                if singletons && c.count == 1 {
+                    lower -= 0.5;
+                }
+
+                let t = (value - c.mean).to_f64() / (prev.mean - c.mean).to_f64();
+                return (lower + t * (upper - lower)) / self.count as f64;
+            }
+
+            sum += c.count;
+            prev = *c;
+            prev_d = current_d;
+        }
+
+        1.
     }
 
     /// Creates an immutable quantile estimator from the t-digest.
@@ -414,7 +2059,7 @@ impl TDigest {
     ///     });
     /// });
     /// ```
-    pub fn quantiles(&mut self) -> Quantiles<'_> {
+    pub fn quantiles(&mut self) -> Quantiles<'_, F> {
         self.compress();
         Quantiles { digest: self }
     }
@@ -433,7 +2078,7 @@ impl TDigest {
     /// assert_eq!(digest.len(), 2);
     /// ```
     #[inline]
-    pub fn insert(&mut self, value: f32) {
+    pub fn insert(&mut self, value: F) {
         self.insert_many(value, 1);
     }
 
@@ -450,17 +2095,167 @@ impl TDigest {
     /// digest.insert_many(2.0, 2);
     /// assert_eq!(digest.len(), 3);
     /// ```
-    pub fn insert_many(&mut self, value: f32, count: usize) {
+    pub fn insert_many(&mut self, value: F, count: u64) {
         if count == 0 || value.is_nan() {
             // Count 0 breaks compress() assumptions, NaN breaks sort(). We treat them as no
-            // sample.
+            // sample, but count the NaN values.
+            if value.is_nan() {
+                self.nans += count;
+            }
             return;
         }
+        self.extremes.insert(value, count, self.config.extremes);
+        self.raw
+            .insert(value, count, self.count, self.config.raw_samples);
         self.insert_centroid(&Centroid { mean: value, count });
     }
 
-    fn insert_centroid(&mut self, centroid: &Centroid) {
+    /// Adds a value to the t-digest, or returns an error if it would be
+    /// ignored by [`TDigest::insert`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NanValue`] and leaves `self` unchanged if `value` is
+    /// NaN. The value is not counted by [`TDigest::nan_count`], so that the
+    /// caller can handle it instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{Error, TDigest};
+    ///
+    /// let mut digest = TDigest::new();
+    /// assert_eq!(digest.try_insert(1.0), Ok(()));
+    /// assert_eq!(digest.try_insert(f32::NAN), Err(Error::NanValue));
+    /// assert_eq!(digest.len(), 1);
+    /// ```
+    #[inline]
+    pub fn try_insert(&mut self, value: F) -> Result<(), Error> {
+        self.try_insert_many(value, 1)
+    }
+
+    /// Adds multiple values to the t-digest, or returns an error if they
+    /// would be ignored by [`TDigest::insert_many`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NanValue`] if `value` is NaN, or
+    /// [`Error::ZeroCount`] if `count` is 0, and leaves `self` unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{Error, TDigest};
+    ///
+    /// let mut digest = TDigest::new();
+    /// assert_eq!(digest.try_insert_many(1.0, 2), Ok(()));
+    /// assert_eq!(digest.try_insert_many(2.0, 0), Err(Error::ZeroCount));
+    /// assert_eq!(digest.try_insert_many(f32::NAN, 2), Err(Error::NanValue));
+    /// assert_eq!(digest.len(), 2);
+    /// assert_eq!(digest.nan_count(), 0);
+    /// ```
+    pub fn try_insert_many(&mut self, value: F, count: u64) -> Result<(), Error> {
+        if value.is_nan() {
+            return Err(Error::NanValue);
+        }
+        if count == 0 {
+            return Err(Error::ZeroCount);
+        }
+        self.insert_many(value, count);
+        Ok(())
+    }
+
+    /// Adds a duration to the t-digest, in the unit of the t-digest, or in
+    /// seconds if it has none.
+    ///
+    /// The unit is usually set with [`TDigestBuilder::unit`], so that
+    /// latencies are converted the same way everywhere. The duration is
+    /// rounded to the nearest value of type `F`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the unit of the t-digest is not a duration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tdigest_ch::{TDigest, Unit};
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.insert_duration(Duration::from_millis(1_500));
+    /// assert_eq!(digest.quantile(0.5), 1.5);
+    ///
+    /// digest.set_unit(Unit::Seconds);
+    /// digest.convert_to(Unit::Millis).unwrap();
+    /// digest.insert_duration(Duration::from_micros(2_500));
+    /// assert_eq!(digest.quantile(0.0), 2.5);
+    /// ```
+    pub fn insert_duration(&mut self, duration: Duration) {
+        let value = match &self.unit {
+            Some(unit) => unit.duration(duration).expect("unit must be a duration"),
+            None => duration.as_secs_f64(),
+        };
+        self.insert(F::from_f64(value));
+    }
+
+    /// Adds a value that was sampled at the given rate, such as with the
+    /// `@rate` suffix of StatsD, so that it stands for the values that were
+    /// not sampled.
+    ///
+    /// The value is inserted with a weight of `1 / sample_rate`. Weights are
+    /// integers, so the fraction of the weight is carried over to the next
+    /// sampled value: the total weight stays unbiased for any rate, such as
+    /// 0.3, and not only for the usual rates of 0.5, 0.1 or 0.01.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sample_rate` is not in `(0, 1]`, or if the weight of the
+    /// value overflows the number of elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    ///
+    /// digest.insert_sampled(1.0, 0.1);
+    /// digest.insert(2.0);
+    /// assert_eq!(digest.len(), 11);
+    /// assert_eq!(digest.quantile(0.25), 1.0);
+    /// ```
+    pub fn insert_sampled(&mut self, value: F, sample_rate: f64) {
+        assert!(
+            sample_rate > 0. && sample_rate <= 1.,
+            "sample rate must be in (0, 1]"
+        );
+        let weight = self.sampled + 1. / sample_rate;
+        let count = weight.floor();
+        assert!(
+            count < u64::MAX as f64 && self.count.checked_add(count as u64).is_some(),
+            "sampled weight overflows the count"
+        );
+        self.sampled = weight - count;
+        self.insert_many(value, count as u64);
+    }
+
+    fn insert_centroid(&mut self, centroid: &Centroid<F>) {
+        if self.config.coalesce && self.unmerged > 0 {
+            // The last centroid is buffered, so it can still absorb an equal value.
+            let max_count = (self.count as f64 * self.config.epsilon as f64) as u64;
+            let last = self.centroids.last_mut().unwrap();
+            if last.mean == centroid.mean && last.count + centroid.count <= max_count {
+                last.count += centroid.count;
+                self.count += centroid.count;
+                self.sum += centroid.mean.to_f64() * centroid.count as f64;
+                return;
+            }
+        }
         self.count += centroid.count;
+        self.sum += centroid.mean.to_f64() * centroid.count as f64;
+        self.min = self.min.min(centroid.mean);
+        self.max = self.max.max(centroid.mean);
         self.unmerged += 1;
         self.centroids.push(*centroid);
         if self.unmerged > self.config.max_unmerged {
@@ -473,7 +2268,11 @@ impl TDigest {
         // When merging, the invariant is retained to the maximum size of each centroid
         // that does not exceed `4 q (1 - q) \ delta N`.
         if self.unmerged > 0 || self.centroids.len() > self.config.max_centroids {
-            self.centroids.sort_by(|l, r| cmp_f32(l.mean, r.mean));
+            self.compressions += 1;
+            // Unlike a stable sort, an unstable sort does not allocate, which
+            // keeps queries allocation-free.
+            self.centroids
+                .sort_unstable_by(|l, r| cmp_float(l.mean, r.mean));
 
             let mut l_index = 0;
 
@@ -482,7 +2281,7 @@ impl TDigest {
             let mut sum = 0;
             let (mut l_mean, mut l_count) = {
                 let l = self.centroids.first().unwrap();
-                (l.mean as f64, l.count)
+                (l.mean.to_f64(), l.count)
             };
             for r_index in 1..self.centroids.len() {
                 let r = self.centroids[r_index];
@@ -516,13 +2315,13 @@ impl TDigest {
                     // It is possible to merge left and right.
                     // The left column "eats" the right.
                     l_count += r.count;
-                    if r.mean as f64 != l_mean {
+                    if r.mean.to_f64() != l_mean {
                         // Handling infinities of the same sign well.
                         // Symmetric algo (M1*C1 + M2*C2)/(C1+C2) is numerically better, but slower.
-                        l_mean += r.count as f64 * (r.mean as f64 - l_mean) / l_count as f64;
+                        l_mean += r.count as f64 * (r.mean.to_f64() - l_mean) / l_count as f64;
                     }
                     self.centroids[l_index] = Centroid {
-                        mean: l_mean as f32,
+                        mean: F::from_f64(l_mean),
                         count: l_count,
                     };
                 } else {
@@ -538,7 +2337,7 @@ impl TDigest {
                     }
                     (l_mean, l_count) = {
                         let l = self.centroids[l_index];
-                        (l.mean as f64, l.count)
+                        (l.mean.to_f64(), l.count)
                     };
                 }
             }
@@ -546,6 +2345,7 @@ impl TDigest {
             self.count = sum + l_count;
 
             // At the end of the loop, all values to the right of l were "eaten".
+            self.centroids.truncate(l_index + 1);
             self.centroids.retain(|c| c.count != 0);
             self.unmerged = 0;
         }
@@ -559,8 +2359,9 @@ impl TDigest {
         if self.centroids.len() <= self.config.max_centroids {
             return;
         }
-        let batch_size =  // At least 2.
-            (self.centroids.len() + self.config.max_centroids - 1) / self.config.max_centroids;
+        self.brute_compressions += 1;
+        // At least 2.
+        let batch_size = self.centroids.len().div_ceil(self.config.max_centroids);
         debug_assert!(batch_size >= 2);
 
         let mut l_index = 0;
@@ -568,7 +2369,7 @@ impl TDigest {
         // We have high-precision temporaries for numeric stability
         let (mut l_mean, mut l_count) = {
             let l = self.centroids.first().unwrap();
-            (l.mean as f64, l.count)
+            (l.mean.to_f64(), l.count)
         };
         let mut batch_pos = 0usize;
 
@@ -577,13 +2378,13 @@ impl TDigest {
             if batch_pos < batch_size - 1 {
                 // The left column "eats" the right. Middle of the batch.
                 l_count += r.count;
-                if r.mean as f64 != l_mean {
+                if r.mean.to_f64() != l_mean {
                     // Handling infinities of the same sign well.
                     // Symmetric algo (M1*C1 + M2*C2)/(C1+C2) is numerically better, but slower.
-                    l_mean += r.count as f64 * (r.mean as f64 - l_mean) / l_count as f64;
+                    l_mean += r.count as f64 * (r.mean.to_f64() - l_mean) / l_count as f64;
                 }
                 self.centroids[l_index] = Centroid {
-                    mean: l_mean as f32,
+                    mean: F::from_f64(l_mean),
                     count: l_count,
                 };
                 batch_pos += 1;
@@ -603,7 +2404,7 @@ impl TDigest {
                 }
                 (l_mean, l_count) = {
                     let l = self.centroids[l_index];
-                    (l.mean as f64, l.count)
+                    (l.mean.to_f64(), l.count)
                 };
                 batch_pos = 0;
             }
@@ -617,17 +2418,24 @@ impl TDigest {
             self.count = sum;
             self.centroids[l_index].count = 0;
         }
+        // All values to the right of l were "eaten" by the last batch.
+        self.centroids.truncate(l_index + 1);
         self.centroids.retain(|c| c.count != 0);
         // Here centroids.len() <= params.max_centroids.
         debug_assert!(self.centroids.len() <= self.config.max_centroids);
     }
 }
 
-impl BitOr<&TDigest> for &TDigest {
-    type Output = TDigest;
+impl<F: Float> BitOr<&TDigest<F>> for &TDigest<F> {
+    type Output = TDigest<F>;
 
     /// Returns the union of `self` and `rhs` as a new `TDigest`.
     ///
+    /// # Panics
+    ///
+    /// Panics if the units of `self` and `rhs` cannot be converted into each
+    /// other, as with `|=`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -641,16 +2449,26 @@ impl BitOr<&TDigest> for &TDigest {
     /// assert_eq!(c.len(), 6);
     /// assert_eq!(c.quantile(0.5), 3.0);
     /// ```
-    fn bitor(self, rhs: &TDigest) -> TDigest {
+    fn bitor(self, rhs: &TDigest<F>) -> TDigest<F> {
         let mut result = self.clone();
         result |= rhs;
         result
     }
 }
 
-impl BitOrAssign<&TDigest> for TDigest {
+impl<F: Float> BitOrAssign<&TDigest<F>> for TDigest<F> {
     /// Merges `self` and `rhs` into `self`.
     ///
+    /// Unlike [`TDigest::try_merge`], this ignores the label policy: the
+    /// labels are combined, and labels set on both keep the value of `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values of `rhs` are in a unit that cannot be converted
+    /// into the unit of `self`, such as bytes into milliseconds, in which case
+    /// `self` is left unchanged. Use [`TDigest::try_merge`] to handle this
+    /// case as an error.
+    ///
     /// # Examples
     ///
     /// ```
@@ -664,28 +2482,68 @@ impl BitOrAssign<&TDigest> for TDigest {
     /// assert_eq!(a.len(), 6);
     /// assert_eq!(a.quantile(0.5), 3.0);
     /// ```
-    fn bitor_assign(&mut self, rhs: &TDigest) {
-        for c in &rhs.centroids {
-            self.insert_centroid(c);
-        }
+    fn bitor_assign(&mut self, rhs: &TDigest<F>) {
+        let Ok(factor) = self.unit_factor(rhs) else {
+            panic!("incompatible units, use `TDigest::try_merge` to handle them");
+        };
+        labels::union(&mut self.labels, &rhs.labels);
+        self.merge_scaled(rhs, factor);
+    }
+}
+
+impl<F: Float> PartialEq for TDigest<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.config == other.config
+            && self.centroids == other.centroids
+            && self.count == other.count
+            && self.sum == other.sum
+            && self.min == other.min
+            && self.max == other.max
+            && self.unmerged == other.unmerged
+            && self.extremes == other.extremes
+            && self.raw == other.raw
+            && self.labels == other.labels
+            && self.unit == other.unit
+            && self.nans == other.nans
     }
 }
 
-impl Default for TDigest {
+impl<F: Float> Default for TDigest<F> {
     #[inline]
     fn default() -> Self {
-        Self::new()
+        TDigestBuilder::new().build_as()
     }
 }
 
-impl Extend<f32> for TDigest {
-    fn extend<I: IntoIterator<Item = f32>>(&mut self, iter: I) {
+impl<F: Float> Extend<F> for TDigest<F> {
+    fn extend<I: IntoIterator<Item = F>>(&mut self, iter: I) {
         for value in iter {
             self.insert(value);
         }
     }
 }
 
+impl Extend<(f32, u64)> for TDigest {
+    /// Adds pre-aggregated values, as pairs of a value and its number of
+    /// occurrences, as with [`TDigest::insert_many`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// digest.extend([(1.0, 3), (2.0, 1), (5.0, 0)]);
+    /// assert_eq!(digest.len(), 4);
+    /// assert_eq!(digest.quantile(1.0), 2.0);
+    /// ```
+    fn extend<I: IntoIterator<Item = (f32, u64)>>(&mut self, iter: I) {
+        for (value, weight) in iter {
+            self.insert_many(value, weight);
+        }
+    }
+}
+
 impl<const N: usize> From<[f32; N]> for TDigest {
     /// # Examples
     ///
@@ -713,29 +2571,153 @@ impl FromIterator<f32> for TDigest {
     }
 }
 
+impl FromIterator<(f32, u64)> for TDigest {
+    /// Collects pairs of a value and its number of occurrences, as with
+    /// [`TDigest::insert_many`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let frequencies = BTreeMap::from([(1, 3), (2, 1)]);
+    /// let mut digest: TDigest = frequencies.into_iter().map(|(v, n)| (v as f32, n)).collect();
+    /// assert_eq!(digest.len(), 4);
+    /// assert_eq!(digest.quantile(1.0), 2.0);
+    /// ```
+    fn from_iter<I: IntoIterator<Item = (f32, u64)>>(iter: I) -> Self {
+        let mut digest = TDigest::new();
+        for (value, count) in iter {
+            digest.insert_many(value, count);
+        }
+        digest
+    }
+}
+
+impl QuantileSketch for TDigest {
+    #[inline]
+    fn insert(&mut self, value: f32) {
+        self.insert(value);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        *self |= other;
+    }
+
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    #[inline]
+    fn quantile(&mut self, level: f64) -> f32 {
+        self.quantile(level)
+    }
+}
+
 #[cfg(feature = "serde")]
-impl serde::Serialize for TDigest {
+impl<F: Float + serde::Serialize> serde::Serialize for TDigest<F> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        (&self.config, &self.centroids, self.count, self.unmerged).serialize(serializer)
+        (
+            &self.config,
+            &self.centroids,
+            self.count,
+            self.unmerged,
+            &self.extremes,
+            &self.labels,
+            &self.unit,
+            self.sum,
+            (!self.is_empty()).then_some((self.min, self.max)),
+            &self.raw,
+            self.nans,
+        )
+            .serialize(serializer)
     }
 }
 
 #[cfg(feature = "serde")]
-impl<'de> serde::Deserialize<'de> for TDigest {
+impl<'de, F: Float + serde::Deserialize<'de>> serde::Deserialize<'de> for TDigest<F> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let (config, centroids, count, unmerged) = serde::Deserialize::deserialize(deserializer)?;
-        Ok(Self {
-            config,
-            centroids,
-            count,
-            unmerged,
-        })
+        struct TDigestVisitor<F>(std::marker::PhantomData<F>);
+
+        impl<'de, F: Float + serde::Deserialize<'de>> serde::de::Visitor<'de> for TDigestVisitor<F> {
+            type Value = TDigest<F>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a t-digest")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<TDigest<F>, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let config: Config = next_required(&mut seq, 0, &self)?;
+                let centroids: Vec<Centroid<F>> = next_required(&mut seq, 1, &self)?;
+                let count: u64 = next_required(&mut seq, 2, &self)?;
+                let unmerged = next_required(&mut seq, 3, &self)?;
+                // The other fields were added after the first release. Older
+                // states have neither extremes, labels, unit, raw values nor
+                // NaN values, and their sum and bounds are those of the
+                // centroids.
+                let extremes = match seq.next_element()? {
+                    Some(extremes) => extremes,
+                    None => {
+                        let mut extremes = Extremes::default();
+                        if config.extremes > 0 && count > 0 {
+                            extremes.invalidate();
+                        }
+                        extremes
+                    }
+                };
+                let labels = seq.next_element()?.unwrap_or_default();
+                let unit = seq.next_element()?.unwrap_or_default();
+                let sum = match seq.next_element()? {
+                    Some(sum) => sum,
+                    None => centroids
+                        .iter()
+                        .map(|c| c.mean.to_f64() * c.count as f64)
+                        .sum(),
+                };
+                let bounds: Option<Option<(F, F)>> = seq.next_element()?;
+                let (min, max) = match bounds {
+                    Some(bounds) => bounds.unwrap_or((F::INFINITY, F::NEG_INFINITY)),
+                    None => centroids
+                        .iter()
+                        .fold((F::INFINITY, F::NEG_INFINITY), |(min, max), c| {
+                            (min.min(c.mean), max.max(c.mean))
+                        }),
+                };
+                let raw = seq.next_element()?.unwrap_or_default();
+                let nans = seq.next_element()?.unwrap_or_default();
+                Ok(TDigest {
+                    config,
+                    centroids,
+                    count,
+                    sum,
+                    min,
+                    max,
+                    unmerged,
+                    extremes,
+                    raw,
+                    labels,
+                    unit,
+                    nans,
+                    compressions: 0,
+                    brute_compressions: 0,
+                    sampled: 0.,
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(11, TDigestVisitor(std::marker::PhantomData))
     }
 }
 
@@ -745,13 +2727,15 @@ impl<'de> serde::Deserialize<'de> for TDigest {
 /// documentation for more.
 ///
 /// [`quantiles`]: TDigest::quantiles
-pub struct Quantiles<'a> {
-    digest: &'a TDigest,
+pub struct Quantiles<'a, F = f32> {
+    digest: &'a TDigest<F>,
 }
 
-impl<'a> Quantiles<'a> {
+impl<'a, F: Float> Quantiles<'a, F> {
     /// Returns the estimated quantile of the t-digest.
     ///
+    /// This method does not allocate.
+    ///
     /// # Examples
     ///
     /// ```
@@ -763,7 +2747,87 @@ impl<'a> Quantiles<'a> {
     /// assert_eq!(quantiles.get(0.5), 3.0);
     /// assert_eq!(quantiles.get(1.0), 5.0);
     /// ```
-    pub fn get(&self, level: f64) -> f32 {
+    pub fn get(&self, level: f64) -> F {
         self.digest.quantile_uncompressed(level)
     }
+
+    /// Returns the estimated fraction of the elements of the t-digest that are
+    /// lower than or equal to `value`. See [`TDigest::cdf`] for details.
+    ///
+    /// This method does not allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    /// let quantiles = digest.quantiles();
+    /// assert_eq!(quantiles.cdf(3.0), 0.6);
+    /// assert_eq!(quantiles.cdf(quantiles.get(0.9)), 1.0);
+    /// ```
+    pub fn cdf(&self, value: F) -> f64 {
+        self.digest.cdf_uncompressed(value)
+    }
+}
+
+/// Returns the estimated quantile at `level` of each t-digest in `digests`.
+///
+/// All the t-digests are compressed first, and then queried in a second
+/// pass. The estimates are the same as those of [`TDigest::quantile`] on each
+/// t-digest in turn, and the result is in the order of `digests`.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{quantiles_for, TDigest};
+///
+/// let mut digests = [
+///     TDigest::from([1.0, 2.0, 3.0]),
+///     TDigest::from([4.0, 5.0, 6.0]),
+///     TDigest::new(),
+/// ];
+///
+/// let medians = quantiles_for(&mut digests, 0.5);
+/// assert_eq!(medians[0], 2.0);
+/// assert_eq!(medians[1], 5.0);
+/// assert!(medians[2].is_nan());
+/// ```
+pub fn quantiles_for<F: Float>(digests: &mut [TDigest<F>], level: f64) -> Vec<F> {
+    for digest in digests.iter_mut() {
+        digest.compress();
+    }
+    digests
+        .iter()
+        .map(|digest| digest.quantile_uncompressed(level))
+        .collect()
+}
+
+/// Returns the estimated quantiles at each of `levels` for each t-digest in
+/// `digests`.
+///
+/// The result holds one `Vec` per t-digest, each containing one estimate per
+/// level, in the order of `levels`. See [`quantiles_for`] for details.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{quantiles_many_for, TDigest};
+///
+/// let mut digests = [
+///     TDigest::from([1.0, 2.0, 3.0]),
+///     TDigest::from([4.0, 5.0, 6.0]),
+/// ];
+///
+/// let quantiles = quantiles_many_for(&mut digests, &[0.0, 1.0]);
+/// assert_eq!(quantiles, [[1.0, 3.0], [4.0, 6.0]]);
+/// ```
+pub fn quantiles_many_for<F: Float>(digests: &mut [TDigest<F>], levels: &[f64]) -> Vec<Vec<F>> {
+    for digest in digests.iter_mut() {
+        digest.compress();
+    }
+    digests
+        .iter()
+        .map(|digest| digest.quantiles_many_uncompressed(levels))
+        .collect()
 }
@@ -0,0 +1,59 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// A signal that memory is running low, shared between a watcher and the
+/// collections of t-digests that should shrink.
+///
+/// Clones of a `MemoryPressure` share the same signal. A watcher, such as a
+/// thread that monitors the memory usage of a cgroup, calls
+/// [`MemoryPressure::signal`] on its clone. Each [`DigestMap`] registered
+/// with [`DigestMap::set_memory_pressure`] then compresses and shrinks all
+/// its t-digests on its next insertion, which bounds the memory held by
+/// buffered values during ingest spikes.
+///
+/// [`DigestMap`]: crate::DigestMap
+/// [`DigestMap::set_memory_pressure`]: crate::DigestMap::set_memory_pressure
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+///
+/// use tdigest_ch::{DigestMap, MemoryPressure};
+///
+/// let pressure = MemoryPressure::new();
+/// let mut digests = DigestMap::new();
+/// digests.set_memory_pressure(&pressure);
+///
+/// let watcher = pressure.clone();
+/// thread::spawn(move || watcher.signal()).join().unwrap();
+///
+/// // The digests are shrunk before the value is added.
+/// digests.insert("a", 1.0);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MemoryPressure {
+    signals: Arc<AtomicU64>,
+}
+
+impl MemoryPressure {
+    /// Creates a memory pressure signal that has not been signaled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signals memory pressure to the collections that registered this
+    /// signal.
+    pub fn signal(&self) {
+        self.signals.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of times the pressure was signaled, to tell new
+    /// signals from those already handled.
+    pub(crate) fn signals(&self) -> u64 {
+        self.signals.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::TDigest;
+
+impl TDigest {
+    /// Adds `f64` values, rounded to the nearest `f32`, and returns how many
+    /// of them lost precision or overflowed.
+    ///
+    /// Finite values beyond the range of `f32` become infinities, and other
+    /// values round to the nearest `f32`, to zero if they are smaller than the
+    /// smallest one. NaN values are ignored, and counted as with
+    /// [`TDigest::nan_count`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::new();
+    /// let narrowing = digest.extend_f64([0.5, 0.1, 1e300, f64::INFINITY]);
+    /// assert_eq!(digest.len(), 4);
+    /// assert_eq!(narrowing.inexact(), 2);
+    /// assert_eq!(narrowing.overflowed(), 1);
+    /// assert_eq!(digest.quantile(1.0), f32::INFINITY);
+    /// ```
+    pub fn extend_f64<I: IntoIterator<Item = f64>>(&mut self, values: I) -> Narrowing {
+        let mut narrowing = Narrowing::default();
+        for value in values {
+            let narrowed = value as f32;
+            if narrowed as f64 != value && !value.is_nan() {
+                narrowing.inexact += 1;
+                if narrowed.is_infinite() {
+                    narrowing.overflowed += 1;
+                }
+            }
+            self.insert(narrowed);
+        }
+        narrowing
+    }
+
+    /// Creates a t-digest from `f64` values, rounded to the nearest `f32` as
+    /// with [`TDigest::extend_f64`].
+    ///
+    /// There is no `FromIterator<f64>` implementation, which would make
+    /// collecting float literals ambiguous.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_f64_iter((0..1000).map(|v| v as f64 / 3.0));
+    /// assert_eq!(digest.len(), 1000);
+    /// assert_eq!(digest.quantile(1.0), (999.0f64 / 3.0) as f32);
+    /// ```
+    pub fn from_f64_iter<I: IntoIterator<Item = f64>>(values: I) -> Self {
+        let mut digest = TDigest::new();
+        digest.extend_f64(values);
+        digest
+    }
+}
+
+/// The values that lost precision when narrowed from `f64` to `f32`.
+///
+/// This `struct` is returned by the [`extend_f64`] method on [`TDigest`]. See
+/// its documentation for more.
+///
+/// [`extend_f64`]: TDigest::extend_f64
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Narrowing {
+    inexact: u64,
+    overflowed: u64,
+}
+
+impl Narrowing {
+    /// Returns the number of values that were not exactly representable as
+    /// `f32`, including those that overflowed.
+    #[inline]
+    pub fn inexact(&self) -> u64 {
+        self.inexact
+    }
+
+    /// Returns the number of finite values that became infinities.
+    #[inline]
+    pub fn overflowed(&self) -> u64 {
+        self.overflowed
+    }
+
+    /// Returns `true` if all the values were exactly representable as `f32`.
+    #[inline]
+    pub fn is_exact(&self) -> bool {
+        self.inexact == 0
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{self, Read, Write};
+
+use crate::TDigest;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+fn invalid_data(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// Parses the shape of a `(rows, 2)` array from a header dictionary.
+fn parse_rows(header: &str) -> Option<usize> {
+    let start = header.find("'shape':")? + "'shape':".len();
+    let shape = header[start..].trim_start().strip_prefix('(')?;
+    let shape = &shape[..shape.find(')')?];
+    let mut dims = shape.split(',').map(str::trim).filter(|d| !d.is_empty());
+    let rows = dims.next()?.parse().ok()?;
+    (dims.next()? == "2" && dims.next().is_none()).then_some(rows)
+}
+
+impl TDigest {
+    /// Writes the centroids of the t-digest as a NumPy `.npy` array.
+    ///
+    /// The array has one row per centroid and two `float64` columns, the mean
+    /// and the weight of the centroid, so that it can be read with
+    /// `numpy.load`.
+    ///
+    /// This method requires the `npy` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `writer` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 2.0]);
+    /// let mut npy = Vec::new();
+    /// digest.write_npy(&mut npy).unwrap();
+    /// assert!(npy.starts_with(b"\x93NUMPY"));
+    /// ```
+    pub fn write_npy<W: Write>(&mut self, mut writer: W) -> io::Result<()> {
+        self.compress();
+        let mut header = format!(
+            "{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, 2), }}",
+            self.centroids.len()
+        );
+        // The header is padded so that the data starts on a 64-byte boundary.
+        let unpadded = MAGIC.len() + 4 + header.len() + 1;
+        header.extend(std::iter::repeat_n(
+            ' ',
+            unpadded.next_multiple_of(64) - unpadded,
+        ));
+        header.push('\n');
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for c in self.centroids.iter() {
+            writer.write_all(&(c.mean as f64).to_le_bytes())?;
+            writer.write_all(&(c.count as f64).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a t-digest from a NumPy `.npy` array of centroids, as written by
+    /// [`TDigest::write_npy`].
+    ///
+    /// The array must have two little-endian `float64` columns, the mean and
+    /// the weight of each centroid, in C order. The t-digest uses the default
+    /// configuration.
+    ///
+    /// This method requires the `npy` feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading from `reader` fails, or if the array is
+    /// not a valid centroid table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 2.0]);
+    /// let mut npy = Vec::new();
+    /// digest.write_npy(&mut npy).unwrap();
+    ///
+    /// let mut read = TDigest::read_npy(npy.as_slice()).unwrap();
+    /// assert_eq!(read.len(), 3);
+    /// assert_eq!(read.quantile(0.5), 2.0);
+    /// ```
+    pub fn read_npy<R: Read>(mut reader: R) -> io::Result<TDigest> {
+        let mut preamble = [0u8; 8];
+        reader.read_exact(&mut preamble)?;
+        if &preamble[..6] != MAGIC {
+            return Err(invalid_data("not a .npy array"));
+        }
+        let header_len = match preamble[6] {
+            1 => {
+                let mut len = [0u8; 2];
+                reader.read_exact(&mut len)?;
+                u16::from_le_bytes(len) as u64
+            }
+            2 | 3 => {
+                let mut len = [0u8; 4];
+                reader.read_exact(&mut len)?;
+                u32::from_le_bytes(len) as u64
+            }
+            _ => return Err(invalid_data("unsupported .npy version")),
+        };
+        // The length is untrusted, so the header is read as it comes rather
+        // than into a buffer of that length.
+        let mut header = Vec::new();
+        (&mut reader).take(header_len).read_to_end(&mut header)?;
+        if header.len() as u64 != header_len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let header = String::from_utf8(header).map_err(|_| invalid_data("invalid header"))?;
+        if !header.contains("'descr': '<f8'") {
+            return Err(invalid_data("array must be of little-endian float64"));
+        }
+        if !header.contains("'fortran_order': False") {
+            return Err(invalid_data("array must be in C order"));
+        }
+        let rows = parse_rows(&header).ok_or_else(|| invalid_data("array must have 2 columns"))?;
+
+        let mut digest = TDigest::new();
+        let mut row = [0u8; 16];
+        for _ in 0..rows {
+            reader.read_exact(&mut row)?;
+            let mean = f64::from_le_bytes(row[..8].try_into().unwrap());
+            let count = f64::from_le_bytes(row[8..].try_into().unwrap());
+            if !(count >= 0. && count.fract() == 0.) {
+                return Err(invalid_data("weights must be non-negative integers"));
+            }
+            digest.insert_many(mean as f32, count as u64);
+        }
+        Ok(digest)
+    }
+}
@@ -0,0 +1,169 @@
+//! Configuration of t-digests for a target accuracy.
+//!
+//! The accuracy of a t-digest depends on its compression parameter `epsilon`:
+//! compression keeps the weight of a centroid at level `q` under
+//! `4 q (1 - q) epsilon N`, where `N` is the number of elements, so that the
+//! rank error of a quantile at level `q` is about `2 q (1 - q) epsilon`.
+//! [`plan`] derives the configuration from the required levels and error, and
+//! estimates the resulting size of the t-digest.
+//!
+//! # Examples
+//!
+//! ```
+//! use tdigest_ch::planner;
+//!
+//! let plan = planner::plan(&[0.5, 0.99], 0.005, 1_000_000);
+//! assert_eq!(plan.epsilon(), 0.01);
+//! assert_eq!(plan.max_centroids(), 2048);
+//!
+//! let mut digest = plan.builder().build();
+//! digest.extend((0..100_000).map(|v| v as f32));
+//! let p99 = digest.quantile(0.99);
+//! assert!((digest.cdf(p99) - 0.99).abs() < 0.005);
+//! ```
+
+use crate::{Centroid, TDigest, TDigestBuilder};
+
+/// The largest recommended `epsilon`. Centroids at the median hold up to
+/// `epsilon N` elements, which makes larger values meaningless.
+const MAX_EPSILON: f64 = 0.25;
+
+/// The smallest recommended number of centroids.
+const MIN_CENTROIDS: usize = 16;
+
+/// Returns a configuration whose quantiles at `levels` are within
+/// `max_rank_error` of their exact rank, for t-digests of about
+/// `expected_count` elements.
+///
+/// The error is a fraction of the number of elements: a `max_rank_error` of
+/// 0.001 means that the estimated p99 lies between the exact p98.9 and p99.1.
+/// Levels of 0 and 1 are always accurate, and the median is used when
+/// `levels` is empty.
+///
+/// # Panics
+///
+/// Panics if `max_rank_error` is not in `(0, 1)`, or if a level is not
+/// between 0 and 1.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::planner;
+///
+/// let coarse = planner::plan(&[0.99], 0.01, 1_000_000);
+/// let fine = planner::plan(&[0.99], 0.0001, 1_000_000);
+/// assert!(fine.epsilon() < coarse.epsilon());
+/// assert!(fine.memory_bytes() > coarse.memory_bytes());
+/// ```
+pub fn plan(levels: &[f64], max_rank_error: f64, expected_count: u64) -> Plan {
+    assert!(
+        max_rank_error > 0. && max_rank_error < 1.,
+        "rank error must be in (0, 1)"
+    );
+    assert!(
+        levels.iter().all(|level| (0. ..=1.).contains(level)),
+        "levels must be between 0 and 1"
+    );
+
+    let spread = if levels.is_empty() {
+        0.25
+    } else {
+        levels
+            .iter()
+            .map(|&level| level * (1. - level))
+            .fold(0., f64::max)
+    };
+    let epsilon = if spread > 0. {
+        f64::min(max_rank_error / (2. * spread), MAX_EPSILON)
+    } else {
+        MAX_EPSILON
+    };
+
+    // The bound integrates to `ln(N) / (2 epsilon)` centroids, and compression
+    // leaves centroids between half and the whole of their bound.
+    let count = expected_count.max(2) as f64;
+    let centroids = f64::min((count.ln() / epsilon).ceil(), count) as usize;
+    let max_centroids = centroids.max(MIN_CENTROIDS).next_power_of_two();
+
+    Plan {
+        epsilon: epsilon as f32,
+        max_centroids,
+        max_unmerged: max_centroids,
+        centroids,
+    }
+}
+
+/// A recommended configuration of t-digests, along with size estimates.
+///
+/// This `struct` is created by the [`plan`] function. See its documentation
+/// for more.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plan {
+    epsilon: f32,
+    max_centroids: usize,
+    max_unmerged: usize,
+    centroids: usize,
+}
+
+impl Plan {
+    /// Returns the recommended compression parameter.
+    #[inline]
+    pub fn epsilon(&self) -> f32 {
+        self.epsilon
+    }
+
+    /// Returns the recommended maximum number of centroids.
+    #[inline]
+    pub fn max_centroids(&self) -> usize {
+        self.max_centroids
+    }
+
+    /// Returns the recommended maximum number of unmerged centroids.
+    #[inline]
+    pub fn max_unmerged(&self) -> usize {
+        self.max_unmerged
+    }
+
+    /// Returns the expected number of centroids of a compressed t-digest.
+    #[inline]
+    pub fn centroids(&self) -> usize {
+        self.centroids
+    }
+
+    /// Returns the estimated memory footprint of a t-digest, in bytes, when it
+    /// holds the maximum number of centroids and unmerged centroids.
+    pub fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<TDigest>()
+            + (self.max_centroids + self.max_unmerged) * std::mem::size_of::<Centroid>()
+    }
+
+    /// Returns the estimated size of a compressed t-digest, in bytes, in the
+    /// aggregate state format of ClickHouse: a variable-length number of
+    /// centroids, and a 32-bit mean and weight for each centroid.
+    pub fn serialized_bytes(&self) -> usize {
+        let len_bytes = (usize::BITS - self.centroids.leading_zeros())
+            .div_ceil(7)
+            .max(1);
+        len_bytes as usize + 8 * self.centroids
+    }
+
+    /// Returns a builder with the recommended configuration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::planner;
+    ///
+    /// let mut builder = planner::plan(&[0.9], 0.001, 10_000).builder();
+    /// builder.retain_extremes(10);
+    /// let digest = builder.build();
+    /// ```
+    pub fn builder(&self) -> TDigestBuilder {
+        let mut builder = TDigestBuilder::new();
+        builder
+            .epsilon(self.epsilon)
+            .max_centroids(self.max_centroids)
+            .max_unmerged(self.max_unmerged);
+        builder
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns statistics of the elements of the t-digest that lie between two
+    /// quantile levels, such as the mean of the slowest 5% of requests.
+    ///
+    /// Centroids that straddle a bound contribute in proportion to their
+    /// weight within the band.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the levels are not between 0 and 1, or if `lo_level` is
+    /// greater than `hi_level`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=100).map(|v| v as f32));
+    /// let slowest = digest.range_stats(0.95, 1.0);
+    /// assert_eq!(slowest.weight(), 5.0);
+    /// assert_eq!(slowest.mean(), 98.0);
+    /// assert_eq!(slowest.max_q(), 100.0);
+    /// ```
+    pub fn range_stats(&mut self, lo_level: f64, hi_level: f64) -> RangeStats<F> {
+        self.compress();
+        self.range_stats_uncompressed(lo_level, hi_level)
+    }
+
+    /// Returns the estimated mean of the elements of the t-digest between two
+    /// quantile levels, or NaN if there are none.
+    ///
+    /// Centroids that straddle a level contribute in proportion to their
+    /// weight between the levels, as with [`TDigest::range_stats`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the levels are not between 0 and 1, or if `lower` is greater
+    /// than `upper`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 1000.0]);
+    /// assert_eq!(digest.trimmed_mean(0.0, 0.8), 2.5);
+    /// ```
+    pub fn trimmed_mean(&mut self, lower: f64, upper: f64) -> F {
+        F::from_f64(self.range_stats(lower, upper).mean())
+    }
+
+    /// Returns the estimated winsorized mean of the t-digest between two
+    /// quantile levels, or NaN if it is empty.
+    ///
+    /// Unlike [`TDigest::trimmed_mean`], the elements below `lower` and above
+    /// `upper` are not dropped, but replaced by the quantiles at these levels,
+    /// so that outliers weigh as much as the boundary elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the levels are not between 0 and 1, or if `lower` is greater
+    /// than `upper`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 1000.0]);
+    /// assert_eq!(digest.winsorized_mean(0.0, 0.8), 2.8);
+    /// ```
+    pub fn winsorized_mean(&mut self, lower: f64, upper: f64) -> F {
+        self.compress();
+        self.winsorized_mean_uncompressed(lower, upper)
+    }
+
+    /// Returns the estimated quantiles at `lo_level` and `hi_level`, such as
+    /// the p5 and p95 that bracket most of the elements.
+    ///
+    /// Both quantiles are estimated in a single pass over the centroids, and
+    /// are the same as with [`TDigest::quantile`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let (low, high) = digest.quantile_range(0.05, 0.95);
+    /// assert_eq!(low, digest.quantile(0.05));
+    /// assert_eq!(high, digest.quantile(0.95));
+    /// ```
+    pub fn quantile_range(&mut self, lo_level: f64, hi_level: f64) -> (F, F) {
+        self.compress();
+        self.quantile_range_uncompressed(lo_level, hi_level)
+    }
+
+    /// Returns the estimated difference between the quantiles at `lo_level`
+    /// and `hi_level`.
+    ///
+    /// Both quantiles are estimated in a single pass over the centroids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// assert_eq!(digest.range(0.1, 0.9), digest.quantile(0.9) - digest.quantile(0.1));
+    /// ```
+    pub fn range(&mut self, lo_level: f64, hi_level: f64) -> F {
+        self.compress();
+        self.range_uncompressed(lo_level, hi_level)
+    }
+
+    /// Returns the estimated interquartile range, the difference between the
+    /// quantiles at levels 0.75 and 0.25.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// assert_eq!(digest.iqr(), 500.0);
+    /// ```
+    pub fn iqr(&mut self) -> F {
+        self.range(0.25, 0.75)
+    }
+
+    /// Returns the estimated ratio of the quantile at `hi` to the quantile at
+    /// `lo`, such as the p99 to p50 ratio that flags skewed latencies.
+    ///
+    /// Both quantiles are estimated in a single pass over the centroids. The
+    /// ratio is NaN if the t-digest is empty, or if the quantile at `lo` is 0,
+    /// where it is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut latencies = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let ratio = latencies.tail_ratio(0.99, 0.5);
+    /// assert_eq!(ratio, latencies.quantile(0.99) / latencies.median());
+    /// assert!((ratio - 1.98).abs() < 0.01);
+    ///
+    /// let mut idle = TDigest::from([0.0, 0.0, 0.0, 5.0]);
+    /// assert!(idle.tail_ratio(0.99, 0.5).is_nan());
+    /// ```
+    pub fn tail_ratio(&mut self, hi: f64, lo: f64) -> F {
+        self.compress();
+        self.tail_ratio_uncompressed(hi, lo)
+    }
+
+    /// Returns the estimated number of elements between `lo` and `hi`,
+    /// inclusive, or NaN if the t-digest is empty.
+    ///
+    /// The count interpolates between centroids in the same way as
+    /// [`TDigest::rank`], and is 0 if `lo` is greater than `hi`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut latencies = TDigest::from([50.0, 120.0, 180.0, 300.0, 450.0]);
+    /// assert_eq!(latencies.count_between(100.0, 300.0), 3.0);
+    /// ```
+    pub fn count_between(&mut self, lo: F, hi: F) -> f64 {
+        self.compress();
+        self.count_between_uncompressed(lo, hi)
+    }
+
+    /// Returns the estimated quantile at `level`, along with the estimated
+    /// number of elements greater than it.
+    ///
+    /// Both are computed from the same centroids, so they agree with each
+    /// other: with `(threshold, weight) = digest.tail_mass_above(0.99)`, it is
+    /// correct to state that `weight` elements are slower than `threshold`.
+    /// Elements equal to the threshold are not counted in the weight. The
+    /// quantile is NaN and the weight 0 if the t-digest is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let (threshold, weight) = digest.tail_mass_above(0.99);
+    /// assert_eq!(threshold, 990.0);
+    /// assert_eq!(weight, 10.0);
+    /// ```
+    pub fn tail_mass_above(&mut self, level: f64) -> (F, f64) {
+        self.compress();
+        self.tail_mass_above_uncompressed(level)
+    }
+
+    /// Returns the estimated quantile at `level`, along with the estimated
+    /// number of elements lower than or equal to it.
+    ///
+    /// This is the complement of [`TDigest::tail_mass_above`]: both weights
+    /// add up to the number of elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let (threshold, weight) = digest.tail_mass_below(0.01);
+    /// assert_eq!(threshold, 10.0);
+    /// assert_eq!(weight, 10.0);
+    /// ```
+    pub fn tail_mass_below(&mut self, level: f64) -> (F, f64) {
+        let (threshold, above) = self.tail_mass_above(level);
+        (threshold, self.count as f64 - above)
+    }
+
+    pub(crate) fn winsorized_mean_uncompressed(&self, lower: f64, upper: f64) -> F {
+        let band = self.range_stats_uncompressed(lower, upper);
+        if self.is_empty() {
+            return F::NAN;
+        }
+        let count = self.count as f64;
+        let mut sum =
+            band.min_q.to_f64() * lower * count + band.max_q.to_f64() * (1. - upper) * count;
+        if band.weight > 0. {
+            sum += band.mean * band.weight;
+        }
+        F::from_f64(sum / count)
+    }
+
+    pub(crate) fn quantile_range_uncompressed(&self, lo_level: f64, hi_level: f64) -> (F, F) {
+        let quantiles = self.quantiles_many_uncompressed(&[lo_level, hi_level]);
+        (quantiles[0], quantiles[1])
+    }
+
+    pub(crate) fn tail_ratio_uncompressed(&self, hi: f64, lo: f64) -> F {
+        let (low, high) = self.quantile_range_uncompressed(lo, hi);
+        if low.to_f64() == 0. {
+            return F::NAN;
+        }
+        high / low
+    }
+
+    pub(crate) fn range_uncompressed(&self, lo_level: f64, hi_level: f64) -> F {
+        let (low, high) = self.quantile_range_uncompressed(lo_level, hi_level);
+        high - low
+    }
+
+    pub(crate) fn count_between_uncompressed(&self, lo: F, hi: F) -> f64 {
+        if self.is_empty() {
+            return f64::NAN;
+        }
+        // Elements equal to `lo` are included, so the elements below it are
+        // those lower than or equal to the previous float.
+        let below = self.cdf_uncompressed(lo.next_down()) * self.count as f64;
+        let upto = self.cdf_uncompressed(hi) * self.count as f64;
+        f64::max(upto - below, 0.)
+    }
+
+    pub(crate) fn tail_mass_above_uncompressed(&self, level: f64) -> (F, f64) {
+        if self.is_empty() {
+            return (F::NAN, 0.);
+        }
+        let threshold = self.quantile_uncompressed(level);
+        let below = self.cdf_uncompressed(threshold) * self.count as f64;
+        (threshold, self.count as f64 - below)
+    }
+
+    pub(crate) fn range_stats_uncompressed(&self, lo_level: f64, hi_level: f64) -> RangeStats<F> {
+        assert!(
+            (0. ..=1.).contains(&lo_level) && (0. ..=1.).contains(&hi_level),
+            "levels must be between 0 and 1"
+        );
+        assert!(lo_level <= hi_level, "levels must be in increasing order");
+        let lo = lo_level * self.count as f64;
+        let hi = hi_level * self.count as f64;
+        let mut weight = 0.;
+        let mut sum = 0.;
+        let mut start = 0.;
+        for c in self.centroids.iter() {
+            let end = start + c.count as f64;
+            let overlap = end.min(hi) - start.max(lo);
+            if overlap > 0. {
+                weight += overlap;
+                sum += overlap * c.mean.to_f64();
+            }
+            if end >= hi {
+                break;
+            }
+            start = end;
+        }
+
+        RangeStats {
+            mean: if weight > 0. { sum / weight } else { f64::NAN },
+            weight,
+            min_q: self.quantile_uncompressed(lo_level),
+            max_q: self.quantile_uncompressed(hi_level),
+        }
+    }
+}
+
+/// Statistics of the elements of a t-digest between two quantile levels.
+///
+/// This `struct` is created by the [`range_stats`] method on [`TDigest`]. See
+/// its documentation for more.
+///
+/// [`range_stats`]: TDigest::range_stats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RangeStats<F = f32> {
+    mean: f64,
+    weight: f64,
+    min_q: F,
+    max_q: F,
+}
+
+impl<F: Float> RangeStats<F> {
+    /// Returns the mean of the elements in the band, or NaN if it is empty.
+    #[inline]
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Returns the estimated number of elements in the band.
+    #[inline]
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// Returns the quantile at the lower level of the band.
+    #[inline]
+    pub fn min_q(&self) -> F {
+        self.min_q
+    }
+
+    /// Returns the quantile at the upper level of the band.
+    #[inline]
+    pub fn max_q(&self) -> F {
+        self.max_q
+    }
+}
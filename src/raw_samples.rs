@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+
+use crate::{
+    codec::{self, Reader},
+    Error, Float,
+};
+
+/// Raw values retained by a t-digest while it has few elements.
+///
+/// The values are all the elements of the t-digest as long as there are as
+/// many of them as elements. Once the capacity is exceeded they are discarded,
+/// and the t-digest only holds centroids until it is cleared.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RawSamples<F = f32> {
+    /// The values, in increasing order.
+    values: Vec<F>,
+}
+
+impl<F: Float> RawSamples<F> {
+    /// Returns the values if they are all the `count` elements of the
+    /// t-digest, which is not empty.
+    #[inline]
+    pub(crate) fn exact(&self, count: u64) -> Option<&[F]> {
+        (count > 0 && self.values.len() as u64 == count).then_some(&self.values)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    #[inline]
+    pub(crate) fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.values.shrink_to_fit();
+    }
+
+    /// Discards the values, so that they are no longer all the elements.
+    fn discard(&mut self) {
+        self.values = Vec::new();
+    }
+
+    /// Multiplies the values by a positive factor.
+    pub(crate) fn scale(&mut self, factor: f64) {
+        for value in self.values.iter_mut() {
+            *value = F::from_f64(value.to_f64() * factor);
+        }
+    }
+
+    /// Inserts `copies` copies of `value` into a t-digest of `count` elements.
+    pub(crate) fn insert(&mut self, value: F, copies: u64, count: u64, cap: usize) {
+        if self.values.len() as u64 != count {
+            return;
+        }
+        if count + copies > cap as u64 {
+            self.discard();
+            return;
+        }
+        let index = self
+            .values
+            .partition_point(|v| v.total_cmp(&value) != Ordering::Greater);
+        self.values
+            .splice(index..index, std::iter::repeat_n(value, copies as usize));
+    }
+
+    /// Merges the values of a t-digest of `other_count` elements into a
+    /// t-digest of `count` elements.
+    pub(crate) fn merge(
+        &mut self,
+        count: u64,
+        other: &RawSamples<F>,
+        other_count: u64,
+        cap: usize,
+    ) {
+        if other_count == 0 || self.values.len() as u64 != count {
+            return;
+        }
+        match other.exact(other_count) {
+            Some(values) if count + other_count <= cap as u64 => {
+                self.values.extend_from_slice(values);
+                self.values.sort_unstable_by(F::total_cmp);
+            }
+            _ => self.discard(),
+        }
+    }
+}
+
+impl<F: Float> RawSamples<F> {
+    /// Writes the values, as their number followed by the values.
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) {
+        codec::write_varuint(buf, self.values.len() as u64);
+        for value in self.values.iter() {
+            codec::write_float(buf, *value);
+        }
+    }
+
+    /// Reads values written by [`RawSamples::encode`], keeping at most `cap`
+    /// of them.
+    pub(crate) fn decode(reader: &mut Reader<'_>, cap: usize) -> Result<Self, Error> {
+        let len = reader.read_varuint()?;
+        if len > cap as u64 {
+            return Err(Error::InvalidState("too many raw values"));
+        }
+        let mut values: Vec<F> = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            values.push(reader.read_float()?);
+        }
+        if !values.is_sorted_by(|l, r| l.total_cmp(r) != Ordering::Greater) {
+            return Err(Error::InvalidState("raw values are not sorted"));
+        }
+        Ok(Self { values })
+    }
+}
+
+/// Returns the smallest of the sorted `values` such that at least a fraction
+/// `level` of them are lower than or equal to it.
+pub(crate) fn quantile<F: Float>(values: &[F], level: f64) -> F {
+    let rank = (level * values.len() as f64).ceil();
+    // NaN levels get the largest value, as with centroids.
+    let index = if rank.is_nan() {
+        values.len()
+    } else {
+        (rank as usize).clamp(1, values.len())
+    };
+    values[index - 1]
+}
+
+/// Returns the fraction of the sorted `values` that are lower than or equal
+/// to `value`.
+pub(crate) fn cdf<F: Float>(values: &[F], value: F) -> f64 {
+    if value.is_nan() {
+        return 1.;
+    }
+    values.partition_point(|&v| v <= value) as f64 / values.len() as f64
+}
+
+/// Returns the fraction of the sorted `values` that are greater than `value`.
+pub(crate) fn sf<F: Float>(values: &[F], value: F) -> f64 {
+    if value.is_nan() {
+        return 0.;
+    }
+    let above = values.len() - values.partition_point(|&v| v <= value);
+    above as f64 / values.len() as f64
+}
+
+#[cfg(feature = "serde")]
+impl<F: serde::Serialize> serde::Serialize for RawSamples<F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.values.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, F: serde::Deserialize<'de>> serde::Deserialize<'de> for RawSamples<F> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let values = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self { values })
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{codec::Reader, Error, TDigest};
+
+/// Module value opcodes of the Redis RDB format.
+const OPCODE_EOF: u64 = 0;
+const OPCODE_UINT: u64 = 2;
+const OPCODE_DOUBLE: u64 = 4;
+
+/// Writes a length with the RDB variable-length encoding.
+fn write_len(buf: &mut Vec<u8>, len: u64) {
+    if len < 1 << 6 {
+        buf.push(len as u8);
+    } else if len < 1 << 14 {
+        buf.extend_from_slice(&(len as u16 | 0x4000).to_be_bytes());
+    } else if len <= u64::from(u32::MAX) {
+        buf.push(0x80);
+        buf.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        buf.push(0x81);
+        buf.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+fn read_len(reader: &mut Reader<'_>) -> Result<u64, Error> {
+    let first = reader.read_u8()?;
+    match first >> 6 {
+        0 => Ok(u64::from(first)),
+        1 => Ok(u64::from(first & 0x3f) << 8 | u64::from(reader.read_u8()?)),
+        _ => match first {
+            0x80 => reader.read_u32_be().map(u64::from),
+            0x81 => reader.read_u64_be(),
+            _ => Err(Error::InvalidState("invalid length encoding")),
+        },
+    }
+}
+
+fn write_unsigned(buf: &mut Vec<u8>, value: u64) {
+    write_len(buf, OPCODE_UINT);
+    write_len(buf, value);
+}
+
+fn write_double(buf: &mut Vec<u8>, value: f64) {
+    write_len(buf, OPCODE_DOUBLE);
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn read_unsigned(reader: &mut Reader<'_>) -> Result<u64, Error> {
+    if read_len(reader)? != OPCODE_UINT {
+        return Err(Error::InvalidState("expected an integer"));
+    }
+    read_len(reader)
+}
+
+fn read_double(reader: &mut Reader<'_>) -> Result<f64, Error> {
+    if read_len(reader)? != OPCODE_DOUBLE {
+        return Err(Error::InvalidState("expected a double"));
+    }
+    reader.read_f64()
+}
+
+impl TDigest {
+    /// Serializes the t-digest as the value of a RedisBloom `TDIGEST` key, in
+    /// the Redis RDB format.
+    ///
+    /// The payload holds the fields that RedisBloom saves for a t-digest: the
+    /// compression (the inverse of `epsilon`), the capacity, the numbers of
+    /// merged and unmerged nodes, the merged and unmerged weights, the minimum
+    /// and the maximum, the number of compressions, and the mean and weight of
+    /// each node. It does not include the module type identifier, nor the RDB
+    /// version and checksum that frame a `DUMP` payload.
+    ///
+    /// The minimum and the maximum are the exact smallest and largest values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from([1.0, 2.0, 3.0]);
+    /// let bytes = digest.to_redis_rdb();
+    /// let mut decoded = TDigest::from_redis_rdb(&bytes).unwrap();
+    /// assert_eq!(decoded.len(), 3);
+    /// assert_eq!(decoded.quantile(0.5), 2.0);
+    /// ```
+    pub fn to_redis_rdb(&mut self) -> Vec<u8> {
+        self.compress();
+        let compression = (1. / self.config.epsilon) as f64;
+        let (min, max) = if self.is_empty() {
+            (f64::MAX, -f64::MAX)
+        } else {
+            (self.min as f64, self.max as f64)
+        };
+
+        let mut buf = Vec::with_capacity(64 + self.centroids.len() * 18);
+        write_double(&mut buf, compression);
+        write_unsigned(&mut buf, (6. * compression + 10.) as u64);
+        write_unsigned(&mut buf, self.centroids.len() as u64);
+        write_unsigned(&mut buf, 0);
+        write_double(&mut buf, self.count as f64);
+        write_double(&mut buf, 0.);
+        write_double(&mut buf, min);
+        write_double(&mut buf, max);
+        write_unsigned(&mut buf, 0);
+        for c in self.centroids.iter() {
+            write_double(&mut buf, c.mean as f64);
+            write_double(&mut buf, c.count as f64);
+        }
+        write_len(&mut buf, OPCODE_EOF);
+        buf
+    }
+
+    /// Deserializes a t-digest from the value of a RedisBloom `TDIGEST` key, in
+    /// the Redis RDB format, as written by [`TDigest::to_redis_rdb`].
+    ///
+    /// The `epsilon` of the t-digest is the inverse of the compression of the
+    /// RedisBloom t-digest, and the weights of the nodes are rounded to the
+    /// nearest integer. The minimum and the maximum are restored, widened to
+    /// the means of the nodes if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not a valid payload.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{Error, TDigest};
+    ///
+    /// assert_eq!(TDigest::from_redis_rdb(&[]), Err(Error::UnexpectedEof));
+    /// ```
+    pub fn from_redis_rdb(bytes: &[u8]) -> Result<TDigest, Error> {
+        let mut reader = Reader::new(bytes);
+        let compression = read_double(&mut reader)?;
+        if !(1. ..).contains(&compression) {
+            return Err(Error::InvalidState("compression must be at least 1"));
+        }
+        let _capacity = read_unsigned(&mut reader)?;
+        let merged_nodes = read_unsigned(&mut reader)?;
+        let unmerged_nodes = read_unsigned(&mut reader)?;
+        let _merged_weight = read_double(&mut reader)?;
+        let _unmerged_weight = read_double(&mut reader)?;
+        let min = read_double(&mut reader)?;
+        let max = read_double(&mut reader)?;
+        let _total_compressions = read_unsigned(&mut reader)?;
+
+        let mut builder = TDigest::builder();
+        builder.epsilon((1. / compression) as f32);
+        let mut digest = builder.build();
+        let nodes = merged_nodes
+            .checked_add(unmerged_nodes)
+            .ok_or(Error::InvalidState("too many nodes"))?;
+        for _ in 0..nodes {
+            let mean = read_double(&mut reader)?;
+            let weight = read_double(&mut reader)?;
+            if !(0. ..f64::INFINITY).contains(&weight) {
+                return Err(Error::InvalidState("invalid node weight"));
+            }
+            digest.insert_many(mean as f32, weight.round() as u64);
+        }
+        if read_len(&mut reader)? != OPCODE_EOF {
+            return Err(Error::InvalidState("expected the end of the value"));
+        }
+        reader.finish()?;
+        if !digest.is_empty() {
+            digest.min = digest.min.min(min as f32);
+            digest.max = digest.max.max(max as f32);
+        }
+        Ok(digest)
+    }
+}
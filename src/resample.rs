@@ -0,0 +1,482 @@
+//! Bootstrap resampling of t-digests, and order sensitivity checks.
+//!
+//! A bootstrap replicate of a t-digest is built by drawing as many elements as
+//! the t-digest holds, with replacement, from its centroids in proportion to
+//! their weights. Estimating a quantile on many replicates gives an empirical
+//! idea of how much the estimate depends on the particular sample that was
+//! observed.
+//!
+//! There is no jackknife counterpart: the delete-one jackknife is
+//! inconsistent for quantiles, which are not smooth functions of the sample.
+//!
+//! The estimates of a t-digest also depend on the order in which the elements
+//! were inserted. [`shuffle_check`] measures this dependency on a given stream
+//! of values, which helps choosing the configuration of the t-digests.
+//!
+//! With random draws from their centroids, t-digests of independent variables
+//! also combine into the distribution of their sum, maximum or minimum, with
+//! [`TDigest::convolve_sum`], [`TDigest::convolve_max`] and
+//! [`TDigest::convolve_min`]. [`TDigest::sample`] draws values from the
+//! distribution of a t-digest, such as to generate load-test traffic from
+//! recorded latencies.
+//!
+//! This module requires the `rand` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use tdigest_ch::{resample, TDigest};
+//!
+//! let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+//! let mut rng = rand::thread_rng();
+//!
+//! let spread = resample::quantile_spread(&mut digest, 0.5, 100, &mut rng);
+//! let (low, high) = spread.interval(0.95);
+//! assert!(low <= 500.0 && 500.0 <= high);
+//! ```
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{Float, TDigest};
+
+/// Returns a bootstrap replicate of `digest`.
+///
+/// The replicate has the same configuration and number of elements as
+/// `digest`. The number of elements drawn from each centroid follows a
+/// multinomial distribution, drawn as a binomial per centroid, so that
+/// drawing the elements takes time proportional to the number of centroids.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{resample, TDigest};
+///
+/// let mut digest = TDigest::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+/// let replicate = resample::bootstrap(&mut digest, &mut rand::thread_rng());
+/// assert_eq!(replicate.len(), 5);
+/// ```
+pub fn bootstrap<R: Rng + ?Sized>(digest: &mut TDigest, rng: &mut R) -> TDigest {
+    digest.compress();
+    let mut replicate = digest.clone();
+    replicate.clear();
+    // The remaining draws are spread over the remaining centroids.
+    let mut draws = digest.len();
+    let mut weight: u64 = digest.centroids.iter().map(|c| c.count).sum();
+    for c in digest.centroids.iter() {
+        if draws == 0 {
+            break;
+        }
+        let count = binomial(rng, draws, c.count as f64 / weight as f64);
+        replicate.insert_many(c.mean, count);
+        draws -= count;
+        weight -= c.count;
+    }
+    replicate
+}
+
+/// Draws from the binomial distribution of `n` trials with probability `p`.
+///
+/// Small means are drawn by counting geometric waiting times, and others
+/// with the BTRD algorithm of Hörmann, in constant expected time.
+fn binomial<R: Rng + ?Sized>(rng: &mut R, n: u64, p: f64) -> u64 {
+    if p >= 1. {
+        return n;
+    }
+    if p <= 0. {
+        return 0;
+    }
+    if p > 0.5 {
+        return n - binomial(rng, n, 1. - p);
+    }
+    let count = n as f64;
+    if count * p < 10. {
+        let log_q = (-p).ln_1p();
+        let mut trials = 0.;
+        let mut successes = 0;
+        loop {
+            trials += (rng.gen::<f64>().ln() / log_q).ceil();
+            if trials > count {
+                return successes;
+            }
+            successes += 1;
+        }
+    }
+
+    let stddev = (count * p * (1. - p)).sqrt();
+    let b = 1.15 + 2.53 * stddev;
+    let a = -0.0873 + 0.0248 * b + 0.01 * p;
+    let c = count * p + 0.5;
+    let v_r = 0.92 - 4.2 / b;
+    let r = p / (1. - p);
+    let alpha = (2.83 + 5.1 / b) * stddev;
+    let m = ((count + 1.) * p).floor();
+    loop {
+        let u = rng.gen::<f64>() - 0.5;
+        let v = rng.gen::<f64>();
+        let us = 0.5 - u.abs();
+        let k = ((2. * a / us + b) * u + c).floor();
+        if us >= 0.07 && v <= v_r {
+            return k as u64;
+        }
+        if k < 0. || k > count {
+            continue;
+        }
+        let v = (v * alpha / (a / (us * us) + b)).ln();
+        let bound = (m + 0.5) * ((m + 1.) / (r * (count - m + 1.))).ln()
+            + (count + 1.) * ((count - m + 1.) / (count - k + 1.)).ln()
+            + (k + 0.5) * (r * (count - k + 1.) / (k + 1.)).ln()
+            + stirling_tail(m)
+            + stirling_tail(count - m)
+            - stirling_tail(k)
+            - stirling_tail(count - k);
+        if v <= bound {
+            return k as u64;
+        }
+    }
+}
+
+/// Returns `ln(k!) - ln(sqrt(2π) (k + 1)^(k + 1/2) e^-(k + 1))`, the error of
+/// the Stirling approximation of `ln(k!)`.
+fn stirling_tail(k: f64) -> f64 {
+    const TAIL: [f64; 10] = [
+        0.0810614667953272,
+        0.0413406959554092,
+        0.0276779256849983,
+        0.02079067210376509,
+        0.0166446911898211,
+        0.0138761288230707,
+        0.0118967099458917,
+        0.0104112652619720,
+        0.00925546218271273,
+        0.00833056343336287,
+    ];
+    if k <= 9. {
+        return TAIL[k as usize];
+    }
+    let k_plus_one_squared = (k + 1.) * (k + 1.);
+    (1. / 12. - (1. / 360. - 1. / 1260. / k_plus_one_squared) / k_plus_one_squared) / (k + 1.)
+}
+
+/// Estimates the quantile at `level` on `replicates` bootstrap replicates of
+/// `digest`.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{resample, TDigest};
+///
+/// let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+/// let spread = resample::quantile_spread(&mut digest, 0.5, 50, &mut rand::thread_rng());
+/// assert_eq!(spread.estimates().len(), 50);
+/// assert!((spread.mean() - 500.0).abs() < 50.0);
+/// ```
+pub fn quantile_spread<R: Rng + ?Sized>(
+    digest: &mut TDigest,
+    level: f64,
+    replicates: usize,
+    rng: &mut R,
+) -> QuantileSpread {
+    let mut estimates: Vec<f32> = (0..replicates)
+        .map(|_| bootstrap(digest, rng).quantile(level))
+        .filter(|estimate| !estimate.is_nan())
+        .collect();
+    estimates.sort_unstable_by(f32::total_cmp);
+    QuantileSpread { estimates }
+}
+
+/// Returns the largest difference between the quantile estimates of
+/// t-digests built from `permutations` random permutations of `values`, across
+/// all `levels`.
+///
+/// The t-digests have the configuration of `template`. The result is 0 if
+/// `values` is empty, or if there are fewer than two permutations.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{resample, TDigest};
+///
+/// let values: Vec<f32> = (0..10_000).map(|v| v as f32).collect();
+///
+/// let mut builder = TDigest::builder();
+/// builder.epsilon(0.05);
+/// let template = builder.build();
+///
+/// let divergence =
+///     resample::shuffle_check(&template, &values, &[0.5, 0.99], 10, &mut rand::thread_rng());
+/// assert!(divergence < 100.0);
+/// ```
+pub fn shuffle_check<R: Rng + ?Sized>(
+    template: &TDigest,
+    values: &[f32],
+    levels: &[f64],
+    permutations: usize,
+    rng: &mut R,
+) -> f32 {
+    if values.is_empty() {
+        return 0.;
+    }
+    let mut digest = template.clone();
+    let mut shuffled = values.to_vec();
+    let mut low = vec![f32::INFINITY; levels.len()];
+    let mut high = vec![f32::NEG_INFINITY; levels.len()];
+    for _ in 0..permutations {
+        shuffled.shuffle(rng);
+        digest.clear();
+        digest.extend(shuffled.iter().copied());
+        for (i, &level) in levels.iter().enumerate() {
+            let estimate = digest.quantile(level);
+            low[i] = low[i].min(estimate);
+            high[i] = high[i].max(estimate);
+        }
+    }
+    low.iter()
+        .zip(high)
+        .map(|(&low, high)| high - low)
+        .fold(0., f32::max)
+}
+
+impl<F: Float> TDigest<F> {
+    /// Draws a random value from the distribution of the t-digest, or returns
+    /// NaN if it is empty.
+    ///
+    /// The value is the quantile at a uniformly random level, which inverts
+    /// the cumulative distribution function of the t-digest.
+    ///
+    /// This method requires the `rand` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// let value = digest.sample(&mut rand::thread_rng());
+    /// assert!((0.0..=999.0).contains(&value));
+    /// ```
+    pub fn sample<R: Rng + ?Sized>(&mut self, rng: &mut R) -> F {
+        self.compress();
+        self.sample_uncompressed(rng)
+    }
+
+    /// Returns an endless iterator of random values drawn from the
+    /// distribution of the t-digest, as with [`TDigest::sample`]. The
+    /// iterator is empty if the t-digest is.
+    ///
+    /// The t-digest is compressed once, when the iterator is created.
+    ///
+    /// This method requires the `rand` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut latencies = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// let mut rng = rand::thread_rng();
+    ///
+    /// let replayed: Vec<f32> = latencies.sample_iter(&mut rng).take(100).collect();
+    /// assert_eq!(replayed.len(), 100);
+    /// assert!(TDigest::new().sample_iter(&mut rng).next().is_none());
+    /// ```
+    pub fn sample_iter<'a, R: Rng + ?Sized>(
+        &'a mut self,
+        rng: &'a mut R,
+    ) -> impl Iterator<Item = F> + 'a {
+        self.compress();
+        let digest = &*self;
+        std::iter::from_fn(move || (!digest.is_empty()).then(|| digest.sample_uncompressed(rng)))
+    }
+
+    pub(crate) fn sample_uncompressed<R: Rng + ?Sized>(&self, rng: &mut R) -> F {
+        self.quantile_uncompressed(rng.gen())
+    }
+
+    /// Approximates the distribution of the sum of an element of `self` and
+    /// an independent element of `other`, such as the latency of two
+    /// sequential calls.
+    ///
+    /// The distribution is estimated by Monte Carlo: each of the `samples`
+    /// elements of the result adds a random draw from each t-digest, drawn by
+    /// interpolating their quantiles at random levels. The result has the
+    /// configuration, labels and unit of `self`, and is empty if either
+    /// t-digest is.
+    ///
+    /// This method requires the `rand` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut auth = TDigest::from_iter((0..1000).map(|v| (v % 10) as f32));
+    /// let mut query = TDigest::from_iter((0..1000).map(|v| (100 + v % 20) as f32));
+    ///
+    /// let mut total = auth.convolve_sum(&mut query, 10_000, &mut rand::thread_rng());
+    /// assert_eq!(total.len(), 10_000);
+    /// assert!((total.quantile(0.5) - 114.0).abs() < 2.0);
+    /// ```
+    pub fn convolve_sum<R: Rng + ?Sized>(
+        &mut self,
+        other: &mut TDigest<F>,
+        samples: usize,
+        rng: &mut R,
+    ) -> TDigest<F> {
+        self.convolve(other, samples, rng, |x, y| x + y)
+    }
+
+    /// Approximates the distribution of the largest of an element of `self`
+    /// and an independent element of `other`, such as the latency of two
+    /// parallel calls that are both awaited.
+    ///
+    /// The distribution is estimated by Monte Carlo, as with
+    /// [`TDigest::convolve_sum`].
+    ///
+    /// This method requires the `rand` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut shard = TDigest::from_iter((0..1000).map(|v| v as f32));
+    ///
+    /// // Waiting for the slowest of two shards moves the median to the p70.
+    /// let mut slowest = shard.convolve_max(&mut shard.clone(), 10_000, &mut rand::thread_rng());
+    /// assert!((slowest.quantile(0.5) - shard.quantile(0.7071)).abs() < 20.0);
+    /// ```
+    pub fn convolve_max<R: Rng + ?Sized>(
+        &mut self,
+        other: &mut TDigest<F>,
+        samples: usize,
+        rng: &mut R,
+    ) -> TDigest<F> {
+        self.convolve(other, samples, rng, F::max)
+    }
+
+    /// Approximates the distribution of the smallest of an element of `self`
+    /// and an independent element of `other`, such as the latency of two
+    /// hedged calls where the first response wins.
+    ///
+    /// The distribution is estimated by Monte Carlo, as with
+    /// [`TDigest::convolve_sum`].
+    ///
+    /// This method requires the `rand` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut replica = TDigest::from_iter((0..1000).map(|v| v as f32));
+    ///
+    /// let mut fastest = replica.convolve_min(&mut replica.clone(), 10_000, &mut rand::thread_rng());
+    /// assert!((fastest.quantile(0.5) - replica.quantile(0.2929)).abs() < 20.0);
+    /// ```
+    pub fn convolve_min<R: Rng + ?Sized>(
+        &mut self,
+        other: &mut TDigest<F>,
+        samples: usize,
+        rng: &mut R,
+    ) -> TDigest<F> {
+        self.convolve(other, samples, rng, F::min)
+    }
+
+    fn convolve<R: Rng + ?Sized>(
+        &mut self,
+        other: &mut TDigest<F>,
+        samples: usize,
+        rng: &mut R,
+        combine: fn(F, F) -> F,
+    ) -> TDigest<F> {
+        self.compress();
+        other.compress();
+        let mut result = self.clone();
+        result.clear();
+        if self.is_empty() || other.is_empty() {
+            return result;
+        }
+        for _ in 0..samples {
+            let x = self.sample_uncompressed(rng);
+            let y = other.sample_uncompressed(rng);
+            result.insert(combine(x, y));
+        }
+        result
+    }
+}
+
+/// Estimates of a quantile across bootstrap replicates.
+///
+/// It is returned by [`quantile_spread`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantileSpread {
+    estimates: Vec<f32>,
+}
+
+impl QuantileSpread {
+    /// Returns the estimates of the quantile, in increasing order.
+    pub fn estimates(&self) -> &[f32] {
+        &self.estimates
+    }
+
+    /// Returns the mean of the estimates, or NaN if there are none.
+    pub fn mean(&self) -> f32 {
+        let sum: f64 = self.estimates.iter().map(|&e| e as f64).sum();
+        (sum / self.estimates.len() as f64) as f32
+    }
+
+    /// Returns the sample standard deviation of the estimates, or NaN if there
+    /// are fewer than two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{resample, TDigest};
+    ///
+    /// let mut digest = TDigest::from([1.0; 100]);
+    /// let spread = resample::quantile_spread(&mut digest, 0.5, 10, &mut rand::thread_rng());
+    /// assert_eq!(spread.std_dev(), 0.0);
+    /// ```
+    pub fn std_dev(&self) -> f32 {
+        let n = self.estimates.len() as f64;
+        let mean = self.mean() as f64;
+        let sum_squares: f64 = self
+            .estimates
+            .iter()
+            .map(|&e| (e as f64 - mean) * (e as f64 - mean))
+            .sum();
+        (sum_squares / (n - 1.)).sqrt() as f32
+    }
+
+    /// Returns the percentile interval that contains the given fraction of the
+    /// estimates, or NaNs if there are none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `confidence` is not between 0 and 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{resample, TDigest};
+    ///
+    /// let mut digest = TDigest::from_iter((0..1000).map(|v| v as f32));
+    /// let spread = resample::quantile_spread(&mut digest, 0.9, 100, &mut rand::thread_rng());
+    /// let (low, high) = spread.interval(0.9);
+    /// assert!(low <= high);
+    /// ```
+    pub fn interval(&self, confidence: f64) -> (f32, f32) {
+        assert!(
+            (0. ..=1.).contains(&confidence),
+            "confidence must be between 0 and 1"
+        );
+        if self.estimates.is_empty() {
+            return (f32::NAN, f32::NAN);
+        }
+        let last = (self.estimates.len() - 1) as f64;
+        let tail = (1. - confidence) / 2.;
+        let low = (tail * last).floor() as usize;
+        let high = ((1. - tail) * last).ceil() as usize;
+        (self.estimates[low], self.estimates[high])
+    }
+}
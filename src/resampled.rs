@@ -0,0 +1,62 @@
+use std::f64::consts::PI;
+
+use crate::{Centroid, Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns a t-digest of at most `max_centroids` centroids that
+    /// approximates the same distribution, leaving `self` unchanged.
+    ///
+    /// The levels are split into `max_centroids` bands, narrower in the tails
+    /// than around the median as with an arcsine scale function, and each
+    /// band becomes a centroid with the mean of the elements in the band. The
+    /// result keeps the configuration, the number of elements, the exact mean
+    /// and extremes of `self`, so that it can be sent in place of `self` to
+    /// consumers with little bandwidth.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_centroids` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..100_000).map(|v| v as f32));
+    /// let mut small = digest.resampled(32);
+    /// assert_eq!(small.len(), 100_000);
+    /// assert!(small.centroid_weight_profile().len() <= 32);
+    /// assert!((small.quantile(0.99) - digest.quantile(0.99)).abs() < 500.0);
+    /// ```
+    pub fn resampled(&mut self, max_centroids: usize) -> TDigest<F> {
+        self.compress();
+        self.resampled_uncompressed(max_centroids)
+    }
+
+    pub(crate) fn resampled_uncompressed(&self, max_centroids: usize) -> TDigest<F> {
+        assert!(max_centroids > 0, "number of centroids must be positive");
+        let mut result = self.clone();
+        if self.centroids.len() <= max_centroids {
+            return result;
+        }
+
+        result.centroids.clear();
+        result.raw.clear();
+        let count = self.count as f64;
+        let mut lo_rank = 0.;
+        for i in 1..=max_centroids {
+            // Arcsine scale: the bands are evenly spaced in `asin(2 q - 1)`.
+            let q = (1. + f64::sin(PI * (i as f64 / max_centroids as f64 - 0.5))) / 2.;
+            let hi_rank = (q * count).round();
+            if hi_rank > lo_rank {
+                let band = self.range_stats_uncompressed(lo_rank / count, hi_rank / count);
+                result.centroids.push(Centroid {
+                    mean: F::from_f64(band.mean()),
+                    count: (hi_rank - lo_rank) as u64,
+                });
+            }
+            lo_rank = hi_rank;
+        }
+        result
+    }
+}
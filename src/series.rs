@@ -0,0 +1,178 @@
+use std::io::{self, Write};
+
+use crate::TDigest;
+
+/// Quantiles of consecutive intervals, such as the p50 and p99 of each
+/// minute.
+///
+/// A `QuantileSeries` keeps the quantiles at fixed levels of t-digests that
+/// are rotated at each interval, so that the t-digests can be discarded once
+/// evaluated. Each interval takes a timestamp, in a unit chosen by the caller,
+/// and one 32-bit quantile per level. The series can be exported as CSV with
+/// [`QuantileSeries::write_csv`].
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{QuantileSeries, TDigest};
+///
+/// let mut series = QuantileSeries::new(vec![0.5, 0.99]);
+/// for minute in 0..3 {
+///     let mut digest = TDigest::from_iter((0..1000).map(|v| (v * (minute + 1)) as f32));
+///     series.push(60 * minute as u64, &mut digest);
+/// }
+///
+/// assert_eq!(series.len(), 3);
+/// assert_eq!(series.timestamps(), [0, 60, 120]);
+/// assert!(series.column(1).all(|p99| p99 > 900.0));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantileSeries {
+    levels: Vec<f64>,
+    timestamps: Vec<u64>,
+    /// The quantiles of each interval, one row of `levels.len()` values
+    /// after the other.
+    quantiles: Vec<f32>,
+}
+
+impl QuantileSeries {
+    /// Creates an empty series of the quantiles at `levels`.
+    #[must_use]
+    pub fn new(levels: Vec<f64>) -> Self {
+        Self {
+            levels,
+            timestamps: Vec::new(),
+            quantiles: Vec::new(),
+        }
+    }
+
+    /// Returns the levels of the quantiles.
+    #[inline]
+    pub fn levels(&self) -> &[f64] {
+        &self.levels
+    }
+
+    /// Returns the number of intervals.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    /// Returns `true` if there are no intervals.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    /// Returns the timestamps of the intervals.
+    #[inline]
+    pub fn timestamps(&self) -> &[u64] {
+        &self.timestamps
+    }
+
+    /// Clears the series, removing all intervals.
+    pub fn clear(&mut self) {
+        self.timestamps.clear();
+        self.quantiles.clear();
+    }
+
+    /// Adds an interval, with the quantiles of `digest`.
+    pub fn push(&mut self, timestamp: u64, digest: &mut TDigest) {
+        self.timestamps.push(timestamp);
+        self.quantiles.extend(digest.quantiles_many(&self.levels));
+    }
+
+    /// Adds an interval, with quantiles already estimated at each level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is not one quantile per level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::QuantileSeries;
+    ///
+    /// let mut series = QuantileSeries::new(vec![0.5, 0.99]);
+    /// series.push_quantiles(0, &[12.0, 80.0]);
+    /// assert_eq!(series.get(0), Some((0, &[12.0, 80.0][..])));
+    /// ```
+    pub fn push_quantiles(&mut self, timestamp: u64, quantiles: &[f32]) {
+        assert_eq!(
+            quantiles.len(),
+            self.levels.len(),
+            "there must be one quantile per level"
+        );
+        self.timestamps.push(timestamp);
+        self.quantiles.extend_from_slice(quantiles);
+    }
+
+    /// Returns the timestamp and the quantiles of an interval.
+    pub fn get(&self, index: usize) -> Option<(u64, &[f32])> {
+        let timestamp = *self.timestamps.get(index)?;
+        let width = self.levels.len();
+        Some((
+            timestamp,
+            &self.quantiles[index * width..(index + 1) * width],
+        ))
+    }
+
+    /// Returns the timestamps and the quantiles of the intervals, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[f32])> {
+        (0..self.len()).map(|index| self.get(index).unwrap())
+    }
+
+    /// Returns the quantiles at the level of index `level_index`, one for
+    /// each interval.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level_index` is out of bounds.
+    pub fn column(&self, level_index: usize) -> impl Iterator<Item = f32> + '_ {
+        assert!(level_index < self.levels.len(), "level index out of bounds");
+        self.quantiles
+            .iter()
+            .skip(level_index)
+            .step_by(self.levels.len())
+            .copied()
+    }
+
+    /// Writes the series as CSV, with a header row, then one row per interval
+    /// holding its timestamp and its quantiles.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to `writer` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::QuantileSeries;
+    ///
+    /// let mut series = QuantileSeries::new(vec![0.5, 0.99]);
+    /// series.push_quantiles(0, &[12.0, 80.5]);
+    /// series.push_quantiles(60, &[15.0, f32::NAN]);
+    ///
+    /// let mut csv = Vec::new();
+    /// series.write_csv(&mut csv).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(csv).unwrap(),
+    ///     "timestamp,0.5,0.99\n0,12,80.5\n60,15,NaN\n",
+    /// );
+    /// ```
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "timestamp")?;
+        for level in self.levels.iter() {
+            write!(writer, ",{level}")?;
+        }
+        writeln!(writer)?;
+        for (timestamp, quantiles) in self.iter() {
+            write!(writer, "{timestamp}")?;
+            for quantile in quantiles {
+                write!(writer, ",{quantile}")?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+/// A data structure that estimates the quantiles of a stream of values.
+///
+/// This trait is implemented by all the sketches of this crate, so that code
+/// can be written once and run against any of them.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{BFloat16Histogram, QuantileSketch, TDigest};
+///
+/// fn median<S: QuantileSketch + Default>(values: &[f32]) -> f32 {
+///     let mut sketch = S::default();
+///     for &value in values {
+///         sketch.insert(value);
+///     }
+///     sketch.quantile(0.5)
+/// }
+///
+/// assert_eq!(median::<TDigest>(&[1.0, 2.0, 3.0]), 2.0);
+/// assert_eq!(median::<BFloat16Histogram>(&[1.0, 2.0, 3.0]), 2.0);
+/// ```
+pub trait QuantileSketch {
+    /// Adds a value to the sketch.
+    fn insert(&mut self, value: f32);
+
+    /// Merges `other` into `self`.
+    fn merge(&mut self, other: &Self);
+
+    /// Returns the number of values in the sketch.
+    fn len(&self) -> u64;
+
+    /// Returns `true` if the sketch contains no values.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the estimated quantile of the sketch.
+    fn quantile(&mut self, level: f64) -> f32;
+}
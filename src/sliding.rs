@@ -0,0 +1,378 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{decayed::instant_of, TDigest};
+
+/// T-digest of approximately the last inserted values, such as the latencies
+/// of the last million requests of a long-running service.
+///
+/// The values are inserted into a chain of t-digests, each holding a fixed
+/// share of the window. When the newest t-digest is full, a new one is
+/// started, and the oldest one is dropped once the others cover the window.
+/// The t-digest thus holds the last `window` values, and up to one share
+/// more: the oldest values expire a share at a time. More shares make the
+/// window more precise, at the cost of memory and of merging more t-digests
+/// for each query.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::SlidingTDigest;
+///
+/// let mut digest = SlidingTDigest::new(1000, 10);
+/// digest.extend((0..10_000).map(|v| v as f32));
+///
+/// assert_eq!(digest.len(), 1000);
+/// assert_eq!(digest.quantile(0.0), 9000.0);
+/// assert_eq!(digest.quantile(1.0), 9999.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlidingTDigest {
+    template: TDigest,
+    window: u64,
+    share: u64,
+    /// The t-digests of the shares, from the oldest to the newest.
+    digests: VecDeque<TDigest>,
+    len: u64,
+}
+
+impl SlidingTDigest {
+    /// Creates an empty `SlidingTDigest` of the last `window` values, split
+    /// into `shares` t-digests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` or `shares` is 0.
+    #[must_use]
+    pub fn new(window: u64, shares: usize) -> Self {
+        Self::with_template(window, shares, TDigest::new())
+    }
+
+    /// Creates an empty `SlidingTDigest` whose t-digests use the configuration
+    /// of `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` or `shares` is 0, or if `template` is not empty.
+    #[must_use]
+    pub fn with_template(window: u64, shares: usize, template: TDigest) -> Self {
+        assert!(window > 0, "window must not be empty");
+        assert!(shares > 0, "shares must not be 0");
+        assert!(template.is_empty(), "template must be empty");
+        Self {
+            template,
+            window,
+            share: window.div_ceil(shares as u64),
+            digests: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the number of values of the window.
+    #[inline]
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Returns the number of values in the t-digest, between the window and
+    /// the window plus a share once the window is full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::SlidingTDigest;
+    ///
+    /// let mut digest = SlidingTDigest::new(100, 4);
+    /// digest.extend((0..110).map(|v| v as f32));
+    /// assert_eq!(digest.len(), 110);
+    /// digest.extend((0..15).map(|v| v as f32));
+    /// assert_eq!(digest.len(), 100);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the t-digest contains no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Clears the t-digest, removing all values.
+    pub fn clear(&mut self) {
+        self.digests.clear();
+        self.len = 0;
+    }
+
+    /// Adds a value to the t-digest, expiring the oldest share of values once
+    /// the others cover the window.
+    ///
+    /// NaN values are ignored, and do not count towards the window.
+    pub fn insert(&mut self, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+        match self.digests.back_mut() {
+            Some(newest) if newest.len() < self.share => newest.insert(value),
+            _ => {
+                let mut newest = self.template.clone();
+                newest.insert(value);
+                self.digests.push_back(newest);
+            }
+        }
+        self.len += 1;
+        if let Some(oldest) = self.digests.front() {
+            if self.len - oldest.len() >= self.window {
+                self.len -= oldest.len();
+                self.digests.pop_front();
+            }
+        }
+    }
+
+    /// Returns a t-digest of the values of the window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::SlidingTDigest;
+    ///
+    /// let mut digest = SlidingTDigest::new(10, 2);
+    /// digest.extend((0..100).map(|v| v as f32));
+    /// assert_eq!(digest.to_digest().len(), 10);
+    /// ```
+    pub fn to_digest(&self) -> TDigest {
+        let mut digest = self.template.clone();
+        for share in self.digests.iter() {
+            digest |= share;
+        }
+        digest
+    }
+
+    /// Returns the estimated quantile of the values of the window.
+    ///
+    /// This merges the t-digests of all the shares: to get several quantiles,
+    /// call [`TDigest::quantiles_many`] on [`SlidingTDigest::to_digest`].
+    pub fn quantile(&self, level: f64) -> f32 {
+        self.to_digest().quantile(level)
+    }
+}
+
+impl Extend<f32> for SlidingTDigest {
+    fn extend<I: IntoIterator<Item = f32>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+/// T-digest of the values observed over approximately the last period of
+/// time, such as the latencies of the last five minutes.
+///
+/// This is the time-based counterpart of [`SlidingTDigest`]: the values are
+/// recorded with the time at which they were observed, into a chain of
+/// t-digests each covering a fixed share of the window. A share expires once
+/// all of its values are older than the window, so that the t-digest holds
+/// the values of the last `window`, and up to one share more. Values may be
+/// recorded out of order, into the share of their time.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use tdigest_ch::TimeWindowTDigest;
+///
+/// let mut digest = TimeWindowTDigest::new(Duration::from_secs(60), 6);
+/// let start = Instant::now();
+/// for second in 0..600 {
+///     digest.record(second as f32, start + Duration::from_secs(second));
+/// }
+///
+/// assert_eq!(digest.len(), 70);
+/// assert_eq!(digest.quantile(0.0), 530.0);
+/// assert_eq!(digest.quantile(1.0), 599.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeWindowTDigest {
+    template: TDigest,
+    window: Duration,
+    share: Duration,
+    /// The start times and t-digests of the shares, from the oldest to the
+    /// newest.
+    digests: VecDeque<(Instant, TDigest)>,
+    len: u64,
+    /// The latest time of a value, or of an expiry.
+    now: Option<Instant>,
+}
+
+impl TimeWindowTDigest {
+    /// Creates an empty `TimeWindowTDigest` of the values of the last
+    /// `window`, split into `shares` t-digests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero, or if `shares` is 0.
+    #[must_use]
+    pub fn new(window: Duration, shares: u32) -> Self {
+        Self::with_template(window, shares, TDigest::new())
+    }
+
+    /// Creates an empty `TimeWindowTDigest` whose t-digests use the
+    /// configuration of `template`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is zero, if `shares` is 0, or if `template` is not
+    /// empty.
+    #[must_use]
+    pub fn with_template(window: Duration, shares: u32, template: TDigest) -> Self {
+        assert!(!window.is_zero(), "window must not be empty");
+        assert!(shares > 0, "shares must not be 0");
+        assert!(template.is_empty(), "template must be empty");
+        Self {
+            template,
+            window,
+            share: (window / shares).max(Duration::from_nanos(1)),
+            digests: VecDeque::new(),
+            len: 0,
+            now: None,
+        }
+    }
+
+    /// Returns the duration of the window.
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns the number of values in the t-digest, those of the window and
+    /// of up to one share more.
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the t-digest contains no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Clears the t-digest, removing all values.
+    pub fn clear(&mut self) {
+        self.digests.clear();
+        self.len = 0;
+        self.now = None;
+    }
+
+    /// Adds a value observed at `at`, expiring the shares of values older than
+    /// the window.
+    ///
+    /// NaN values are ignored, as are values already older than the window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use tdigest_ch::TimeWindowTDigest;
+    ///
+    /// let mut digest = TimeWindowTDigest::new(Duration::from_secs(10), 2);
+    /// let start = Instant::now();
+    /// digest.record(1.0, start + Duration::from_secs(20));
+    /// // Late, but still within the window.
+    /// digest.record(2.0, start + Duration::from_secs(15));
+    /// // Too late.
+    /// digest.record(3.0, start);
+    /// assert_eq!(digest.len(), 2);
+    /// ```
+    pub fn record(&mut self, value: f32, at: Instant) {
+        if value.is_nan() {
+            return;
+        }
+        let now = self.expire(at);
+        if now.saturating_duration_since(at) >= self.window {
+            return;
+        }
+        // The value goes into the latest share started before it, or into a
+        // new share if it is past the end of that one.
+        let index = self.digests.iter().rposition(|&(start, _)| start <= at);
+        match index {
+            Some(index) if at < self.digests[index].0 + self.share => {
+                self.digests[index].1.insert(value);
+            }
+            _ => {
+                let mut digest = self.template.clone();
+                digest.insert(value);
+                let index = index.map_or(0, |index| index + 1);
+                self.digests.insert(index, (at, digest));
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Adds a value observed at a time of the system clock, such as the
+    /// timestamp of a log line. See [`TimeWindowTDigest::record`].
+    ///
+    /// The time is converted into an [`Instant`] as with
+    /// [`DecayedTDigest::record_system_time`].
+    ///
+    /// [`DecayedTDigest::record_system_time`]: crate::DecayedTDigest::record_system_time
+    pub fn record_system_time(&mut self, value: f32, at: SystemTime) {
+        if let Some(at) = instant_of(at) {
+            self.record(value, at);
+        }
+    }
+
+    /// Expires the shares of values older than the window as of `now`, such
+    /// as before a query after a period without values, and returns the
+    /// latest time seen by the t-digest.
+    ///
+    /// The time only moves forward: an earlier `now` has no effect.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, Instant};
+    /// use tdigest_ch::TimeWindowTDigest;
+    ///
+    /// let mut digest = TimeWindowTDigest::new(Duration::from_secs(60), 4);
+    /// let start = Instant::now();
+    /// digest.record(1.0, start);
+    /// digest.expire(start + Duration::from_secs(60));
+    /// assert_eq!(digest.len(), 1);
+    /// digest.expire(start + Duration::from_secs(75));
+    /// assert!(digest.is_empty());
+    /// ```
+    pub fn expire(&mut self, now: Instant) -> Instant {
+        let now = self.now.map_or(now, |latest| latest.max(now));
+        self.now = Some(now);
+        while let Some((start, oldest)) = self.digests.front() {
+            if now.saturating_duration_since(*start) < self.share + self.window {
+                break;
+            }
+            self.len -= oldest.len();
+            self.digests.pop_front();
+        }
+        now
+    }
+
+    /// Returns a t-digest of the values of the window.
+    pub fn to_digest(&self) -> TDigest {
+        let mut digest = self.template.clone();
+        for (_, share) in self.digests.iter() {
+            digest |= share;
+        }
+        digest
+    }
+
+    /// Returns the estimated quantile of the values of the window.
+    ///
+    /// This merges the t-digests of all the shares, as with
+    /// [`SlidingTDigest::quantile`], without expiring any: call
+    /// [`TimeWindowTDigest::expire`] first if no value was recorded lately.
+    pub fn quantile(&self, level: f64) -> f32 {
+        self.to_digest().quantile(level)
+    }
+}
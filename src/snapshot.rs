@@ -0,0 +1,200 @@
+//! A lossless binary encoding of t-digests, used to checkpoint them to disk.
+//!
+//! Unlike the state format of ClickHouse, a snapshot keeps the whole
+//! t-digest: its configuration, the exact count, sum and bounds of its
+//! values, its extremes and raw values, its labels and its unit. It starts
+//! with a magic number and a version, so that later versions can still read
+//! older snapshots, followed by the width in bytes of the values, so that a
+//! snapshot of a `TDigest<f64>` is not read back as a `TDigest<f32>`.
+
+use crate::{
+    codec::{self, Reader},
+    extremes::Extremes,
+    labels::Labels,
+    raw_samples::RawSamples,
+    Centroid, Config, Error, Float, Interpolation, LabelPolicy, TDigest, Unit,
+};
+
+const MAGIC: &[u8; 4] = b"TDCH";
+
+const VERSION: u8 = 1;
+
+impl<F: Float> TDigest<F> {
+    /// Serializes the t-digest as a snapshot, once compressed.
+    pub(crate) fn encode_snapshot(&mut self) -> Vec<u8> {
+        self.compress();
+        let width = std::mem::size_of::<F>();
+        let mut buf = Vec::with_capacity(64 + self.centroids.len() * (width + 4));
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.push(width as u8);
+
+        let config = &self.config;
+        buf.extend_from_slice(&config.epsilon.to_le_bytes());
+        codec::write_varuint(&mut buf, config.max_centroids as u64);
+        codec::write_varuint(&mut buf, config.max_unmerged as u64);
+        buf.push(config.coalesce as u8);
+        codec::write_varuint(&mut buf, config.extremes as u64);
+        codec::write_varuint(&mut buf, config.raw_samples as u64);
+        buf.push(match config.interpolation {
+            Interpolation::ClickHouse => 0,
+            Interpolation::Dunning => 1,
+            Interpolation::Midpoint => 2,
+        });
+        buf.push(match config.label_policy {
+            LabelPolicy::MustMatch => 0,
+            LabelPolicy::Union => 1,
+        });
+
+        codec::write_varuint(&mut buf, self.count);
+        buf.extend_from_slice(&self.sum.to_le_bytes());
+        codec::write_float(&mut buf, self.min);
+        codec::write_float(&mut buf, self.max);
+        codec::write_varuint(&mut buf, self.nans);
+
+        codec::write_varuint(&mut buf, self.centroids.len() as u64);
+        for c in self.centroids.iter() {
+            codec::write_float(&mut buf, c.mean);
+            codec::write_varuint(&mut buf, c.count);
+        }
+        self.extremes.encode(&mut buf);
+        self.raw.encode(&mut buf);
+
+        codec::write_varuint(&mut buf, self.labels.len() as u64);
+        for (key, value) in self.labels.iter() {
+            codec::write_string(&mut buf, key);
+            codec::write_string(&mut buf, value);
+        }
+        match &self.unit {
+            None => buf.push(0),
+            Some(unit) => {
+                buf.push(1);
+                codec::write_string(&mut buf, unit.dimension());
+                buf.extend_from_slice(&unit.scale().to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Deserializes a t-digest from a snapshot written by
+    /// [`TDigest::encode_snapshot`].
+    pub(crate) fn decode_snapshot(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        if reader.read_bytes(MAGIC.len() as u64)? != MAGIC {
+            return Err(Error::InvalidState("not a t-digest snapshot"));
+        }
+        if reader.read_u8()? != VERSION {
+            return Err(Error::InvalidState("unsupported snapshot version"));
+        }
+        if reader.read_u8()? as usize != std::mem::size_of::<F>() {
+            return Err(Error::InvalidState("snapshot of another float type"));
+        }
+
+        let epsilon = reader.read_f32()?;
+        if !(epsilon > 0. && epsilon <= 1.) {
+            return Err(Error::InvalidState("epsilon is not between 0 and 1"));
+        }
+        let config = Config {
+            epsilon,
+            max_centroids: read_usize(&mut reader)?,
+            max_unmerged: read_usize(&mut reader)?,
+            coalesce: read_bool(&mut reader)?,
+            extremes: read_usize(&mut reader)?,
+            raw_samples: read_usize(&mut reader)?,
+            interpolation: match reader.read_u8()? {
+                0 => Interpolation::ClickHouse,
+                1 => Interpolation::Dunning,
+                2 => Interpolation::Midpoint,
+                _ => return Err(Error::InvalidState("unknown interpolation")),
+            },
+            label_policy: match reader.read_u8()? {
+                0 => LabelPolicy::MustMatch,
+                1 => LabelPolicy::Union,
+                _ => return Err(Error::InvalidState("unknown label policy")),
+            },
+        };
+
+        let count = reader.read_varuint()?;
+        let sum = reader.read_f64()?;
+        let min = reader.read_float()?;
+        let max = reader.read_float()?;
+        let nans = reader.read_varuint()?;
+
+        let len = read_usize(&mut reader)?;
+        if len > config.max_centroids {
+            return Err(Error::InvalidState("too many centroids"));
+        }
+        let mut centroids = Vec::with_capacity(len);
+        let mut total = 0u64;
+        for _ in 0..len {
+            let mean: F = reader.read_float()?;
+            let weight = reader.read_varuint()?;
+            if mean.is_nan() {
+                return Err(Error::InvalidState("centroid mean is NaN"));
+            }
+            if weight == 0 {
+                return Err(Error::InvalidState("centroid weight is zero"));
+            }
+            total = total
+                .checked_add(weight)
+                .ok_or(Error::InvalidState("centroid weights overflow"))?;
+            centroids.push(Centroid {
+                mean,
+                count: weight,
+            });
+        }
+        if total != count {
+            return Err(Error::InvalidState(
+                "centroid weights do not sum to the count",
+            ));
+        }
+        let extremes = Extremes::decode(&mut reader, config.extremes)?;
+        let raw = RawSamples::decode(&mut reader, config.raw_samples)?;
+
+        let mut labels = Labels::new();
+        for _ in 0..reader.read_varuint()? {
+            let key = reader.read_string()?;
+            let value = reader.read_string()?;
+            labels.insert(key, value);
+        }
+        let unit = match reader.read_u8()? {
+            0 => None,
+            1 => {
+                let dimension = reader.read_string()?;
+                Some(Unit::from_parts(&dimension, reader.read_f64()?))
+            }
+            _ => return Err(Error::InvalidState("invalid flag")),
+        };
+        reader.finish()?;
+
+        Ok(TDigest {
+            config,
+            centroids,
+            count,
+            sum,
+            min,
+            max,
+            unmerged: 0,
+            extremes,
+            raw,
+            labels,
+            unit,
+            nans,
+            compressions: 0,
+            brute_compressions: 0,
+            sampled: 0.,
+        })
+    }
+}
+
+fn read_usize(reader: &mut Reader<'_>) -> Result<usize, Error> {
+    usize::try_from(reader.read_varuint()?).map_err(|_| Error::InvalidState("size is too large"))
+}
+
+fn read_bool(reader: &mut Reader<'_>) -> Result<bool, Error> {
+    match reader.read_u8()? {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(Error::InvalidState("invalid flag")),
+    }
+}
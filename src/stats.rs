@@ -0,0 +1,138 @@
+use std::borrow::Cow;
+
+use crate::{Centroid, Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns health statistics of the t-digest, for monitoring.
+    ///
+    /// The statistics help alert on t-digests whose accuracy degrades, such
+    /// as those that often fall back to brute-force compression because they
+    /// exceed their maximum number of centroids. If the t-digest has unmerged
+    /// elements, the error estimates are computed on a compressed copy of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    /// let stats = digest.stats();
+    /// assert_eq!(stats.unmerged(), 10_000 % 2049);
+    /// assert!(stats.compressions() > 0);
+    /// assert_eq!(stats.brute_compressions(), 0);
+    /// assert!(stats.error_p99() < stats.error_p50());
+    /// ```
+    pub fn stats(&self) -> DigestStats<F> {
+        let digest = if self.unmerged > 0 {
+            let mut digest = self.clone();
+            digest.compress();
+            Cow::Owned(digest)
+        } else {
+            Cow::Borrowed(self)
+        };
+        DigestStats {
+            centroids: self.centroids.len() - self.unmerged,
+            unmerged: self.unmerged,
+            compressions: self.compressions,
+            brute_compressions: self.brute_compressions,
+            error_p50: digest.error_bound_uncompressed(0.5),
+            error_p99: digest.error_bound_uncompressed(0.99),
+            memory_bytes: self.memory_bytes(),
+        }
+    }
+
+    /// Returns the estimated memory footprint of the t-digest, in bytes.
+    pub(crate) fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<TDigest<F>>()
+            + self.centroids.capacity() * std::mem::size_of::<Centroid<F>>()
+            + self.raw.capacity() * std::mem::size_of::<F>()
+    }
+}
+
+/// Health statistics of a t-digest.
+///
+/// This `struct` is created by the [`stats`] method on [`TDigest`]. See its
+/// documentation for more.
+///
+/// [`stats`]: TDigest::stats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DigestStats<F = f32> {
+    centroids: usize,
+    unmerged: usize,
+    compressions: u64,
+    brute_compressions: u64,
+    error_p50: F,
+    error_p99: F,
+    memory_bytes: usize,
+}
+
+impl<F: Float> DigestStats<F> {
+    /// Returns the number of merged centroids.
+    #[inline]
+    pub fn centroids(&self) -> usize {
+        self.centroids
+    }
+
+    /// Returns the number of centroids buffered since the last compression.
+    #[inline]
+    pub fn unmerged(&self) -> usize {
+        self.unmerged
+    }
+
+    /// Returns the number of compressions since the t-digest was created.
+    #[inline]
+    pub fn compressions(&self) -> u64 {
+        self.compressions
+    }
+
+    /// Returns the number of compressions that exceeded the maximum number of
+    /// centroids, and merged neighboring centroids regardless of their
+    /// weight. Frequent brute-force compressions degrade accuracy, and call
+    /// for a larger maximum number of centroids.
+    #[inline]
+    pub fn brute_compressions(&self) -> u64 {
+        self.brute_compressions
+    }
+
+    /// Returns the estimated error of the median. See
+    /// [`TDigest::error_bound`].
+    #[inline]
+    pub fn error_p50(&self) -> F {
+        self.error_p50
+    }
+
+    /// Returns the estimated error of the 99th percentile. See
+    /// [`TDigest::error_bound`].
+    #[inline]
+    pub fn error_p99(&self) -> F {
+        self.error_p99
+    }
+
+    /// Returns the estimated memory footprint of the t-digest, in bytes.
+    #[inline]
+    pub fn memory_bytes(&self) -> usize {
+        self.memory_bytes
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<F: Float + serde::Serialize> serde::Serialize for DigestStats<F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        // Statistics are serialized with named fields, for consumption by
+        // metrics exporters.
+        let mut stats = serializer.serialize_struct("DigestStats", 7)?;
+        stats.serialize_field("centroids", &self.centroids)?;
+        stats.serialize_field("unmerged", &self.unmerged)?;
+        stats.serialize_field("compressions", &self.compressions)?;
+        stats.serialize_field("brute_compressions", &self.brute_compressions)?;
+        stats.serialize_field("error_p50", &self.error_p50)?;
+        stats.serialize_field("error_p99", &self.error_p99)?;
+        stats.serialize_field("memory_bytes", &self.memory_bytes)?;
+        stats.end()
+    }
+}
@@ -0,0 +1,591 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::{codec::Reader, Error, QuantileSketch};
+
+/// Values below this threshold are counted exactly.
+const SMALL_THRESHOLD: usize = 1024;
+/// Values above this threshold are clamped to it.
+const BIG_THRESHOLD: usize = 30000;
+/// Width of the buckets between the small and big thresholds.
+const BIG_PRECISION: usize = 16;
+const BIG_SIZE: usize = (BIG_THRESHOLD - SMALL_THRESHOLD) / BIG_PRECISION;
+/// Number of values stored in the tiny representation.
+const TINY_MAX_ELEMS: usize = 31;
+/// Number of values above which the medium representation takes more room
+/// than the large one.
+const MEDIUM_MAX_ELEMS: usize = (SMALL_THRESHOLD + BIG_SIZE) * 8 / 2;
+
+const KIND_TINY: u8 = 1;
+const KIND_MEDIUM: u8 = 2;
+const KIND_LARGE: u8 = 3;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Repr {
+    /// Up to `TINY_MAX_ELEMS` values.
+    Tiny(Vec<u16>),
+    /// Up to `MEDIUM_MAX_ELEMS` values.
+    Medium(Vec<u16>),
+    /// Histogram of the values.
+    Large(Box<Large>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Large {
+    count: u64,
+    count_small: [u64; SMALL_THRESHOLD],
+    count_big: [u64; BIG_SIZE],
+}
+
+impl Large {
+    fn new() -> Self {
+        Self {
+            count: 0,
+            count_small: [0; SMALL_THRESHOLD],
+            count_big: [0; BIG_SIZE],
+        }
+    }
+
+    fn insert_many(&mut self, value: u16, count: u64) {
+        let value = value as usize;
+        self.count = self.count.saturating_add(count);
+        let bucket = if value < SMALL_THRESHOLD {
+            &mut self.count_small[value]
+        } else if value < BIG_THRESHOLD {
+            &mut self.count_big[(value - SMALL_THRESHOLD) / BIG_PRECISION]
+        } else {
+            return;
+        };
+        *bucket = bucket.saturating_add(count);
+    }
+
+    fn merge(&mut self, other: &Large) {
+        self.count = self.count.saturating_add(other.count);
+        for (l, r) in self.count_small.iter_mut().zip(&other.count_small) {
+            *l = l.saturating_add(*r);
+        }
+        for (l, r) in self.count_big.iter_mut().zip(&other.count_big) {
+            *l = l.saturating_add(*r);
+        }
+    }
+
+    fn quantile(&self, level: f64) -> u16 {
+        let pos = if level < 1. {
+            (level * self.count as f64) as u64
+        } else {
+            self.count - 1
+        };
+        let mut accumulated = 0u64;
+        for (value, &count) in self.count_small.iter().enumerate() {
+            accumulated = accumulated.saturating_add(count);
+            if accumulated > pos {
+                return value as u16;
+            }
+        }
+        for (index, &count) in self.count_big.iter().enumerate() {
+            accumulated = accumulated.saturating_add(count);
+            if accumulated > pos {
+                return index_in_big_to_value(index);
+            }
+        }
+        BIG_THRESHOLD as u16
+    }
+}
+
+/// Returns the value of a bucket of `count_big`, as ClickHouse
+/// `indexInBigToValue`: the start of the bucket, shifted by a pseudo-random
+/// offset between -8 and 7 so that the quantiles are not all even.
+fn index_in_big_to_value(index: usize) -> u16 {
+    let offset = int_hash32(index as u64) as usize % BIG_PRECISION;
+    (index * BIG_PRECISION + SMALL_THRESHOLD + offset - BIG_PRECISION / 2) as u16
+}
+
+/// The 64-bit to 32-bit integer hash of Thomas Wang, as ClickHouse
+/// `intHash32<0>`.
+fn int_hash32(mut key: u64) -> u32 {
+    key = (!key).wrapping_add(key << 18);
+    key ^= key.rotate_right(31);
+    key = key.wrapping_mul(21);
+    key ^= key.rotate_right(11);
+    key = key.wrapping_add(key << 6);
+    key ^= key.rotate_right(22);
+    key as u32
+}
+
+fn exact_quantile(values: &mut [u16], level: f64) -> u16 {
+    let n = if level < 1. {
+        (level * values.len() as f64) as usize
+    } else {
+        values.len() - 1
+    };
+    let n = n.min(values.len() - 1);
+    *values.select_nth_unstable(n).1
+}
+
+/// Fixed-resolution histogram of timings, as used by ClickHouse
+/// [`quantileTiming`][ClickHouseRefTiming].
+///
+/// The structure is designed for values that are durations in milliseconds,
+/// typically page load times. Values are truncated to integers, values below
+/// 1024 are counted exactly, values up to 30000 are counted in buckets of 16,
+/// and greater values are clamped to 30000. Small sets of values are stored
+/// as-is, so that quantiles are exact until the histogram grows.
+///
+/// The histogram can be read from and written to the state format of the
+/// `quantileTiming` aggregate function.
+///
+/// [ClickHouseRefTiming]: https://clickhouse.com/docs/en/sql-reference/aggregate-functions/reference/quantiletiming
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::TimingHistogram;
+///
+/// let mut histogram = TimingHistogram::new();
+///
+/// // Add some elements.
+/// histogram.insert(100);
+/// histogram.insert(200);
+/// histogram.insert(300);
+///
+/// // Get the median of the distribution.
+/// let quantile = histogram.quantile(0.5);
+/// assert_eq!(quantile, 200.0);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimingHistogram {
+    repr: Repr,
+}
+
+impl TimingHistogram {
+    /// Creates an empty `TimingHistogram`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    /// let histogram = TimingHistogram::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            repr: Repr::Tiny(Vec::new()),
+        }
+    }
+
+    /// Returns the number of elements in the histogram.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let mut histogram = TimingHistogram::new();
+    /// assert_eq!(histogram.len(), 0);
+    /// histogram.insert(1);
+    /// assert_eq!(histogram.len(), 1);
+    /// ```
+    pub fn len(&self) -> u64 {
+        match &self.repr {
+            Repr::Tiny(values) | Repr::Medium(values) => values.len() as u64,
+            Repr::Large(large) => large.count,
+        }
+    }
+
+    /// Returns `true` if the histogram contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let mut histogram = TimingHistogram::new();
+    /// assert!(histogram.is_empty());
+    /// histogram.insert(1);
+    /// assert!(!histogram.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Clears the histogram, removing all values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let mut histogram = TimingHistogram::new();
+    /// histogram.insert(1);
+    /// histogram.clear();
+    /// assert!(histogram.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.repr = Repr::Tiny(Vec::new());
+    }
+
+    /// Adds a timing to the histogram.
+    ///
+    /// Timings greater than 30000 are clamped to 30000.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let mut histogram = TimingHistogram::new();
+    ///
+    /// histogram.insert(1);
+    /// histogram.insert(50_000);
+    /// assert_eq!(histogram.len(), 2);
+    /// assert_eq!(histogram.quantile(1.0), 30_000.0);
+    /// ```
+    #[inline]
+    pub fn insert(&mut self, value: u64) {
+        self.insert_many(value, 1);
+    }
+
+    /// Adds multiple timings to the histogram.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let mut histogram = TimingHistogram::new();
+    ///
+    /// histogram.insert_many(1, 1);
+    /// histogram.insert_many(2, 2);
+    /// assert_eq!(histogram.len(), 3);
+    /// ```
+    pub fn insert_many(&mut self, value: u64, count: u64) {
+        let value = value.min(BIG_THRESHOLD as u64) as u16;
+        match &mut self.repr {
+            Repr::Tiny(values)
+                if (values.len() as u64).saturating_add(count) <= TINY_MAX_ELEMS as u64 =>
+            {
+                values.extend(std::iter::repeat_n(value, count as usize));
+            }
+            Repr::Tiny(values) | Repr::Medium(values)
+                if (values.len() as u64).saturating_add(count) <= MEDIUM_MAX_ELEMS as u64 =>
+            {
+                values.extend(std::iter::repeat_n(value, count as usize));
+                if let Repr::Tiny(values) = &mut self.repr {
+                    self.repr = Repr::Medium(std::mem::take(values));
+                }
+            }
+            Repr::Tiny(values) | Repr::Medium(values) => {
+                let mut large = Box::new(Large::new());
+                for &value in values.iter() {
+                    large.insert_many(value, 1);
+                }
+                large.insert_many(value, count);
+                self.repr = Repr::Large(large);
+            }
+            Repr::Large(large) => large.insert_many(value, count),
+        }
+    }
+
+    /// Returns the estimated quantile of the histogram.
+    ///
+    /// The quantile is exact as long as the histogram holds few values, or
+    /// when it is below 1024. Otherwise, it is the value that ClickHouse
+    /// returns for its bucket of 16: the start of the bucket, shifted by a
+    /// pseudo-random offset between -8 and 7. For an empty histogram returns
+    /// NaN.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let mut histogram = TimingHistogram::from([1, 2, 3, 4, 5]);
+    /// assert_eq!(histogram.quantile(0.0), 1.0);
+    /// assert_eq!(histogram.quantile(0.5), 3.0);
+    /// assert_eq!(histogram.quantile(1.0), 5.0);
+    /// ```
+    pub fn quantile(&mut self, level: f64) -> f32 {
+        if self.is_empty() {
+            return f32::NAN;
+        }
+        let quantile = match &mut self.repr {
+            Repr::Tiny(values) | Repr::Medium(values) => exact_quantile(values, level),
+            Repr::Large(large) => large.quantile(level),
+        };
+        f32::from(quantile)
+    }
+
+    /// Serializes the histogram in the state format of ClickHouse
+    /// `quantileTiming` aggregate function.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let histogram = TimingHistogram::from([1, 2, 3]);
+    /// let bytes = histogram.to_bytes();
+    /// assert_eq!(TimingHistogram::from_bytes(&bytes), Ok(histogram));
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match &self.repr {
+            Repr::Tiny(values) => {
+                buf.push(KIND_TINY);
+                buf.extend_from_slice(&(values.len() as u16).to_le_bytes());
+                for value in values.iter() {
+                    buf.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Repr::Medium(values) => {
+                buf.push(KIND_MEDIUM);
+                buf.extend_from_slice(&(values.len() as u64).to_le_bytes());
+                for value in values.iter() {
+                    buf.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Repr::Large(large) => {
+                buf.push(KIND_LARGE);
+                buf.extend_from_slice(&large.count.to_le_bytes());
+                if large.count > (SMALL_THRESHOLD + BIG_SIZE) as u64 / 2 {
+                    // Simple serialization for a heavily dense case.
+                    for count in large.count_small.iter().chain(large.count_big.iter()) {
+                        buf.extend_from_slice(&count.to_le_bytes());
+                    }
+                } else {
+                    // More compact serialization for a sparse case.
+                    let counts = large.count_small.iter().chain(large.count_big.iter());
+                    for (index, &count) in counts.enumerate() {
+                        if count != 0 {
+                            buf.extend_from_slice(&(index as u16).to_le_bytes());
+                            buf.extend_from_slice(&count.to_le_bytes());
+                        }
+                    }
+                    // Symbolizes end of data.
+                    buf.extend_from_slice(&(BIG_THRESHOLD as u16).to_le_bytes());
+                }
+            }
+        }
+        buf
+    }
+
+    /// Deserializes a histogram from the state format of ClickHouse
+    /// `quantileTiming` aggregate function.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` is not a valid state.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let bytes = [
+    ///     0x01, // Tiny.
+    ///     0x02, 0x00, // Two values.
+    ///     0x64, 0x00, // 100.
+    ///     0xc8, 0x00, // 200.
+    /// ];
+    /// let mut histogram = TimingHistogram::from_bytes(&bytes).unwrap();
+    /// assert_eq!(histogram.len(), 2);
+    /// assert_eq!(histogram.quantile(1.0), 200.0);
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(bytes);
+        let repr = match reader.read_u8()? {
+            KIND_TINY => {
+                let size = reader.read_u16()? as usize;
+                if size > TINY_MAX_ELEMS {
+                    return Err(Error::InvalidState("too many values for tiny kind"));
+                }
+                Repr::Tiny(read_values(&mut reader, size)?)
+            }
+            KIND_MEDIUM => {
+                let size = reader.read_u64()?;
+                if size > bytes.len() as u64 {
+                    return Err(Error::UnexpectedEof);
+                }
+                Repr::Medium(read_values(&mut reader, size as usize)?)
+            }
+            KIND_LARGE => {
+                let mut large = Box::new(Large::new());
+                large.count = reader.read_u64()?;
+                if large.count > (SMALL_THRESHOLD + BIG_SIZE) as u64 / 2 {
+                    for count in large.count_small.iter_mut() {
+                        *count = reader.read_u64()?;
+                    }
+                    for count in large.count_big.iter_mut() {
+                        *count = reader.read_u64()?;
+                    }
+                } else {
+                    loop {
+                        let index = reader.read_u16()? as usize;
+                        if index == BIG_THRESHOLD {
+                            break;
+                        }
+                        let count = reader.read_u64()?;
+                        if index < SMALL_THRESHOLD {
+                            large.count_small[index] = count;
+                        } else if index < SMALL_THRESHOLD + BIG_SIZE {
+                            large.count_big[index - SMALL_THRESHOLD] = count;
+                        } else {
+                            return Err(Error::InvalidState("bucket index out of range"));
+                        }
+                    }
+                }
+                // Values clamped to the big threshold are in no bucket.
+                let total = large
+                    .count_small
+                    .iter()
+                    .chain(large.count_big.iter())
+                    .try_fold(0u64, |total, &count| total.checked_add(count));
+                if total.is_none_or(|total| total > large.count) {
+                    return Err(Error::InvalidState("bucket counts exceed the count"));
+                }
+                Repr::Large(large)
+            }
+            _ => return Err(Error::InvalidState("unknown kind")),
+        };
+        reader.finish()?;
+        Ok(Self { repr })
+    }
+}
+
+fn read_values(reader: &mut Reader<'_>, size: usize) -> Result<Vec<u16>, Error> {
+    let mut values = Vec::with_capacity(size);
+    for _ in 0..size {
+        values.push(reader.read_u16()?.min(BIG_THRESHOLD as u16));
+    }
+    Ok(values)
+}
+
+impl BitOr<&TimingHistogram> for &TimingHistogram {
+    type Output = TimingHistogram;
+
+    /// Returns the union of `self` and `rhs` as a new `TimingHistogram`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let a = TimingHistogram::from([1, 2, 3]);
+    /// let b = TimingHistogram::from([3, 4, 5]);
+    ///
+    /// let mut c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 6);
+    /// assert_eq!(c.quantile(0.5), 3.0);
+    /// ```
+    fn bitor(self, rhs: &TimingHistogram) -> TimingHistogram {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&TimingHistogram> for TimingHistogram {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let mut a = TimingHistogram::from([1, 2, 3]);
+    /// let b = TimingHistogram::from([3, 4, 5]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 6);
+    /// assert_eq!(a.quantile(0.5), 3.0);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &TimingHistogram) {
+        match &rhs.repr {
+            Repr::Tiny(values) | Repr::Medium(values) => {
+                for &value in values.iter() {
+                    self.insert(u64::from(value));
+                }
+            }
+            Repr::Large(rhs_large) => {
+                if !matches!(self.repr, Repr::Large(_)) {
+                    let mut large = rhs_large.clone();
+                    if let Repr::Tiny(values) | Repr::Medium(values) = &self.repr {
+                        for &value in values.iter() {
+                            large.insert_many(value, 1);
+                        }
+                    }
+                    self.repr = Repr::Large(large);
+                } else if let Repr::Large(large) = &mut self.repr {
+                    large.merge(rhs_large);
+                }
+            }
+        }
+    }
+}
+
+impl Default for TimingHistogram {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<u64> for TimingHistogram {
+    fn extend<I: IntoIterator<Item = u64>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<const N: usize> From<[u64; N]> for TimingHistogram {
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TimingHistogram;
+    ///
+    /// let histogram1 = TimingHistogram::from([1, 2, 3, 4]);
+    /// let histogram2: TimingHistogram = [1, 2, 3, 4].into();
+    /// assert_eq!(histogram1, histogram2);
+    /// ```
+    fn from(array: [u64; N]) -> Self {
+        let mut histogram = TimingHistogram::new();
+        histogram.extend(array);
+        histogram
+    }
+}
+
+impl FromIterator<u64> for TimingHistogram {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        let mut histogram = TimingHistogram::new();
+        histogram.extend(iter);
+        histogram
+    }
+}
+
+impl QuantileSketch for TimingHistogram {
+    /// Adds a timing to the histogram. The value is truncated to an integer,
+    /// and ignored if negative or NaN.
+    fn insert(&mut self, value: f32) {
+        if value >= 0. {
+            self.insert(value as u64);
+        }
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        *self |= other;
+    }
+
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    #[inline]
+    fn quantile(&mut self, level: f64) -> f32 {
+        self.quantile(level)
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{interpolate, Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns the estimated quantile at level `1 - tail_level`, computed from
+    /// the upper end of the distribution.
+    ///
+    /// For tiny tail levels, such as 1e-7 for the p99.99999, `1.0 -
+    /// tail_level` loses most of the digits of `tail_level`: its rounding
+    /// error of up to 1.1e-16 is a large fraction of it, and it rounds to 1
+    /// below 5.6e-17. This method instead takes the rank from the top as
+    /// `tail_level` times the number of elements, and interpolates between
+    /// the largest centroids from there. Otherwise, the estimate is the same as
+    /// `quantile(1.0 - tail_level)`, up to rounding. It is the largest value
+    /// for a `tail_level` of 0 or lower, and the smallest for 1 or greater.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// assert_eq!(digest.top_quantile(0.01), digest.quantile(0.99));
+    /// assert_eq!(digest.top_quantile(0.0), 1000.0);
+    ///
+    /// let mut requests = TDigest::new();
+    /// requests.insert_many(10.0, 999_999_999_998);
+    /// requests.extend([500.0, 900.0]);
+    /// assert_eq!(requests.top_quantile(5e-13), 900.0);
+    /// assert_eq!(requests.top_quantile(1.5e-12), 500.0);
+    /// ```
+    pub fn top_quantile(&mut self, tail_level: f64) -> F {
+        self.compress();
+        self.top_quantile_uncompressed(tail_level)
+    }
+
+    pub(crate) fn top_quantile_uncompressed(&self, tail_level: f64) -> F {
+        let Some(&last) = self.centroids.last() else {
+            return F::NAN;
+        };
+        if tail_level <= 0. {
+            return self.max;
+        }
+        if tail_level >= 1. {
+            return self.min;
+        }
+        let count = self.count as f64;
+        let d = tail_level * count;
+        // The smallest value with at least `count - d` values lower than or
+        // equal to it.
+        let index = (self.count - 1).saturating_sub(d as u64);
+        if let Some(values) = self.raw.exact(self.count) {
+            return values[index as usize];
+        }
+        if let Some(value) = self.extremes.value(index, self.count, self.config.extremes) {
+            return value;
+        }
+        if let Some(tail) = self.dunning_tail(count - d) {
+            return tail;
+        }
+
+        // Same as `quantile_uncompressed`, with ranks counted from the top.
+        let singletons = self.config.interpolation.singletons();
+        let mut prev_d = 0f64;
+        let mut sum = 0u64;
+        let mut prev = last;
+        for c in self.centroids.iter().rev() {
+            let current_d = sum as f64 + c.count as f64 * 0.5;
+            if current_d >= d {
+                // Special handling of singletons.
+                let mut left = prev_d;
+                if singletons && prev.count == 1 {
+                    left += 0.5;
+                }
+                let mut right = current_d;
+                if singletons && c.count == 1 {
+                    right -= 0.5;
+                }
+                // Ties go to the lower value, as ranks from the bottom do.
+                return if d >= right {
+                    c.mean
+                } else if d <= left {
+                    prev.mean
+                } else {
+                    interpolate(
+                        F::from_f64(d),
+                        F::from_f64(left),
+                        prev.mean,
+                        F::from_f64(right),
+                        c.mean,
+                    )
+                };
+            }
+            sum += c.count;
+            prev = *c;
+            prev_d = current_d;
+        }
+        self.centroids[0].mean
+    }
+}
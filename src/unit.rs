@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+/// The unit of the values of a t-digest.
+///
+/// Units of the same dimension, such as seconds and milliseconds, can be
+/// converted into each other: merging t-digests with such units converts the
+/// values of the right-hand side. Merging t-digests with units of different
+/// dimensions fails.
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::{TDigest, Unit};
+///
+/// let mut seconds = TDigest::from([1.0, 2.0]);
+/// seconds.set_unit(Unit::Seconds);
+/// let mut millis = TDigest::from([3000.0]);
+/// millis.set_unit(Unit::Millis);
+///
+/// seconds.try_merge(&millis).unwrap();
+/// assert_eq!(seconds.quantile(1.0), 3.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Unit {
+    /// Durations, in seconds.
+    Seconds,
+    /// Durations, in milliseconds.
+    Millis,
+    /// Durations, in microseconds.
+    Micros,
+    /// Durations, in nanoseconds.
+    Nanos,
+    /// Sizes, in bytes.
+    Bytes,
+    /// Numbers of items.
+    Count,
+    /// A custom unit, worth `scale` times the base unit of its dimension.
+    ///
+    /// The base units of the built-in dimensions are seconds for `"time"`,
+    /// bytes for `"data"`, and items for `"count"`.
+    Custom {
+        /// The dimension of the unit.
+        dimension: String,
+        /// The value of the unit, in the base unit of the dimension.
+        scale: f64,
+    },
+}
+
+impl Unit {
+    /// Creates a unit from its dimension and scale, using the built-in units
+    /// when possible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::Unit;
+    ///
+    /// assert_eq!(Unit::from_parts("time", 0.001), Unit::Millis);
+    /// assert_eq!(
+    ///     Unit::from_parts("time", 60.0),
+    ///     Unit::Custom { dimension: "time".to_string(), scale: 60.0 },
+    /// );
+    /// ```
+    pub fn from_parts(dimension: &str, scale: f64) -> Self {
+        match (dimension, scale) {
+            ("time", 1.) => Self::Seconds,
+            ("time", 0.001) => Self::Millis,
+            ("time", 1e-6) => Self::Micros,
+            ("time", 1e-9) => Self::Nanos,
+            ("data", 1.) => Self::Bytes,
+            ("count", 1.) => Self::Count,
+            _ => Self::Custom {
+                dimension: dimension.to_string(),
+                scale,
+            },
+        }
+    }
+
+    /// Returns the dimension of the unit.
+    pub fn dimension(&self) -> &str {
+        match self {
+            Self::Seconds | Self::Millis | Self::Micros | Self::Nanos => "time",
+            Self::Bytes => "data",
+            Self::Count => "count",
+            Self::Custom { dimension, .. } => dimension,
+        }
+    }
+
+    /// Returns the value of the unit, in the base unit of its dimension.
+    pub fn scale(&self) -> f64 {
+        match self {
+            Self::Seconds | Self::Bytes | Self::Count => 1.,
+            Self::Millis => 0.001,
+            Self::Micros => 1e-6,
+            Self::Nanos => 1e-9,
+            Self::Custom { scale, .. } => *scale,
+        }
+    }
+
+    /// Returns the factor that converts values in `self` into values in
+    /// `other`, or `None` if the units have different dimensions or if a scale
+    /// is not a positive number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::Unit;
+    ///
+    /// assert_eq!(Unit::Seconds.factor_to(&Unit::Millis), Some(1000.0));
+    /// assert_eq!(Unit::Seconds.factor_to(&Unit::Bytes), None);
+    /// ```
+    pub fn factor_to(&self, other: &Unit) -> Option<f64> {
+        let factor = self.scale() / other.scale();
+        (self.dimension() == other.dimension() && factor.is_finite() && factor > 0.)
+            .then_some(factor)
+    }
+
+    /// Returns the length of `duration` in the unit, or `None` if the unit is
+    /// not a duration.
+    ///
+    /// The sub-second units are converted from whole nanoseconds, so that
+    /// they are exact up to the precision of `f64`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tdigest_ch::Unit;
+    ///
+    /// let duration = Duration::from_micros(1_250);
+    /// assert_eq!(Unit::Millis.duration(duration), Some(1.25));
+    /// assert_eq!(Unit::Nanos.duration(duration), Some(1_250_000.0));
+    /// assert_eq!(Unit::Bytes.duration(duration), None);
+    /// ```
+    pub fn duration(&self, duration: Duration) -> Option<f64> {
+        let nanos = duration.as_nanos() as f64;
+        match self {
+            Self::Seconds => Some(duration.as_secs_f64()),
+            Self::Millis => Some(nanos / 1e6),
+            Self::Micros => Some(nanos / 1e3),
+            Self::Nanos => Some(nanos),
+            _ => (self.dimension() == "time").then(|| duration.as_secs_f64() / self.scale()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Unit {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (self.dimension(), self.scale()).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Unit {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (dimension, scale): (String, f64) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self::from_parts(&dimension, scale))
+    }
+}
@@ -0,0 +1,100 @@
+use std::borrow::Cow;
+
+use crate::{Float, TDigest};
+
+impl<F: Float> TDigest<F> {
+    /// Returns the weight of each centroid, along with the bound that
+    /// compression tries to keep it under.
+    ///
+    /// Compression merges neighboring centroids as long as their combined
+    /// weight does not exceed `4 q (1 - q) epsilon N`, where `q` is the level
+    /// of the centroids and `N` the number of elements. Centroids far above
+    /// their bound degrade accuracy around their level: they typically come
+    /// from [`TDigest::insert_many`] with large counts, or from long runs of
+    /// equal values.
+    ///
+    /// If the t-digest has unmerged elements, the profile is computed on a
+    /// compressed copy of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::TDigest;
+    ///
+    /// let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    /// let profile = digest.centroid_weight_profile();
+    /// assert_eq!(profile.iter().map(|c| c.weight()).sum::<u64>(), 10_000);
+    /// assert!(profile.iter().all(|c| c.weight() as f64 <= 1.5 * c.bound() + 1.0));
+    ///
+    /// digest.insert_many(5_000.0, 10_000);
+    /// let profile = digest.centroid_weight_profile();
+    /// assert!(profile.iter().any(|c| c.weight() as f64 > 10.0 * c.bound()));
+    /// ```
+    pub fn centroid_weight_profile(&self) -> Vec<CentroidWeight<F>> {
+        let digest = if self.unmerged > 0 {
+            let mut digest = self.clone();
+            digest.compress();
+            Cow::Owned(digest)
+        } else {
+            Cow::Borrowed(self)
+        };
+
+        let count = digest.count as f64;
+        let count_epsilon_4 = count * digest.config.epsilon as f64 * 4.;
+        let mut sum = 0;
+        digest
+            .centroids
+            .iter()
+            .map(|c| {
+                let level = (sum as f64 + c.count as f64 * 0.5) / count;
+                sum += c.count;
+                CentroidWeight {
+                    mean: c.mean,
+                    level,
+                    weight: c.count,
+                    bound: count_epsilon_4 * level * (1. - level),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The weight of a centroid of a t-digest, compared with its theoretical bound.
+///
+/// This `struct` is created by the [`centroid_weight_profile`] method on
+/// [`TDigest`]. See its documentation for more.
+///
+/// [`centroid_weight_profile`]: TDigest::centroid_weight_profile
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CentroidWeight<F = f32> {
+    mean: F,
+    level: f64,
+    weight: u64,
+    bound: f64,
+}
+
+impl<F: Float> CentroidWeight<F> {
+    /// Returns the mean of the centroid.
+    #[inline]
+    pub fn mean(&self) -> F {
+        self.mean
+    }
+
+    /// Returns the level at the middle of the centroid.
+    #[inline]
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Returns the number of elements in the centroid.
+    #[inline]
+    pub fn weight(&self) -> u64 {
+        self.weight
+    }
+
+    /// Returns the bound `4 q (1 - q) epsilon N` at the level of the centroid.
+    #[inline]
+    pub fn bound(&self) -> f64 {
+        self.bound
+    }
+}
@@ -0,0 +1,668 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::{can_be_merged, QuantileSketch, TDigest};
+
+const DEFAULT_EPSILON: f64 = 0.01;
+const MAX_UNMERGED: usize = 2048;
+
+/// Stores a fractional weight of points around their mean value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct WeightedCentroid {
+    mean: f32,
+    weight: f64,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for WeightedCentroid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (self.mean, self.weight).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for WeightedCentroid {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (mean, weight) = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self { mean, weight })
+    }
+}
+
+/// T-digest whose elements have fractional weights, such as the importance
+/// weights of a biased sample or the weights of an exponentially decayed
+/// stream.
+///
+/// Centroids are merged with the same rule as [`TDigest`], applied to the
+/// total weight instead of the number of elements. A centroid of fractional
+/// weight does not stand for a whole number of elements, so that quantiles
+/// are interpolated between the midpoints of neighboring centroids without
+/// the special handling of singletons, as with
+/// [`Interpolation::Midpoint`](crate::Interpolation::Midpoint).
+///
+/// # Examples
+///
+/// ```
+/// use tdigest_ch::WeightedTDigest;
+///
+/// let mut digest = WeightedTDigest::new();
+///
+/// // The value 1.0 was sampled at a rate of 1 in 3.5.
+/// digest.insert_weighted(1.0, 3.5);
+/// digest.insert_weighted(2.0, 0.5);
+///
+/// assert_eq!(digest.len(), 2);
+/// assert_eq!(digest.total_weight(), 4.0);
+/// assert_eq!(digest.quantile(0.25), 1.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeightedTDigest {
+    epsilon: f64,
+    centroids: Vec<WeightedCentroid>,
+    len: u64,
+    weight: f64,
+    /// The exact smallest and largest inserted values, or infinities of the
+    /// opposite sign if empty.
+    min: f32,
+    max: f32,
+    unmerged: usize,
+}
+
+impl WeightedTDigest {
+    /// Creates an empty `WeightedTDigest` with a compression parameter of
+    /// 0.01.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    /// let digest = WeightedTDigest::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_epsilon(DEFAULT_EPSILON)
+    }
+
+    /// Creates an empty `WeightedTDigest` with the given compression
+    /// parameter, as with [`TDigestBuilder::epsilon`].
+    ///
+    /// [`TDigestBuilder::epsilon`]: crate::TDigestBuilder::epsilon
+    ///
+    /// # Panics
+    ///
+    /// Panics if `epsilon` is not between 0 and 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    /// let digest = WeightedTDigest::with_epsilon(0.001);
+    /// ```
+    #[must_use]
+    pub fn with_epsilon(epsilon: f64) -> Self {
+        assert!(
+            epsilon > 0. && epsilon < 1.,
+            "epsilon must be between 0 and 1"
+        );
+        Self {
+            epsilon,
+            centroids: Vec::new(),
+            len: 0,
+            weight: 0.,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            unmerged: 0,
+        }
+    }
+
+    /// Returns the number of elements in the t-digest, regardless of their
+    /// weights.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// assert_eq!(digest.len(), 0);
+    /// digest.insert_weighted(1.0, 0.25);
+    /// assert_eq!(digest.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns `true` if the t-digest contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// assert!(digest.is_empty());
+    /// digest.insert(1.0);
+    /// assert!(!digest.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the sum of the weights of the elements in the t-digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.insert_weighted(1.0, 0.25);
+    /// digest.insert_weighted(2.0, 0.5);
+    /// assert_eq!(digest.total_weight(), 0.75);
+    /// ```
+    #[inline]
+    pub fn total_weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// Clears the t-digest, removing all values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.insert(1.0);
+    /// digest.clear();
+    /// assert!(digest.is_empty());
+    /// ```
+    pub fn clear(&mut self) {
+        self.centroids.clear();
+        self.len = 0;
+        self.weight = 0.;
+        self.min = f32::INFINITY;
+        self.max = f32::NEG_INFINITY;
+        self.unmerged = 0;
+    }
+
+    /// Adds a value of weight 1 to the t-digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.insert(1.0);
+    /// assert_eq!(digest.total_weight(), 1.0);
+    /// ```
+    #[inline]
+    pub fn insert(&mut self, value: f32) {
+        self.insert_weighted(value, 1.);
+    }
+
+    /// Adds a value of the given weight to the t-digest.
+    ///
+    /// NaN values and values of weight 0 are ignored, as with
+    /// [`TDigest::insert_many`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is negative, infinite or NaN.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.insert_weighted(1.0, 0.1);
+    /// digest.insert_weighted(2.0, 0.3);
+    /// assert_eq!(digest.quantile(0.75), 2.0);
+    /// ```
+    pub fn insert_weighted(&mut self, value: f32, weight: f64) {
+        assert!(
+            weight >= 0. && weight.is_finite(),
+            "weight must be finite and non-negative"
+        );
+        if weight == 0. || value.is_nan() {
+            return;
+        }
+        self.len += 1;
+        self.weight += weight;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.centroids.push(WeightedCentroid {
+            mean: value,
+            weight,
+        });
+        self.unmerged += 1;
+        if self.unmerged > MAX_UNMERGED {
+            self.compress();
+        }
+    }
+
+    /// Multiplies the weights of all the elements by `factor`, such as to
+    /// decay the past of a stream before inserting new values.
+    ///
+    /// Decaying by a constant factor at regular intervals gives the elements
+    /// exponentially decreasing weights with their age, so that quantiles
+    /// track recent values, without a window of them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `factor` is not positive and finite.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.extend([1.0, 1.0, 1.0]);
+    /// digest.decay(0.1);
+    /// digest.insert(10.0);
+    /// assert!((digest.total_weight() - 1.3).abs() < 1e-12);
+    /// assert_eq!(digest.quantile(0.9), 10.0);
+    /// ```
+    pub fn decay(&mut self, factor: f64) {
+        assert!(
+            factor > 0. && factor.is_finite(),
+            "factor must be positive and finite"
+        );
+        for c in self.centroids.iter_mut() {
+            c.weight *= factor;
+        }
+        self.weight *= factor;
+    }
+
+    /// Returns the smallest value of the t-digest, or NaN if it is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let digest = WeightedTDigest::from_iter([3.0, 1.0, 2.0]);
+    /// assert_eq!(digest.min(), 1.0);
+    /// ```
+    #[inline]
+    pub fn min(&self) -> f32 {
+        if self.is_empty() {
+            f32::NAN
+        } else {
+            self.min
+        }
+    }
+
+    /// Returns the largest value of the t-digest, or NaN if it is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let digest = WeightedTDigest::from_iter([3.0, 1.0, 2.0]);
+    /// assert_eq!(digest.max(), 3.0);
+    /// ```
+    #[inline]
+    pub fn max(&self) -> f32 {
+        if self.is_empty() {
+            f32::NAN
+        } else {
+            self.max
+        }
+    }
+
+    /// Returns the estimated quantile of the t-digest: the value below which
+    /// lies a fraction `level` of the total weight.
+    ///
+    /// The quantiles at levels 0 and 1 are the exact smallest and largest
+    /// values, and the quantiles are NaN for an empty t-digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.insert_weighted(1.0, 0.5);
+    /// digest.insert_weighted(3.0, 1.5);
+    /// assert_eq!(digest.quantile(0.0), 1.0);
+    /// assert_eq!(digest.quantile(0.5), 2.5);
+    /// assert_eq!(digest.quantile(1.0), 3.0);
+    /// ```
+    pub fn quantile(&mut self, level: f64) -> f32 {
+        self.compress();
+        self.quantile_compressed(level)
+    }
+
+    fn quantile_compressed(&self, level: f64) -> f32 {
+        let Some(&first) = self.centroids.first() else {
+            return f32::NAN;
+        };
+        if level <= 0. {
+            return self.min;
+        }
+        if level >= 1. {
+            return self.max;
+        }
+
+        let x = level * self.weight;
+        let mut prev_x = 0f64;
+        let mut sum = 0f64;
+        let mut prev = first;
+        for c in self.centroids.iter() {
+            let current_x = sum + c.weight * 0.5;
+            if current_x >= x {
+                return if x <= prev_x {
+                    prev.mean
+                } else {
+                    let t = (x - prev_x) / (current_x - prev_x);
+                    (prev.mean as f64 + t * (c.mean as f64 - prev.mean as f64)) as f32
+                };
+            }
+            sum += c.weight;
+            prev = *c;
+            prev_x = current_x;
+        }
+        self.centroids[self.centroids.len() - 1].mean
+    }
+
+    /// Returns the estimated fraction of the total weight of the t-digest that
+    /// is lower than or equal to `value`.
+    ///
+    /// This is the inverse of [`WeightedTDigest::quantile`]. It is 0 below the
+    /// smallest centroid, 1 from the largest one, and NaN for an empty
+    /// t-digest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.insert_weighted(1.0, 0.5);
+    /// digest.insert_weighted(3.0, 1.5);
+    /// assert_eq!(digest.cdf(0.0), 0.0);
+    /// assert_eq!(digest.cdf(2.5), 0.5);
+    /// assert_eq!(digest.cdf(3.0), 1.0);
+    /// ```
+    pub fn cdf(&mut self, value: f32) -> f64 {
+        self.compress();
+        self.cdf_compressed(value)
+    }
+
+    fn cdf_compressed(&self, value: f32) -> f64 {
+        let (Some(&first), Some(last)) = (self.centroids.first(), self.centroids.last()) else {
+            return f64::NAN;
+        };
+        if value < first.mean {
+            return 0.;
+        }
+        if value >= last.mean || value.is_nan() {
+            return 1.;
+        }
+
+        let mut prev_x = first.weight * 0.5;
+        let mut sum = first.weight;
+        let mut prev = first;
+        for c in self.centroids[1..].iter() {
+            let current_x = sum + c.weight * 0.5;
+            if value < c.mean {
+                let t = (value - prev.mean) as f64 / (c.mean - prev.mean) as f64;
+                return (prev_x + t * (current_x - prev_x)) / self.weight;
+            }
+            sum += c.weight;
+            prev = *c;
+            prev_x = current_x;
+        }
+        1.
+    }
+
+    fn compress(&mut self) {
+        if self.unmerged == 0 {
+            return;
+        }
+        self.centroids
+            .sort_unstable_by(|l, r| l.mean.total_cmp(&r.mean));
+
+        // The same merge rule as `TDigest::compress`, with weights: the weight
+        // of a centroid does not exceed `4 q (1 - q) epsilon W`.
+        let weight_epsilon_4 = self.weight * self.epsilon * 4.;
+        let mut l_index = 0;
+        let mut sum = 0f64;
+        let (mut l_mean, mut l_weight) = {
+            let l = self.centroids[0];
+            (l.mean as f64, l.weight)
+        };
+        for r_index in 1..self.centroids.len() {
+            let r = self.centroids[r_index];
+            let ql = (sum + l_weight * 0.5) / self.weight;
+            let qr = (sum + l_weight + r.weight * 0.5) / self.weight;
+            let err = f64::min(ql * (1. - ql), qr * (1. - qr));
+            let k = weight_epsilon_4 * err;
+
+            if l_weight + r.weight <= k && can_be_merged(l_mean, r.mean) {
+                l_weight += r.weight;
+                if r.mean as f64 != l_mean {
+                    l_mean += r.weight * (r.mean as f64 - l_mean) / l_weight;
+                }
+                self.centroids[l_index] = WeightedCentroid {
+                    mean: l_mean as f32,
+                    weight: l_weight,
+                };
+            } else {
+                sum += l_weight;
+                l_index += 1;
+                self.centroids[l_index] = r;
+                (l_mean, l_weight) = (r.mean as f64, r.weight);
+            }
+        }
+        self.centroids.truncate(l_index + 1);
+        self.unmerged = 0;
+    }
+}
+
+impl BitOr<&WeightedTDigest> for &WeightedTDigest {
+    type Output = WeightedTDigest;
+
+    /// Returns the union of `self` and `rhs` as a new `WeightedTDigest`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let a = WeightedTDigest::from_iter([1.0, 2.0, 3.0]);
+    /// let b = WeightedTDigest::from_iter([3.0, 4.0, 5.0]);
+    ///
+    /// let c = &a | &b;
+    ///
+    /// assert_eq!(c.len(), 6);
+    /// assert_eq!(c.total_weight(), 6.0);
+    /// ```
+    fn bitor(self, rhs: &WeightedTDigest) -> WeightedTDigest {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitOrAssign<&WeightedTDigest> for WeightedTDigest {
+    /// Merges `self` and `rhs` into `self`.
+    ///
+    /// The compression parameter of `self` applies to the result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut a = WeightedTDigest::from_iter([1.0, 2.0, 3.0]);
+    /// let b = WeightedTDigest::from_iter([3.0, 4.0, 5.0]);
+    ///
+    /// a |= &b;
+    ///
+    /// assert_eq!(a.len(), 6);
+    /// assert_eq!(a.quantile(1.0), 5.0);
+    /// ```
+    fn bitor_assign(&mut self, rhs: &WeightedTDigest) {
+        if rhs.is_empty() {
+            return;
+        }
+        self.centroids.extend_from_slice(&rhs.centroids);
+        self.len += rhs.len;
+        self.weight += rhs.weight;
+        self.min = self.min.min(rhs.min);
+        self.max = self.max.max(rhs.max);
+        self.unmerged += rhs.centroids.len();
+        self.compress();
+    }
+}
+
+impl Default for WeightedTDigest {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<f32> for WeightedTDigest {
+    fn extend<I: IntoIterator<Item = f32>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl Extend<(f32, f64)> for WeightedTDigest {
+    /// Adds pairs of a value and its weight, as with
+    /// [`WeightedTDigest::insert_weighted`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::WeightedTDigest;
+    ///
+    /// let mut digest = WeightedTDigest::new();
+    /// digest.extend([(1.0, 0.5), (2.0, 1.5)]);
+    /// assert_eq!(digest.total_weight(), 2.0);
+    /// ```
+    fn extend<I: IntoIterator<Item = (f32, f64)>>(&mut self, iter: I) {
+        for (value, weight) in iter {
+            self.insert_weighted(value, weight);
+        }
+    }
+}
+
+impl FromIterator<f32> for WeightedTDigest {
+    fn from_iter<I: IntoIterator<Item = f32>>(iter: I) -> Self {
+        let mut digest = WeightedTDigest::new();
+        digest.extend(iter);
+        digest
+    }
+}
+
+impl From<TDigest> for WeightedTDigest {
+    /// Converts a t-digest into a weighted t-digest, with the number of
+    /// elements of each centroid as its weight.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tdigest_ch::{TDigest, WeightedTDigest};
+    ///
+    /// let digest = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    /// let mut weighted = WeightedTDigest::from(digest);
+    /// assert_eq!(weighted.len(), 1000);
+    /// assert_eq!(weighted.total_weight(), 1000.0);
+    /// assert!((weighted.quantile(0.5) - 500.0).abs() <= 10.0);
+    /// ```
+    fn from(mut digest: TDigest) -> Self {
+        digest.compress();
+        let mut weighted = WeightedTDigest::with_epsilon(digest.config.epsilon as f64);
+        weighted.centroids = digest
+            .centroids
+            .iter()
+            .map(|c| WeightedCentroid {
+                mean: c.mean,
+                weight: c.count as f64,
+            })
+            .collect();
+        weighted.len = digest.count;
+        weighted.weight = digest.count as f64;
+        weighted.min = digest.min;
+        weighted.max = digest.max;
+        weighted
+    }
+}
+
+impl QuantileSketch for WeightedTDigest {
+    #[inline]
+    fn insert(&mut self, value: f32) {
+        self.insert(value);
+    }
+
+    #[inline]
+    fn merge(&mut self, other: &Self) {
+        *self |= other;
+    }
+
+    #[inline]
+    fn len(&self) -> u64 {
+        self.len()
+    }
+
+    #[inline]
+    fn quantile(&mut self, level: f64) -> f32 {
+        self.quantile(level)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for WeightedTDigest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (
+            self.epsilon,
+            &self.centroids,
+            self.len,
+            self.weight,
+            (!self.is_empty()).then_some((self.min, self.max)),
+            self.unmerged,
+        )
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for WeightedTDigest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (epsilon, centroids, len, weight, bounds, unmerged) =
+            serde::Deserialize::deserialize(deserializer)?;
+        let bounds: Option<(f32, f32)> = bounds;
+        let (min, max) = bounds.unwrap_or((f32::INFINITY, f32::NEG_INFINITY));
+        Ok(Self {
+            epsilon,
+            centroids,
+            len,
+            weight,
+            min,
+            max,
+            unmerged,
+        })
+    }
+}
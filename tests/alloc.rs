@@ -0,0 +1,81 @@
+//! Checks that queries do not allocate, with a global allocator that counts
+//! the allocations of each thread.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use tdigest_ch::TDigest;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations made by `f` on the current thread.
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    std::hint::black_box(f());
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Returns a t-digest with many unmerged elements, so that the first query
+/// compresses it.
+fn unmerged_digest() -> TDigest {
+    let mut digest = TDigest::new();
+    digest.extend((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    digest.extend((0..1_000).map(|v| v as f32));
+    digest
+}
+
+#[test]
+fn quantile_does_not_allocate() {
+    let mut digest = unmerged_digest();
+    assert_eq!(allocations(|| digest.quantile(0.5)), 0);
+    assert_eq!(allocations(|| digest.quantile(0.99)), 0);
+}
+
+#[test]
+fn quantiles_do_not_allocate() {
+    let mut digest = unmerged_digest();
+    assert_eq!(
+        allocations(|| {
+            let quantiles = digest.quantiles();
+            quantiles.get(0.5) + quantiles.get(0.99) + quantiles.cdf(500.) as f32
+        }),
+        0
+    );
+}
+
+#[test]
+fn cdf_does_not_allocate() {
+    let mut digest = unmerged_digest();
+    assert_eq!(allocations(|| digest.cdf(50_000.)), 0);
+}
+
+#[test]
+fn apdex_does_not_allocate() {
+    let mut digest = unmerged_digest();
+    assert_eq!(allocations(|| digest.apdex(10_000., 40_000.)), 0);
+}
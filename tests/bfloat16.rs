@@ -0,0 +1,52 @@
+use tdigest_ch::BFloat16Histogram;
+
+#[test]
+fn truncation() {
+    // 1.0078125 is the smallest value above 1.0 representable as a bfloat16.
+    let histogram = BFloat16Histogram::from([1.0, 1.001, 1.0078125, 1.01]);
+    assert_eq!(histogram.quantile(0.25), 1.0);
+    assert_eq!(histogram.quantile(0.5), 1.0);
+    assert_eq!(histogram.quantile(0.75), 1.0078125);
+    assert_eq!(histogram.quantile(1.0), 1.0078125);
+}
+
+#[test]
+fn negative() {
+    let histogram = BFloat16Histogram::from([-1.0, -2.0, -3.0, 0.0, 1.0]);
+    assert_eq!(histogram.quantile(0.0), -3.0);
+    assert_eq!(histogram.quantile(0.5), -1.0);
+    assert_eq!(histogram.quantile(1.0), 1.0);
+}
+
+#[test]
+fn nan() {
+    let mut histogram = BFloat16Histogram::new();
+    histogram.insert(f32::NAN);
+    assert!(histogram.is_empty());
+    assert!(histogram.quantile(0.5).is_nan());
+}
+
+#[test]
+fn state() {
+    let histogram = BFloat16Histogram::from([0.0, 1.0, 2.0, 2.0]);
+    assert_eq!(
+        histogram.to_bytes(),
+        [
+            0x03, // Number of cells.
+            0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 0.0
+            0x80, 0x3f, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 1.0
+            0x00, 0x40, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 2.0
+        ]
+    );
+}
+
+#[test]
+fn invalid_state() {
+    assert!(BFloat16Histogram::from_bytes(&[]).is_err());
+    assert!(BFloat16Histogram::from_bytes(&[0x01, 0x80, 0x3f]).is_err());
+    assert!(BFloat16Histogram::from_bytes(&[0x00, 0x00]).is_err());
+    assert_eq!(
+        BFloat16Histogram::from_bytes(&[0x00]),
+        Ok(BFloat16Histogram::new())
+    );
+}
@@ -0,0 +1,153 @@
+use tdigest_ch::{bulk, TDigest};
+
+#[test]
+fn matches_inserts() {
+    let chunks: Vec<Vec<f32>> = (0..8)
+        .map(|chunk| (0..25_000).map(|v| (v * 8 + chunk) as f32).collect())
+        .collect();
+    let mut bulk = bulk::from_sorted_chunks(TDigest::new(), chunks.clone());
+    let mut inserted = TDigest::from_iter(chunks.into_iter().flatten());
+
+    assert_eq!(bulk.len(), 200_000);
+    for level in [0., 0.01, 0.25, 0.5, 0.75, 0.99, 1.] {
+        let (b, i) = (bulk.quantile(level), inserted.quantile(level));
+        assert!((b - i).abs() < 200., "level {}: {} vs {}", level, b, i);
+    }
+    assert_eq!(bulk.quantile(0.), 0.);
+    assert_eq!(bulk.quantile(1.), 199_999.);
+}
+
+#[test]
+fn respects_config() {
+    let mut builder = TDigest::builder();
+    builder.max_centroids(16);
+    builder.retain_extremes(2);
+    let chunks = [vec![1., 5., f32::NAN], vec![], vec![2., 3., 4.]];
+
+    let mut digest = bulk::from_sorted_chunks(builder.build(), chunks);
+    assert_eq!(digest.len(), 5);
+    assert_eq!(digest.smallest(), [1., 2.]);
+    assert_eq!(digest.largest(), [5., 4.]);
+    assert_eq!(digest.quantile(0.5), 3.);
+
+    // The t-digest keeps working as usual.
+    digest.insert(6.);
+    assert_eq!(digest.quantile(1.), 6.);
+}
+
+#[test]
+fn empty() {
+    let chunks: [Vec<f32>; 0] = [];
+    assert!(bulk::from_sorted_chunks(TDigest::new(), chunks).is_empty());
+}
+
+#[test]
+fn nans_do_not_size_centroids() {
+    let chunks: Vec<Vec<f32>> = (0..4)
+        .map(|chunk| (0..10_000).map(|v| (v * 4 + chunk) as f32).collect())
+        .collect();
+    let mut plain = bulk::from_sorted_chunks(TDigest::new(), chunks.clone());
+    let with_nans = chunks.into_iter().map(|chunk| {
+        std::iter::repeat_n(f32::NAN, 100_000)
+            .chain(chunk)
+            .collect::<Vec<_>>()
+    });
+    let mut digest = bulk::from_sorted_chunks(TDigest::new(), with_nans);
+
+    assert_eq!(digest.nan_count(), 400_000);
+    assert_eq!(digest.stats().centroids(), plain.stats().centroids());
+    for level in [0., 0.001, 0.01, 0.5, 0.99, 0.999, 1.] {
+        assert_eq!(digest.quantile(level), plain.quantile(level));
+    }
+}
+
+#[test]
+#[should_panic(expected = "chunks must be sorted")]
+fn unsorted_chunk() {
+    bulk::from_sorted_chunks(TDigest::new(), [vec![2., 1.]]);
+}
+
+#[test]
+fn extend_sorted_matches_inserts() {
+    let mut sorted = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    let mut inserted = sorted.clone();
+    let values: Vec<f32> = (0..100_000).map(|v| v as f32 + 0.5).collect();
+    sorted.insert_sorted_slice(&values);
+    inserted.extend(values.iter().copied());
+
+    assert_eq!(sorted.len(), 200_000);
+    assert_eq!(sorted.mean(), inserted.mean());
+    for level in [0., 0.01, 0.25, 0.5, 0.75, 0.99, 1.] {
+        let (s, i) = (sorted.quantile(level), inserted.quantile(level));
+        assert!((s - i).abs() < 200., "level {}: {} vs {}", level, s, i);
+    }
+
+    sorted.extend_sorted([f32::NAN, 200_000., f32::NAN]);
+    assert_eq!(sorted.len(), 200_001);
+    assert_eq!(sorted.nan_count(), 2);
+    assert_eq!(sorted.quantile(1.), 200_000.);
+}
+
+#[test]
+fn extend_sorted_keeps_config() {
+    let mut builder = TDigest::builder();
+    builder
+        .max_centroids(16)
+        .retain_extremes(2)
+        .retain_raw_samples(10);
+    let mut digest = builder.build();
+    digest.insert(3.);
+    digest.insert_sorted_slice(&[1., 2., 4., 5.]);
+    assert_eq!(digest.smallest(), [1., 2.]);
+    assert_eq!(digest.largest(), [5., 4.]);
+    assert!(digest.is_exact());
+    assert_eq!(digest.quantile(0.5), 3.);
+
+    digest.insert_sorted_slice(&(0..1000).map(|v| v as f32).collect::<Vec<_>>());
+    assert!(!digest.is_exact());
+    assert!(digest.stats().centroids() <= 16);
+}
+
+#[test]
+#[should_panic(expected = "values must be sorted")]
+fn extend_unsorted() {
+    TDigest::new().insert_sorted_slice(&[2., 1.]);
+}
+
+#[test]
+fn extend_unsorted_leaves_digest() {
+    let mut digest = TDigest::new();
+    digest.extend_sorted((0..1000).map(|v| v as f32));
+    let before = digest.clone();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        digest.extend_sorted([f32::NAN, 1., 3., 2.]);
+    }));
+    assert!(result.is_err());
+    assert_eq!(digest.len(), 1000);
+    assert_eq!(digest.nan_count(), 0);
+    assert_eq!(digest, before);
+}
+
+#[test]
+fn extend_from_slice_matches_inserts() {
+    let values: Vec<f32> = (0..100_000)
+        .map(|v| ((v * 7919) % 100_000) as f32)
+        .collect();
+    let mut sliced = TDigest::from_iter((0..1000).map(|v| v as f32 * 0.25));
+    let mut inserted = sliced.clone();
+    sliced.extend_from_slice(&values);
+    inserted.extend(values.iter().copied());
+
+    assert_eq!(sliced.len(), 101_000);
+    assert_eq!(sliced.mean(), inserted.mean());
+    for level in [0., 0.01, 0.25, 0.5, 0.75, 0.99, 1.] {
+        let (s, i) = (sliced.quantile(level), inserted.quantile(level));
+        assert!((s - i).abs() < 200., "level {}: {} vs {}", level, s, i);
+    }
+
+    sliced.extend_from_slice(&[f32::NAN, -1., f32::NAN, f32::INFINITY]);
+    assert_eq!(sliced.len(), 101_002);
+    assert_eq!(sliced.nan_count(), 2);
+    assert_eq!(sliced.quantile(0.), -1.);
+    assert_eq!(sliced.quantile(1.), f32::INFINITY);
+}
@@ -36,7 +36,7 @@ fn ints() {
     let values = [1.0, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 2.0, 3.0];
     let mut digest = TDigest::from_iter(values.iter().copied());
     assert_eq!(digest.quantile(0.5), 2.0);
-    assert_eq!(digest.len(), values.len());
+    assert_eq!(digest.len(), values.len() as u64);
 
     let mut digest = TDigest::from([1.0, 1.0, 2.0, 2.0, 3.0, 4.0, 4.0, 4.0, 5.0, 5.0]);
     assert_eq!(digest.quantile(0.3), 2.0);
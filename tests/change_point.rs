@@ -0,0 +1,48 @@
+use tdigest_ch::{ChangePointDetector, Divergence};
+
+/// Returns a permutation of `0..n`, so that each window sees the whole range.
+fn spread(n: u32) -> impl Iterator<Item = f32> {
+    (0..n).map(move |i| (i * 7919 % n) as f32)
+}
+
+#[test]
+fn stable_distribution() {
+    for divergence in [Divergence::Ks, Divergence::Psi] {
+        let mut detector = ChangePointDetector::new(divergence, 0.1, 1_000);
+        for _ in 0..5 {
+            assert!(spread(1_000).all(|v| detector.insert(v).is_none()));
+        }
+        assert_eq!(detector.reference().len(), 5_000);
+        assert!(detector.window().is_empty());
+    }
+}
+
+#[test]
+fn ks_upper_tail_shift() {
+    let mut detector = ChangePointDetector::new(Divergence::Ks, 0.1, 1_000);
+    detector.extend(spread(1_000));
+
+    // The upper half of the distribution is stretched.
+    let stretched = spread(1_000).map(|v| if v < 500. { v } else { 2. * v });
+    let events: Vec<_> = stretched.filter_map(|v| detector.insert(v)).collect();
+    assert_eq!(events.len(), 1);
+    let event = events[0];
+    assert!((event.statistic() - 0.5).abs() < 0.02);
+    assert_eq!(event.level(), 1.0);
+    assert!(event.after() > event.before());
+
+    // The shifted window is the new reference.
+    assert!((detector.reference().clone().quantile(1.0) - 1998.).abs() < 2.);
+}
+
+#[test]
+fn psi_upper_tail_shift() {
+    let mut detector = ChangePointDetector::new(Divergence::Psi, 0.25, 1_000);
+    detector.extend(spread(1_000));
+
+    // The upper decile moves to the lower one.
+    let shifted = spread(1_000).map(|v| if v >= 900. { v - 900. } else { v });
+    let event = shifted.filter_map(|v| detector.insert(v)).next().unwrap();
+    assert!(event.statistic() > 0.25);
+    assert_eq!(event.level(), 0.95);
+}
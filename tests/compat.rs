@@ -0,0 +1,51 @@
+#![cfg(feature = "compat")]
+
+use tdigest_ch::{compat, Error, TDigest};
+
+#[test]
+fn golden_vectors() {
+    for vector in compat::golden_vectors() {
+        assert_eq!(vector.levels().len(), vector.expected().len());
+        let mut digest = vector.digest();
+        assert_eq!(
+            digest.len(),
+            (vector.runs().iter().map(|run| run.1).sum::<usize>() * vector.repeat()) as u64
+        );
+        let estimates = digest.quantiles_many(vector.levels());
+        assert!(
+            vector.matches(&estimates),
+            "{}: {estimates:?}",
+            vector.name()
+        );
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    let state = compat::encode(&mut digest);
+    let mut decoded = compat::decode(&state).unwrap();
+    assert_eq!(decoded.len(), digest.len());
+    for level in [0.01, 0.5, 0.99] {
+        assert_eq!(decoded.quantile(level), digest.quantile(level));
+    }
+    assert_eq!(compat::encode(&mut decoded), state);
+    assert_eq!(compat::encode(&mut TDigest::new()), [0]);
+}
+
+#[test]
+fn invalid_states() {
+    assert_eq!(compat::decode(&[]), Err(Error::UnexpectedEof));
+    assert_eq!(compat::decode(&[0, 0]), Err(Error::TrailingBytes));
+    let mut nan = vec![1];
+    nan.extend(f32::NAN.to_le_bytes());
+    nan.extend(1f32.to_le_bytes());
+    assert!(matches!(compat::decode(&nan), Err(Error::InvalidState(_))));
+    let mut fractional = vec![1];
+    fractional.extend(1f32.to_le_bytes());
+    fractional.extend(0.5f32.to_le_bytes());
+    assert!(matches!(
+        compat::decode(&fractional),
+        Err(Error::InvalidState(_))
+    ));
+}
@@ -0,0 +1,47 @@
+use tdigest_ch::{CompressedTDigest, TDigest};
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn send_sync() {
+    assert_send_sync::<CompressedTDigest>();
+}
+
+#[test]
+fn matches_digest() {
+    let mut builder = TDigest::builder();
+    builder.retain_extremes(2);
+    let mut digest = builder.build();
+    digest.extend((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+    let frozen = digest.clone().freeze();
+
+    assert_eq!(frozen.len(), digest.len());
+    assert_eq!(frozen.smallest(), digest.smallest());
+    assert_eq!(frozen.largest(), digest.largest());
+    for level in [0., 0.1, 0.5, 0.99, 1.] {
+        assert_eq!(frozen.quantile(level), digest.quantile(level));
+        assert_eq!(frozen.quantiles().get(level), digest.quantile(level));
+        let (lhs, rhs) = (
+            frozen.quantile_explain(level),
+            digest.quantile_explain(level),
+        );
+        assert_eq!(lhs.rule(), rhs.rule());
+        assert_eq!((lhs.lower(), lhs.upper()), (rhs.lower(), rhs.upper()));
+    }
+    for value in [-1., 500., 5_000., 20_000.] {
+        assert_eq!(frozen.cdf(value), digest.cdf(value));
+        assert_eq!(frozen.rank(value), digest.rank(value));
+    }
+    assert_eq!(frozen.apdex(100., 400.), digest.apdex(100., 400.));
+    assert_eq!(frozen.range_stats(0.9, 1.), digest.range_stats(0.9, 1.));
+    assert_eq!(frozen.trimmed_mean(0.1, 0.9), digest.trimmed_mean(0.1, 0.9));
+    assert_eq!(frozen.build_lookup_table(11), digest.build_lookup_table(11));
+    assert_eq!(
+        frozen.centroid_weight_profile(),
+        digest.centroid_weight_profile()
+    );
+
+    let mut thawed = frozen.thaw();
+    thawed.insert(-5.);
+    assert_eq!(thawed.quantile(0.), -5.);
+}
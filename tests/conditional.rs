@@ -0,0 +1,84 @@
+use tdigest_ch::{BinnedDigests, Bins, ConditionalDigests, TDigest};
+
+#[test]
+fn binned_condition() {
+    let bins = Bins::new(vec![100., 1_000.]);
+    let mut digests = ConditionalDigests::new();
+    for size in 0..2_000 {
+        // Latency grows with the payload size.
+        digests.insert(bins.index(size as f32).unwrap(), size as f32 / 10.);
+    }
+
+    assert_eq!(digests.conditions().copied().collect::<Vec<_>>(), [0, 1, 2]);
+    assert_eq!(digests.get(&0).unwrap().len(), 100);
+    assert_eq!(digests.get(&1).unwrap().len(), 900);
+    assert_eq!(digests.get(&2).unwrap().len(), 1_000);
+    assert!((digests.quantile(&2, 0.5) - 150.).abs() < 2.);
+    assert!(digests.shift(&0, &2, 0.5) > 100.);
+}
+
+#[test]
+fn merge_and_template() {
+    let mut builder = TDigest::builder();
+    builder.retain_extremes(1);
+    let template = builder.build();
+
+    let mut a = ConditionalDigests::with_template(template.clone());
+    a.extend([(200, 10.0), (200, 20.0), (500, 300.0)]);
+    let mut b = ConditionalDigests::with_template(template);
+    b.extend([(200, 30.0), (404, 5.0)]);
+
+    a |= &b;
+    assert_eq!(a.len(), 5);
+    assert_eq!(a.quantile(&200, 0.5), 20.0);
+    assert_eq!(a.get(&404).unwrap().largest(), [5.0]);
+    assert_eq!(a.overall().len(), 5);
+    assert_eq!(a.overall().smallest(), [5.0]);
+}
+
+#[test]
+fn quantile_given_covariate_range() {
+    let edges: Vec<f32> = (1..100).map(|i| i as f32 * 100.).collect();
+    let mut digests = BinnedDigests::new(Bins::new(edges));
+    for size in 0..10_000 {
+        // Latency grows with the payload size.
+        digests.insert(size as f32, size as f32 / 10.);
+    }
+    assert_eq!(digests.len(), 10_000);
+    assert_eq!(digests.digests().conditions().count(), 100);
+
+    // Ranges on the edges of the bins are exact.
+    let mut given = digests.digest_given(2_000.0..3_000.0);
+    assert_eq!(given.len(), 1_000);
+    assert_eq!(given.quantile(0.0), 200.);
+    assert_eq!(given.quantile(1.0), 299.9);
+    assert!((digests.quantile_given(2_000.0..3_000.0, 0.5) - 250.).abs() < 1.);
+    assert_eq!(digests.digest_given(2_000.0..=3_000.0).len(), 1_100);
+    assert_eq!(digests.digest_given(2_050.0..2_060.0).len(), 100);
+    assert_eq!(digests.digest_given(..).len(), 10_000);
+    assert!(digests.digest_given(3_000.0..2_000.0).is_empty());
+
+    let mut other = BinnedDigests::new(digests.bins().clone());
+    other.extend([(50_000., 1e6)]);
+    digests |= &other;
+    assert_eq!(digests.quantile_given(9_900.0.., 1.0), 1e6);
+}
+
+#[test]
+fn nan_covariates() {
+    let bins = Bins::new(vec![1.0]);
+    assert_eq!(bins.index(f32::NAN), None);
+
+    let mut digests = BinnedDigests::new(bins);
+    digests.extend([(0.5, 10.0), (f32::NAN, 20.0), (2.0, 30.0)]);
+    assert_eq!(digests.len(), 2);
+    assert_eq!(digests.digest_given(..).len(), 2);
+    assert!(digests.digest_given(f32::NAN..).is_empty());
+    assert!(digests.digest_given(..f32::NAN).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "edges must be strictly increasing")]
+fn nan_edge() {
+    let _ = Bins::new(vec![f32::NAN]);
+}
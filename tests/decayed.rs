@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+
+use tdigest_ch::DecayedTDigest;
+
+#[test]
+fn tracks_recent_values() {
+    let mut digest = DecayedTDigest::new(Duration::from_secs(10));
+    assert!(digest.is_empty());
+    assert!(digest.quantile(0.5).is_nan());
+
+    let start = Instant::now();
+    for second in 0..1000 {
+        let at = start + Duration::from_secs(second);
+        digest.record(if second < 900 { 1.0 } else { 100.0 }, at);
+    }
+    digest.record(f32::NAN, start);
+    assert_eq!(digest.len(), 1000);
+
+    // 100 seconds of new values dwarf 900 seconds of old ones.
+    assert_eq!(digest.quantile(0.01), 100.0);
+    let now = start + Duration::from_secs(999);
+    let expected = 1. / (1. - 0.1f64.exp2().recip());
+    assert!((digest.total_weight_at(now) - expected).abs() < 1e-6 * expected);
+}
+
+#[test]
+fn rescales_weights_after_long_gaps() {
+    let mut digest = DecayedTDigest::new(Duration::from_millis(1));
+    let start = Instant::now();
+    digest.record(1.0, start);
+    digest.record(2.0, start + Duration::from_millis(100));
+    assert_eq!(digest.len(), 2);
+    assert_eq!(
+        digest.total_weight_at(start + Duration::from_millis(100)),
+        1.0
+    );
+
+    // Far more half-lives than a weight can span.
+    let later = start + Duration::from_secs(10);
+    digest.record(3.0, later);
+    assert_eq!(digest.len(), 1);
+    assert_eq!(digest.quantile(0.0), 3.0);
+    assert_eq!(digest.total_weight_at(later), 1.0);
+
+    digest.clear();
+    assert!(digest.is_empty());
+}
@@ -0,0 +1,82 @@
+use tdigest_ch::DeterministicReservoir;
+
+#[test]
+fn order_independence() {
+    let values: Vec<(f32, u64)> = (0..100_000u64).map(|i| ((i % 977) as f32, i)).collect();
+
+    let mut forward = DeterministicReservoir::from_iter(values.iter().copied());
+    let mut backward = DeterministicReservoir::from_iter(values.iter().rev().copied());
+    assert!(forward.sample_len() <= 8192);
+    for level in [0.0, 0.1, 0.5, 0.9, 0.99, 1.0] {
+        assert_eq!(forward.quantile(level), backward.quantile(level));
+    }
+}
+
+#[test]
+fn merge_independence() {
+    let values: Vec<(f32, u64)> = (0..50_000u64).map(|i| (i as f32, i)).collect();
+
+    let mut whole = DeterministicReservoir::from_iter(values.iter().copied());
+    let mut merged = DeterministicReservoir::new();
+    for chunk in values.chunks(7_000) {
+        merged |= &DeterministicReservoir::from_iter(chunk.iter().copied());
+    }
+    assert_eq!(merged.len(), whole.len());
+    assert_eq!(merged.sample_len(), whole.sample_len());
+    for level in [0.0, 0.25, 0.5, 0.75, 1.0] {
+        assert_eq!(merged.quantile(level), whole.quantile(level));
+    }
+}
+
+#[test]
+fn state() {
+    let reservoir = DeterministicReservoir::from_iter([(1.0, 0), (2.0, 1)]);
+    let bytes = reservoir.to_bytes();
+    assert_eq!(bytes.len(), 16 + 2 * 8);
+    assert_eq!(
+        bytes[..16],
+        [2, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]
+    );
+    // intHash64(0) is zero.
+    assert_eq!(
+        bytes[16..24],
+        [0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0x00]
+    );
+
+    let mut other = DeterministicReservoir::from_bytes(&bytes).unwrap();
+    assert_eq!(other.len(), 2);
+    assert_eq!(other.quantile(1.0), 2.0);
+}
+
+#[test]
+fn invalid_state() {
+    assert!(DeterministicReservoir::from_bytes(&[0; 15]).is_err());
+    assert!(DeterministicReservoir::from_bytes(&[0; 17]).is_err());
+    assert!(
+        DeterministicReservoir::from_bytes(&[1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0])
+            .is_err()
+    );
+}
+
+#[test]
+fn constant_determinator() {
+    let mut reservoir = DeterministicReservoir::new();
+    for i in 0..9000 {
+        reservoir.insert(i as f32, 0);
+    }
+    assert_eq!(reservoir.len(), 9000);
+    assert_eq!(reservoir.sample_len(), 8192);
+    assert_eq!(reservoir.quantile(0.0), 0.0);
+    assert_eq!(reservoir.quantile(1.0), 8191.0);
+
+    let mut merged = reservoir.clone();
+    merged |= &reservoir;
+    assert_eq!(merged.sample_len(), 8192);
+}
+
+#[test]
+fn quantile_clamps_level() {
+    let mut reservoir = DeterministicReservoir::from_iter([(1.0, 0), (2.0, 1), (3.0, 2)]);
+    assert_eq!(reservoir.quantile(-0.5), 1.0);
+    assert_eq!(reservoir.quantile(1.5), 3.0);
+}
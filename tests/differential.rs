@@ -0,0 +1,36 @@
+#![cfg(feature = "differential")]
+
+use tdigest_ch::{differential::Differential, TDigest};
+
+#[test]
+fn error_grows_with_compression() {
+    let values = (0..100_000).map(|v| ((v * 7919) % 100_000) as f32);
+    let levels = [0.01, 0.25, 0.5, 0.75, 0.99];
+
+    let mut exact = Differential::with_template(TDigest::exact_for_test());
+    exact.extend(values.clone());
+    let report = exact.report(&levels);
+    assert_eq!(report.estimates(), report.exact());
+    assert_eq!(report.max_rank_error(), 0.);
+
+    let mut builder = TDigest::builder();
+    builder.max_centroids(32);
+    let mut coarse = Differential::with_template(builder.build());
+    coarse.extend(values);
+    let report = coarse.report(&levels);
+    assert_eq!(report.levels(), levels);
+    assert!(report.max_rank_error() > 0.);
+    assert!(report.max_rank_error() < 0.05);
+    assert!(report.max_value_error() < 5_000.);
+}
+
+#[test]
+fn empty() {
+    let mut differential = Differential::new();
+    differential.insert(f32::NAN);
+    assert!(differential.is_empty());
+
+    let report = differential.report(&[0.5]);
+    assert!(report.exact()[0].is_nan());
+    assert!(report.rank_errors()[0].is_nan());
+}
@@ -0,0 +1,331 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use tdigest_ch::{DigestMap, Interpolation, MemoryPressure, TDigest, Unit};
+
+#[test]
+fn aggregate_matches_per_key_digests() {
+    let pairs: Vec<(u32, f32)> = (0..100_000).map(|v| (v % 7, v as f32)).collect();
+    let mut digests = DigestMap::from_pairs(pairs.iter().copied());
+    assert_eq!(digests.len(), 7);
+
+    for key in 0..7 {
+        let mut expected = TDigest::from_iter(pairs.iter().filter(|p| p.0 == key).map(|p| p.1));
+        let digest = digests.get(&key).unwrap();
+        assert_eq!(digest.len(), expected.len());
+        assert_eq!(digests.quantile(&key, 0.5), expected.quantile(0.5));
+    }
+}
+
+#[test]
+fn grouped_batches() {
+    let mut builder = TDigest::builder();
+    builder.retain_extremes(1);
+    let mut digests = DigestMap::with_template(builder.build());
+    digests.reserve(3);
+    digests.aggregate_by_key([
+        ("a", 1.0),
+        ("a", 2.0),
+        ("b", f32::NAN),
+        ("b", 10.0),
+        ("a", 3.0),
+        ("c", f32::NAN),
+    ]);
+
+    assert_eq!(digests.len(), 2);
+    assert_eq!(digests.get(&"a").unwrap().len(), 3);
+    assert_eq!(digests.get(&"a").unwrap().largest(), [3.0]);
+    assert_eq!(digests.get(&"b").unwrap().len(), 1);
+    assert!(digests.get(&"c").is_none());
+
+    digests.extend([("c", 5.0)]);
+    assert_eq!(digests.remove(&"c").unwrap().len(), 1);
+    assert_eq!(digests.len(), 2);
+
+    // Only the t-digests of the aggregated keys are compressed.
+    digests.insert("a", 4.0);
+    digests.aggregate_by_key([("b", 11.0), ("b", 12.0)]);
+    assert_eq!(digests.get(&"a").unwrap().stats().unmerged(), 1);
+    assert_eq!(digests.get(&"b").unwrap().stats().unmerged(), 0);
+}
+
+#[test]
+fn merge() {
+    let mut a: DigestMap<_> = [("x", 1.0), ("y", 2.0)].into_iter().collect();
+    let b = DigestMap::from_pairs([("y", 4.0), ("z", 8.0)]);
+    a |= &b;
+    assert_eq!(a.len(), 3);
+    assert_eq!(a.quantile(&"y", 1.0), 4.0);
+    a.clear();
+    assert!(a.is_empty());
+}
+
+#[test]
+fn memory_pressure() {
+    let pressure = MemoryPressure::new();
+    let mut digests = DigestMap::new();
+    pressure.signal();
+    digests.set_memory_pressure(&pressure);
+
+    // Signals sent before the registration are ignored.
+    digests.insert("a", 1.0);
+    digests.insert("b", 2.0);
+    digests.insert("a", 3.0);
+    let mut expected = TDigest::from([1.0]);
+    expected.insert(3.0);
+    assert_eq!(digests.get(&"a"), Some(&expected));
+
+    pressure.clone().signal();
+    digests.insert("c", 4.0);
+    assert_eq!(digests.get(&"a"), Some(&expected.clone().freeze().thaw()));
+    assert_eq!(
+        digests.get(&"b"),
+        Some(&TDigest::from([2.0]).freeze().thaw())
+    );
+    assert_eq!(digests.get(&"c"), Some(&TDigest::from([4.0])));
+}
+
+/// Returns an empty directory for the checkpoints of a test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tdigest-ch-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Returns the files of the keys in the manifest of a checkpoint.
+fn manifest_files(dir: &Path) -> HashMap<String, String> {
+    let manifest = fs::read_to_string(dir.join("MANIFEST")).unwrap();
+    manifest
+        .lines()
+        .skip(2)
+        .map(|line| {
+            let (file, key) = line.split_once(' ').unwrap();
+            (key.to_string(), file.to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn persist_restore() {
+    let dir = scratch_dir("persist-restore");
+    let mut builder = TDigest::builder();
+    builder
+        .retain_extremes(2)
+        .retain_raw_samples(4)
+        .interpolation(Interpolation::Dunning)
+        .unit(Unit::Millis);
+    let mut digests = DigestMap::with_template(builder.build());
+    for v in 0..1000 {
+        digests.insert("plain".to_string(), v as f32);
+    }
+    digests.insert("with space".to_string(), 1.0);
+    digests.insert("line\nbreak\\".to_string(), 2.0);
+    digests
+        .get_mut(&"plain".to_string())
+        .unwrap()
+        .set_label("host", "a");
+    digests
+        .get_mut(&"with space".to_string())
+        .unwrap()
+        .insert(f32::NAN);
+    digests.persist(&dir).unwrap();
+
+    let mut restored: DigestMap<String> = DigestMap::restore(&dir).unwrap();
+    assert_eq!(restored, digests);
+    let plain = restored.get(&"plain".to_string()).unwrap();
+    assert_eq!(plain.labels().get("host").map(String::as_str), Some("a"));
+    assert_eq!(plain.unit(), Some(&Unit::Millis));
+    assert_eq!(
+        restored.quantile(&"plain".to_string(), 0.99),
+        digests.quantile(&"plain".to_string(), 0.99)
+    );
+    assert_eq!(
+        restored.get(&"with space".to_string()).unwrap().nan_count(),
+        1
+    );
+
+    // New keys get the configuration of the template.
+    restored.insert("new".to_string(), 3.0);
+    digests.insert("new".to_string(), 3.0);
+    assert_eq!(restored, digests);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn persist_incremental() {
+    let dir = scratch_dir("persist-incremental");
+    let mut digests = DigestMap::from_pairs((0..100).map(|v| (v % 4, v as f32)));
+    digests.persist(&dir).unwrap();
+    let before = manifest_files(&dir);
+    assert_eq!(before.len(), 4);
+
+    digests.insert(1, 1000.0);
+    digests.remove(&2);
+    digests.quantile(&3, 0.5);
+    digests.persist(&dir).unwrap();
+    let after = manifest_files(&dir);
+    assert_eq!(after.len(), 3);
+    assert_eq!(after["0"], before["0"]);
+    assert_ne!(after["1"], before["1"]);
+    assert_eq!(after["3"], before["3"]);
+
+    // The files of the previous checkpoint are removed.
+    assert!(!dir.join(format!("{}.tdigest", before["1"])).exists());
+    assert!(!dir.join(format!("{}.tdigest", before["2"])).exists());
+    assert_eq!(DigestMap::<u32>::restore(&dir).unwrap(), digests);
+
+    // A restored map only writes what changed since.
+    let mut restored = DigestMap::<u32>::restore(&dir).unwrap();
+    restored.get_mut(&0).unwrap().insert(-1.0);
+    restored.persist(&dir).unwrap();
+    let last = manifest_files(&dir);
+    assert_ne!(last["0"], after["0"]);
+    assert_eq!(last["1"], after["1"]);
+    assert_eq!(last["3"], after["3"]);
+
+    // Clones and other directories get all the t-digests.
+    let other = scratch_dir("persist-incremental-other");
+    restored.clone().persist(&other).unwrap();
+    assert_eq!(manifest_files(&other).len(), 3);
+    assert_eq!(DigestMap::<u32>::restore(&other).unwrap(), restored);
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&other).unwrap();
+}
+
+#[test]
+fn persist_leftovers() {
+    let dir = scratch_dir("persist-leftovers");
+    fs::create_dir_all(&dir).unwrap();
+    // Files of an interrupted checkpoint, and an unrelated file.
+    fs::write(dir.join("7.tdigest"), b"stale").unwrap();
+    fs::write(dir.join("8.tmp"), b"partial").unwrap();
+    fs::write(dir.join("notes.txt"), b"keep").unwrap();
+
+    let mut digests = DigestMap::from_pairs([("a".to_string(), 1.0)]);
+    digests.persist(&dir).unwrap();
+    assert!(!dir.join("7.tdigest").exists());
+    assert!(!dir.join("8.tmp").exists());
+    assert!(dir.join("notes.txt").exists());
+
+    // A crash before the manifest is written leaves the previous checkpoint.
+    fs::write(dir.join("100.tdigest"), b"partial").unwrap();
+    fs::write(dir.join("MANIFEST.tmp"), b"partial").unwrap();
+    assert_eq!(DigestMap::<String>::restore(&dir).unwrap(), digests);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn restore_errors() {
+    let dir = scratch_dir("restore-errors");
+    let err = DigestMap::<String>::restore(&dir).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let mut digests = DigestMap::from_pairs([("a".to_string(), 1.0)]);
+    digests.persist(&dir).unwrap();
+    let err = DigestMap::<u32>::restore(&dir).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let file = &manifest_files(&dir)["a"];
+    fs::write(dir.join(format!("{file}.tdigest")), b"TDCH").unwrap();
+    let err = DigestMap::<String>::restore(&dir).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn evict_least_recently_used() {
+    let (archive, archived) = mpsc::channel();
+    let mut digests = DigestMap::new();
+    digests.set_eviction_callback(move |key, digest: TDigest| {
+        archive.send((key, digest.len())).unwrap()
+    });
+    digests.set_max_keys(32);
+    for key in 0..32 {
+        digests.insert(key, key as f32);
+    }
+    assert!(archived.try_recv().is_err());
+
+    // Used keys are kept, and a sixteenth of the keys are evicted at once.
+    digests.insert(0, 1.0);
+    digests.get_mut(&1).unwrap();
+    assert!(digests.quantile(&2, 0.5) == 2.0);
+    digests.insert(32, 32.0);
+    assert_eq!(digests.len(), 30);
+    let mut evicted: Vec<_> = archived.try_iter().collect();
+    evicted.sort_unstable();
+    assert_eq!(evicted, [(3, 1), (4, 1), (5, 1)]);
+    for key in [0, 1, 2, 6, 32] {
+        assert!(digests.get(&key).is_some());
+    }
+
+    // Lowering the limit evicts right away.
+    digests.set_max_keys(1);
+    assert_eq!(digests.len(), 1);
+    assert_eq!(digests.get(&32).map(TDigest::len), Some(1));
+    assert_eq!(archived.try_iter().count(), 29);
+    digests.remove(&32);
+    assert!(archived.try_recv().is_err());
+}
+
+#[test]
+fn evict_idle() {
+    let ttl = Duration::from_secs(3600);
+    let mut digests = DigestMap::new();
+    digests.insert("old", 1.0);
+    digests.set_idle_ttl(ttl);
+    thread::sleep(Duration::from_millis(1));
+    let start = Instant::now();
+    digests.insert("new", 2.0);
+
+    digests.evict_idle(start + ttl / 2);
+    assert_eq!(digests.len(), 2);
+    digests.evict_idle(start + ttl);
+    assert_eq!(digests.keys().collect::<Vec<_>>(), [&"new"]);
+
+    // Later uses of the map evict idle keys too.
+    digests.set_idle_ttl(Duration::from_millis(1));
+    thread::sleep(Duration::from_millis(5));
+    digests.insert("newer", 3.0);
+    assert_eq!(digests.keys().collect::<Vec<_>>(), [&"newer"]);
+}
+
+#[test]
+fn evict_memory_budget() {
+    let mut digests = DigestMap::new();
+    digests.insert(0, 1.0);
+    let bytes = digests.get(&0).unwrap().stats().memory_bytes();
+    digests.set_memory_budget(4 * bytes);
+    for key in 1..4 {
+        digests.insert(key, 1.0);
+    }
+    assert_eq!(digests.len(), 4);
+
+    // The oldest keys are evicted to within 15/16 of the budget.
+    digests.insert(4, 1.0);
+    let mut keys: Vec<_> = digests.keys().copied().collect();
+    keys.sort_unstable();
+    assert_eq!(keys, [2, 3, 4]);
+
+    // The most recently used key is kept, even beyond the budget.
+    digests
+        .get_mut(&2)
+        .unwrap()
+        .extend((0..10_000).map(|v| v as f32));
+    digests.insert(5, 1.0);
+    assert_eq!(digests.keys().collect::<Vec<_>>(), [&5]);
+    digests.set_memory_budget(0);
+    assert_eq!(digests.len(), 1);
+}
+
+#[test]
+fn digest_map_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<DigestMap<String>>();
+}
@@ -0,0 +1,63 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tdigest_ch::{fit, fit::Distribution, TDigest};
+
+#[test]
+fn normal() {
+    // Normal values with the Box-Muller transform.
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut digest = TDigest::new();
+    for _ in 0..50_000 {
+        let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+        let z = (-2. * (1. - u).ln()).sqrt() * (2. * std::f64::consts::PI * v).cos();
+        digest.insert((100. + 15. * z) as f32);
+    }
+
+    let normal = Distribution::Normal {
+        mean: 100.,
+        std_dev: 15.,
+    };
+    assert!(fit::ks_statistic(&mut digest, &normal) < 0.02);
+
+    let wider = Distribution::Normal {
+        mean: 100.,
+        std_dev: 30.,
+    };
+    assert!(fit::ks_statistic(&mut digest, &wider) > 0.15);
+
+    let uniform = Distribution::Uniform {
+        low: 55.,
+        high: 145.,
+    };
+    assert!(fit::ks_statistic(&mut digest, &uniform) > 0.05);
+}
+
+#[test]
+fn exponential() {
+    let mut digest =
+        TDigest::from_iter((0..100_000).map(|v| -f32::ln(1. - (v as f32 + 0.5) / 100_000.) / 4.));
+    let exponential = Distribution::Exponential { rate: 4. };
+    assert!(fit::ks_statistic(&mut digest, &exponential) < 0.005);
+    let slower = Distribution::Exponential { rate: 2. };
+    assert!(fit::ks_statistic(&mut digest, &slower) > 0.2);
+}
+
+#[test]
+fn cdf() {
+    let normal = Distribution::Normal {
+        mean: 0.,
+        std_dev: 1.,
+    };
+    for (value, expected) in [
+        (-3., 0.0013499),
+        (-1., 0.1586553),
+        (0.5, 0.6914625),
+        (2., 0.9772499),
+    ] {
+        assert!((normal.cdf(value) - expected).abs() < 1e-6);
+    }
+    let uniform = Distribution::Uniform { low: 1., high: 3. };
+    assert_eq!(uniform.cdf(0.), 0.);
+    assert_eq!(uniform.cdf(2.), 0.5);
+    assert_eq!(uniform.cdf(4.), 1.);
+    assert!(fit::ks_statistic(&mut TDigest::new(), &uniform).is_nan());
+}
@@ -0,0 +1,92 @@
+use rand::seq::SliceRandom;
+use tdigest_ch::{GkSummary, TDigest};
+
+fn assert_rank_error(summary: &mut GkSummary, n: usize, epsilon: f64) {
+    for level in [0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99] {
+        let quantile = summary.quantile(level);
+        let rank = quantile as f64 / n as f64;
+        assert!(
+            (rank - level).abs() <= epsilon,
+            "level {}, quantile {}",
+            level,
+            quantile
+        );
+    }
+}
+
+#[test]
+fn uniform() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let mut summary = GkSummary::from_iter(values.iter().map(|&v| v as f32));
+    assert_eq!(summary.len(), 100_000);
+    assert_rank_error(&mut summary, values.len(), 0.01);
+}
+
+#[test]
+fn merge() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let mut summary = GkSummary::new();
+    for chunk in values.chunks(7_000) {
+        summary |= &GkSummary::from_iter(chunk.iter().map(|&v| v as f32));
+    }
+    assert_eq!(summary.len(), 100_000);
+    assert_rank_error(&mut summary, values.len(), 0.02);
+}
+
+#[test]
+fn empty() {
+    let mut summary = GkSummary::new();
+    assert!(summary.quantile(0.5).is_nan());
+    summary |= &GkSummary::new();
+    assert!(summary.is_empty());
+}
+
+#[test]
+fn tdigest_roundtrip() {
+    let values: Vec<f32> = (0..10_000).map(|v| v as f32).collect();
+    let digest = TDigest::from_iter(values.iter().copied());
+    let mut summary = GkSummary::from(digest);
+    assert_eq!(summary.len(), 10_000);
+    assert!((summary.quantile(0.5) - 5_000.).abs() <= 200.);
+
+    let mut digest = TDigest::from(summary);
+    assert_eq!(digest.len(), 10_000);
+    assert!((digest.quantile(0.5) - 5_000.).abs() <= 200.);
+}
+
+#[test]
+fn spark_roundtrip() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut summary = GkSummary::from_iter(values.iter().map(|&v| v as f32));
+
+    let bytes = summary.to_spark_bytes();
+    assert_eq!(&bytes[..4], 10000i32.to_be_bytes());
+    assert_eq!(&bytes[4..12], 0.01f64.to_be_bytes());
+    assert_eq!(&bytes[12..20], 100_000i64.to_be_bytes());
+
+    let mut decoded = GkSummary::from_spark_bytes(&bytes).unwrap();
+    assert_eq!(decoded, summary);
+    assert_eq!(decoded.quantile(0.5), summary.quantile(0.5));
+}
+
+#[test]
+fn spark_invalid() {
+    let mut summary = GkSummary::from([1.0, 2.0, 3.0]);
+    let bytes = summary.to_spark_bytes();
+    assert_eq!(
+        GkSummary::from_spark_bytes(&bytes[..bytes.len() - 1]),
+        Err(tdigest_ch::Error::UnexpectedEof)
+    );
+
+    let mut wrong_count = bytes.clone();
+    wrong_count[12..20].copy_from_slice(&4i64.to_be_bytes());
+    assert!(matches!(
+        GkSummary::from_spark_bytes(&wrong_count),
+        Err(tdigest_ch::Error::InvalidState(_))
+    ));
+}
@@ -0,0 +1,57 @@
+use tdigest_ch::{IntegerScale, TDigest};
+
+#[test]
+fn nanosecond_timers() {
+    let mut timer = IntegerScale::new(1e-9);
+    let mut reference = TDigest::new();
+    for v in 0..100_000u64 {
+        let ticks = 1_000_000 + (v * 7919) % 100_000;
+        timer.insert(ticks);
+        reference.insert(ticks as f32);
+    }
+    assert_eq!(timer.len(), 100_000);
+    for level in [0., 0.5, 0.99, 1.] {
+        let expected = reference.quantile(level).round() as u64;
+        assert_eq!(timer.quantile(level), Some(expected));
+    }
+    assert_eq!(timer.quantile(0.0), Some(1_000_000));
+    assert_eq!(timer.quantile(1.0), Some(1_099_999));
+    assert_eq!(
+        timer.quantiles_many(&[0.0, 1.0]),
+        [Some(1_000_000), Some(1_099_999)]
+    );
+}
+
+#[test]
+fn large_ticks() {
+    // Ticks beyond 2^24 are rounded to the nearest f32.
+    let mut timer = IntegerScale::new(1e-9);
+    timer.insert(u64::MAX);
+    timer.insert(1 << 40);
+    assert_eq!(timer.quantile(1.0), Some(u64::MAX));
+    assert_eq!(timer.quantile(0.0), Some(1 << 40));
+}
+
+#[test]
+fn merge_resolutions() {
+    let mut nanos = IntegerScale::new(1e-9);
+    nanos.extend([1_000, 3_000]);
+    let mut micros = IntegerScale::new(1e-6);
+    micros.extend([2, 4]);
+
+    let merged = &nanos | &micros;
+    assert_eq!(merged.resolution(), 1e-9);
+    assert_eq!(merged.len(), 4);
+    assert_eq!(merged.clone().quantile(1.0), Some(4_000));
+
+    let mut empty = IntegerScale::new(1e-3);
+    assert_eq!(empty.quantile(0.5), None);
+    assert!(empty.quantile_value(0.5).is_nan());
+    assert!(empty.cdf(1).is_nan());
+}
+
+#[test]
+#[should_panic(expected = "resolution must be positive and finite")]
+fn zero_resolution() {
+    let _ = IntegerScale::new(0.);
+}
@@ -0,0 +1,47 @@
+use rand::seq::SliceRandom;
+use tdigest_ch::JointDigest;
+
+#[test]
+fn report() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let mut joint = JointDigest::with_sample_size(100);
+    joint.extend(values.iter().map(|&v| (v as f32, 2. * v as f32)));
+    assert_eq!(joint.len(), 100_000);
+
+    let report = joint.report(&[0.5]);
+    assert!((report.x_quantiles()[0] - 50_000.).abs() < 500.);
+    assert!((report.y_quantiles()[0] - 100_000.).abs() < 1_000.);
+    assert_eq!(report.samples().len(), 100);
+    assert!(report.samples().iter().all(|&(x, y)| y == 2. * x));
+}
+
+#[test]
+fn merge_matches_single_stream() {
+    let pairs: Vec<(f32, f32)> = (0..10_000).map(|v| (v as f32, (v % 7) as f32)).collect();
+
+    let mut whole = JointDigest::with_sample_size(50);
+    whole.extend(pairs.iter().copied());
+
+    let mut merged = JointDigest::with_sample_size(50);
+    for chunk in pairs.chunks(1_000) {
+        let mut part = JointDigest::with_sample_size(50);
+        part.extend(chunk.iter().copied());
+        merged |= &part;
+    }
+
+    assert_eq!(merged.len(), whole.len());
+    let mut merged_samples = merged.report(&[]).samples().to_vec();
+    merged_samples.sort_unstable_by(|l, r| l.0.total_cmp(&r.0));
+    assert_eq!(merged_samples.len(), 50);
+    assert!(merged_samples.windows(2).all(|w| w[0] != w[1]));
+}
+
+#[test]
+fn empty() {
+    let mut joint = JointDigest::new();
+    let report = joint.report(&[0.5]);
+    assert!(report.x_quantiles()[0].is_nan());
+    assert!(report.samples().is_empty());
+}
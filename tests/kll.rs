@@ -0,0 +1,56 @@
+use rand::seq::SliceRandom;
+use tdigest_ch::KllSketch;
+
+fn assert_rank_error(sketch: &KllSketch, n: usize, tolerance: f64) {
+    for level in [0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99] {
+        let quantile = sketch.quantile(level);
+        let rank = quantile as f64 / n as f64;
+        assert!(
+            (rank - level).abs() < tolerance,
+            "level {}, quantile {}",
+            level,
+            quantile
+        );
+    }
+}
+
+#[test]
+fn uniform() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let sketch = KllSketch::from_iter(values.iter().map(|&v| v as f32));
+    assert_eq!(sketch.len(), 100_000);
+    assert_rank_error(&sketch, values.len(), 0.02);
+}
+
+#[test]
+fn merge() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let mut sketch = KllSketch::new();
+    for chunk in values.chunks(3_000) {
+        sketch |= &KllSketch::from_iter(chunk.iter().map(|&v| v as f32));
+    }
+    assert_eq!(sketch.len(), 100_000);
+    assert_rank_error(&sketch, values.len(), 0.02);
+}
+
+#[test]
+fn rank_is_consistent_with_len() {
+    let sketch = KllSketch::from_iter((0..100_000).map(|v| v as f32));
+    assert_eq!(sketch.rank(f32::INFINITY), 100_000);
+    assert_eq!(sketch.rank(f32::NEG_INFINITY), 0);
+}
+
+#[test]
+fn invalid_bytes() {
+    let mut bytes = KllSketch::from([1.0, 2.0, 3.0]).to_bytes();
+    assert!(KllSketch::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    let mut zero_rng = bytes.clone();
+    zero_rng[13..21].fill(0);
+    assert!(KllSketch::from_bytes(&zero_rng).is_err());
+    bytes[0] = 2;
+    assert!(KllSketch::from_bytes(&bytes).is_err());
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "npy")]
+
+use tdigest_ch::TDigest;
+
+#[test]
+fn header() {
+    let mut digest = TDigest::from([1.0, 2.0]);
+    let mut npy = Vec::new();
+    digest.write_npy(&mut npy).unwrap();
+
+    let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+    assert_eq!((10 + header_len) % 64, 0);
+    let header = std::str::from_utf8(&npy[10..10 + header_len]).unwrap();
+    assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (2, 2), }"));
+    assert!(header.ends_with('\n'));
+
+    let data = &npy[10 + header_len..];
+    assert_eq!(data.len(), 2 * 16);
+    assert_eq!(&data[..8], 1f64.to_le_bytes());
+    assert_eq!(&data[8..16], 1f64.to_le_bytes());
+}
+
+#[test]
+fn roundtrip() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| v as f32));
+    let mut npy = Vec::new();
+    digest.write_npy(&mut npy).unwrap();
+
+    let mut read = TDigest::read_npy(npy.as_slice()).unwrap();
+    assert_eq!(read.len(), digest.len());
+    for level in [0.01, 0.5, 0.99] {
+        assert_eq!(read.quantile(level), digest.quantile(level));
+    }
+}
+
+#[test]
+fn invalid() {
+    assert!(TDigest::read_npy(&b"NUMPY"[..]).is_err());
+
+    let mut digest = TDigest::from([1.0, 2.0]);
+    let mut npy = Vec::new();
+    digest.write_npy(&mut npy).unwrap();
+    npy.truncate(npy.len() - 1);
+    assert!(TDigest::read_npy(npy.as_slice()).is_err());
+
+    let header = "{'descr': '<f8', 'fortran_order': False, 'shape': (1, 3), }";
+    let mut npy = b"\x93NUMPY\x01\x00".to_vec();
+    npy.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    npy.extend_from_slice(header.as_bytes());
+    npy.extend_from_slice(&[0; 24]);
+    assert!(TDigest::read_npy(npy.as_slice()).is_err());
+
+    let mut npy = b"\x93NUMPY\x02\x00".to_vec();
+    npy.extend_from_slice(&u32::MAX.to_le_bytes());
+    npy.extend_from_slice(b"{'descr': '<f8'");
+    let error = TDigest::read_npy(npy.as_slice()).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+}
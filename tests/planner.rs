@@ -0,0 +1,53 @@
+use tdigest_ch::planner;
+
+#[test]
+fn planned_accuracy() {
+    let levels = [0.5, 0.9, 0.99];
+    for max_rank_error in [0.01, 0.002] {
+        let plan = planner::plan(&levels, max_rank_error, 200_000);
+        let mut digest = plan.builder().build();
+        digest.extend((0..200_000).map(|v| ((v * 7919) % 200_000) as f32));
+
+        assert!(digest.clone().centroid_weight_profile().len() <= plan.centroids());
+        for level in levels {
+            let quantile = digest.quantile(level);
+            let rank_error = (digest.cdf(quantile) - level).abs();
+            assert!(
+                rank_error <= max_rank_error,
+                "level {}: {}",
+                level,
+                rank_error
+            );
+        }
+    }
+}
+
+#[test]
+fn tails_allow_larger_epsilon() {
+    let median = planner::plan(&[0.5], 0.001, 1_000_000);
+    let tail = planner::plan(&[0.999], 0.001, 1_000_000);
+    assert!(tail.epsilon() > median.epsilon());
+    assert!(tail.centroids() < median.centroids());
+    assert!(tail.serialized_bytes() < median.serialized_bytes());
+
+    let extremes = planner::plan(&[0., 1.], 0.001, 1_000_000);
+    assert_eq!(extremes.epsilon(), 0.25);
+    assert_eq!(
+        planner::plan(&[], 0.001, 10),
+        planner::plan(&[0.5], 0.001, 10)
+    );
+}
+
+#[test]
+fn small_counts() {
+    let plan = planner::plan(&[0.5], 0.0001, 100);
+    assert_eq!(plan.centroids(), 100);
+    assert_eq!(plan.max_centroids(), 128);
+    assert_eq!(plan.serialized_bytes(), 801);
+}
+
+#[test]
+#[should_panic(expected = "rank error must be in (0, 1)")]
+fn invalid_error() {
+    planner::plan(&[0.5], 0., 100);
+}
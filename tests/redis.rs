@@ -0,0 +1,74 @@
+use tdigest_ch::{Error, TDigest};
+
+#[test]
+fn layout() {
+    let mut digest = TDigest::from([1.0, 2.0]);
+    let bytes = digest.to_redis_rdb();
+
+    let double = |value: f64| {
+        let mut field = vec![0x04];
+        field.extend_from_slice(&value.to_le_bytes());
+        field
+    };
+    let mut expected = Vec::new();
+    expected.extend(double(100.)); // Compression.
+    expected.extend([0x02, 0x42, 0x62]); // Capacity of 610.
+    expected.extend([0x02, 0x02]); // Merged nodes.
+    expected.extend([0x02, 0x00]); // Unmerged nodes.
+    expected.extend(double(2.)); // Merged weight.
+    expected.extend(double(0.)); // Unmerged weight.
+    expected.extend(double(1.)); // Minimum.
+    expected.extend(double(2.)); // Maximum.
+    expected.extend([0x02, 0x00]); // Total compressions.
+    expected.extend(double(1.));
+    expected.extend(double(1.));
+    expected.extend(double(2.));
+    expected.extend(double(1.));
+    expected.push(0x00);
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn roundtrip() {
+    let mut builder = TDigest::builder();
+    builder.epsilon(0.005);
+    let mut digest = builder.build();
+    digest.extend((0..100_000).map(|v| v as f32));
+
+    let mut decoded = TDigest::from_redis_rdb(&digest.to_redis_rdb()).unwrap();
+    assert_eq!(decoded.len(), digest.len());
+    for level in [0.01, 0.5, 0.99] {
+        assert_eq!(decoded.quantile(level), digest.quantile(level));
+    }
+    assert_eq!(decoded.to_redis_rdb(), digest.to_redis_rdb());
+}
+
+#[test]
+fn roundtrip_bounds() {
+    let mut builder = TDigest::builder();
+    builder.max_centroids(8);
+    let mut digest = builder.build();
+    digest.extend((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+
+    let mut decoded = TDigest::from_redis_rdb(&digest.to_redis_rdb()).unwrap();
+    assert_eq!(decoded.min(), 0.);
+    assert_eq!(decoded.max(), 9_999.);
+    assert_eq!(decoded.quantile(0.), 0.);
+    assert_eq!(decoded.quantile(1.), 9_999.);
+}
+
+#[test]
+fn invalid() {
+    let mut bytes = TDigest::from([1.0]).to_redis_rdb();
+    bytes.push(0);
+    assert_eq!(TDigest::from_redis_rdb(&bytes), Err(Error::TrailingBytes));
+
+    bytes.truncate(bytes.len() - 2);
+    assert_eq!(TDigest::from_redis_rdb(&bytes), Err(Error::UnexpectedEof));
+
+    bytes[0] = 0x02;
+    assert!(matches!(
+        TDigest::from_redis_rdb(&bytes),
+        Err(Error::InvalidState(_))
+    ));
+}
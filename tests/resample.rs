@@ -0,0 +1,169 @@
+#![cfg(feature = "rand")]
+
+use rand::{rngs::StdRng, SeedableRng};
+use tdigest_ch::{resample, TDigest};
+
+#[test]
+fn bootstrap_preserves_config_and_count() {
+    let mut builder = TDigest::builder();
+    builder.max_centroids(64);
+    let mut digest = builder.build();
+    digest.extend((0..100_000).map(|v| v as f32));
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut replicate = resample::bootstrap(&mut digest, &mut rng);
+    assert_eq!(replicate.len(), 100_000);
+    assert!((replicate.quantile(0.5) - 50_000.).abs() < 2_000.);
+}
+
+#[test]
+fn bootstrap_scales_with_centroids() {
+    let mut digest = TDigest::new();
+    digest.insert_many(1.0, 1 << 40);
+    digest.insert_many(2.0, 1 << 40);
+    digest.insert_many(3.0, 3);
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let replicate = resample::bootstrap(&mut digest, &mut rng);
+    assert_eq!(replicate.len(), digest.len());
+}
+
+#[test]
+fn bootstrap_counts_are_binomial() {
+    let mut rng = StdRng::seed_from_u64(42);
+    for (n, low) in [(1_000_000u64, 250_000u64), (40, 10), (40, 2)] {
+        let mut digest = TDigest::new();
+        digest.insert_many(0.0, low);
+        digest.insert_many(1.0, n - low);
+
+        let p = low as f64 / n as f64;
+        let replicates = 2000;
+        let draws: Vec<f64> = (0..replicates)
+            .map(|_| (1. - resample::bootstrap(&mut digest, &mut rng).mean()) * n as f64)
+            .collect();
+        let mean = draws.iter().sum::<f64>() / replicates as f64;
+        let variance =
+            draws.iter().map(|d| (d - mean) * (d - mean)).sum::<f64>() / (replicates - 1) as f64;
+
+        let expected_variance = n as f64 * p * (1. - p);
+        assert!(
+            (mean - n as f64 * p).abs() < 4. * (expected_variance / replicates as f64).sqrt(),
+            "n={n} mean={mean}"
+        );
+        assert!(
+            (variance / expected_variance - 1.).abs() < 0.15,
+            "n={n} variance={variance}"
+        );
+    }
+}
+
+#[test]
+fn spread_narrows_with_more_data() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut small = TDigest::from_iter((0..100).map(|v| v as f32 / 100.));
+    let mut large = TDigest::from_iter((0..100_000).map(|v| v as f32 / 100_000.));
+
+    let small = resample::quantile_spread(&mut small, 0.5, 50, &mut rng);
+    let large = resample::quantile_spread(&mut large, 0.5, 50, &mut rng);
+    assert!(large.std_dev() < small.std_dev());
+
+    let (low, high) = large.interval(0.95);
+    assert!(low <= 0.5 && 0.5 <= high, "interval {} {}", low, high);
+}
+
+#[test]
+fn empty() {
+    let mut digest = TDigest::new();
+    let mut rng = StdRng::seed_from_u64(42);
+    assert!(resample::bootstrap(&mut digest, &mut rng).is_empty());
+
+    let spread = resample::quantile_spread(&mut digest, 0.5, 10, &mut rng);
+    assert!(spread.estimates().is_empty());
+    assert!(spread.mean().is_nan());
+    assert!(spread.interval(0.9).0.is_nan());
+}
+
+#[test]
+fn shuffle_check() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let values: Vec<f32> = (0..20_000).map(|v| v as f32).collect();
+
+    let mut builder = TDigest::builder();
+    builder.max_centroids(16);
+    let coarse = resample::shuffle_check(&builder.build(), &values, &[0.5], 10, &mut rng);
+    let fine = resample::shuffle_check(&TDigest::new(), &values, &[0.5], 10, &mut rng);
+    assert!(fine <= coarse, "{} > {}", fine, coarse);
+
+    assert_eq!(
+        resample::shuffle_check(&TDigest::new(), &values, &[0.5], 1, &mut rng),
+        0.
+    );
+    assert_eq!(
+        resample::shuffle_check(&TDigest::new(), &[], &[0.5], 10, &mut rng),
+        0.
+    );
+}
+
+#[test]
+fn convolve_sum() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut x = TDigest::from_iter((0..10_000).map(|v| (v % 100) as f32));
+    let mut constant = TDigest::from([1_000.; 10]);
+
+    let mut shifted = x.convolve_sum(&mut constant, 100_000, &mut rng);
+    assert_eq!(shifted.len(), 100_000);
+    for level in [0.1, 0.5, 0.9] {
+        let expected = x.quantile(level) + 1_000.;
+        assert!((shifted.quantile(level) - expected).abs() < 2., "{level}");
+    }
+
+    // The sum of two uniform variables has a triangular distribution.
+    let mut sum = x.convolve_sum(&mut x.clone(), 100_000, &mut rng);
+    assert!((sum.quantile(0.5) - 99.).abs() < 2.);
+    assert!((sum.cdf(29.7) - 0.045).abs() < 0.01);
+
+    assert!(x.convolve_sum(&mut TDigest::new(), 10, &mut rng).is_empty());
+}
+
+#[test]
+fn convolve_max_min() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut x = TDigest::from_iter((0..10_000).map(|v| (v % 1000) as f32));
+    let mut y = TDigest::from_iter((0..10_000).map(|v| (v % 1000) as f32));
+
+    // The CDF of the maximum is the product of the CDFs, and the survival
+    // function of the minimum the product of the survival functions.
+    let mut max = x.convolve_max(&mut y, 100_000, &mut rng);
+    let mut min = x.convolve_min(&mut y, 100_000, &mut rng);
+    for level in [0.1, 0.5, 0.9] {
+        let max_expected = x.quantile(f64::sqrt(level));
+        assert!((max.quantile(level) - max_expected).abs() < 15., "{level}");
+        let min_expected = x.quantile(1. - f64::sqrt(1. - level));
+        assert!((min.quantile(level) - min_expected).abs() < 15., "{level}");
+    }
+
+    // Against a constant, the maximum and minimum clamp the distribution.
+    let mut constant = TDigest::from([500.; 10]);
+    let mut clamped = x.convolve_max(&mut constant, 10_000, &mut rng);
+    assert_eq!(clamped.quantile(0.3), 500.);
+    assert!(x.convolve_min(&mut TDigest::new(), 10, &mut rng).is_empty());
+}
+
+#[test]
+fn sample() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    let mut rng = StdRng::seed_from_u64(7);
+
+    let mut replayed = TDigest::from_iter(digest.sample_iter(&mut rng).take(100_000));
+    assert_eq!(replayed.len(), 100_000);
+    for level in [0.1, 0.5, 0.9, 0.99] {
+        assert!((replayed.quantile(level) - digest.quantile(level)).abs() < 1_000.);
+    }
+
+    let frozen = digest.clone().freeze();
+    let value = frozen.sample(&mut rng);
+    assert!((0. ..=99_999.).contains(&value));
+    assert_eq!(frozen.sample_iter(&mut rng).take(10).count(), 10);
+    assert!(TDigest::new().sample(&mut rng).is_nan());
+    assert!(digest.sample(&mut rng) >= 0.);
+}
@@ -1,6 +1,12 @@
 #![cfg(feature = "serde")]
 
-use tdigest_ch::TDigest;
+use tdigest_ch::{TDigest, TDigestBuilder};
+
+fn built(configure: impl FnOnce(&mut TDigestBuilder)) -> TDigest {
+    let mut builder = TDigest::builder();
+    configure(&mut builder);
+    builder.build()
+}
 
 #[test]
 fn serialize() {
@@ -8,10 +14,67 @@ fn serialize() {
     let serialized = serde_json::to_string(&digest).unwrap();
     assert_eq!(
         serialized,
-        "[[0.01,2048,2048],[[1.0,1],[2.0,1],[3.0,1],[4.0,1],[5.0,1]],5,5]"
+        "[[0.01,2048,2048],[[1.0,1],[2.0,1],[3.0,1],[4.0,1],[5.0,1]],5,5,[[],[],false],{},null,15.0,[1.0,5.0],[],0]"
     );
 }
 
+#[test]
+fn serialize_config_shape() {
+    // Options at their defaults are only written before one that is not.
+    for (digest, config) in [
+        (built(|_| {}), "[0.01,2048,2048]"),
+        (
+            built(|builder| {
+                builder.coalesce_duplicates(true);
+            }),
+            "[0.01,2048,2048,true]",
+        ),
+        (
+            built(|builder| {
+                builder.retain_raw_samples(4);
+            }),
+            "[0.01,2048,2048,false,0,4]",
+        ),
+        (
+            built(|builder| {
+                builder.label_policy(tdigest_ch::LabelPolicy::Union);
+            }),
+            "[0.01,2048,2048,false,0,0,\"clickhouse\",\"union\"]",
+        ),
+    ] {
+        let serialized = serde_json::to_string(&digest).unwrap();
+        assert!(
+            serialized.starts_with(&format!("[{},", config)),
+            "{}",
+            serialized
+        );
+        assert_eq!(
+            serde_json::from_str::<TDigest>(&serialized).unwrap(),
+            digest
+        );
+    }
+}
+
+#[test]
+fn coalesced_centroids_are_capped() {
+    let mut digest = built(|builder| {
+        builder.coalesce_duplicates(true).max_unmerged(100_000);
+    });
+    digest.extend(std::iter::repeat_n(1.0, 10_000));
+
+    // The buffered centroids are serialized as they are.
+    let state: serde_json::Value = serde_json::to_value(&digest).unwrap();
+    let weights: Vec<u64> = state[1]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|centroid| centroid[1].as_u64().unwrap())
+        .collect();
+    assert_eq!(weights.iter().sum::<u64>(), 10_000);
+    assert!(weights.len() < 1_000, "{}", weights.len());
+    assert!(weights.iter().all(|&weight| weight <= 100), "{:?}", weights);
+}
+
 #[test]
 fn deserialize() {
     let mut digest: TDigest =
@@ -21,3 +84,357 @@ fn deserialize() {
     assert_eq!(digest.quantile(0.5), 3.0);
     assert_eq!(digest.quantile(1.0), 5.0);
 }
+
+#[test]
+fn deserialize_coalesce() {
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048,true],[[1.0,1],[2.0,1]],2,2]").unwrap();
+    let mut expected = built(|builder| {
+        builder.coalesce_duplicates(true);
+    });
+    expected.extend([1.0, 2.0]);
+    assert_eq!(digest, expected);
+}
+
+#[test]
+fn deserialize_config_extremes() {
+    let digest: TDigest = serde_json::from_str("[[0.01,2048,2048,false,2],[],0,0]").unwrap();
+    assert_eq!(
+        digest,
+        built(|builder| {
+            builder.retain_extremes(2);
+        })
+    );
+
+    // Extremes missing from a non-empty state are unknown, not empty.
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048,false,2],[[1.0,1],[2.0,1]],2,2]").unwrap();
+    let stale: TDigest =
+        serde_json::from_str("[[0.01,2048,2048,false,2],[[1.0,1],[2.0,1]],2,2,[[],[],true]]")
+            .unwrap();
+    assert_eq!(digest, stale);
+}
+
+#[test]
+fn deserialize_raw_samples() {
+    let digest: TDigest = serde_json::from_str("[[0.01,2048,2048,false,0,4],[],0,0]").unwrap();
+    assert_eq!(
+        digest,
+        built(|builder| {
+            builder.retain_raw_samples(4);
+        })
+    );
+}
+
+#[test]
+fn deserialize_interpolation() {
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048,false,0,0,\"midpoint\"],[],0,0]").unwrap();
+    assert_eq!(
+        digest,
+        built(|builder| {
+            builder.interpolation(tdigest_ch::Interpolation::Midpoint);
+        })
+    );
+}
+
+#[test]
+fn deserialize_label_policy() {
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048,false,0,0,\"clickhouse\",\"union\"],[],0,0]")
+            .unwrap();
+    assert_eq!(
+        digest,
+        built(|builder| {
+            builder.label_policy(tdigest_ch::LabelPolicy::Union);
+        })
+    );
+}
+
+#[test]
+fn deserialize_extremes() {
+    let digest: TDigest = serde_json::from_str(
+        "[[0.01,2048,2048,false,1],[[1.0,1],[2.0,1]],2,2,[[1.0],[2.0],false]]",
+    )
+    .unwrap();
+    assert_eq!(digest.smallest(), [1.0]);
+    assert_eq!(digest.largest(), [2.0]);
+    let mut expected = built(|builder| {
+        builder.retain_extremes(1);
+    });
+    expected.extend([1.0, 2.0]);
+    assert_eq!(digest, expected);
+}
+
+#[test]
+fn deserialize_labels() {
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048],[],0,0,[[],[],false],{\"host\":\"a\"}]").unwrap();
+    assert_eq!(digest.labels().get("host").map(String::as_str), Some("a"));
+
+    let digest: TDigest = serde_json::from_str("[[0.01,2048,2048],[],0,0]").unwrap();
+    assert!(digest.labels().is_empty());
+}
+
+#[test]
+fn deserialize_unit() {
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048],[],0,0,[[],[],false],{},[\"time\",0.001]]")
+            .unwrap();
+    assert_eq!(digest.unit(), Some(&tdigest_ch::Unit::Millis));
+
+    let digest: TDigest = serde_json::from_str("[[0.01,2048,2048],[],0,0]").unwrap();
+    assert_eq!(digest.unit(), None);
+}
+
+#[test]
+fn deserialize_sum() {
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048],[[1.0,1],[2.0,1]],2,2,[[],[],false],{},null,4.0]")
+            .unwrap();
+    assert_eq!(digest.mean(), 2.0);
+
+    let digest: TDigest = serde_json::from_str("[[0.01,2048,2048],[[1.0,1],[2.0,1]],2,2]").unwrap();
+    assert_eq!(digest.mean(), 1.5);
+}
+
+#[test]
+fn deserialize_bounds() {
+    let digest: TDigest = serde_json::from_str(
+        "[[0.01,2048,2048],[[1.0,1],[2.0,1]],2,2,[[],[],false],{},null,3.0,[0.5,2.5]]",
+    )
+    .unwrap();
+    assert_eq!(digest.min(), 0.5);
+    assert_eq!(digest.max(), 2.5);
+
+    let digest: TDigest = serde_json::from_str("[[0.01,2048,2048],[[1.0,1],[2.0,1]],2,2]").unwrap();
+    assert_eq!(digest.min(), 1.0);
+    assert_eq!(digest.max(), 2.0);
+}
+
+#[test]
+fn deserialize_raw() {
+    let digest: TDigest = serde_json::from_str(
+        "[[0.01,2048,2048,false,0,4],[[1.0,1],[2.0,1]],2,2,[[],[],false],{},null,3.0,[1.0,2.0],[1.0,2.0]]",
+    )
+    .unwrap();
+    assert!(digest.is_exact());
+
+    let digest: TDigest =
+        serde_json::from_str("[[0.01,2048,2048,false,0,4],[[1.0,1],[2.0,1]],2,2]").unwrap();
+    assert!(!digest.is_exact());
+}
+
+#[test]
+fn deserialize_nans() {
+    let digest: TDigest = serde_json::from_str(
+        "[[0.01,2048,2048],[[1.0,1],[2.0,1]],2,2,[[],[],false],{},null,3.0,[1.0,2.0],[],3]",
+    )
+    .unwrap();
+    assert_eq!(digest.nan_count(), 3);
+
+    let digest: TDigest = serde_json::from_str("[[0.01,2048,2048],[[1.0,1],[2.0,1]],2,2]").unwrap();
+    assert_eq!(digest.nan_count(), 0);
+}
+
+#[test]
+fn extremes_roundtrip() {
+    let mut builder = TDigest::builder();
+    builder.retain_extremes(2);
+    let mut digest = builder.build();
+    digest.extend([3.0, 1.0, 5.0, 2.0, 4.0]);
+
+    let serialized = serde_json::to_string(&digest).unwrap();
+    let deserialized: TDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+    assert_eq!(deserialized.smallest(), [1.0, 2.0]);
+    assert_eq!(deserialized.largest(), [5.0, 4.0]);
+}
+
+#[test]
+fn joint_roundtrip() {
+    let digest = tdigest_ch::JointDigest::from([(1.0, 2.0), (3.0, 4.0)]);
+    let serialized = serde_json::to_string(&digest).unwrap();
+    let deserialized: tdigest_ch::JointDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+}
+
+#[test]
+fn digest_map_roundtrip() {
+    let pairs: Vec<(u32, f32)> = (0..1000).map(|v| ((v * 7) % 50, v as f32)).collect();
+    let digests = tdigest_ch::DigestMap::from_pairs(pairs.iter().copied());
+    let serialized = serde_json::to_string(&digests).unwrap();
+    let deserialized: tdigest_ch::DigestMap<u32> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digests);
+
+    // The same digests inserted in another order serialize identically.
+    let mut reversed = tdigest_ch::DigestMap::new();
+    for key in (0..50).rev() {
+        reversed.extend(pairs.iter().copied().filter(|p| p.0 == key));
+    }
+    assert_eq!(serde_json::to_string(&reversed).unwrap(), serialized);
+}
+
+#[test]
+fn conditional_roundtrip() {
+    let digests = tdigest_ch::ConditionalDigests::from([(200, 1.0), (500, 2.0), (200, 3.0)]);
+    let serialized = serde_json::to_string(&digests).unwrap();
+    let deserialized: tdigest_ch::ConditionalDigests<u16> =
+        serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digests);
+}
+
+#[test]
+fn labels_roundtrip() {
+    let mut digest = TDigest::from([1.0, 2.0]);
+    digest.set_label("service", "api");
+    let serialized = serde_json::to_string(&digest).unwrap();
+    assert!(serialized.ends_with(",{\"service\":\"api\"},null,3.0,[1.0,2.0],[],0]"));
+    let deserialized: TDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+}
+
+#[test]
+fn unit_roundtrip() {
+    let mut digest = TDigest::from([1.0, 2.0]);
+    digest.set_unit(tdigest_ch::Unit::Millis);
+    let serialized = serde_json::to_string(&digest).unwrap();
+    assert!(serialized.ends_with(",[\"time\",0.001],3.0,[1.0,2.0],[],0]"));
+    let deserialized: TDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+}
+
+#[test]
+fn comparison_report() {
+    let mut baseline = TDigest::from([1.0, 2.0]);
+    let report = TDigest::from([2.0, 4.0, 6.0]).compare(&mut baseline, &[1.0]);
+    assert_eq!(
+        serde_json::to_string(&report).unwrap(),
+        "{\"levels\":[1.0],\"baseline\":[2.0],\"current\":[6.0],\"deltas\":[4.0],\
+         \"relative_changes\":[2.0],\"ks_distance\":0.6666666666666667,\
+         \"baseline_count\":2,\"current_count\":3,\"count_change\":1}"
+    );
+}
+
+#[test]
+fn empty_roundtrip() {
+    let digest = TDigest::new();
+    let serialized = serde_json::to_string(&digest).unwrap();
+    assert!(serialized.ends_with(",0.0,null,[],0]"));
+    let deserialized: TDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+}
+
+#[test]
+fn raw_samples_roundtrip() {
+    let mut builder = TDigest::builder();
+    builder.retain_raw_samples(10);
+    let mut digest = builder.build();
+    digest.extend([3.0, 1.0, 2.0]);
+
+    let serialized = serde_json::to_string(&digest).unwrap();
+    assert!(serialized.ends_with(",[1.0,3.0],[1.0,2.0,3.0],0]"));
+    let deserialized: TDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+    assert!(deserialized.is_exact());
+}
+
+#[test]
+fn nan_count_roundtrip() {
+    let digest = TDigest::from([1.0, f32::NAN, 2.0, f32::NAN]);
+    let serialized = serde_json::to_string(&digest).unwrap();
+    assert!(serialized.ends_with(",[1.0,2.0],[],2]"));
+    let deserialized: TDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized.nan_count(), 2);
+    assert_eq!(deserialized, digest);
+}
+
+#[test]
+fn f64_roundtrip() {
+    let mut digest = TDigest::<f64>::default();
+    digest.extend([16_777_217.0, 16_777_219.0]);
+    let serialized = serde_json::to_string(&digest).unwrap();
+    assert!(serialized.contains("[16777217.0,1],[16777219.0,1]"));
+    let deserialized: TDigest<f64> = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+}
+
+#[test]
+fn weighted_roundtrip() {
+    let mut digest = tdigest_ch::WeightedTDigest::new();
+    digest.extend([(1.0, 0.5), (2.0, 1.5)]);
+    let serialized = serde_json::to_string(&digest).unwrap();
+    assert_eq!(serialized, "[0.01,[[1.0,0.5],[2.0,1.5]],2,2.0,[1.0,2.0],2]");
+    let deserialized: tdigest_ch::WeightedTDigest = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digest);
+}
+
+#[test]
+fn binned_digests_roundtrip() {
+    let mut digests = tdigest_ch::BinnedDigests::new(tdigest_ch::Bins::new(vec![1.0, 2.0]));
+    digests.extend([(0.5, 10.0), (1.5, 20.0)]);
+    let serialized = serde_json::to_string(&digests).unwrap();
+    assert!(serialized.starts_with("[[1.0,2.0],"));
+    let deserialized: tdigest_ch::BinnedDigests = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, digests);
+    assert!(
+        serde_json::from_str::<tdigest_ch::BinnedDigests>(&serialized.replacen(
+            "[1.0,2.0]",
+            "[2.0,1.0]",
+            1
+        ))
+        .is_err()
+    );
+}
+
+#[test]
+fn integer_scale_roundtrip() {
+    let mut timer = tdigest_ch::IntegerScale::new(1e-9);
+    timer.extend([1_000, 2_000, 3_000]);
+    let serialized = serde_json::to_string(&timer).unwrap();
+    assert!(serialized.starts_with("[1e-9,"));
+    let deserialized: tdigest_ch::IntegerScale = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, timer);
+}
+
+#[test]
+fn digest_stats() {
+    let digest = TDigest::new();
+    let serialized = serde_json::to_string(&digest.stats()).unwrap();
+    assert!(serialized.starts_with(
+        "{\"centroids\":0,\"unmerged\":0,\"compressions\":0,\"brute_compressions\":0,"
+    ));
+}
+
+#[test]
+fn kll_validation() {
+    let sketch = tdigest_ch::KllSketch::from_iter((0..1000).map(|v| v as f32));
+    let serialized = serde_json::to_string(&sketch).unwrap();
+    let deserialized: tdigest_ch::KllSketch = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, sketch);
+
+    for invalid in [
+        "[200,0,[],1]",
+        "[0,1,[[1.0]],1]",
+        "[200,2,[[1.0]],1]",
+        "[200,1,[[1.0]],0]",
+    ] {
+        assert!(
+            serde_json::from_str::<tdigest_ch::KllSketch>(invalid).is_err(),
+            "{invalid}"
+        );
+    }
+    let mut valid: tdigest_ch::KllSketch = serde_json::from_str("[200,3,[[1.0],[2.0]],1]").unwrap();
+    valid.insert(3.0);
+    assert_eq!(valid.len(), 4);
+}
+
+#[test]
+fn bfloat16_weight_overflow() {
+    let histogram: tdigest_ch::BFloat16Histogram =
+        serde_json::from_str("[[16256,3],[16256,4]]").unwrap();
+    assert_eq!(histogram.len(), 7);
+
+    let invalid = format!("[[16256,{}],[16256,1]]", u64::MAX);
+    assert!(serde_json::from_str::<tdigest_ch::BFloat16Histogram>(&invalid).is_err());
+}
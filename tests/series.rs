@@ -0,0 +1,38 @@
+use tdigest_ch::{QuantileSeries, TDigest};
+
+#[test]
+fn rotating_digests() {
+    let levels = vec![0.5, 0.9, 0.99];
+    let mut series = QuantileSeries::new(levels.clone());
+    let mut digest = TDigest::new();
+    for interval in 0..10u64 {
+        digest.extend((0..1000).map(|v| (v as u64 * (interval + 1)) as f32));
+        let expected = digest.quantiles_many(&levels);
+        series.push(interval * 60, &mut digest);
+        assert_eq!(series.get(interval as usize).unwrap().1, expected);
+        digest.clear();
+    }
+
+    assert_eq!(series.len(), 10);
+    assert_eq!(series.levels(), levels);
+    assert_eq!(series.iter().count(), 10);
+    assert_eq!(series.get(10), None);
+    let medians: Vec<f32> = series.column(0).collect();
+    assert_eq!(medians.len(), 10);
+    assert!(medians.windows(2).all(|w| w[0] < w[1]));
+
+    let mut csv = Vec::new();
+    series.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().count(), 11);
+    assert!(csv.starts_with("timestamp,0.5,0.9,0.99\n0,"));
+
+    series.clear();
+    assert!(series.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "there must be one quantile per level")]
+fn mismatched_quantiles() {
+    QuantileSeries::new(vec![0.5]).push_quantiles(0, &[1.0, 2.0]);
+}
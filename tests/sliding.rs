@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use tdigest_ch::{SlidingTDigest, TDigest, TimeWindowTDigest};
+
+#[test]
+fn window_bounds() {
+    let mut digest = SlidingTDigest::new(1_000, 8);
+    assert!(digest.is_empty());
+    assert!(digest.quantile(0.5).is_nan());
+    for v in 0..100_000 {
+        digest.insert(v as f32);
+        let len = digest.len();
+        assert!(len == v + 1 || (1_000..1_000 + 125).contains(&len), "{v}");
+    }
+    // The oldest values are at most one share older than the window.
+    assert!(digest.quantile(0.0) >= 99_000. - 125.);
+    assert_eq!(digest.quantile(1.0), 99_999.);
+    assert!((digest.quantile(0.5) - 99_500.).abs() <= 100.);
+
+    digest.insert(f32::NAN);
+    assert_eq!(digest.to_digest().len(), digest.len());
+    digest.clear();
+    assert!(digest.is_empty());
+}
+
+#[test]
+fn regime_change() {
+    let mut builder = TDigest::builder();
+    builder.retain_extremes(1);
+    let mut digest = SlidingTDigest::with_template(10_000, 10, builder.build());
+    digest.extend((0..50_000).map(|_| 10.));
+    assert_eq!(digest.quantile(0.99), 10.);
+    digest.extend((0..11_000).map(|_| 100.));
+    // The values before the change have all expired.
+    assert_eq!(digest.quantile(0.0), 100.);
+    assert_eq!(digest.to_digest().smallest(), [100.]);
+}
+
+#[test]
+fn time_window_bounds() {
+    let mut digest = TimeWindowTDigest::new(Duration::from_secs(100), 4);
+    let start = Instant::now();
+    for second in 0..10_000 {
+        let at = start + Duration::from_secs(second);
+        digest.record(second as f32, at);
+        let len = digest.len();
+        assert!(len == second + 1 || (100..=125).contains(&len), "{second}");
+    }
+    assert!(digest.quantile(0.0) >= 9_900. - 25.);
+    assert_eq!(digest.quantile(1.0), 9_999.);
+
+    // Late values go into the share of their time.
+    digest.record(-1.0, start + Duration::from_secs(9_950));
+    assert_eq!(digest.quantile(0.0), -1.0);
+    digest.record(-2.0, start);
+    assert_eq!(digest.quantile(0.0), -1.0);
+
+    digest.expire(start + Duration::from_secs(10_075));
+    assert!(digest.quantile(0.0) >= 9_975.);
+    digest.expire(start);
+    digest.expire(start + Duration::from_secs(20_000));
+    assert!(digest.is_empty());
+    assert_eq!(digest.to_digest().len(), 0);
+}
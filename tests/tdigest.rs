@@ -0,0 +1,1601 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::Duration,
+};
+
+use rand::seq::SliceRandom;
+use tdigest_ch::{
+    bulk, quantiles_for, quantiles_many_for, Error, Interpolation, QuantileRule, TDigest, Unit,
+};
+
+#[test]
+fn subtract_window() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let hour_ago = TDigest::from_iter(values.iter().map(|&v| v as f32 / 2.));
+    let mut now = hour_ago.clone();
+    now.extend(values.iter().map(|&v| 100_000. + v as f32));
+
+    now.subtract(&hour_ago).unwrap();
+    assert_eq!(now.len(), 100_000);
+    for level in [0.1, 0.5, 0.9] {
+        let expected = 100_000. + level as f32 * 100_000.;
+        let quantile = now.quantile(level);
+        assert!(
+            (quantile - expected).abs() < 2_000.,
+            "level {}, quantile {}",
+            level,
+            quantile
+        );
+    }
+}
+
+#[test]
+fn subtract_self() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    let snapshot = digest.clone();
+    digest.subtract(&snapshot).unwrap();
+    assert!(digest.is_empty());
+    assert!(digest.quantile(0.5).is_nan());
+}
+
+#[test]
+fn subtract_underflow() {
+    let mut digest = TDigest::from([1.0, 2.0]);
+    let other = TDigest::from([1.0, 2.0, 3.0]);
+    assert_eq!(digest.subtract(&other), Err(Error::CountUnderflow));
+    assert_eq!(digest.len(), 2);
+}
+
+#[test]
+fn compress_drops_eaten_centroids() {
+    let mut builder = TDigest::builder();
+    builder.epsilon(1.0);
+    // The last two centroids merge, so that the last one is eaten.
+    let digest = TDigest::from_centroids(builder.build(), [(1.0, 1000), (2.0, 1), (3.0, 2)]);
+    let centroids = digest.clone().into_centroids();
+    assert_eq!(centroids.len(), 2);
+    assert_eq!(
+        centroids.iter().map(|&(_, count)| count).sum::<u64>(),
+        digest.len()
+    );
+}
+
+#[test]
+fn compress_brute_drops_eaten_centroids() {
+    let mut builder = TDigest::builder();
+    builder.max_centroids(64);
+    let mut digest = builder.build();
+    digest.extend((0..100_000).map(|v| v as f32));
+    assert_eq!(digest.len(), 100_000);
+    assert!((digest.quantile(0.5) - 50_000.).abs() < 2_000.);
+}
+
+#[test]
+fn extremes_merge() {
+    let mut builder = TDigest::builder();
+    builder.retain_extremes(3);
+
+    let mut values: Vec<u32> = (0..10_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut digest = builder.build();
+    for chunk in values.chunks(1_000) {
+        let mut part = TDigest::builder();
+        part.retain_extremes(3);
+        let mut part = part.build();
+        part.extend(chunk.iter().map(|&v| v as f32));
+        digest |= &part;
+    }
+    assert_eq!(digest.smallest(), [0.0, 1.0, 2.0]);
+    assert_eq!(digest.largest(), [9_999.0, 9_998.0, 9_997.0]);
+}
+
+#[test]
+fn extend_weighted() {
+    let pairs: Vec<(f32, u64)> = (0..1000).map(|v| (v as f32, v % 7 + 1)).collect();
+    let mut weighted = TDigest::new();
+    weighted.extend(pairs.iter().copied());
+
+    let mut expected = TDigest::new();
+    for &(value, weight) in &pairs {
+        expected.insert_many(value, weight);
+    }
+    assert_eq!(weighted, expected);
+    assert_eq!(weighted.len(), pairs.iter().map(|p| p.1).sum::<u64>());
+
+    weighted.extend([(f32::NAN, 2), (3.0, 0)]);
+    assert_eq!(weighted.nan_count(), 2);
+    assert_eq!(weighted.len(), expected.len());
+}
+
+#[test]
+fn from_iter_weighted() {
+    let pairs: Vec<(f32, u64)> = (0..1000).map(|v| (v as f32, v % 7)).collect();
+    let weighted: TDigest = pairs.iter().copied().collect();
+
+    let mut expected = TDigest::new();
+    for &(value, count) in &pairs {
+        expected.insert_many(value, count);
+    }
+    assert_eq!(weighted, expected);
+    assert_eq!(weighted.len(), pairs.iter().map(|p| p.1).sum::<u64>());
+}
+
+#[test]
+fn nan_count() {
+    let mut digest = TDigest::from([1.0, f32::NAN, 2.0]);
+    digest.insert_many(f32::NAN, 4);
+    digest.insert_many(f32::NAN, 0);
+    assert_eq!(digest.len(), 2);
+    assert_eq!(digest.nan_count(), 5);
+
+    let snapshot = digest.clone();
+    digest |= &TDigest::from([f32::NAN, 3.0]);
+    assert_eq!(digest.nan_count(), 6);
+    digest.subtract(&snapshot).unwrap();
+    assert_eq!(digest.nan_count(), 1);
+    assert_ne!(digest, TDigest::from([3.0]));
+
+    let chunks = [vec![1., f32::NAN, 5.], vec![f32::NAN]];
+    let bulk = tdigest_ch::bulk::from_sorted_chunks(TDigest::new(), chunks);
+    assert_eq!(bulk.nan_count(), 2);
+
+    digest.clear();
+    assert_eq!(digest.nan_count(), 0);
+}
+
+#[test]
+fn extremes_weighted() {
+    let mut builder = TDigest::builder();
+    builder.retain_extremes(3);
+    let mut digest = builder.build();
+    digest.insert_many(1.0, 2);
+    digest.insert_many(0.0, 1);
+    digest.insert_many(7.0, 5);
+    assert_eq!(digest.smallest(), [0.0, 1.0, 1.0]);
+    assert_eq!(digest.largest(), [7.0, 7.0, 7.0]);
+
+    digest.clear();
+    assert!(digest.smallest().is_empty());
+    assert!(digest.largest().is_empty());
+}
+
+#[test]
+fn extremes_quantiles() {
+    let build = || {
+        let mut builder = TDigest::builder();
+        builder.retain_extremes(10);
+        builder.build()
+    };
+    let mut digest = build();
+    digest.extend((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    assert_eq!(digest.quantile(3e-5), 2.);
+    assert_eq!(digest.quantile(1. - 5e-5), 99_994.);
+    assert_eq!(digest.top_quantile(5e-5), 99_994.);
+    assert_eq!(digest.value_at_rank(99_990), 99_990.);
+    assert_eq!(digest.quantile_explain(1e-4).rule(), QuantileRule::Extremes);
+    assert_ne!(digest.quantile_explain(0.5).rule(), QuantileRule::Extremes);
+    let levels = [1. - 5e-5, 0.5, 3e-5];
+    let many = digest.quantiles_many(&levels);
+    assert_eq!(many, levels.map(|level| digest.quantile(level)));
+
+    // Merging a t-digest that retains fewer values loses the exact quantiles.
+    let mut merged = digest.clone();
+    merged |= &TDigest::from_iter((0..1000).map(|v| -(v as f32)));
+    assert_ne!(merged.quantile_explain(1e-5).rule(), QuantileRule::Extremes);
+    let mut other = build();
+    other.extend((0..1000).map(|v| -(v as f32)));
+    merged = digest.clone();
+    merged |= &other;
+    assert_eq!(merged.quantile(1e-5), -998.);
+
+    let before = TDigest::from_iter((0..1000).map(|v| v as f32));
+    digest.subtract(&before).unwrap();
+    digest.insert(1.);
+    assert_ne!(digest.quantile_explain(1e-5).rule(), QuantileRule::Extremes);
+}
+
+#[test]
+fn blend_adapts_slowly() {
+    let mut baseline = TDigest::from_iter((0..10_000).map(|v| v as f32 / 10_000.));
+    let shifted = TDigest::from_iter((0..10_000).map(|v| 1. + v as f32 / 10_000.));
+
+    let mut previous = baseline.quantile(0.5);
+    for _ in 0..20 {
+        baseline.blend(&shifted, 0.1);
+        assert_eq!(baseline.len(), 10_000);
+        let median = baseline.quantile(0.5);
+        assert!(median >= previous);
+        previous = median;
+    }
+    // After 20 steps, (1 - 0.1)^20 ≈ 12% of the weight is left on the old values.
+    assert!(baseline.quantile(0.05) < 1.);
+    assert!(baseline.quantile(0.25) > 1.);
+}
+
+#[test]
+fn blend_bounds() {
+    let baseline = TDigest::from_iter((0..1000).map(|_| 10.0));
+    let spike = TDigest::from_iter((0..100).map(|_| 20.0));
+
+    // No element of the spike is kept.
+    for alpha in [0.0, 1e-6] {
+        let mut blended = baseline.clone();
+        blended.blend(&spike, alpha);
+        assert_eq!(blended.len(), 1000);
+        assert_eq!(blended.max(), 10.0);
+        assert_eq!(blended.quantile(1.0), 10.0);
+        assert_eq!(blended.mean(), 10.0);
+    }
+
+    // Only the spike is kept.
+    let mut blended = baseline.clone();
+    blended.blend(&spike, 1.0);
+    assert_eq!(blended.len(), 1000);
+    assert_eq!(blended.min(), 20.0);
+    assert_eq!(blended.quantile(0.0), 20.0);
+    assert_eq!(blended.mean(), 20.0);
+
+    // Exact bounds are kept along with their centroids, and otherwise
+    // follow the centroids that are kept.
+    let mut blended = TDigest::from_iter((0..1000).map(|v| v as f32));
+    blended.blend(&spike, 0.5);
+    assert_eq!(blended.min(), 0.0);
+    assert_eq!(blended.max(), blended.quantile(1.0));
+    assert!(blended.max() < 999.0);
+}
+
+#[test]
+fn blend_empty() {
+    let mut baseline = TDigest::new();
+    let current = TDigest::from([1.0, 2.0, 3.0]);
+    baseline.blend(&current, 0.1);
+    assert_eq!(baseline.len(), 3);
+    assert_eq!(baseline.quantile(0.5), 2.0);
+
+    baseline.blend(&TDigest::new(), 0.5);
+    assert_eq!(baseline.len(), 3);
+}
+
+#[test]
+fn half_precision() {
+    let mut digest = TDigest::new();
+    // 0.5, 65504 (the largest f16), 2^-24 (the smallest subnormal f16), -0.
+    for bits in [0x3800, 0x7bff, 0x0001, 0x8000] {
+        digest.insert_f16_bits(bits);
+    }
+    // NaNs are ignored.
+    digest.insert_f16_bits(0x7e00);
+    assert_eq!(digest.len(), 4);
+    assert_eq!(digest.quantile(0.0), -0.0);
+    assert_eq!(digest.quantile(1.0), 65504.0);
+
+    let mut digest = TDigest::new();
+    digest.insert_f16_bits(0xfc00);
+    digest.insert_bf16_bits(0xc2f7); // -123.5
+    assert_eq!(digest.quantile(0.0), f32::NEG_INFINITY);
+    assert_eq!(digest.quantile(1.0), -123.5);
+}
+
+#[test]
+fn labels_must_match() {
+    let mut a = TDigest::from([1.0]);
+    a.set_label("service", "api");
+    let mut b = TDigest::from([2.0]);
+    assert_eq!(
+        a.try_merge(&b),
+        Err(Error::LabelMismatch("service".to_string()))
+    );
+    assert_eq!(a.len(), 1);
+
+    b.set_label("service", "api");
+    b.set_label("unit", "ms");
+    assert_eq!(
+        a.try_merge(&b),
+        Err(Error::LabelMismatch("unit".to_string()))
+    );
+
+    a.set_label("unit", "ms");
+    a |= &b;
+    assert_eq!(a.len(), 2);
+}
+
+#[test]
+fn labels_union() {
+    let mut builder = TDigest::builder();
+    builder.label_policy(tdigest_ch::LabelPolicy::Union);
+    let mut a = builder.build();
+    a.set_label("service", "api");
+
+    let mut b = TDigest::from([1.0]);
+    b.set_label("unit", "ms");
+    a.try_merge(&b).unwrap();
+    assert_eq!(a.labels().len(), 2);
+
+    let mut c = TDigest::from([2.0]);
+    c.set_label("unit", "s");
+    assert_eq!(
+        a.try_merge(&c),
+        Err(Error::LabelMismatch("unit".to_string()))
+    );
+    assert_eq!(a.len(), 1);
+
+    a.clear();
+    assert_eq!(a.labels().len(), 2);
+}
+
+#[test]
+fn labels_bitor() {
+    let mut a = TDigest::from([1.0]);
+    a.set_label("unit", "ms");
+    let mut b = TDigest::from([2.0]);
+    b.set_label("unit", "s");
+    b.set_label("service", "api");
+
+    a |= &b;
+    assert_eq!(a.len(), 2);
+    assert_eq!(a.labels().len(), 2);
+    assert_eq!(a.labels()["unit"], "ms");
+    assert_eq!(a.labels()["service"], "api");
+}
+
+#[test]
+fn units_convert_on_merge() {
+    let mut millis = TDigest::from_iter((1..=1000).map(|v| v as f32));
+    millis.set_unit(Unit::Millis);
+    let mut seconds = TDigest::from_iter((1..=1000).map(|v| v as f32 / 1000.));
+    seconds.set_unit(Unit::Seconds);
+
+    millis.try_merge(&seconds).unwrap();
+    assert_eq!(millis.len(), 2000);
+    assert!((millis.quantile(0.5) - 500.).abs() < 5.);
+    assert!((millis.quantile(1.0) - 1000.).abs() < 1e-3);
+
+    let minutes = Unit::Custom {
+        dimension: "time".to_string(),
+        scale: 60.,
+    };
+    let mut digest = TDigest::from([2.0]);
+    digest.set_unit(minutes);
+    millis |= &digest;
+    assert_eq!(millis.quantile(1.0), 120_000.);
+}
+
+#[test]
+fn units_incompatible() {
+    let mut millis = TDigest::from([1.0]);
+    millis.set_unit(Unit::Millis);
+    let mut bytes = TDigest::from([2.0]);
+    bytes.set_unit(Unit::Bytes);
+    assert_eq!(millis.try_merge(&bytes), Err(Error::IncompatibleUnits));
+    assert_eq!(millis.len(), 1);
+
+    // The values are not blended by `|` either.
+    let merged = panic::catch_unwind(AssertUnwindSafe(|| &millis | &bytes));
+    assert!(merged.is_err());
+    let mut merged = millis.clone();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| merged |= &bytes));
+    assert!(result.is_err());
+    assert_eq!(merged, millis);
+
+    // Untagged t-digests adopt the unit they are merged with.
+    let mut untagged = TDigest::from([3.0]);
+    untagged.try_merge(&bytes).unwrap();
+    assert_eq!(untagged.unit(), Some(&Unit::Bytes));
+}
+
+#[test]
+#[should_panic(expected = "incompatible units")]
+fn units_incompatible_bitor_panics() {
+    let mut millis = TDigest::from([1.0]);
+    millis.set_unit(Unit::Millis);
+    let mut bytes = TDigest::from([2.0]);
+    bytes.set_unit(Unit::Bytes);
+    millis |= &bytes;
+}
+
+#[test]
+fn coalesce_duplicates() {
+    let mut builder = TDigest::builder();
+    builder.max_unmerged(100_000);
+    let mut plain = builder.build();
+    let mut builder = TDigest::builder();
+    builder.max_unmerged(100_000).coalesce_duplicates(true);
+    let mut coalesced = builder.build();
+    for digest in [&mut plain, &mut coalesced] {
+        digest.insert(0.0);
+        digest.extend(std::iter::repeat_n(1.0, 10_000));
+    }
+
+    let (plain_stats, coalesced_stats) = (plain.stats(), coalesced.stats());
+    assert_eq!(plain_stats.unmerged(), 10_001);
+    assert_eq!(coalesced_stats.compressions(), 0);
+    // No centroid grows past epsilon times the count, 100 elements, so that
+    // the run is still split into at least 100 centroids.
+    assert!(
+        (100..1_000).contains(&coalesced_stats.unmerged()),
+        "{}",
+        coalesced_stats.unmerged()
+    );
+
+    assert_eq!(coalesced.len(), plain.len());
+    for level in [0.0, 0.01, 0.5, 1.0] {
+        assert_eq!(coalesced.quantile(level), plain.quantile(level));
+    }
+}
+
+#[test]
+fn insert_sampled() {
+    let mut sampled = TDigest::new();
+    let mut full = TDigest::new();
+    for v in 0..10_000 {
+        // Slow requests are always kept, fast ones are sampled at 1%.
+        if v >= 9_000 {
+            sampled.insert(v as f32);
+        } else if v % 100 == 0 {
+            sampled.insert_sampled(v as f32, 0.01);
+        }
+        full.insert(v as f32);
+    }
+    assert_eq!(sampled.len(), full.len());
+    for level in [0.5, 0.95] {
+        let (s, f) = (sampled.quantile(level), full.quantile(level));
+        assert!((s - f).abs() < 150., "level {}: {} vs {}", level, s, f);
+    }
+}
+
+#[test]
+fn insert_sampled_fractional_rate() {
+    // A weight of 3.33 rounded to 3 would lose a tenth of the elements.
+    let mut digest = TDigest::new();
+    for v in 0..3_000 {
+        digest.insert_sampled(v as f32, 0.3);
+    }
+    assert!(digest.len().abs_diff(10_000) <= 1, "{}", digest.len());
+    assert!((digest.quantile(0.5) - 1_500.).abs() < 30.);
+
+    digest.clear();
+    digest.insert_sampled(1.0, 0.4);
+    digest.insert_sampled(1.0, 0.4);
+    assert_eq!(digest.len(), 5);
+}
+
+#[test]
+#[should_panic(expected = "sample rate must be in (0, 1]")]
+fn insert_sampled_invalid_rate() {
+    TDigest::new().insert_sampled(1.0, 0.);
+}
+
+#[test]
+#[should_panic(expected = "sampled weight overflows the count")]
+fn insert_sampled_overflow() {
+    let mut digest = TDigest::new();
+    digest.insert_many(1.0, u64::MAX - 1);
+    digest.insert_sampled(2.0, 0.5);
+}
+
+#[test]
+#[should_panic(expected = "sampled weight overflows the count")]
+fn insert_sampled_tiny_rate() {
+    TDigest::new().insert_sampled(1.0, 1e-300);
+}
+
+#[test]
+fn apdex() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    assert!((digest.apdex(2_000., 6_000.) - 0.4).abs() < 0.01);
+    assert_eq!(digest.apdex(-1., -1.), 0.);
+    assert_eq!(digest.apdex(10_000., 10_000.), 1.);
+    assert!(TDigest::new().apdex(1., 4.).is_nan());
+}
+
+#[test]
+fn range_stats() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| v as f32));
+    let tail = digest.range_stats(0.95, 1.);
+    assert!((tail.weight() - 5_000.).abs() < 1e-6);
+    assert!((tail.mean() - 97_500.).abs() < 100.);
+    assert!((tail.min_q() - 95_000.).abs() < 100.);
+    assert_eq!(tail.max_q(), 99_999.);
+
+    let whole = digest.range_stats(0., 1.);
+    assert_eq!(whole.weight(), 100_000.);
+    assert!((whole.mean() - 49_999.5).abs() < 1.);
+
+    let band = digest.range_stats(0.5, 0.5);
+    assert_eq!(band.weight(), 0.);
+    assert!(band.mean().is_nan());
+    assert!(TDigest::new().range_stats(0., 1.).mean().is_nan());
+}
+
+#[test]
+fn exact_for_test() {
+    let mut values: Vec<f32> = (1..=1_000).map(|v| v as f32).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut digest = TDigest::exact_for_test();
+    digest.extend(values);
+
+    assert_eq!(digest.len(), 1_000);
+    assert_eq!(digest.quantile(0.), 1.);
+    assert_eq!(digest.quantile(0.123), 123.);
+    assert_eq!(digest.quantile(0.1235), 124.);
+    assert_eq!(digest.quantile(1.), 1_000.);
+
+    let quantiles = digest.quantiles();
+    assert_eq!(quantiles.get(0.9), 900.);
+}
+
+#[test]
+fn build_lookup_table() {
+    let mut digest = TDigest::from_iter((0..10_001).map(|v| v as f32));
+    let table = digest.build_lookup_table(101);
+    assert_eq!(table.len(), 101);
+    assert_eq!(table[0], 0.);
+    assert_eq!(table[100], 10_000.);
+    for (i, &q) in table.iter().enumerate() {
+        assert!((q - 100. * i as f32).abs() < 50., "entry {}: {}", i, q);
+    }
+    assert!(table.windows(2).all(|w| w[0] <= w[1]));
+
+    let empty = TDigest::new().build_lookup_table(4);
+    assert!(empty.iter().all(|q| q.is_nan()));
+}
+
+#[test]
+fn centroid_weight_profile() {
+    let mut builder = TDigest::builder();
+    builder.epsilon(0.05);
+    let mut digest = builder.build();
+    digest.extend((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+
+    let profile = digest.centroid_weight_profile();
+    assert_eq!(profile.iter().map(|c| c.weight()).sum::<u64>(), 100_000);
+    assert!(profile.windows(2).all(|w| w[0].level() < w[1].level()));
+    assert!(profile.windows(2).all(|w| w[0].mean() <= w[1].mean()));
+    // Centroids stay within a small factor of their bound, except singletons
+    // at the edges.
+    assert!(profile
+        .iter()
+        .all(|c| c.weight() == 1 || (c.weight() as f64) < 2. * c.bound()));
+
+    assert!(TDigest::new().centroid_weight_profile().is_empty());
+}
+
+#[test]
+fn cdf_inverts_quantile() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    for level in [0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99] {
+        let quantile = digest.quantile(level);
+        let cdf = digest.cdf(quantile);
+        assert!((cdf - level).abs() < 1e-3, "level {}: {}", level, cdf);
+    }
+    assert!((digest.cdf(25_000.) - 0.25).abs() < 1e-3);
+    assert_eq!(digest.cdf(-1.), 0.);
+    assert_eq!(digest.cdf(f32::INFINITY), 1.);
+
+    let mut single = TDigest::from([3.0]);
+    assert_eq!(single.cdf(2.), 0.);
+    assert_eq!(single.cdf(3.), 1.);
+    assert!(TDigest::new().cdf(1.).is_nan());
+}
+
+#[test]
+fn quantile_explain() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+    digest.insert_many(20_000., 100);
+    for i in 0..=1_000 {
+        let level = i as f64 / 1_000.;
+        let explanation = digest.quantile_explain(level);
+        assert_eq!(explanation.value(), digest.quantile(level));
+        assert_eq!(explanation.level(), level);
+        assert_eq!(explanation.rank(), level * 10_100.);
+        if explanation.rule() == QuantileRule::Interpolated {
+            let (lower, upper) = (explanation.lower().unwrap(), explanation.upper().unwrap());
+            assert!(lower.0 <= explanation.value() && explanation.value() <= upper.0);
+            assert!(explanation.lower_rank() < explanation.rank());
+            assert!(explanation.rank() < explanation.upper_rank());
+        }
+    }
+    let tail = digest.quantile_explain(0.999);
+    assert_eq!(tail.rule(), QuantileRule::AfterLast);
+    assert_eq!(tail.lower(), Some((20_000., 100)));
+    assert_eq!(digest.quantile_explain(0.).rule(), QuantileRule::Min);
+    assert_eq!(digest.quantile_explain(1.).rule(), QuantileRule::Max);
+
+    assert_eq!(
+        TDigest::new().quantile_explain(0.5).rule(),
+        QuantileRule::Empty
+    );
+    let single = TDigest::from([1.0]).quantile_explain(0.5);
+    assert_eq!(single.rule(), QuantileRule::SingleCentroid);
+    assert_eq!(single.upper(), None);
+}
+
+#[test]
+fn rank() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    assert_eq!(digest.rank(-1.), 0.);
+    assert_eq!(digest.rank(99_999.), digest.len() as f64);
+    assert!((digest.rank(500.) - 501.).abs() < 5.);
+    assert!((digest.rank(90_000.) - 90_001.).abs() < 100.);
+    assert!(TDigest::new().rank(1.).is_nan());
+}
+
+#[test]
+fn cdf_many_matches_cdf() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+    digest.insert_many(5_000., 500);
+    let mut values: Vec<f32> = (0..=200).map(|i| i as f32 * 50. - 10.).collect();
+    values.extend([f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 5_000., 5_000.]);
+    values.shuffle(&mut rand::thread_rng());
+
+    let many = digest.cdf_many(&values);
+    assert_eq!(many.len(), values.len());
+    for (&value, &fraction) in values.iter().zip(many.iter()) {
+        assert_eq!(fraction, digest.cdf(value), "value {}", value);
+    }
+    assert!(TDigest::new().cdf_many(&[1.])[0].is_nan());
+    assert!(digest.cdf_many(&[]).is_empty());
+}
+
+#[test]
+fn score() {
+    let mut baseline = TDigest::from_iter((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+    let values = [9_000., -5., 2_500., f32::NAN, 20_000.];
+    let scores = baseline.score(&values);
+    for (&value, &score) in values.iter().zip(scores.iter()) {
+        assert_eq!(score, 100. * baseline.cdf(value), "value {}", value);
+    }
+    assert!((scores[0] - 90.).abs() < 0.1);
+    assert_eq!(scores[1], 0.);
+    assert_eq!(scores[4], 100.);
+    assert!(TDigest::new().score(&[1.])[0].is_nan());
+}
+
+#[test]
+fn sf() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    for value in [
+        -1., 0., 10.5, 500., 50_000., 99_000., 99_998.5, 99_999., 1e6,
+    ] {
+        let expected = 1. - digest.cdf(value);
+        assert!((digest.sf(value) - expected).abs() < 1e-9, "{value}");
+    }
+    assert_eq!(digest.sf(f32::NAN), 0.);
+    assert!(TDigest::new().sf(1.).is_nan());
+
+    // The fraction above the second largest value keeps its precision.
+    let mut large = TDigest::new();
+    large.insert_many(0., 1_000_000_000_000 - 3);
+    large.extend([1., 2., 3.]);
+    assert_eq!(large.sf(2.), 1e-12);
+    assert_ne!(1. - large.cdf(2.), 1e-12);
+
+    let mut builder = TDigest::builder();
+    builder.retain_raw_samples(10);
+    let mut small = builder.build();
+    small.extend([1.0, 2.0, 3.0, 4.0, 5.0]);
+    assert_eq!(small.sf(3.), 0.4);
+    assert_eq!(small.sf(f32::NAN), 0.);
+}
+
+#[test]
+fn quantile_checked() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+    for level in [0., 0.01, 0.5, 0.999, 1.] {
+        assert_eq!(digest.quantile_checked(level), Some(digest.quantile(level)));
+    }
+    for level in [-0.1, 1.1, f64::NAN, f64::INFINITY] {
+        assert_eq!(digest.quantile_checked(level), None);
+    }
+    assert_eq!(TDigest::new().quantile_checked(0.5), None);
+}
+
+#[test]
+fn value_at_rank() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    assert_eq!(digest.value_at_rank(0), 0.);
+    assert_eq!(digest.value_at_rank(99_999), 99_999.);
+    assert!((digest.value_at_rank(500) - 500.).abs() < 5.);
+    assert!((digest.value_at_rank(90_000) - 90_000.).abs() < 100.);
+    assert!(digest.value_at_rank(100_000).is_nan());
+    assert!(TDigest::new().value_at_rank(0).is_nan());
+
+    let mut builder = TDigest::builder();
+    builder.retain_raw_samples(10);
+    let mut small = builder.build();
+    small.extend([4.0, 2.0, 3.0, 1.0]);
+    let values: Vec<f32> = (0..4).map(|k| small.value_at_rank(k)).collect();
+    assert_eq!(values, [1., 2., 3., 4.]);
+}
+
+#[test]
+fn quantiles_many_matches_quantile() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+    digest.insert_many(5_000., 500);
+    let mut levels: Vec<f64> = (0..=200).map(|i| i as f64 / 200.).collect();
+    levels.extend([f64::NAN, -0.5, 1.5, 0.5, 0.5]);
+    levels.shuffle(&mut rand::thread_rng());
+
+    let many = digest.quantiles_many(&levels);
+    assert_eq!(many.len(), levels.len());
+    for (&level, &quantile) in levels.iter().zip(many.iter()) {
+        assert_eq!(quantile, digest.quantile(level), "level {}", level);
+    }
+
+    assert!(TDigest::new().quantiles_many(&[0.5])[0].is_nan());
+    assert_eq!(TDigest::from([2.0]).quantiles_many(&[0., 1.]), [2., 2.]);
+    assert!(digest.quantiles_many(&[]).is_empty());
+}
+
+#[test]
+fn trimmed_mean() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    digest.insert_many(1e9, 10);
+    assert!((digest.trimmed_mean(0.05, 0.95) - 5_000.).abs() < 10.);
+    assert!(digest.trimmed_mean(0., 1.) > 900_000.);
+    assert!(digest.trimmed_mean(0.5, 0.5).is_nan());
+}
+
+#[test]
+fn winsorized_mean() {
+    let mut digest = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    digest.insert_many(1e9, 10);
+    assert!((digest.winsorized_mean(0.05, 0.95) - 4_995.).abs() < 10.);
+    assert!(digest.winsorized_mean(0.05, 0.95) > digest.trimmed_mean(0.05, 0.95) - 10.);
+    assert!((digest.winsorized_mean(0., 1.) as f64 - digest.mean()).abs() < 1e3);
+    assert_eq!(digest.winsorized_mean(0.5, 0.5), digest.quantile(0.5));
+    assert!(TDigest::new().winsorized_mean(0.05, 0.95).is_nan());
+}
+
+#[test]
+fn compare() {
+    let mut baseline = TDigest::from_iter((0..10_000).map(|v| v as f32));
+    let mut current = TDigest::from_iter((0..12_000).map(|v| (v + 1_000) as f32));
+
+    let levels = [0.5, 0.99];
+    let report = current.compare(&mut baseline, &levels);
+    assert_eq!(report.levels(), levels);
+    assert_eq!(report.count_change(), 2_000);
+    assert_eq!(report.baseline_count(), 10_000);
+    assert_eq!(report.current_count(), 12_000);
+    for i in 0..2 {
+        let delta = report.current()[i] - report.baseline()[i];
+        assert_eq!(report.deltas()[i], delta);
+        assert!(report.relative_changes()[i] > 0.);
+    }
+    assert!((report.deltas()[0] - 2_000.).abs() < 50.);
+    assert!((report.ks_distance() - 0.25).abs() < 0.01);
+
+    let same = baseline.clone().compare(&mut baseline, &levels);
+    assert_eq!(same.deltas(), [0., 0.]);
+    assert_eq!(same.ks_distance(), 0.);
+    assert!(TDigest::new()
+        .compare(&mut baseline, &levels)
+        .ks_distance()
+        .is_nan());
+}
+
+#[test]
+fn mean() {
+    let mut digest = TDigest::new();
+    assert!(digest.mean().is_nan());
+
+    let values: Vec<f32> = (0..100_000).map(|v| (v % 1000) as f32 + 0.1).collect();
+    digest.extend(values.iter().copied());
+    digest.quantile(0.5);
+    let exact = values.iter().map(|&v| v as f64).sum::<f64>() / 100_000.;
+    assert!((digest.mean() - exact).abs() < 1e-9);
+
+    let other = TDigest::from([1e6, 2e6]);
+    let mut merged = &digest | &other;
+    assert_eq!(merged.len(), 100_002);
+    let total = digest.mean() * 100_000. + 3e6;
+    assert!((merged.mean() - total / 100_002.).abs() < 1e-9);
+
+    merged.subtract(&other).unwrap();
+    assert!((merged.mean() - digest.mean()).abs() < 1e-9);
+
+    digest.clear();
+    assert!(digest.mean().is_nan());
+}
+
+#[test]
+fn min_max() {
+    let mut digest = TDigest::new();
+    assert!(digest.min().is_nan() && digest.max().is_nan());
+
+    // Merged centroids drift away from the extremes.
+    let mut builder = TDigest::builder();
+    builder.epsilon(0.2).max_centroids(8);
+    let mut coarse = builder.build();
+    coarse.extend((0..10_000).map(|v| ((v * 7919) % 10_000) as f32 + 0.25));
+    assert_eq!(coarse.min(), 0.25);
+    assert_eq!(coarse.max(), 9_999.25);
+    assert_eq!(coarse.quantile(0.), 0.25);
+    assert_eq!(coarse.quantile(1.), 9_999.25);
+    assert_eq!(
+        coarse.quantiles_many(&[1., 0.5, 0.]),
+        [9_999.25, coarse.quantile(0.5), 0.25]
+    );
+    assert!(coarse.quantile(1e-6) >= coarse.min());
+
+    digest.extend([5., 6.]);
+    digest |= &coarse;
+    assert_eq!((digest.min(), digest.max()), (0.25, 9_999.25));
+
+    // Subtracting values that do not reach the extremes keeps them exact.
+    digest.subtract(&TDigest::from([6.])).unwrap();
+    assert_eq!((digest.min(), digest.max()), (0.25, 9_999.25));
+
+    digest.clear();
+    assert!(digest.min().is_nan() && digest.max().is_nan());
+}
+
+#[test]
+fn tail_mass() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    for level in [0., 0.01, 0.5, 0.9, 0.99, 0.999, 1.] {
+        let (threshold, above) = digest.tail_mass_above(level);
+        assert_eq!(threshold, digest.quantile(level));
+        assert_eq!(above, digest.len() as f64 - digest.rank(threshold));
+        assert!(
+            (above - (1. - level) * 100_000.).abs() <= 100.,
+            "{level}: {above}"
+        );
+
+        let (below_threshold, below) = digest.tail_mass_below(level);
+        assert_eq!(below_threshold, threshold);
+        assert_eq!(above + below, 100_000.);
+    }
+    assert_eq!(digest.tail_mass_above(1.).1, 0.);
+
+    let (threshold, weight) = TDigest::new().tail_mass_above(0.5);
+    assert!(threshold.is_nan());
+    assert_eq!(weight, 0.);
+}
+
+#[test]
+fn percentile() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    for percentile in [0., 1., 50., 90., 99., 99.9, 100.] {
+        assert_eq!(
+            digest.percentile(percentile),
+            digest.quantile(percentile / 100.)
+        );
+    }
+    assert_eq!(digest.median(), digest.quantile(0.5));
+    assert!(TDigest::new().median().is_nan());
+}
+
+#[test]
+fn count_between() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    assert_eq!(digest.count_between(-10., 200_000.), 100_000.);
+    assert_eq!(digest.count_between(0., 99_999.), 100_000.);
+    assert_eq!(digest.count_between(10., 5.), 0.);
+    assert!((digest.count_between(10_000., 30_000.) - 20_001.).abs() < 100.);
+    let (lo, hi) = (digest.quantile(0.25), digest.quantile(0.75));
+    assert!((digest.count_between(lo, hi) - 50_000.).abs() < 100.);
+
+    let mut small = TDigest::from([1., 2., 2., 3.]);
+    assert_eq!(small.count_between(2., 2.), 2.);
+    assert_eq!(small.count_between(1., 1.), 1.);
+    assert!(TDigest::new().count_between(0., 1.).is_nan());
+}
+
+#[test]
+fn histogram() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    let edges = [-5., 1_000., 25_000., 50_000., 99_999., 200_000.];
+    let counts = digest.histogram(&edges);
+    assert_eq!(counts.len(), 7);
+    assert_eq!(counts.iter().sum::<u64>(), 100_000);
+    assert_eq!(counts[0], 0);
+    assert_eq!(counts[5], 1);
+    assert_eq!(counts[6], 0);
+    for (i, expected) in [1_000, 24_000, 25_000, 49_999].iter().enumerate() {
+        assert!(counts[i + 1].abs_diff(*expected) < 100, "{counts:?}");
+    }
+
+    assert_eq!(digest.histogram(&[]), [100_000]);
+    assert_eq!(TDigest::new().histogram(&[1., 2.]), [0, 0, 0]);
+    assert_eq!(
+        TDigest::from([1., 2., 2., 3.]).histogram(&[2., 3.]),
+        [1, 2, 1]
+    );
+}
+
+#[test]
+#[should_panic(expected = "edges must be strictly increasing")]
+fn histogram_unsorted_edges() {
+    TDigest::new().histogram(&[2., 1.]);
+}
+
+#[test]
+fn equal_weight_bins() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    let edges = digest.equal_weight_bins(10);
+    assert_eq!(edges.len(), 9);
+    assert!(edges.windows(2).all(|w| w[0] < w[1]));
+    for count in digest.histogram(&edges) {
+        assert!(count.abs_diff(10_000) < 100, "{count}");
+    }
+
+    assert!(digest.equal_weight_bins(1).is_empty());
+    assert!(TDigest::new().equal_weight_bins(2)[0].is_nan());
+}
+
+#[test]
+fn density() {
+    // Logistic data with a scale of 10, whose density peaks at 1 / 40.
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| {
+        let u = (v as f64 + 0.5) / 100_000.;
+        (150. + 10. * (u / (1. - u)).ln()) as f32
+    }));
+    assert!((digest.density(150.) - 0.025).abs() < 0.001);
+    assert!(digest.density(150.) > digest.density(170.));
+    assert!(digest.density(170.) > digest.density(200.));
+    assert_eq!(digest.density(0.), 0.);
+    assert_eq!(digest.density(1_000.), 0.);
+
+    // The density integrates to about 1.
+    let integral: f64 = (0..3_000)
+        .map(|v| digest.density(v as f32 / 10.) / 10.)
+        .sum();
+    assert!((integral - 1.).abs() < 0.01, "{integral}");
+
+    assert!(TDigest::new().density(0.).is_nan());
+}
+
+#[test]
+fn resampled() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    digest.insert_many(1e6, 10);
+    digest.quantile(0.5);
+    let original = digest.clone();
+
+    let mut small = digest.resampled(50);
+    assert_eq!(digest, original);
+    assert!(small.centroid_weight_profile().len() <= 50);
+    assert_eq!(small.len(), digest.len());
+    assert_eq!(small.mean(), digest.mean());
+    assert_eq!(small.max(), 1e6);
+    for level in [0.01, 0.1, 0.5, 0.9, 0.99] {
+        let (value, estimate) = (digest.quantile(level), small.quantile(level));
+        assert!(
+            (digest.cdf(estimate) - level).abs() < 0.02,
+            "{level}: {value} {estimate}"
+        );
+    }
+
+    let mut large = digest.resampled(100_000);
+    assert_eq!(large.quantile(0.5), digest.quantile(0.5));
+    assert!(TDigest::new().resampled(10).is_empty());
+}
+
+#[test]
+fn quantile_density() {
+    // Logistic data with a scale of 10, whose quantile density is
+    // 10 / (p * (1 - p)).
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| {
+        let u = (v as f64 + 0.5) / 100_000.;
+        (150. + 10. * (u / (1. - u)).ln()) as f32
+    }));
+    assert!((digest.quantile_density(0.5) - 40.).abs() < 1.);
+    assert!((digest.quantile_density(0.9) - 111.1).abs() < 3.);
+    assert!(digest.quantile_density(0.99) > digest.quantile_density(0.9));
+    let median = digest.median();
+    assert!((digest.quantile_density(0.5) * digest.density(median) - 1.).abs() < 0.05);
+    assert_eq!(digest.quantile_density(-0.5), 0.);
+    assert_eq!(digest.quantile_density(1.5), 0.);
+
+    assert_eq!(TDigest::from([3.]).quantile_density(0.5), 0.);
+    assert!(TDigest::new().quantile_density(0.5).is_nan());
+    assert!(digest.quantile_density(f64::NAN).is_nan());
+}
+
+#[test]
+fn mode() {
+    // Right-skewed data, whose mode is well below the median.
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| {
+        let u = (v as f64 + 0.5) / 100_000.;
+        (10. * (u / (1. - u)).sqrt()) as f32
+    }));
+    let (mode, median) = (digest.mode(), digest.median());
+    assert!(mode < median, "{mode}");
+    assert!(digest.density(mode) > digest.density(median));
+
+    // A point mass is the mode.
+    digest.insert_many(50., 10_000);
+    assert_eq!(digest.mode(), 50.);
+
+    assert_eq!(TDigest::from([3.]).mode(), 3.);
+    assert!(TDigest::new().mode().is_nan());
+}
+
+#[test]
+fn iqr() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    assert_eq!(digest.iqr(), digest.quantile(0.75) - digest.quantile(0.25));
+    assert!((digest.iqr() - 50_000.).abs() < 100.);
+    assert_eq!(digest.range(0., 1.), 99_999.);
+    assert_eq!(digest.range(0.9, 0.1), -digest.range(0.1, 0.9));
+    assert!(TDigest::new().iqr().is_nan());
+}
+
+#[test]
+fn tail_ratio() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    assert_eq!(
+        digest.tail_ratio(0.99, 0.5),
+        digest.quantile(0.99) / digest.quantile(0.5)
+    );
+    assert!((digest.tail_ratio(0.99, 0.5) - 1.98).abs() < 0.01);
+    assert!(digest.tail_ratio(0.5, 0.).is_nan());
+    assert!(TDigest::new().tail_ratio(0.99, 0.5).is_nan());
+
+    let mut negative = TDigest::from([-4.0, -2.0]);
+    assert_eq!(negative.tail_ratio(1., 0.), 0.5);
+}
+
+#[test]
+fn quantile_range() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    for (lo, hi) in [
+        (0.05, 0.95),
+        (0., 1.),
+        (0.5, 0.5),
+        (0.9, 0.1),
+        (0.001, 0.999),
+    ] {
+        assert_eq!(
+            digest.quantile_range(lo, hi),
+            (digest.quantile(lo), digest.quantile(hi))
+        );
+    }
+    let (low, high) = TDigest::new().quantile_range(0.05, 0.95);
+    assert!(low.is_nan() && high.is_nan());
+}
+
+#[test]
+fn raw_samples() {
+    let build = || {
+        let mut builder = TDigest::builder();
+        builder.retain_raw_samples(1000).max_centroids(16);
+        builder.build()
+    };
+    let mut digest = build();
+    let mut exact = TDigest::exact_for_test();
+    for v in 0..1000 {
+        let value = ((v * 7919) % 1000) as f32;
+        digest.insert(value);
+        exact.insert(value);
+    }
+    assert!(digest.is_exact());
+    for level in [0., 0.001, 0.25, 0.5, 0.99, 0.999, 1.] {
+        assert_eq!(digest.quantile(level), exact.quantile(level));
+    }
+    assert_eq!(digest.cdf(499.), 0.5);
+    assert_eq!(
+        digest.quantile_explain(0.5).rule(),
+        QuantileRule::RawSamples
+    );
+
+    // Merges keep the raw values while they fit.
+    let mut half = build();
+    half.extend((0..500).map(|v| v as f32));
+    let mut merged = half.clone();
+    merged |= &half;
+    assert!(merged.is_exact());
+    assert_eq!(merged.quantile(0.5), 249.);
+    merged |= &digest;
+    assert!(!merged.is_exact());
+    assert_eq!(merged.len(), 2000);
+
+    digest.insert(1000.);
+    assert!(!digest.is_exact());
+    digest.clear();
+    digest.insert(1.);
+    assert!(digest.is_exact());
+}
+
+#[test]
+fn error_bound() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut digest = TDigest::from_iter(values.iter().map(|&v| v as f32));
+    for level in [0., 0.001, 0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99, 0.999, 1.] {
+        let exact = (level * 99_999.) as f32;
+        let bound = digest.error_bound(level);
+        assert!(bound >= 0.);
+        assert!((digest.quantile(level) - exact).abs() <= bound + 1.);
+    }
+
+    let mut builder = TDigest::builder();
+    builder.retain_raw_samples(10);
+    let mut small = builder.build();
+    small.extend([1.0, 2.0, 3.0]);
+    assert_eq!(small.error_bound(0.5), 0.);
+    assert!(TDigest::new().error_bound(0.5).is_nan());
+}
+
+#[test]
+fn quantiles_many_monotonic() {
+    let mut values: Vec<f32> = (0..10_000)
+        .map(|v| {
+            if v % 100 == 0 {
+                1e6 * v as f32
+            } else {
+                (v % 7) as f32
+            }
+        })
+        .collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut digest = TDigest::from_iter(values);
+
+    let mut levels: Vec<f64> = (0..=10_000).map(|i| i as f64 / 10_000.).collect();
+    levels.shuffle(&mut rand::thread_rng());
+    let quantiles = digest.quantiles_many(&levels);
+
+    let mut pairs: Vec<(f64, f32)> = levels.into_iter().zip(quantiles).collect();
+    pairs.sort_by(|l, r| l.0.total_cmp(&r.0));
+    assert!(pairs.windows(2).all(|w| w[0].1 <= w[1].1));
+    assert!(digest.quantiles_many(&[0.5, f64::NAN])[1].is_finite());
+}
+
+#[test]
+fn stats() {
+    let mut builder = TDigest::builder();
+    builder.max_centroids(64).max_unmerged(64);
+    let mut digest = builder.build();
+    assert_eq!(digest.stats().compressions(), 0);
+    assert!(digest.stats().error_p50().is_nan());
+
+    digest.extend((0..10_000).map(|v| v as f32));
+    let stats = digest.stats();
+    assert_eq!(stats.unmerged(), 10_000 % 65);
+    assert!(stats.centroids() <= 64);
+    assert_eq!(stats.compressions(), 10_000 / 65);
+    assert!(stats.memory_bytes() >= 64 * 8);
+
+    // Large weights at the median cannot be merged under the bound, and fall
+    // back to brute-force compression.
+    let mut builder = TDigest::builder();
+    builder.max_centroids(4).epsilon(0.001);
+    let mut tiny = builder.build();
+    tiny.extend((0..1000).map(|v| v as f32));
+    tiny.quantile(0.5);
+    assert!(tiny.stats().brute_compressions() > 0);
+
+    // The counters do not take part in equality.
+    let mut a = TDigest::from([1.0, 2.0]);
+    let b = a.clone();
+    a.quantile(0.5);
+    assert_eq!(a.stats().compressions(), b.stats().compressions() + 1);
+    assert_eq!(a.stats().unmerged(), 0);
+}
+
+#[test]
+fn centroids_roundtrip() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut digest = TDigest::from_iter(values.iter().map(|&v| v as f32));
+    let centroids = digest.clone().into_centroids();
+
+    let mut rebuilt = TDigest::from_centroids(TDigest::new(), centroids.iter().copied());
+    assert_eq!(rebuilt.len(), 100_000);
+    assert_eq!(rebuilt.clone().into_centroids(), centroids);
+    for level in [0.01, 0.25, 0.5, 0.75, 0.99] {
+        assert_eq!(rebuilt.quantile(level), digest.quantile(level));
+    }
+
+    let digest = TDigest::from_centroids(TDigest::new(), [(f32::NAN, 3), (1.0, 0), (2.0, 1)]);
+    assert_eq!(digest.into_centroids(), [(2.0, 1)]);
+}
+
+#[test]
+fn interpolation() {
+    let build = |interpolation| {
+        let mut builder = TDigest::builder();
+        builder.max_centroids(8).interpolation(interpolation);
+        let mut digest = builder.build();
+        digest.extend((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+        digest
+    };
+    let mut clickhouse = build(Interpolation::ClickHouse);
+    let mut dunning = build(Interpolation::Dunning);
+    let mut midpoint = build(Interpolation::Midpoint);
+
+    // Large tail centroids are interpolated towards the exact extremes.
+    for level in [0.0002, 0.9998] {
+        let exact = level as f32 * 9_999.;
+        let dunning_error = (dunning.quantile(level) - exact).abs();
+        assert!(dunning_error < (clickhouse.quantile(level) - exact).abs());
+        assert_eq!(dunning.quantile_explain(level).rule(), QuantileRule::Tail);
+    }
+    assert_eq!(dunning.quantile(0.5), clickhouse.quantile(0.5));
+    assert_eq!(midpoint.quantile(0.5), clickhouse.quantile(0.5));
+
+    let levels: Vec<f64> = (0..=100).map(|i| i as f64 / 100.).collect();
+    for digest in [&mut clickhouse, &mut dunning, &mut midpoint] {
+        let many = digest.quantiles_many(&levels);
+        for (&level, &quantile) in levels.iter().zip(&many) {
+            assert_eq!(quantile, digest.quantile(level));
+        }
+    }
+}
+
+#[test]
+fn interpolation_tail_of_two() {
+    // A tail centroid of two elements has no tail to interpolate.
+    let mut builder = TDigest::builder();
+    builder.interpolation(Interpolation::Dunning);
+    let mut upper = builder.build();
+    let mut lower = upper.clone();
+    upper.extend([1.0, 2.0]);
+    upper.insert_many(3.0, 2);
+    lower.insert_many(1.0, 2);
+    lower.extend([2.0, 3.0]);
+    for digest in [&mut upper, &mut lower] {
+        assert_eq!(digest.clone().into_centroids().len(), 3);
+        for i in 0..=100 {
+            let level = i as f64 / 100.;
+            assert!(!digest.quantile(level).is_nan(), "{level}");
+        }
+    }
+    assert_eq!(upper.quantile(0.75), 3.0);
+    assert_eq!(lower.quantile(0.25), 1.0);
+}
+
+#[test]
+fn cdf_inverts_interpolation() {
+    for interpolation in [
+        Interpolation::ClickHouse,
+        Interpolation::Dunning,
+        Interpolation::Midpoint,
+    ] {
+        // Large tail centroids, and singletons.
+        let mut compressed = TDigest::builder();
+        compressed.max_centroids(8).interpolation(interpolation);
+        let mut compressed = compressed.build();
+        compressed.extend((0..10_000).map(|v| ((v * 7919) % 10_000) as f32));
+        let mut singletons = TDigest::builder();
+        singletons.interpolation(interpolation);
+        let mut singletons = singletons.build();
+        singletons.extend((0..20).map(|v| (v * v) as f32));
+
+        for digest in [&mut compressed, &mut singletons] {
+            let cdf = digest.to_cdf();
+            for i in 1..1000 {
+                let level = i as f64 / 1000.;
+                let quantile = digest.quantile(level);
+                let fraction = digest.cdf(quantile);
+                // The quantile is the same at all the levels in between, as
+                // the fraction is the highest level of a flat quantile.
+                assert!(fraction >= level - 1e-6, "{interpolation:?} {level}");
+                assert!(
+                    fraction - level < 1e-6 || digest.quantile((level + fraction) / 2.) == quantile,
+                    "{interpolation:?} {level}"
+                );
+                assert!((digest.sf(quantile) - (1. - fraction)).abs() < 1e-6);
+                assert!((cdf.eval(quantile) - fraction).abs() < 1e-6);
+                assert_eq!(digest.cdf_many(&[quantile]), [fraction]);
+            }
+        }
+    }
+}
+
+#[test]
+fn to_cdf() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    let cdf = digest.to_cdf();
+    for value in [
+        -1.,
+        0.,
+        0.5,
+        10.,
+        999.5,
+        50_000.,
+        99_998.,
+        99_999.,
+        1e6,
+        f32::NAN,
+    ] {
+        assert_eq!(cdf.eval(value), digest.cdf(value), "{value}");
+    }
+    for level in [0., 0.01, 0.5, 0.99, 1.] {
+        assert!((cdf.eval(cdf.inverse(level)) - level).abs() < 1e-3);
+    }
+    assert!(cdf.iter().all(|(_, level)| (0. ..=1.).contains(&level)));
+
+    let mut builder = TDigest::builder();
+    builder.retain_raw_samples(10);
+    let mut small = builder.build();
+    small.extend([1.0, 2.0, 2.0, 5.0]);
+    let cdf = small.to_cdf();
+    assert_eq!(cdf.eval(1.5), 0.25);
+    assert_eq!(cdf.eval(2.0), 0.75);
+    assert_eq!(cdf.inverse(0.5), 2.0);
+
+    let empty = TDigest::new().to_cdf();
+    assert!(empty.eval(1.0).is_nan());
+    assert!(empty.inverse(0.5).is_nan());
+    assert_eq!(empty.iter().count(), 0);
+}
+
+#[test]
+fn ecdf() {
+    let mut digest = TDigest::from_iter((0..100_000).map(|v| ((v * 7919) % 100_000) as f32));
+    let points: Vec<(f32, f64)> = digest.ecdf().collect();
+    assert_eq!(points, digest.to_cdf().iter().collect::<Vec<_>>());
+    assert_eq!(points.first(), Some(&(0., 0.)));
+    assert_eq!(points.last(), Some(&(99_999., 1.)));
+    assert!(points
+        .windows(2)
+        .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1));
+    assert_eq!(TDigest::new().ecdf().count(), 0);
+}
+
+#[test]
+fn ks_distance() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut baseline = TDigest::from_iter(values.iter().map(|&v| v as f32));
+    let mut shifted = TDigest::from_iter(values.iter().map(|&v| v as f32 + 25_000.));
+    let mut disjoint = TDigest::from_iter(values.iter().map(|&v| v as f32 + 1e6));
+
+    assert!((shifted.ks_distance(&mut baseline) - 0.25).abs() < 0.005);
+    assert_eq!(
+        baseline.ks_distance(&mut shifted),
+        shifted.ks_distance(&mut baseline)
+    );
+    assert_eq!(disjoint.ks_distance(&mut baseline), 1.);
+    assert!(baseline.ks_distance(&mut TDigest::new()).is_nan());
+
+    let frozen = baseline.clone().freeze();
+    assert_eq!(
+        frozen.ks_distance(&shifted.clone().freeze()),
+        baseline.ks_distance(&mut shifted)
+    );
+}
+
+#[test]
+fn wasserstein_distance() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut baseline = TDigest::from_iter(values.iter().map(|&v| v as f32));
+    let mut shifted = TDigest::from_iter(values.iter().map(|&v| v as f32 + 1_000.));
+    let mut scaled = TDigest::from_iter(values.iter().map(|&v| v as f32 * 2.));
+
+    assert!((shifted.wasserstein_distance(&mut baseline) - 1_000.).abs() < 10.);
+    assert!((scaled.wasserstein_distance(&mut baseline) - 50_000.).abs() < 100.);
+    assert!(baseline.wasserstein_distance(&mut baseline.clone()).abs() < 1e-9);
+    assert!(baseline.wasserstein_distance(&mut TDigest::new()).is_nan());
+
+    let frozen = shifted.clone().freeze();
+    assert_eq!(
+        frozen.wasserstein_distance(&baseline.clone().freeze()),
+        shifted.wasserstein_distance(&mut baseline)
+    );
+}
+
+#[test]
+fn top_quantile() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut digest = TDigest::from_iter(values.iter().map(|&v| v as f32));
+    for tail_level in [1e-5, 1e-4, 0.001, 0.01, 0.1, 0.5, 0.9, 0.999] {
+        let top = digest.top_quantile(tail_level);
+        let quantile = digest.quantile(1. - tail_level);
+        assert!(
+            (top - quantile).abs() <= 1e-3 * quantile.abs().max(1.),
+            "{tail_level}"
+        );
+    }
+    assert_eq!(digest.top_quantile(0.), 99_999.);
+    assert_eq!(digest.top_quantile(1.), 0.);
+
+    // With a billion elements, 1 - 1e-12 keeps few digits of the tail level.
+    let mut large = TDigest::new();
+    large.insert_many(0., 1_000_000_000_000 - 3);
+    large.insert_many(1., 1);
+    large.insert_many(2., 1);
+    large.insert_many(3., 1);
+    assert_eq!(large.top_quantile(0.5e-12), 3.);
+    assert_eq!(large.top_quantile(1.5e-12), 2.);
+    assert_eq!(large.top_quantile(2.5e-12), 1.);
+
+    let mut builder = TDigest::builder();
+    builder.retain_raw_samples(10);
+    let mut small = builder.build();
+    small.extend([1.0, 2.0, 3.0, 4.0]);
+    assert_eq!(small.top_quantile(0.25), small.quantile(0.75));
+    assert_eq!(small.top_quantile(0.3), small.quantile(0.7));
+    assert!(TDigest::new().top_quantile(0.1).is_nan());
+}
+
+#[test]
+fn f64_values() {
+    // Consecutive timestamps in milliseconds, which `f32` cannot tell apart.
+    let start = 1_700_000_000_000.;
+    let mut digest = TDigest::<f64>::default();
+    digest.extend((0..10_000).map(|v| start + v as f64));
+    assert_eq!(digest.min(), start);
+    assert_eq!(digest.max(), start + 9_999.);
+    assert!((digest.quantile(0.5) - (start + 5_000.)).abs() <= 50.);
+    assert!((digest.cdf(start + 2_500.) - 0.25).abs() <= 0.01);
+
+    let mut other = TDigest::<f64>::default();
+    other.insert_many(start + 20_000., 10_000);
+    digest |= &other;
+    assert_eq!(digest.len(), 20_000);
+    assert_eq!(digest.quantile(1.0), start + 20_000.);
+    assert_eq!(digest.quantiles_many(&[0.0, 1.0]), [start, start + 20_000.]);
+
+    let mut builder = TDigest::builder();
+    builder.retain_raw_samples(4);
+    let mut exact = builder.build_as::<f64>();
+    exact.extend([start + 1., start + 2., start + 3.]);
+    assert_eq!(exact.quantile(0.5), start + 2.);
+    assert_eq!(exact.quantile_explain(0.5).rule(), QuantileRule::RawSamples);
+}
+
+#[test]
+fn f64_analytics() {
+    // Timestamps 1 ms apart, where consecutive `f32` are 131 s apart.
+    let start = 1_700_000_000_000.;
+    let values: Vec<f64> = (0..1000).map(|v| start + v as f64).collect();
+    let mut digest = bulk::from_sorted_chunks(TDigest::<f64>::default(), [values.clone()]);
+    assert_eq!((digest.min(), digest.max()), (start, start + 999.));
+
+    let cdf = digest.to_cdf();
+    assert_eq!(cdf.eval(start + 250.), digest.cdf(start + 250.));
+    assert!((cdf.inverse(0.5) - (start + 500.)).abs() <= 10.);
+    assert!((digest.count_between(start + 100., start + 199.) - 100.).abs() <= 10.);
+    let histogram = digest.histogram(&[start + 500.]);
+    assert_eq!(histogram.iter().sum::<u64>(), 1000);
+    assert!(histogram.iter().all(|&bin| bin.abs_diff(500) <= 10));
+    assert!(digest.error_bound(0.5) <= 10.);
+    assert!((digest.trimmed_mean(0.1, 0.9) - (start + 499.5)).abs() <= 1.);
+
+    let mut shifted = TDigest::<f64>::default();
+    shifted.extend_from_slice(&values.iter().map(|v| v + 1000.).collect::<Vec<_>>());
+    let report = shifted.compare(&mut digest, &[0.5]);
+    assert!((report.deltas()[0] - 1000.).abs() <= 10.);
+    assert!((digest.wasserstein_distance(&mut shifted) - 1000.).abs() <= 10.);
+
+    let frozen = digest.clone().freeze();
+    assert_eq!(frozen.quantile(0.5), digest.quantile(0.5));
+    assert_eq!(frozen.histogram(&[start + 500.]), histogram);
+    assert_eq!(frozen.stats().error_p50(), digest.error_bound(0.5));
+    assert_eq!(TDigest::from(frozen), digest);
+}
+
+#[test]
+fn try_insert() {
+    let mut digest = TDigest::new();
+    let rejected: Vec<Error> = [1.0, f32::NAN, 2.0, f32::NAN]
+        .into_iter()
+        .filter_map(|v| digest.try_insert(v).err())
+        .collect();
+    assert_eq!(rejected, [Error::NanValue, Error::NanValue]);
+    assert_eq!(digest.len(), 2);
+    assert_eq!(digest.nan_count(), 0);
+
+    assert_eq!(digest.try_insert_many(3.0, 0), Err(Error::ZeroCount));
+    assert_eq!(digest.try_insert_many(f32::NAN, 0), Err(Error::NanValue));
+    assert_eq!(digest.try_insert_many(3.0, 2), Ok(()));
+    assert_eq!(digest.len(), 4);
+    assert_eq!(digest.quantile(1.0), 3.0);
+    assert_eq!(Error::ZeroCount.to_string(), "count is zero");
+}
+
+#[test]
+fn remove_duplicated_batch() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+    let mut expected = TDigest::from_iter(values.iter().map(|&v| v as f32));
+    let mut digest = expected.clone();
+    let batch: Vec<f32> = (0..1_000).map(|v| 50_000. + v as f32).collect();
+    digest.extend(batch.iter().copied());
+
+    for &value in batch.iter() {
+        digest.remove(value).unwrap();
+    }
+    assert_eq!(digest.len(), 100_000);
+    assert!((digest.mean() - expected.mean()).abs() < 1e-6);
+    for level in [0.01, 0.1, 0.5, 0.9, 0.99] {
+        let quantile = digest.quantile(level);
+        assert!(
+            (quantile - expected.quantile(level)).abs() <= 500.,
+            "level {level}"
+        );
+    }
+
+    let before = digest.clone();
+    assert_eq!(digest.remove_many(0., 100_001), Err(Error::CountUnderflow));
+    assert_eq!(digest, before);
+    assert_eq!(digest.remove_many(f32::NAN, 10), Ok(()));
+    assert_eq!(digest.len(), 100_000);
+
+    digest.remove_many(0., 100_000).unwrap();
+    assert!(digest.is_empty());
+    assert!(digest.quantile(0.5).is_nan());
+}
+
+#[test]
+fn extend_f64_narrowing() {
+    let values: Vec<f64> = (0..1000).map(|v| v as f64 * 0.5).collect();
+    let mut digest = TDigest::new();
+    assert!(digest.extend_f64(values.iter().copied()).is_exact());
+    let collected = TDigest::from_f64_iter(values.iter().copied());
+    assert_eq!(digest, collected);
+
+    let narrowing = digest.extend_f64([1.0 + f64::EPSILON, f64::MAX, -f64::MAX, 1e-300, f64::NAN]);
+    assert_eq!(narrowing.inexact(), 4);
+    assert_eq!(narrowing.overflowed(), 2);
+    assert_eq!(digest.len(), 1004);
+    assert_eq!(digest.nan_count(), 1);
+    assert_eq!(digest.min(), f32::NEG_INFINITY);
+    assert_eq!(digest.max(), f32::INFINITY);
+
+    // Float literals are still inferred as `f32`.
+    let mut literals = TDigest::from_iter([1.0, 2.0]);
+    literals.extend([3.0]);
+    assert_eq!(literals.len(), 3);
+}
+
+#[test]
+fn insert_duration_units() {
+    let durations = [
+        Duration::from_nanos(1),
+        Duration::from_micros(1_500),
+        Duration::from_millis(20),
+    ];
+    for (unit, expected) in [
+        (Unit::Seconds, [1e-9, 0.0015, 0.02]),
+        (Unit::Millis, [1e-6, 1.5, 20.0]),
+        (Unit::Micros, [0.001, 1_500.0, 20_000.0]),
+        (Unit::Nanos, [1.0, 1_500_000.0, 20_000_000.0]),
+    ] {
+        let mut builder = TDigest::builder();
+        builder.unit(unit.clone());
+        let mut digest = builder.build();
+        for duration in durations {
+            digest.insert_duration(duration);
+        }
+        assert_eq!(digest.unit(), Some(&unit));
+        assert_eq!(digest.quantiles_many(&[0.0, 0.5, 1.0]), expected);
+    }
+    assert_eq!(Unit::from_parts("time", 1e-9), Unit::Nanos);
+}
+
+#[test]
+#[should_panic(expected = "unit must be a duration")]
+fn insert_duration_bytes() {
+    let mut digest = TDigest::new();
+    digest.set_unit(Unit::Bytes);
+    digest.insert_duration(Duration::from_secs(1));
+}
+
+#[test]
+fn quantiles_for_matches_quantile() {
+    let mut digests: Vec<TDigest> = (0..50)
+        .map(|i| TDigest::from_iter((0..i * 100).map(|v| ((v * 7919) % 1000 + i) as f32)))
+        .collect();
+    let levels = [0.0, 0.01, 0.5, 0.99, 1.0];
+
+    let mut expected = digests.clone();
+    let medians = quantiles_for(&mut digests, 0.5);
+    let many = quantiles_many_for(&mut digests, &levels);
+    for (i, digest) in expected.iter_mut().enumerate() {
+        let median = digest.quantile(0.5);
+        assert!(median.is_nan() && medians[i].is_nan() || median == medians[i]);
+        for (&level, &quantile) in levels.iter().zip(&many[i]) {
+            let estimate = digest.quantile(level);
+            assert!(estimate.is_nan() && quantile.is_nan() || estimate == quantile);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use tdigest_ch::TimingHistogram;
+
+#[test]
+fn tiny_to_large() {
+    let mut histogram = TimingHistogram::new();
+    for value in 0..100_000 {
+        histogram.insert(value % 2000);
+    }
+    assert_eq!(histogram.len(), 100_000);
+    assert_eq!(histogram.quantile(0.0), 0.0);
+    assert_eq!(histogram.quantile(0.25), 500.0);
+    // Values above 1024 fall into buckets of 16.
+    assert_eq!(histogram.quantile(0.75), 1491.0);
+    assert_eq!(histogram.quantile(1.0), 1991.0);
+}
+
+#[test]
+fn merge_kinds() {
+    let tiny = TimingHistogram::from([1, 2, 3]);
+    let medium = TimingHistogram::from_iter(0..100);
+    let large = TimingHistogram::from_iter(0..20_000);
+
+    let mut merged = &tiny | &large;
+    merged |= &medium;
+    assert_eq!(merged.len(), 20_103);
+    let mut merged = &large | &(&tiny | &medium);
+    assert_eq!(merged.len(), 20_103);
+    assert_eq!(merged.quantile(0.0), 0.0);
+}
+
+#[test]
+fn state_roundtrip() {
+    for histogram in [
+        TimingHistogram::new(),
+        TimingHistogram::from([1, 2, 3]),
+        TimingHistogram::from_iter(0..100),
+        TimingHistogram::from_iter([5, 10_000, 40_000].into_iter().cycle().take(20_000)),
+        TimingHistogram::from_iter(0..20_000),
+    ] {
+        let bytes = histogram.to_bytes();
+        assert_eq!(TimingHistogram::from_bytes(&bytes), Ok(histogram));
+    }
+}
+
+#[test]
+fn sparse_large_state() {
+    let bytes = [
+        0x03, // Large.
+        0xc8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Count.
+        0x05, 0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 5.
+        0x3d, 0x04, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 2000.
+        0x30, 0x75, // End of data.
+    ];
+    let mut histogram = TimingHistogram::from_bytes(&bytes).unwrap();
+    assert_eq!(histogram.len(), 200);
+    assert_eq!(histogram.quantile(0.25), 5.0);
+    assert_eq!(histogram.quantile(0.75), 2006.0);
+    assert_eq!(histogram.to_bytes(), bytes);
+}
+
+#[test]
+fn large_bucket_values() {
+    // The values of ClickHouse `indexInBigToValue` for the buckets of 1488,
+    // 2000, 2992, 17024 and 28976, which are not their middles.
+    for (value, expected) in [
+        (1500, 1491.0),
+        (2000, 2006.0),
+        (3000, 2998.0),
+        (17_030, 17_028.0),
+        (28_980, 28_972.0),
+    ] {
+        let mut histogram = TimingHistogram::new();
+        histogram.insert_many(value, 20_000);
+        assert_eq!(histogram.quantile(0.5), expected, "value {}", value);
+    }
+}
+
+#[test]
+fn saturating_counts() {
+    let mut histogram = TimingHistogram::new();
+    histogram.insert_many(5_000, u64::MAX);
+    histogram.insert_many(5_000, u64::MAX);
+    assert_eq!(histogram.len(), u64::MAX);
+    histogram |= &histogram.clone();
+    assert_eq!(histogram.len(), u64::MAX);
+    assert_eq!(histogram.quantile(1.0), 4_986.0);
+}
+
+#[test]
+fn invalid_state() {
+    assert!(TimingHistogram::from_bytes(&[]).is_err());
+    assert!(TimingHistogram::from_bytes(&[0x04]).is_err());
+    assert!(TimingHistogram::from_bytes(&[0x01, 0x20, 0x00]).is_err());
+    assert!(TimingHistogram::from_bytes(&[0x03, 0x01, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    // A count below the sum of the buckets.
+    let mut bytes = vec![0x03, 0x01, 0, 0, 0, 0, 0, 0, 0];
+    bytes.extend_from_slice(&[0x05, 0x00, 0x02, 0, 0, 0, 0, 0, 0, 0, 0x30, 0x75]);
+    assert!(TimingHistogram::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn huge_large_count() {
+    let mut bytes = vec![0x03];
+    bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+    assert!(TimingHistogram::from_bytes(&bytes).is_err());
+}
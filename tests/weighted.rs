@@ -0,0 +1,77 @@
+use rand::seq::SliceRandom;
+use tdigest_ch::{TDigest, WeightedTDigest};
+
+#[test]
+fn importance_weights() {
+    // Values below 50,000 were sampled at a rate of one in four, so that
+    // weighting them by 4 recovers a uniform distribution.
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let mut digest = WeightedTDigest::new();
+    for &v in values.iter() {
+        if v >= 50_000 {
+            digest.insert(v as f32);
+        } else if v % 4 == 0 {
+            digest.insert_weighted(v as f32, 4.);
+        }
+    }
+    assert_eq!(digest.len(), 62_500);
+    assert_eq!(digest.total_weight(), 100_000.);
+    for level in [0.01, 0.1, 0.25, 0.5, 0.75, 0.9, 0.99] {
+        let quantile = digest.quantile(level);
+        assert!(
+            (quantile as f64 / 100_000. - level).abs() <= 0.01,
+            "level {level}, quantile {quantile}"
+        );
+        assert!(
+            (digest.cdf(quantile) - level).abs() <= 0.01,
+            "level {level}"
+        );
+    }
+}
+
+#[test]
+fn decay() {
+    let mut digest = WeightedTDigest::new();
+    digest.extend((0..10_000).map(|v| v as f32 / 10_000.));
+    for _ in 0..10 {
+        digest.decay(0.5);
+    }
+    // The old values now weigh about 10, against 10,000 new ones.
+    digest.extend((0..10_000).map(|v| 100. + v as f32 / 10_000.));
+    assert_eq!(digest.len(), 20_000);
+    assert!((digest.total_weight() - 10_009.765625).abs() < 1e-6);
+    assert!(digest.quantile(0.01) >= 100.);
+    assert_eq!(digest.min(), 0.);
+}
+
+#[test]
+fn matches_tdigest() {
+    let values: Vec<f32> = (0..10_000).map(|v| v as f32).collect();
+    let mut weighted = WeightedTDigest::from_iter(values.iter().copied());
+    let mut digest = TDigest::from_iter(values.iter().copied());
+    let mut converted = WeightedTDigest::from(digest.clone());
+    for level in [0.0, 0.01, 0.5, 0.99, 1.0] {
+        assert!((weighted.quantile(level) - digest.quantile(level)).abs() <= 10.);
+        assert!((converted.quantile(level) - digest.quantile(level)).abs() <= 10.);
+    }
+}
+
+#[test]
+fn merge() {
+    let mut values: Vec<u32> = (0..100_000).collect();
+    values.shuffle(&mut rand::thread_rng());
+
+    let mut digest = WeightedTDigest::new();
+    for chunk in values.chunks(7_000) {
+        let mut part = WeightedTDigest::new();
+        part.extend(chunk.iter().map(|&v| (v as f32, 0.5)));
+        digest |= &part;
+    }
+    assert_eq!(digest.len(), 100_000);
+    assert_eq!(digest.total_weight(), 50_000.);
+    assert!((digest.quantile(0.5) - 50_000.).abs() <= 1_000.);
+    assert!(WeightedTDigest::new().quantile(0.5).is_nan());
+    assert!(WeightedTDigest::new().cdf(0.).is_nan());
+}